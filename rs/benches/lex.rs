@@ -0,0 +1,88 @@
+//! Benchmarks for the lexer and parser on long runs of the characters
+//! they scan most: indentation, identifiers, and comments. The runs of
+//! operator characters are there to catch matching that goes quadratic.
+//!
+//! Run with `cargo bench`. Each benchmark is timed over a number of runs,
+//! and the fastest is reported, which is the one least disturbed by
+//! whatever else the machine was doing.
+
+extern crate brouwer;
+
+use std::time::{Duration, Instant};
+
+use brouwer::{Parser, lexer};
+
+
+/// How many times each benchmark is run.
+const RUNS: u32 = 20;
+
+fn main() {
+    let src = source(1000);
+    bench("tokenize", &src, |src| { lexer::tokenize(src).unwrap(); });
+    bench("parse", &src, |src| { Parser::from_str(src).parse().unwrap(); });
+
+    let src = operator_run(":", 10000);
+    bench("parse_colon_run", &src, |src| {
+        Parser::from_str(src).parse().unwrap();
+    });
+    bench("tokenize_colon_run", &src, |src| {
+        lexer::tokenize(src).unwrap();
+    });
+
+    let src = operator_run("=>", 10000);
+    bench("parse_arrow_run", &src, |src| {
+        Parser::from_str(src).parse().unwrap();
+    });
+}
+
+/// Times `f` on `src`, and prints the fastest run and the throughput.
+fn bench<F: Fn(&str)>(name: &str, src: &str, f: F) {
+    let mut fastest = Duration::from_secs(u64::MAX);
+
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        f(src);
+        fastest = fastest.min(start.elapsed());
+    }
+
+    let nanos = fastest.as_secs() * 1_000_000_000 +
+                u64::from(fastest.subsec_nanos());
+    let mb_per_s = (src.len() as u64 * 1000).checked_div(nanos).unwrap_or(0);
+
+    println!(
+        "{:<20} {:>12} ns/iter {:>6} MB/s",
+        name,
+        nanos,
+        mb_per_s
+    );
+}
+
+/// A source with `lines` lines, each indented inside a function and
+/// followed by a comment on the next line.
+fn source(lines: usize) -> String {
+    let mut src = String::from("module Main\n\nfn long_function_name x y\n");
+
+    for i in 0..lines {
+        src.push_str("                ");
+        src.push_str(&format!(
+            "some_long_identifier_{} = another_long_identifier x y",
+            i
+        ));
+        src.push_str("\n                -- a comment on a line of its own\n");
+    }
+
+    src
+}
+
+/// A source assigning `a`, applied by an operator `op` repeated `n`
+/// times, to `b`.
+fn operator_run(op: &str, n: usize) -> String {
+    let mut src = String::from("module Main\n\nx = a ");
+
+    for _ in 0..n {
+        src.push_str(op);
+    }
+    src.push_str(" b\n");
+
+    src
+}
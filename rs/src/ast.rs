@@ -0,0 +1,1286 @@
+//! A typed syntax tree, lowered from the parse tree.
+//!
+//! The parse tree keeps every token, which is what dumps, diffs, and the
+//! formatter want. Later stages only want what the program means: `lower`
+//! drops the punctuation and layout, and gives each construct a type of
+//! its own, so that a pass matches on `ExprKind::If` instead of picking
+//! through the children of an `IfElse` node by position.
+//!
+//! ```
+//! use brouwer::Parser;
+//! use brouwer::ast::{self, ExprKind, Stmt};
+//!
+//! let mut parser = Parser::from_str("module Main\n\nx = 1 + 2 * 3\n");
+//! let module = ast::lower(&parser.parse().unwrap().unwrap()).unwrap();
+//!
+//! match module.body[0] {
+//!     Stmt::Let(ref let_) => match let_.value.kind {
+//!         ExprKind::BinOp(ref op, _, _) => assert_eq!(op.name(), "+"),
+//!         _ => panic!("expected a binary operation"),
+//!     },
+//!     _ => panic!("expected a binding"),
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+
+use lexer;
+use parser::{AST, Assoc, Fixity};
+use token::{Kind, NodeKind, Span, TokenType};
+
+
+/// A whole source file.
+#[derive(Clone, Debug)]
+pub struct Module {
+    /// The name in the module declaration.
+    pub name:    Ident,
+    /// The names the module declaration exposes or hides, if any.
+    pub exports: Option<Exports>,
+    /// The imports, in order.
+    pub imports: Vec<Import>,
+    /// The top-level lines that have code on them.
+    pub body:    Vec<Stmt>,
+}
+
+/// `exposing a, b` or `hiding a, b` in a module declaration.
+#[derive(Clone, Debug)]
+pub struct Exports {
+    /// Whether the names are hidden rather than exposed.
+    pub hiding: bool,
+    /// The names.
+    pub names:  Vec<Ident>,
+}
+
+/// `import Name (a, b)`, `import Name hiding (a, b)`, or `import Name as N`.
+#[derive(Clone, Debug)]
+pub struct Import {
+    /// The imported module.
+    pub module: Ident,
+    /// What is brought into scope.
+    pub names:  ImportNames,
+    /// Where the import is.
+    pub span:   Span,
+}
+
+/// What an `Import` brings into scope.
+#[derive(Clone, Debug)]
+pub enum ImportNames {
+    /// Everything, qualified by an alias: `as N`.
+    Alias(Ident),
+    /// Just these names: `(a, b)`.
+    Exposing(Vec<Ident>),
+    /// Everything but these names: `hiding (a, b)`.
+    Hiding(Vec<Ident>),
+}
+
+/// A plain identifier.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ident {
+    /// The identifier itself.
+    pub name: String,
+    /// Where it is.
+    pub span: Span,
+}
+
+/// A possibly qualified name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Name {
+    /// `a`
+    Plain(Ident),
+    /// `a.b.c`: the value, then each member taken of it, in order.
+    Member(Ident, Vec<Ident>),
+    /// `A::B::c`: each module, outermost first, then the name.
+    Scoped(Vec<Ident>, Ident),
+}
+
+/// An infix operator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operator {
+    /// A symbolic operator such as `+`.
+    Symbol(Ident),
+    /// A function used infix, `` `f` ``.
+    Infixed(Name),
+}
+
+/// A line of code.
+#[derive(Clone, Debug)]
+pub enum Stmt {
+    /// A binding, `x = 1`, or a mutable one, `var x = 1`.
+    Let(Let),
+    /// A function declaration.
+    Fn(FnDecl),
+    /// A fixity declaration, `infixl 6 <+>`.
+    Fixity(FixityDecl),
+    /// Any other expression.
+    Expr(Expr),
+}
+
+/// `pattern: Type = value`, or the same after `var`.
+#[derive(Clone, Debug)]
+pub struct Let {
+    /// Whether this is a `var`.
+    pub mutable: bool,
+    /// What is bound.
+    pub pattern: Pattern,
+    /// The declared type, if any.
+    pub type_:   Option<TypeExpr>,
+    /// The value bound.
+    pub value:   Expr,
+    /// Where the binding is.
+    pub span:    Span,
+}
+
+/// `fn name params -> Type` and its body.
+#[derive(Clone, Debug)]
+pub struct FnDecl {
+    /// The function's name.
+    pub name:     Ident,
+    /// The parameters, in order.
+    pub params:   Vec<Param>,
+    /// The declared return type, if any.
+    pub ret_type: Option<TypeExpr>,
+    /// The lines of the body; the last is the function's value.
+    pub body:     Vec<Stmt>,
+    /// Where the declaration is.
+    pub span:     Span,
+}
+
+/// A function or lambda parameter.
+#[derive(Clone, Debug)]
+pub struct Param {
+    /// What the argument is matched against.
+    pub pattern: Pattern,
+    /// The declared type, if any.
+    pub type_:   Option<TypeExpr>,
+    /// Where the parameter is.
+    pub span:    Span,
+}
+
+/// `infixl 6 <+>`
+#[derive(Clone, Debug)]
+pub struct FixityDecl {
+    /// The declared precedence and associativity.
+    pub fixity: Fixity,
+    /// The operator declared.
+    pub op:     Operator,
+    /// Where the declaration is.
+    pub span:   Span,
+}
+
+/// An expression.
+#[derive(Clone, Debug)]
+pub struct Expr {
+    /// What kind of expression this is.
+    pub kind: ExprKind,
+    /// Where it is.
+    pub span: Span,
+}
+
+/// The kinds of `Expr`.
+#[derive(Clone, Debug)]
+pub enum ExprKind {
+    /// An integer literal.
+    Int(i64),
+    /// A real literal, including `NaN` and `Infinity`.
+    Real(f64),
+    /// `True` or `False`.
+    Bool(bool),
+    /// A character literal, with escapes resolved.
+    Char(char),
+    /// A string literal, with escapes resolved.
+    Str(String),
+    /// A variable.
+    Name(Name),
+    /// An operator used as a value, as in `(+)`.
+    Op(Operator),
+    /// A function applied to one or more arguments.
+    Apply(Box<Expr>, Vec<Expr>),
+    /// An infix operator applied to two operands.
+    BinOp(Operator, Box<Expr>, Box<Expr>),
+    /// A prefix `-`.
+    Neg(Box<Expr>),
+    /// `a..b`, the integers from `a` up to but not including `b`, or
+    /// `a..=b`, up to and including `b`; and whether it is the latter.
+    Range(Box<Expr>, Box<Expr>, bool),
+    /// `xs[i]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `xs[a:b]`, and the ends that are not left out.
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `(a, b)`, or `()` with no elements.
+    Tuple(Vec<Expr>),
+    /// `[a, b]`
+    List(Vec<Expr>),
+    /// `{a, b}`
+    Set(Vec<Expr>),
+    /// `{k = v}`, as key-value pairs.
+    Dict(Vec<(Expr, Expr)>),
+    /// `[expr | qualifiers]`
+    ListComp(Box<Expr>, Vec<Qualifier>),
+    /// `{expr | qualifiers}`
+    SetComp(Box<Expr>, Vec<Qualifier>),
+    /// `{k = v | qualifiers}`
+    DictComp(Box<(Expr, Expr)>, Vec<Qualifier>),
+    /// `\x, y -> body`
+    Lambda(Vec<Param>, Box<Expr>),
+    /// `return expr`
+    Return(Box<Expr>),
+    /// `x?`: what `x`, a `Result`, holds if it is a success. If it is a
+    /// failure, it is returned from the enclosing function instead.
+    Propagate(Box<Expr>),
+    /// `if cond`, its body, and the `else` body if there is one. An
+    /// `else if` is an `else` body holding just another `If`.
+    If(Box<Expr>, Vec<Stmt>, Option<Vec<Stmt>>),
+    /// `case subject` and its branches. `if pattern <- subject` is lowered
+    /// to one, with a `_` branch for the `else` body.
+    Case(Box<Expr>, Vec<CaseBranch>),
+    /// `try` body, then `catch name` and the handler's body.
+    Try(Vec<Stmt>, Ident, Vec<Stmt>),
+    /// `while cond` and its body.
+    While(Box<Expr>, Vec<Stmt>),
+    /// `while pattern <- subject` and its body, run each time the subject
+    /// is evaluated again and matches.
+    WhileLet(Pattern, Box<Expr>, Vec<Stmt>),
+    /// `for pattern in iterated` and its body.
+    For(Pattern, Box<Expr>, Vec<Stmt>),
+}
+
+/// `pattern => body` within a `case`.
+#[derive(Clone, Debug)]
+pub struct CaseBranch {
+    /// What the subject is matched against.
+    pub pattern:  Pattern,
+    /// The condition after `if`, which must also hold for the branch to
+    /// be taken.
+    pub guard:    Option<Expr>,
+    /// The lines run on a match.
+    pub body:     Vec<Stmt>,
+    /// Where the branch is.
+    pub span:     Span,
+    /// Whether the branch was not written but added when lowering: the
+    /// `else` of an `if pattern <- subject`, which no value may reach if
+    /// the pattern cannot fail to match.
+    pub implicit: bool,
+}
+
+/// A part of a comprehension after the `|`.
+#[derive(Clone, Debug)]
+pub enum Qualifier {
+    /// `pattern <- expr`
+    Generator(Pattern, Expr),
+    /// A condition.
+    Guard(Expr),
+}
+
+/// A pattern.
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    /// What kind of pattern this is.
+    pub kind: PatternKind,
+    /// Where it is.
+    pub span: Span,
+}
+
+/// The kinds of `Pattern`.
+#[derive(Clone, Debug)]
+pub enum PatternKind {
+    /// A name to bind.
+    Bind(Ident),
+    /// `_`
+    Wildcard,
+    /// An integer literal.
+    Int(i64),
+    /// A real literal.
+    Real(f64),
+    /// `True` or `False`.
+    Bool(bool),
+    /// A character literal.
+    Char(char),
+    /// A string literal.
+    Str(String),
+    /// `a..b`, the integers from `a` up to but not including `b`, or
+    /// `a..=b`, up to and including `b`; and whether it is the latter.
+    Range(i64, i64, bool),
+    /// `"s" ++ rest`: a string that starts with `s`, the rest of which is
+    /// matched against `rest`.
+    Prefix(String, Box<Pattern>),
+    /// `rest ++ "s"`: a string that ends with `s`, what comes before which
+    /// is matched against `rest`.
+    Suffix(Box<Pattern>, String),
+    /// `name @ pattern`: a value that matches the pattern, bound to the
+    /// name as a whole.
+    As(Ident, Box<Pattern>),
+    /// `a | b`: a value that matches any of the alternatives, each of which
+    /// binds the same names.
+    Or(Vec<Pattern>),
+    /// `(a, b)`, or `()`.
+    Tuple(Vec<Pattern>),
+    /// `[a, b]`
+    List(Vec<Pattern>),
+    /// `{a, b}`
+    Set(Vec<Pattern>),
+    /// `{k = v}`, or `{}`.
+    Dict(Vec<(Pattern, Pattern)>),
+}
+
+/// A type.
+#[derive(Clone, Debug)]
+pub struct TypeExpr {
+    /// What kind of type this is.
+    pub kind: TypeKind,
+    /// Where it is.
+    pub span: Span,
+}
+
+/// The kinds of `TypeExpr`.
+#[derive(Clone, Debug)]
+pub enum TypeKind {
+    /// A named type.
+    Name(Name),
+    /// `(A, B)`, or `()`.
+    Tuple(Vec<TypeExpr>),
+    /// `[A]`
+    List(Box<TypeExpr>),
+    /// `{A}`
+    Set(Box<TypeExpr>),
+    /// `{K, V}`
+    Dict(Box<TypeExpr>, Box<TypeExpr>),
+}
+
+/// Why a parse tree could not be lowered.
+#[derive(Clone, Debug)]
+pub enum LowerError {
+    /// A declaration that is not on a line of its own, such as an
+    /// assignment used as a function argument.
+    Misplaced {
+        /// The kind of declaration.
+        construct: NodeKind,
+        /// Where it is.
+        span:      Span,
+    },
+    /// An integer literal that does not fit in 64 bits.
+    IntOutOfRange {
+        /// The literal, including any sign and radix prefix.
+        literal: String,
+        /// Where it is.
+        span:    Span,
+    },
+    /// An end of a range pattern that is not an integer, as in `0..1.5`.
+    RangeBound {
+        /// Where it is.
+        span: Span,
+    },
+    /// A node whose children are not laid out the way the parser lays
+    /// them out, as can happen to a tree built or edited by hand.
+    Malformed {
+        /// The kind of the node.
+        kind: Kind,
+        /// Where it is.
+        span: Span,
+    },
+}
+
+
+impl Name {
+    /// Where the name is.
+    pub fn span(&self) -> Span {
+        match *self {
+            Name::Plain(ref ident) => ident.span,
+            Name::Member(ref a, ref members) => {
+                members.last().map_or(a.span, |b| a.span.to(&b.span))
+            },
+            Name::Scoped(ref modules, ref b) => {
+                modules.first().map_or(b.span, |a| a.span.to(&b.span))
+            },
+        }
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Name::Plain(ref ident) => write!(f, "{}", ident.name),
+            Name::Member(ref a, ref members) => {
+                write!(f, "{}", a.name)?;
+
+                for member in members {
+                    write!(f, ".{}", member.name)?;
+                }
+
+                Ok(())
+            },
+            Name::Scoped(ref modules, ref b) => {
+                for module in modules {
+                    write!(f, "{}::", module.name)?;
+                }
+
+                write!(f, "{}", b.name)
+            },
+        }
+    }
+}
+
+impl Operator {
+    /// The operator as written, without backticks.
+    pub fn name(&self) -> String {
+        match *self {
+            Operator::Symbol(ref op)    => op.name.clone(),
+            Operator::Infixed(ref name) => name.to_string(),
+        }
+    }
+
+    /// Where the operator is.
+    pub fn span(&self) -> Span {
+        match *self {
+            Operator::Symbol(ref op)    => op.span,
+            Operator::Infixed(ref name) => name.span(),
+        }
+    }
+}
+
+impl Pattern {
+    /// The names the pattern binds, in order.
+    pub fn idents(&self) -> Vec<&Ident> {
+        let mut idents = Vec::new();
+        self.collect_idents(&mut idents);
+
+        idents
+    }
+
+    fn collect_idents<'a>(&'a self, idents: &mut Vec<&'a Ident>) {
+        match self.kind {
+            PatternKind::Bind(ref ident) => idents.push(ident),
+            PatternKind::Tuple(ref elems) |
+            PatternKind::List(ref elems)  |
+            PatternKind::Set(ref elems)   => {
+                for elem in elems {
+                    elem.collect_idents(idents);
+                }
+            },
+            PatternKind::Dict(ref entries) => {
+                for (key, value) in entries {
+                    key.collect_idents(idents);
+                    value.collect_idents(idents);
+                }
+            },
+            PatternKind::Prefix(_, ref rest) |
+            PatternKind::Suffix(ref rest, _) => rest.collect_idents(idents),
+            PatternKind::As(ref ident, ref matched) => {
+                idents.push(ident);
+                matched.collect_idents(idents);
+            },
+            // The names of the first alternative stand for those of all of
+            // them, which `resolve` checks are the same.
+            PatternKind::Or(ref alternatives) => {
+                if let Some(first) = alternatives.first() {
+                    first.collect_idents(idents);
+                }
+            },
+            PatternKind::Wildcard  |
+            PatternKind::Int(_)    |
+            PatternKind::Real(_)   |
+            PatternKind::Bool(_)   |
+            PatternKind::Char(_)   |
+            PatternKind::Str(_)    |
+            PatternKind::Range(..) => {},
+        }
+    }
+}
+
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LowerError::Misplaced { construct, span } => write!(
+                f,
+                "{}:{}: {:?} must be on a line of its own",
+                span.line,
+                span.col,
+                construct
+            ),
+            LowerError::IntOutOfRange { ref literal, span } => write!(
+                f,
+                "{}:{}: integer literal {} does not fit in 64 bits",
+                span.line,
+                span.col,
+                literal
+            ),
+            LowerError::RangeBound { span } => write!(
+                f,
+                "{}:{}: the ends of a range pattern must be integers",
+                span.line,
+                span.col
+            ),
+            LowerError::Malformed { kind, span } => write!(
+                f,
+                "{}:{}: malformed {:?} node",
+                span.line,
+                span.col,
+                kind
+            ),
+        }
+    }
+}
+
+impl Error for LowerError {}
+
+/// Lowers a whole program, as returned by `Parser::parse`.
+pub fn lower(ast: &AST) -> Result<Module, LowerError> {
+    let prog = match ast.val().type_ {
+        TokenType::Root => child(ast, 0)?,
+        _               => ast,
+    };
+    let mod_decl = child(prog, 0)?;
+
+    if mod_decl.val().type_ != TokenType::ModDecl {
+        return Err(malformed(prog));
+    }
+
+    let mod_children = mod_decl.children();
+    let exports = match mod_children.get(2).map(|c| &c.val().type_) {
+        Some(&TokenType::ExposingKeyword) => Some(false),
+        Some(&TokenType::HidingKeyword)   => Some(true),
+        _                                 => None,
+    }.map(|hiding| Exports {
+        hiding: hiding,
+        names:  idents(&mod_children[3..]),
+    });
+
+    let mut imports = Vec::new();
+
+    for import in prog.children()
+                      .iter()
+                      .filter(|c| c.val().type_ == TokenType::Import)
+    {
+        imports.push(lower_import(import)?);
+    }
+
+    Ok(Module {
+        name:    ident(child(mod_decl, 1)?),
+        exports: exports,
+        imports: imports,
+        body:    lower_lines(prog.children())?,
+    })
+}
+
+/// Lowers the `Line`s among `lines`, skipping those without code, as for
+/// the children of a `Prog` returned by `parse_block_fragment`.
+pub fn lower_lines(lines: &[AST]) -> Result<Vec<Stmt>, LowerError> {
+    let mut stmts = Vec::with_capacity(lines.len());
+
+    for line in lines.iter().filter(|l| l.val().type_ == TokenType::Line) {
+        if let Some(expr) = line.children().first() {
+            stmts.push(lower_stmt(expr)?);
+        }
+    }
+
+    Ok(stmts)
+}
+
+fn lower_import(import: &AST) -> Result<Import, LowerError> {
+    let children = import.children();
+    let names = match children.get(2).map(|c| &c.val().type_) {
+        Some(&TokenType::AsKeyword)     => {
+            ImportNames::Alias(ident(child(import, 3)?))
+        },
+        Some(&TokenType::HidingKeyword) => {
+            ImportNames::Hiding(idents(&children[3..]))
+        },
+        _ => ImportNames::Exposing(idents(&children[2..])),
+    };
+
+    Ok(Import {
+        module: ident(child(import, 1)?),
+        names:  names,
+        span:   import.span(),
+    })
+}
+
+/// Lowers the `Expr` of a line, which may be a declaration.
+fn lower_stmt(expr: &AST) -> Result<Stmt, LowerError> {
+    if let Some(decl) = sole_subexpr(expr) {
+        match decl.val().type_ {
+            TokenType::Var | TokenType::Assign => {
+                return lower_let(decl).map(Stmt::Let);
+            },
+            TokenType::FnDecl     => return lower_fn(decl).map(Stmt::Fn),
+            TokenType::FixityDecl => {
+                return lower_fixity(decl).map(Stmt::Fixity);
+            },
+            _ => {},
+        }
+    }
+
+    lower_expr(expr).map(Stmt::Expr)
+}
+
+fn lower_let(decl: &AST) -> Result<Let, LowerError> {
+    let children = decl.children();
+    let pattern = children.iter()
+                          .find(|c| c.val().type_ == TokenType::Pattern)
+                          .ok_or_else(|| malformed(decl))?;
+    let type_ = match children.iter()
+                              .find(|c| c.val().type_ == TokenType::TypeIdent)
+    {
+        Some(t) => Some(lower_type(t)?),
+        None    => None,
+    };
+
+    Ok(Let {
+        mutable: decl.val().type_ == TokenType::Var,
+        pattern: lower_pattern(pattern)?,
+        type_:   type_,
+        value:   lower_expr(last_child(decl)?)?,
+        span:    decl.span(),
+    })
+}
+
+fn lower_fn(fn_decl: &AST) -> Result<FnDecl, LowerError> {
+    let children = fn_decl.children();
+    let mut params = Vec::new();
+
+    for param in children.iter()
+                         .filter(|c| c.val().type_ == TokenType::Param)
+    {
+        params.push(lower_param(param)?);
+    }
+
+    let ret_type = match children.iter()
+                                 .position(|c|
+                                     c.val().type_ == TokenType::RArrow
+                                 )
+    {
+        Some(at) => {
+            let ret = child(fn_decl, at + 1)?;
+
+            Some(TypeExpr {
+                kind: TypeKind::Name(lower_name(ret)?),
+                span: ret.span(),
+            })
+        },
+        None => None,
+    };
+
+    Ok(FnDecl {
+        name:     ident(child(fn_decl, 1)?),
+        params:   params,
+        ret_type: ret_type,
+        body:     lower_lines(children)?,
+        span:     fn_decl.span(),
+    })
+}
+
+fn lower_fixity(decl: &AST) -> Result<FixityDecl, LowerError> {
+    let assoc = match child(decl, 0)?.val().type_ {
+        TokenType::InfixlKeyword => Assoc::Left,
+        TokenType::InfixrKeyword => Assoc::Right,
+        _                        => Assoc::NonAssoc,
+    };
+    let prec = child(decl, 1)?.val()
+                              .lexeme()
+                              .parse()
+                              .map_err(|_| malformed(decl))?;
+
+    Ok(FixityDecl {
+        fixity: Fixity {
+            prec:  prec,
+            assoc: assoc,
+        },
+        op:     lower_op(child(decl, 2)?)?,
+        span:   decl.span(),
+    })
+}
+
+fn lower_param(param: &AST) -> Result<Param, LowerError> {
+    let children = param.children();
+    let pattern = children.iter()
+                          .find(|c| c.val().type_ == TokenType::Pattern)
+                          .ok_or_else(|| malformed(param))?;
+    let type_ = match children.iter()
+                              .find(|c| c.val().type_ == TokenType::TypeIdent)
+    {
+        Some(t) => Some(lower_type(t)?),
+        None    => None,
+    };
+
+    Ok(Param {
+        pattern: lower_pattern(pattern)?,
+        type_:   type_,
+        span:    param.span(),
+    })
+}
+
+fn lower_expr(expr: &AST) -> Result<Expr, LowerError> {
+    let first = child(expr, 0)?;
+
+    let kind = match first.val().type_ {
+        TokenType::BinOp => ExprKind::BinOp(
+            lower_op(child(child(first, 1)?, 0)?)?,
+            Box::new(lower_expr(child(first, 0)?)?),
+            Box::new(lower_expr(child(first, 2)?)?)
+        ),
+        TokenType::UnaryOp => {
+            ExprKind::Neg(Box::new(lower_expr(child(first, 1)?)?))
+        },
+        TokenType::Range => ExprKind::Range(
+            Box::new(lower_expr(child(first, 0)?)?),
+            Box::new(lower_expr(child(first, 2)?)?),
+            child(first, 1)?.val().type_ == TokenType::DotDotEquals
+        ),
+        TokenType::FnCall => {
+            let mut args = Vec::new();
+            let mut callee = first;
+
+            // `f x y` is `(f x) y`: the callee of the outermost call holds
+            // all but the last argument.
+            while callee.val().type_ == TokenType::FnCall {
+                args.push(lower_subexpr(child(callee, 1)?)?);
+                callee = child(callee, 0)?;
+            }
+
+            args.reverse();
+
+            ExprKind::Apply(Box::new(lower_subexpr(callee)?), args)
+        },
+        _ => return lower_subexpr(first),
+    };
+
+    Ok(Expr {
+        kind: kind,
+        span: expr.span(),
+    })
+}
+
+fn lower_subexpr(subexpr: &AST) -> Result<Expr, LowerError> {
+    lower_construct(child(subexpr, 0)?)
+}
+
+/// Lowers the construct inside a `Subexpr`.
+fn lower_construct(inner: &AST) -> Result<Expr, LowerError> {
+    let children = inner.children();
+
+    let kind = match inner.val().type_ {
+        TokenType::Var        |
+        TokenType::Assign     |
+        TokenType::FnDecl     |
+        TokenType::FixityDecl => return Err(LowerError::Misplaced {
+            construct: inner.val()
+                            .type_
+                            .node_kind()
+                            .unwrap_or(NodeKind::Subexpr),
+            span:      inner.span(),
+        }),
+        TokenType::Parened => return lower_expr(child(inner, 1)?),
+        TokenType::Index   => {
+            let indexed = Box::new(lower_subexpr(child(inner, 0)?)?);
+            let inside = &children[2..children.len() - 1];
+            let end = |ends: &[AST]| match ends.first() {
+                Some(end) => lower_expr(end).map(|e| Some(Box::new(e))),
+                None      => Ok(None),
+            };
+
+            match inside.iter()
+                        .position(|c| c.val().type_ == TokenType::Colon)
+            {
+                Some(colon) => ExprKind::Slice(
+                    indexed,
+                    end(&inside[..colon])?,
+                    end(&inside[colon + 1..])?
+                ),
+                None => ExprKind::Index(
+                    indexed,
+                    Box::new(lower_expr(child(inner, 2)?)?)
+                ),
+            }
+        },
+        TokenType::Propagate => {
+            ExprKind::Propagate(Box::new(lower_subexpr(child(inner, 0)?)?))
+        },
+        TokenType::Return  => {
+            ExprKind::Return(Box::new(lower_expr(child(inner, 1)?)?))
+        },
+        TokenType::Case => {
+            let mut branches = Vec::new();
+
+            for branch in children.iter()
+                                  .filter(|c|
+                                      c.val().type_ == TokenType::CaseBranch
+                                  )
+            {
+                let guard = match branch.children().get(1) {
+                    Some(guard) if guard.val().type_ == TokenType::Guard => {
+                        Some(lower_expr(child(guard, 1)?)?)
+                    },
+                    _ => None,
+                };
+
+                branches.push(CaseBranch {
+                    pattern:  lower_pattern(child(branch, 0)?)?,
+                    guard:    guard,
+                    body:     lower_lines(branch.children())?,
+                    span:     branch.span(),
+                    implicit: false,
+                });
+            }
+
+            ExprKind::Case(Box::new(lower_expr(child(inner, 1)?)?), branches)
+        },
+        TokenType::IfElse => {
+            let else_at = children.iter()
+                                  .position(|c|
+                                      c.val().type_ == TokenType::ElseKeyword
+                                  )
+                                  .unwrap_or(children.len());
+            let else_body = match children.get(else_at + 1) {
+                Some(nested) if nested.val().type_ == TokenType::IfElse => {
+                    Some(vec![Stmt::Expr(lower_construct(nested)?)])
+                },
+                Some(_) => Some(lower_lines(&children[else_at + 1..])?),
+                None    => None,
+            };
+
+            let cond = child(inner, 1)?;
+            let body = lower_lines(&children[2..else_at])?;
+
+            if cond.val().type_ != TokenType::Generator {
+                ExprKind::If(Box::new(lower_expr(cond)?), body, else_body)
+            } else {
+                // `if pattern <- subject` is a `case subject` with a branch
+                // for the pattern, and one for anything else, at the `else`
+                // if there is one.
+                let pattern = lower_pattern(child(cond, 0)?)?;
+                let anything = Pattern {
+                    kind: PatternKind::Wildcard,
+                    span: children.get(else_at)
+                                  .map_or(cond.span(), |e| e.span()),
+                };
+                let implicit = else_body.is_none();
+
+                ExprKind::Case(
+                    Box::new(lower_expr(child(cond, 2)?)?),
+                    vec![
+                        CaseBranch {
+                            pattern:  pattern,
+                            guard:    None,
+                            body:     body,
+                            span:     inner.span(),
+                            implicit: false,
+                        },
+                        CaseBranch {
+                            pattern:  anything,
+                            guard:    None,
+                            body:     else_body.unwrap_or_default(),
+                            span:     inner.span(),
+                            implicit: implicit,
+                        },
+                    ]
+                )
+            }
+        },
+        TokenType::Try => {
+            let catch_at = children.iter()
+                                   .position(|c|
+                                       c.val().type_ == TokenType::CatchKeyword
+                                   )
+                                   .ok_or_else(|| malformed(inner))?;
+
+            ExprKind::Try(
+                lower_lines(&children[..catch_at])?,
+                ident(child(inner, catch_at + 1)?),
+                lower_lines(&children[catch_at + 2..])?
+            )
+        },
+        TokenType::While => {
+            let cond = child(inner, 1)?;
+
+            if cond.val().type_ == TokenType::Generator {
+                ExprKind::WhileLet(
+                    lower_pattern(child(cond, 0)?)?,
+                    Box::new(lower_expr(child(cond, 2)?)?),
+                    lower_lines(children)?
+                )
+            } else {
+                ExprKind::While(
+                    Box::new(lower_expr(cond)?),
+                    lower_lines(children)?
+                )
+            }
+        },
+        TokenType::For => ExprKind::For(
+            lower_pattern(child(inner, 1)?)?,
+            Box::new(lower_expr(child(inner, 3)?)?),
+            lower_lines(children)?
+        ),
+        TokenType::Lambda => {
+            let mut params = Vec::new();
+
+            for param in children.iter()
+                                 .filter(|c| c.val().type_ == TokenType::Param)
+            {
+                params.push(lower_param(param)?);
+            }
+
+            ExprKind::Lambda(params, Box::new(lower_expr(last_child(inner)?)?))
+        },
+        TokenType::TupleLit => ExprKind::Tuple(lower_exprs(children)?),
+        TokenType::ListLit  => ExprKind::List(lower_exprs(children)?),
+        TokenType::SetLit   => ExprKind::Set(lower_exprs(children)?),
+        TokenType::DictLit  => {
+            let mut entries = Vec::new();
+
+            for entry in children.iter()
+                                 .filter(|c|
+                                     c.val().type_ == TokenType::DictEntry
+                                 )
+            {
+                entries.push(lower_dict_entry(entry)?);
+            }
+
+            ExprKind::Dict(entries)
+        },
+        TokenType::ListComp => ExprKind::ListComp(
+            Box::new(lower_expr(child(inner, 1)?)?),
+            lower_qualifiers(&children[3..])?
+        ),
+        TokenType::SetComp => ExprKind::SetComp(
+            Box::new(lower_expr(child(inner, 1)?)?),
+            lower_qualifiers(&children[3..])?
+        ),
+        TokenType::DictComp => ExprKind::DictComp(
+            Box::new(lower_dict_entry(child(inner, 1)?)?),
+            lower_qualifiers(&children[3..])?
+        ),
+        TokenType::QualIdent => ExprKind::Name(lower_name(inner)?),
+        TokenType::Op | TokenType::Infixed => ExprKind::Op(lower_op(inner)?),
+        TokenType::NumLit => match lower_num_lit(inner)? {
+            Number::Int(i)  => ExprKind::Int(i),
+            Number::Real(r) => ExprKind::Real(r),
+        },
+        TokenType::BoolLit => ExprKind::Bool(lower_bool_lit(inner)?),
+        TokenType::ChrLit => {
+            ExprKind::Char(unescape(child(inner, 1)?.val().lexeme()))
+        },
+        TokenType::StrLit => ExprKind::Str(str_lit_value(inner)),
+        _ => return Err(malformed(inner)),
+    };
+
+    Ok(Expr {
+        kind: kind,
+        span: inner.span(),
+    })
+}
+
+/// Lowers the `Expr` children among `children`, as for the elements of a
+/// literal.
+fn lower_exprs(children: &[AST]) -> Result<Vec<Expr>, LowerError> {
+    let mut exprs = Vec::with_capacity(children.len() / 2);
+
+    for expr in children.iter().filter(|c| c.val().type_ == TokenType::Expr) {
+        exprs.push(lower_expr(expr)?);
+    }
+
+    Ok(exprs)
+}
+
+fn lower_dict_entry(entry: &AST) -> Result<(Expr, Expr), LowerError> {
+    Ok((lower_expr(child(entry, 0)?)?, lower_expr(child(entry, 2)?)?))
+}
+
+/// Lowers the `Generator`s and conditions among `children`.
+fn lower_qualifiers(children: &[AST]) -> Result<Vec<Qualifier>, LowerError> {
+    let mut qualifiers = Vec::new();
+
+    for qual in children {
+        match qual.val().type_ {
+            TokenType::Generator => qualifiers.push(Qualifier::Generator(
+                lower_pattern(child(qual, 0)?)?,
+                lower_expr(child(qual, 2)?)?
+            )),
+            TokenType::Expr => {
+                qualifiers.push(Qualifier::Guard(lower_expr(qual)?));
+            },
+            _ => {},
+        }
+    }
+
+    Ok(qualifiers)
+}
+
+fn lower_pattern(pattern: &AST) -> Result<Pattern, LowerError> {
+    let children = pattern.children();
+    let first = child(pattern, 0)?;
+    let subpatterns = || -> Result<Vec<Pattern>, LowerError> {
+        let mut pats = Vec::new();
+
+        for pat in children.iter()
+                           .filter(|c| c.val().type_ == TokenType::Pattern)
+        {
+            pats.push(lower_pattern(pat)?);
+        }
+
+        Ok(pats)
+    };
+
+    let kind = match first.val().type_ {
+        TokenType::Ident if children.len() == 3 => PatternKind::As(
+            ident(first),
+            Box::new(lower_pattern(&children[2])?)
+        ),
+        TokenType::Ident      => PatternKind::Bind(ident(first)),
+        TokenType::Underscore => PatternKind::Wildcard,
+        TokenType::NumLit if children.len() == 3 => {
+            let bound = |num_lit: &AST| match lower_num_lit(num_lit)? {
+                Number::Int(i)  => Ok(i),
+                Number::Real(_) => Err(LowerError::RangeBound {
+                    span: num_lit.span(),
+                }),
+            };
+            let inclusive = child(pattern, 1)?.val().type_ ==
+                            TokenType::DotDotEquals;
+
+            PatternKind::Range(bound(first)?, bound(&children[2])?, inclusive)
+        },
+        TokenType::NumLit     => match lower_num_lit(first)? {
+            Number::Int(i)  => PatternKind::Int(i),
+            Number::Real(r) => PatternKind::Real(r),
+        },
+        TokenType::BoolLit => PatternKind::Bool(lower_bool_lit(first)?),
+        TokenType::ChrLit => {
+            PatternKind::Char(unescape(child(first, 1)?.val().lexeme()))
+        },
+        TokenType::StrLit if children.len() == 3 => PatternKind::Prefix(
+            str_lit_value(first),
+            Box::new(lower_pattern(&children[2])?)
+        ),
+        TokenType::StrLit    => PatternKind::Str(str_lit_value(first)),
+        TokenType::Pattern if child(pattern, 1)?.val().type_ ==
+                              TokenType::Bar => {
+            PatternKind::Or(subpatterns()?)
+        },
+        TokenType::Pattern   => PatternKind::Suffix(
+            Box::new(lower_pattern(first)?),
+            str_lit_value(child(pattern, 2)?)
+        ),
+        TokenType::LParen    => PatternKind::Tuple(subpatterns()?),
+        TokenType::LSqBracket => PatternKind::List(subpatterns()?),
+        TokenType::LCurlyBracket => {
+            let is_dict = children.len() == 2 ||
+                          children.iter().any(|c|
+                              c.val().type_ == TokenType::Equals
+                          );
+            let pats = subpatterns()?;
+
+            if is_dict {
+                let mut entries = Vec::with_capacity(pats.len() / 2);
+                let mut pats = pats.into_iter();
+
+                while let (Some(k), Some(v)) = (pats.next(), pats.next()) {
+                    entries.push((k, v));
+                }
+
+                PatternKind::Dict(entries)
+            } else {
+                PatternKind::Set(pats)
+            }
+        },
+        _ => return Err(malformed(pattern)),
+    };
+
+    Ok(Pattern {
+        kind: kind,
+        span: pattern.span(),
+    })
+}
+
+/// Lowers the parse tree of a type, as written in an annotation.
+pub fn lower_type(type_ident: &AST) -> Result<TypeExpr, LowerError> {
+    let children = type_ident.children();
+    let first = child(type_ident, 0)?;
+    let mut elements = Vec::new();
+
+    for element in children.iter()
+                           .filter(|c| c.val().type_ == TokenType::TypeIdent)
+    {
+        elements.push(lower_type(element)?);
+    }
+
+    let kind = match first.val().type_ {
+        TokenType::NamespacedIdent => TypeKind::Name(lower_name(first)?),
+        TokenType::LParen          => TypeKind::Tuple(elements),
+        TokenType::LSqBracket if elements.len() == 1 => {
+            TypeKind::List(Box::new(elements.remove(0)))
+        },
+        TokenType::LCurlyBracket if elements.len() == 1 => {
+            TypeKind::Set(Box::new(elements.remove(0)))
+        },
+        TokenType::LCurlyBracket if elements.len() == 2 => {
+            let value = elements.remove(1);
+
+            TypeKind::Dict(Box::new(elements.remove(0)), Box::new(value))
+        },
+        _ => return Err(malformed(type_ident)),
+    };
+
+    Ok(TypeExpr {
+        kind: kind,
+        span: type_ident.span(),
+    })
+}
+
+/// Lowers a `QualIdent` or `NamespacedIdent`.
+fn lower_name(qual_ident: &AST) -> Result<Name, LowerError> {
+    let inner = child(qual_ident, 0)?;
+
+    match inner.val().type_ {
+        TokenType::Ident       => Ok(Name::Plain(ident(inner))),
+        TokenType::MemberIdent => {
+            let mut members = idents(inner.children());
+            let first = ident(child(inner, 0)?);
+            members.remove(0);
+
+            Ok(Name::Member(first, members))
+        },
+        TokenType::ScopedIdent => {
+            let mut modules = idents(inner.children());
+            let last = modules.pop().ok_or_else(|| malformed(inner))?;
+
+            Ok(Name::Scoped(modules, last))
+        },
+        _ => Err(malformed(qual_ident)),
+    }
+}
+
+/// Lowers an `Op` or `Infixed`.
+fn lower_op(op: &AST) -> Result<Operator, LowerError> {
+    match op.val().type_ {
+        TokenType::Op      => Ok(Operator::Symbol(ident(op))),
+        TokenType::Infixed => Ok(Operator::Infixed(lower_name(child(op, 1)?)?)),
+        _                  => Err(malformed(op)),
+    }
+}
+
+/// The value of a `BoolLit`.
+fn lower_bool_lit(bool_lit: &AST) -> Result<bool, LowerError> {
+    Ok(child(bool_lit, 0)?.val().type_ == TokenType::TrueKeyword)
+}
+
+/// The value of a `NumLit`.
+enum Number {
+    Int(i64),
+    Real(f64),
+}
+
+fn lower_num_lit(num_lit: &AST) -> Result<Number, LowerError> {
+    let lit = child(num_lit, 0)?;
+    let negative = child(lit, 0)?.val().type_ == TokenType::Minus;
+    let abs = last_child(lit)?;
+
+    if lit.val().type_ == TokenType::IntLit {
+        let radix = lit.children()
+                       .iter()
+                       .find(|c| c.val().type_ == TokenType::Radix)
+                       .map_or(Some(10), |r| lexer::radix(r.val().lexeme()))
+                       .ok_or_else(|| malformed(lit))?;
+        let mut digits = abs.val().lexeme().to_string();
+
+        if negative {
+            digits.insert(0, '-');
+        }
+
+        return match i64::from_str_radix(&digits, radix) {
+            Ok(i)  => Ok(Number::Int(i)),
+            Err(_) => Err(LowerError::IntOutOfRange {
+                literal: lit.children()
+                            .iter()
+                            .map(|c| c.val().lexeme())
+                            .collect(),
+                span:    lit.span(),
+            }),
+        };
+    }
+
+    let magnitude = match abs.val().type_ {
+        TokenType::NanKeyword      => f64::NAN,
+        TokenType::InfinityKeyword => f64::INFINITY,
+        _ => abs.val().lexeme().parse().map_err(|_| malformed(abs))?,
+    };
+
+    Ok(Number::Real(if negative { -magnitude } else { magnitude }))
+}
+
+/// The text of a `StrLit`, with escapes resolved.
+fn str_lit_value(str_lit: &AST) -> String {
+    str_lit.children()
+           .iter()
+           .filter(|c| match c.val().type_ {
+               TokenType::StrChr        |
+               TokenType::HexEscape     |
+               TokenType::UnicodeEscape => true,
+               _                        => false,
+           })
+           .map(|c| unescape(c.val().lexeme()))
+           .collect()
+}
+
+/// The character a `ChrChr`, `StrChr`, `HexEscape`, or `UnicodeEscape`
+/// lexeme stands for.
+pub fn unescape(lexeme: &str) -> char {
+    if let Some(c) = lexer::code_escape(lexeme) {
+        return c;
+    }
+
+    let mut chars = lexeme.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some('\\'), Some(esc)) => match esc {
+            't' => '\t',
+            'v' => '\u{b}',
+            'n' => '\n',
+            'r' => '\r',
+            'b' => '\u{8}',
+            '0' => '\0',
+            c   => c,
+        },
+        (Some(c), _) => c,
+        (None, _)    => '\0',
+    }
+}
+
+/// The construct an `Expr` consists of, if it is a single `Subexpr`.
+fn sole_subexpr(expr: &AST) -> Option<&AST> {
+    match expr.children().first() {
+        Some(subexpr) if subexpr.val().type_ == TokenType::Subexpr => {
+            subexpr.children().first()
+        },
+        _ => None,
+    }
+}
+
+fn ident(leaf: &AST) -> Ident {
+    Ident {
+        name: leaf.val().lexeme().to_string(),
+        span: leaf.span(),
+    }
+}
+
+/// The `Ident` children among `children`.
+fn idents(children: &[AST]) -> Vec<Ident> {
+    children.iter()
+            .filter(|c| c.val().type_ == TokenType::Ident)
+            .map(ident)
+            .collect()
+}
+
+fn child(ast: &AST, i: usize) -> Result<&AST, LowerError> {
+    ast.children().get(i).ok_or_else(|| malformed(ast))
+}
+
+fn last_child(ast: &AST) -> Result<&AST, LowerError> {
+    ast.children().last().ok_or_else(|| malformed(ast))
+}
+
+fn malformed(ast: &AST) -> LowerError {
+    LowerError::Malformed {
+        kind: ast.val().type_.kind(),
+        span: ast.span(),
+    }
+}
@@ -0,0 +1,162 @@
+//! Code generators: what turns a typed syntax tree (see `ast`), and the
+//! types worked out for it (see `typeck`), into an artifact to write out.
+//!
+//! The bytecode compiler (see `compiler`) is the one backend built in.
+//! Others, such as ones emitting JavaScript, C, or WebAssembly text, are
+//! implementations of `Backend` added to a `Backends` registry: one kept in
+//! this crate registers itself in `Backends::new`, behind a feature of its
+//! own, and one from another crate is registered by whatever builds the
+//! registry. `brouwer compile --backend NAME` picks from the built-in ones.
+//!
+//! ```
+//! use brouwer::{Parser, ast, typeck};
+//! use brouwer::ast::Module;
+//! use brouwer::backend::{Backend, Backends};
+//! use brouwer::typeck::Typing;
+//!
+//! /// Lists the names a module binds, with their types.
+//! struct Signatures;
+//!
+//! impl Backend for Signatures {
+//!     fn name(&self) -> &str {
+//!         "signatures"
+//!     }
+//!
+//!     fn emit(&self, _: &Module, typing: &Typing) -> Result<String, String> {
+//!         Ok(typing.types
+//!                  .iter()
+//!                  .map(|&(ref name, ref type_)| {
+//!                      format!("{} : {}\n", name, type_)
+//!                  })
+//!                  .collect())
+//!     }
+//! }
+//!
+//! let mut backends = Backends::new();
+//! backends.register(Signatures);
+//!
+//! let tree = Parser::from_str("module Main\n\nx = 'x'\n").parse().unwrap();
+//! let module = ast::lower(&tree.unwrap()).unwrap();
+//! let typing = typeck::infer(&module);
+//!
+//! let backend = backends.get("signatures").unwrap();
+//! assert_eq!(backend.emit(&module, &typing).unwrap(), "x : Char\n");
+//! assert_eq!(backends.names(), vec!["bytecode", "signatures"]);
+//! ```
+
+use ast::Module;
+use compiler::{self, Chunk, Constant};
+use typeck::Typing;
+
+
+/// A code generator.
+pub trait Backend: Send + Sync {
+    /// The name the backend is picked by, such as `"bytecode"`.
+    fn name(&self) -> &str;
+
+    /// The artifact for `module`, whose types are `typing`, as text.
+    ///
+    /// Fails with a message, starting `line:col: ` if it is about one place
+    /// in the module, if the backend cannot generate code for it.
+    fn emit(&self, module: &Module, typing: &Typing) -> Result<String, String>;
+}
+
+/// The backends to pick from, by name.
+pub struct Backends {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+/// The built-in backend: the bytecode of `compiler`, as a listing of each
+/// chunk's instructions and constants.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bytecode;
+
+
+impl Backends {
+    /// A registry of the backends built into this crate.
+    pub fn new() -> Self {
+        Backends {
+            backends: vec![Box::new(Bytecode)],
+        }
+    }
+
+    /// Adds `backend`, in place of any already registered under its name.
+    pub fn register<B: Backend + 'static>(&mut self, backend: B) {
+        let backend: Box<dyn Backend> = Box::new(backend);
+
+        match self.backends.iter().position(|b| b.name() == backend.name()) {
+            Some(ix) => self.backends[ix] = backend,
+            None     => self.backends.push(backend),
+        }
+    }
+
+    /// The backend registered under `name`.
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .map(|backend| &**backend)
+    }
+
+    /// The names of the backends, in the order they were registered.
+    pub fn names(&self) -> Vec<&str> {
+        self.backends.iter().map(|backend| backend.name()).collect()
+    }
+}
+
+impl Default for Backends {
+    fn default() -> Self {
+        Backends::new()
+    }
+}
+
+impl Backend for Bytecode {
+    fn name(&self) -> &str {
+        "bytecode"
+    }
+
+    // `typeck` only gives the types of top-level names, not of each
+    // operand, so there is nothing yet to pick typed instructions by.
+    fn emit(
+        &self,
+        module:  &Module,
+        _typing: &Typing
+    ) -> Result<String, String> {
+        let chunk = compiler::compile(module).map_err(|e| e.to_string())?;
+        let mut listing = String::new();
+        write_chunk(&mut listing, &chunk);
+
+        Ok(listing)
+    }
+}
+
+/// Writes out the instructions and constants of `chunk`, then those of
+/// every function among its constants.
+fn write_chunk(out: &mut String, chunk: &Chunk) {
+    *out += &format!(
+        "{} (arity {}, {} locals):\n",
+        chunk.name,
+        chunk.arity,
+        chunk.locals
+    );
+
+    for (ix, instr) in chunk.code.iter().enumerate() {
+        *out += &format!("{:>6}  {:?}\n", ix, instr);
+    }
+
+    for (ix, constant) in chunk.constants.iter().enumerate() {
+        match *constant {
+            Constant::Function(ref f) => {
+                *out += &format!("{:>6}  fn {}\n", format!("#{}", ix), f.name);
+            },
+            ref c => *out += &format!("{:>6}  {:?}\n", format!("#{}", ix), c),
+        }
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(ref f) = *constant {
+            *out += "\n";
+            write_chunk(out, f);
+        }
+    }
+}
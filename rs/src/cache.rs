@@ -0,0 +1,450 @@
+//! A content-addressed store for build artifacts, such as compiled chunks
+//! and module interfaces, kept in a directory between runs.
+//!
+//! Each artifact is stored once, in `objects/`, under the hash of what is
+//! in it, however many builds produced it. What a build looks artifacts up
+//! by is a `Key`, the hash of what went into making the artifact; a file in
+//! `keys/` under each key names the object made from it. An object whose
+//! contents no longer hash to its name is treated as missing.
+//!
+//! Nothing is ever removed but by `Cache::gc`, which removes the objects
+//! used least recently until the store fits in a given size.
+//!
+//! ```
+//! use std::env;
+//! use brouwer::cache::{Cache, Key};
+//!
+//! let cache = Cache::open(env::temp_dir().join("brouwer-cache-example"));
+//! let key = Key::new("chunk", &["module Main\n\nx = 1\n"]);
+//!
+//! cache.put(&key, "main (arity 0, 0 locals): ...").unwrap();
+//! assert_eq!(cache.get(&key).unwrap(), "main (arity 0, 0 locals): ...");
+//!
+//! cache.gc(0).unwrap();
+//! assert_eq!(cache.get(&key), None);
+//! ```
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use edit;
+
+
+/// Where the cache is kept, relative to the working directory, unless
+/// told otherwise.
+pub const DEFAULT_DIR: &str = ".brouwer-cache";
+
+/// A hash: of what went into making an artifact, to look it up by, or of
+/// an artifact itself, to store it under.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key(u128);
+
+/// A store of artifacts in a directory, which is made when the first
+/// artifact is put in it.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// What `Cache::gc` did.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct GcReport {
+    /// How many artifacts were removed.
+    pub removed:   usize,
+    /// How many bytes were freed.
+    pub freed:     u64,
+    /// How many bytes the store takes up now.
+    pub remaining: u64,
+}
+
+/// A file in the store, as `gc` sees it.
+struct Entry {
+    path: PathBuf,
+    len:  u64,
+    used: SystemTime,
+}
+
+
+impl Key {
+    /// The key of an artifact of kind `kind` (`"chunk"`, say) made from
+    /// `inputs`, in order.
+    pub fn new(kind: &str, inputs: &[&str]) -> Self {
+        let mut hash = Fnv::new();
+        hash.write(kind.as_bytes());
+
+        for input in inputs {
+            // The length keeps `["ab", "c"]` apart from `["a", "bc"]`.
+            hash.write(&(input.len() as u64).to_le_bytes());
+            hash.write(input.as_bytes());
+        }
+
+        Key(hash.finish())
+    }
+
+    /// The key of the artifact `contents` itself.
+    fn of_contents(contents: &str) -> Self {
+        let mut hash = Fnv::new();
+        hash.write(contents.as_bytes());
+
+        Key(hash.finish())
+    }
+
+    /// Parses a key written out by `Display`.
+    fn parse(s: &str) -> Option<Self> {
+        if s.len() != 32 {
+            return None;
+        }
+
+        u128::from_str_radix(s, 16).ok().map(Key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl Cache {
+    /// The store in `dir`.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Self {
+        Cache {
+            dir: dir.into(),
+        }
+    }
+
+    /// The directory the store is in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The artifact stored under `key`, if there is one, marking it as just
+    /// used so that `gc` keeps it over others.
+    pub fn get(&self, key: &Key) -> Option<String> {
+        let key_path = self.key_path(key);
+        let object = Key::parse(read(&key_path).ok()?.trim())?;
+        let object_path = self.object_path(&object);
+        let contents = read(&object_path).ok()?;
+
+        if Key::of_contents(&contents) != object {
+            return None;
+        }
+
+        touch(&key_path);
+        touch(&object_path);
+
+        Some(contents)
+    }
+
+    /// Stores `artifact` under `key`, replacing what was stored under it.
+    pub fn put(&self, key: &Key, artifact: &str) -> Result<(), String> {
+        let object = Key::of_contents(artifact);
+        let object_path = self.object_path(&object);
+
+        // The same artifact made from other inputs is stored only once.
+        match read(&object_path) {
+            Ok(ref stored) if stored == artifact => touch(&object_path),
+            _ => {
+                create_parent(&object_path)?;
+                edit::write_atomic(&object_path, artifact)?;
+            },
+        }
+
+        let key_path = self.key_path(key);
+        create_parent(&key_path)?;
+
+        edit::write_atomic(&key_path, &object.to_string())
+    }
+
+    /// How many bytes the store takes up.
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.entries()?.iter().map(|entry| entry.len).sum())
+    }
+
+    /// Removes the artifacts used least recently until the store takes up
+    /// no more than `max_size` bytes, then the keys of artifacts that are
+    /// gone.
+    pub fn gc(&self, max_size: u64) -> io::Result<GcReport> {
+        let mut entries = self.entries()?;
+        let mut report = GcReport {
+            remaining: entries.iter().map(|entry| entry.len).sum(),
+            ..GcReport::default()
+        };
+
+        entries.sort_by_key(|entry| entry.used);
+
+        for entry in entries {
+            if report.remaining <= max_size {
+                break;
+            }
+
+            if entry.path.starts_with(self.dir.join("objects")) {
+                fs::remove_file(&entry.path)?;
+
+                report.removed += 1;
+                report.freed += entry.len;
+                report.remaining -= entry.len;
+            }
+        }
+
+        for entry in self.entries()? {
+            if !entry.path.starts_with(self.dir.join("keys")) {
+                continue;
+            }
+
+            let object = read(&entry.path).ok()
+                                          .and_then(|s| Key::parse(s.trim()));
+            let dangling = object.is_none_or(|object|
+                !self.object_path(&object).is_file()
+            );
+
+            if dangling {
+                fs::remove_file(&entry.path)?;
+
+                report.freed += entry.len;
+                report.remaining -= entry.len;
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn key_path(&self, key: &Key) -> PathBuf {
+        self.sharded("keys", key)
+    }
+
+    fn object_path(&self, object: &Key) -> PathBuf {
+        self.sharded("objects", object)
+    }
+
+    /// Where `key` goes in the subdirectory `sub`: in a directory named
+    /// after its first two digits, so that no one directory gets too big.
+    fn sharded(&self, sub: &str, key: &Key) -> PathBuf {
+        let name = key.to_string();
+
+        self.dir.join(sub).join(&name[..2]).join(&name[2..])
+    }
+
+    /// Every key and object in the store. There are none if the directory
+    /// has not been made yet.
+    fn entries(&self) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+
+        for sub in &["keys", "objects"] {
+            let shards = match fs::read_dir(self.dir.join(sub)) {
+                Ok(shards) => shards,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            for shard in shards {
+                let shard = shard?.path();
+
+                if !shard.is_dir() {
+                    continue;
+                }
+
+                for file in fs::read_dir(&shard)? {
+                    let file = file?;
+                    let metadata = file.metadata()?;
+
+                    // Skip what `write_atomic` has not finished writing.
+                    if !metadata.is_file() ||
+                       file.file_name().to_string_lossy().starts_with('.')
+                    {
+                        continue;
+                    }
+
+                    entries.push(Entry {
+                        path: file.path(),
+                        len:  metadata.len(),
+                        used: metadata.modified()?,
+                    });
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Parses a size in bytes, such as `1048576`, `512K`, `100M`, or `2G`.
+///
+/// ```
+/// use brouwer::cache::parse_size;
+///
+/// assert_eq!(parse_size("100M"), Ok(100 * 1024 * 1024));
+/// assert!(parse_size("lots").is_err());
+/// ```
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, scale) = match s.chars().last() {
+        Some('K') | Some('k') => (&s[..s.len() - 1], 1 << 10),
+        Some('M') | Some('m') => (&s[..s.len() - 1], 1 << 20),
+        Some('G') | Some('g') => (&s[..s.len() - 1], 1 << 30),
+        _                     => (s, 1),
+    };
+
+    digits.parse::<u64>()
+          .ok()
+          .and_then(|n| n.checked_mul(scale))
+          .ok_or_else(|| format!("{} is not a size, like 512K or 100M", s))
+}
+
+/// 128-bit FNV-1a, which is plenty to tell apart the artifacts of one
+/// project, and needs nothing outside the standard library.
+struct Fnv(u128);
+
+impl Fnv {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME:  u128 = 0x0000000001000000000000000000013b;
+
+    fn new() -> Self {
+        Fnv(Fnv::OFFSET)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u128::from(byte);
+            self.0 = self.0.wrapping_mul(Fnv::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+fn read(path: &Path) -> io::Result<String> {
+    let mut contents = String::new();
+    File::open(path)?.read_to_string(&mut contents)?;
+
+    Ok(contents)
+}
+
+/// Marks the file at `path` as just used. If that fails, the file is only
+/// more likely to be collected.
+fn touch(path: &Path) {
+    if let Ok(file) = fs::OpenOptions::new().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+fn create_parent(path: &Path) -> Result<(), String> {
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent).map_err(|e|
+            format!("{}: {}", parent.display(), e)
+        ),
+        None         => Ok(()),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::time::Duration;
+
+    /// An empty store of the test's own, named after it.
+    fn scratch(name: &str) -> Cache {
+        let dir = env::temp_dir().join(format!(
+            "brouwer-cache-{}-{}",
+            name,
+            process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        Cache::open(dir)
+    }
+
+    /// Marks the file at `path` as last used `secs` seconds ago.
+    fn age(path: &Path, secs: u64) {
+        let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn an_object_that_does_not_match_its_hash_is_missing() {
+        let cache = scratch("corrupt");
+        let key = Key::new("chunk", &["x = 1"]);
+        cache.put(&key, "load 1").unwrap();
+
+        let object = Key::of_contents("load 1");
+        fs::write(cache.object_path(&object), "load 2").unwrap();
+        assert_eq!(cache.get(&key), None);
+
+        fs::write(cache.key_path(&key), "not a key").unwrap();
+        assert_eq!(cache.get(&key), None);
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn an_artifact_made_from_other_inputs_is_stored_once() {
+        let cache = scratch("dedup");
+        cache.put(&Key::new("chunk", &["x = 1"]), "load 1").unwrap();
+        let once = cache.size().unwrap();
+        cache.put(&Key::new("chunk", &["x = (1)"]), "load 1").unwrap();
+
+        // Only the second key takes up more room.
+        assert_eq!(cache.size().unwrap(), once + 32);
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn gc_removes_what_was_used_least_recently_and_its_keys() {
+        let cache = scratch("gc");
+        let (old, new) = (Key::new("chunk", &["a"]), Key::new("chunk", &["b"]));
+        cache.put(&old, "old artifact").unwrap();
+        cache.put(&new, "new artifact").unwrap();
+
+        age(&cache.key_path(&old), 60);
+        age(&cache.object_path(&Key::of_contents("old artifact")), 60);
+
+        let size = cache.size().unwrap();
+        let report = cache.gc(size - 1).unwrap();
+
+        assert_eq!(report.removed, 1);
+        assert_eq!(report.freed, 32 + "old artifact".len() as u64);
+        assert_eq!(report.remaining, cache.size().unwrap());
+        assert_eq!(cache.get(&old), None);
+        assert!(!cache.key_path(&old).exists());
+        assert_eq!(cache.get(&new).unwrap(), "new artifact");
+
+        fs::remove_dir_all(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn a_store_never_written_to_is_empty() {
+        let cache = scratch("empty");
+
+        assert_eq!(cache.size().unwrap(), 0);
+        assert_eq!(cache.gc(0).unwrap(), GcReport::default());
+        assert_eq!(cache.get(&Key::new("chunk", &[])), None);
+    }
+
+    #[test]
+    fn put_fails_where_the_store_cannot_be_made() {
+        let cache = scratch("blocked");
+        fs::write(cache.dir(), "not a directory").unwrap();
+
+        assert!(cache.put(&Key::new("chunk", &[]), "load 1").is_err());
+
+        fs::remove_file(cache.dir()).unwrap();
+    }
+
+    #[test]
+    fn sizes_that_do_not_fit_are_not_sizes() {
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert!(parse_size("99999999999999999G").is_err());
+        assert!(parse_size("G").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! The passes that `brouwer check` runs over each module of a program, so
+//! that everything that checks a program (the command line, the compile
+//! server) finds the same problems in it.
+//!
+//! ```
+//! use brouwer::check;
+//! use brouwer::loader::Loader;
+//! use brouwer::plugin::Plugins;
+//! use brouwer::vfs::MemoryFs;
+//!
+//! let mut files = MemoryFs::new();
+//! files.insert("Main.bwr", "module Main\nimport A (a)\n\nb\n");
+//! files.insert("A.bwr", "module A exposing a\n\na = 1\n");
+//!
+//! let program = Loader::with_fs(files, vec![".".into()])
+//!     .load("Main.bwr")
+//!     .unwrap();
+//! let main = program.get("Main").unwrap();
+//! let report = check::check_module(&Plugins::new(), &program, main);
+//!
+//! assert_eq!(report.errors, vec!["4:1: b is not defined"]);
+//! ```
+
+use diagnostics::Diagnostic;
+use exhaustive::{self, CaseProblem};
+use ice;
+use loader::{LoadedModule, Program};
+use plugin::{ModuleView, Plugins};
+use resolve::{self, ResolveWarning};
+use typeck;
+
+
+/// What checking a module found wrong with it.
+#[derive(Clone, Debug, Default)]
+pub struct CheckReport {
+    /// What is wrong with its names, then with its types.
+    pub errors:   Vec<String>,
+    /// What is probably wrong with its names.
+    pub warnings: Vec<ResolveWarning>,
+    /// What is wrong with its `case`s.
+    pub cases:    Vec<CaseProblem>,
+    /// What the plugins reported, in the order they reported it.
+    pub plugins:  Vec<Diagnostic>,
+}
+
+
+impl CheckReport {
+    /// The warnings, the problems with `case`s, and what the plugins
+    /// reported, in that order, as diagnostics.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.warnings
+            .iter()
+            .map(Diagnostic::from)
+            .chain(self.cases.iter().map(Diagnostic::from))
+            .chain(self.plugins.iter().cloned())
+            .collect()
+    }
+}
+
+/// Resolves the names in `loaded`, a module of `program`, infers its
+/// types, and checks its `case`s, calling `plugins` after each pass.
+pub fn check_module(
+    plugins: &Plugins,
+    program: &Program,
+    loaded:  &LoadedModule
+) -> CheckReport {
+    let view = ModuleView::new(program, loaded);
+
+    ice::enter_pass("plugins", &loaded.path);
+    let mut reported = plugins.after_parse(&view);
+
+    ice::enter_pass("resolve", &loaded.path);
+    let resolution = resolve::resolve(&loaded.module);
+    let mut errors: Vec<String> = resolution.errors
+                                            .iter()
+                                            .map(|e| e.to_string())
+                                            .collect();
+
+    ice::enter_pass("plugins", &loaded.path);
+    reported.extend(plugins.after_resolve(&view, &resolution));
+
+    ice::enter_pass("typeck", &loaded.path);
+    let typing = typeck::infer(&loaded.module);
+    errors.extend(typing.errors.iter().map(|e| e.to_string()));
+
+    ice::enter_pass("plugins", &loaded.path);
+    reported.extend(plugins.after_typeck(&view, &typing));
+
+    ice::enter_pass("exhaustive", &loaded.path);
+
+    CheckReport {
+        errors:   errors,
+        warnings: resolution.warnings,
+        cases:    exhaustive::check(&loaded.module),
+        plugins:  reported,
+    }
+}
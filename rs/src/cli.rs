@@ -0,0 +1,189 @@
+//! Parsing the command line of a subcommand: its flags, in any order and
+//! mixed in with its operands (the files and the like that it works on).
+//!
+//! A flag that takes a value is given it either as the next argument,
+//! `--width 60`, or after an `=`, `--width=60`. Everything after `--` is
+//! an operand, even if it starts with `--`.
+//!
+//! Arguments need not be UTF-8, as file names need not be: operands and
+//! the values of flags are kept as they were given, and `path` gives back
+//! a value untouched, where `value` replaces what is not UTF-8.
+
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::str::FromStr;
+
+
+/// A flag that a subcommand accepts.
+pub struct Flag {
+    /// The flag's name, with its leading `--`.
+    pub name:  &'static str,
+    /// What the flag's value is, for error messages, if it takes one.
+    pub value: Option<&'static str>,
+}
+
+/// The flags that every subcommand accepts.
+pub const SHARED_FLAGS: &[Flag] = &[
+    Flag { name: "--format",     value: Some("a format") },
+    Flag { name: "--quiet",      value: None },
+    Flag { name: "--max-errors", value: Some("a number") },
+];
+
+/// The arguments given to a subcommand.
+pub struct Args {
+    /// The arguments that are not flags or the values of flags, in order.
+    pub operands: Vec<OsString>,
+    flags:        Vec<(&'static str, Option<OsString>)>,
+}
+
+impl Args {
+    /// Splits `args` into operands and flags, which must be in `flags` or
+    /// `SHARED_FLAGS`.
+    pub fn parse(args: &[OsString], flags: &[Flag]) -> Result<Args, String> {
+        let mut parsed = Args {
+            operands: Vec::new(),
+            flags:    Vec::new(),
+        };
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.operands.extend(args.cloned());
+
+                break;
+            }
+
+            let arg = match arg.to_str() {
+                Some(arg) if arg.starts_with("--") => arg,
+                _ if !arg.to_string_lossy().starts_with("--") => {
+                    parsed.operands.push(arg.clone());
+
+                    continue;
+                },
+                _ => return Err(format!(
+                    "{}: give a value that is not UTF-8 as the next argument",
+                    arg.to_string_lossy()
+                )),
+            };
+
+            let (name, value) = match arg.find('=') {
+                Some(ix) => (&arg[..ix], Some(OsString::from(&arg[ix + 1..]))),
+                None     => (arg, None),
+            };
+
+            let flag = match flags.iter()
+                                  .chain(SHARED_FLAGS)
+                                  .find(|f| f.name == name)
+            {
+                Some(flag) => flag,
+                None       => return Err(format!("unknown flag {}", name)),
+            };
+
+            let value = match (flag.value, value) {
+                (None, None)       => None,
+                (None, Some(_))    => {
+                    return Err(format!("{} does not take a value", name));
+                },
+                (Some(_), Some(v)) => Some(v),
+                (Some(what), None) => match args.next() {
+                    Some(v) => Some(v.clone()),
+                    None    => {
+                        return Err(format!("{} expects {}", name, what));
+                    },
+                },
+            };
+
+            parsed.flags.push((flag.name, value));
+        }
+
+        Ok(parsed)
+    }
+
+    /// Whether the flag `name` was given.
+    pub fn has(&self, name: &str) -> bool {
+        self.flags.iter().any(|&(flag, _)| flag == name)
+    }
+
+    /// The value last given for the flag `name`, if it was given, with
+    /// anything in it that is not UTF-8 replaced by `U+FFFD`.
+    pub fn value(&self, name: &str) -> Option<Cow<'_, str>> {
+        self.raw_value(name).map(OsStr::to_string_lossy)
+    }
+
+    /// The value last given for the flag `name`, a file name, if it was
+    /// given.
+    pub fn path(&self, name: &str) -> Option<&Path> {
+        self.raw_value(name).map(Path::new)
+    }
+
+    /// Every value given for the flag `name`, each a file name, in order.
+    pub fn paths(&self, name: &str) -> Vec<&Path> {
+        self.flags
+            .iter()
+            .filter(|&&(flag, _)| flag == name)
+            .filter_map(|(_, value)| value.as_ref())
+            .map(Path::new)
+            .collect()
+    }
+
+    fn raw_value(&self, name: &str) -> Option<&OsStr> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|&&(flag, _)| flag == name)
+            .and_then(|(_, value)| value.as_ref())
+            .map(|value| value.as_os_str())
+    }
+
+    /// The value of the flag `name` as a number, if it was given.
+    pub fn number<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(value) => value.parse().map(Some).map_err(|_|
+                format!("{} expects a number", name)
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// The value of the flag `name`, if it was given, which must be one of
+    /// `choices`.
+    pub fn choice(
+        &self,
+        name:    &str,
+        choices: &[&'static str]
+    ) -> Result<Option<&'static str>, String> {
+        let value = match self.value(name) {
+            Some(value) => value,
+            None        => return Ok(None),
+        };
+
+        match choices.iter().find(|&&choice| choice == value) {
+            Some(&choice) => Ok(Some(choice)),
+            None          => Err(format!(
+                "{} expects {}",
+                name,
+                one_of(choices)
+            )),
+        }
+    }
+
+    /// The only operand, which is called `what` if it is missing.
+    pub fn operand(&self, what: &str) -> Result<&OsStr, String> {
+        match self.operands.len() {
+            1 => Ok(&self.operands[0]),
+            0 => Err(format!("Please provide the {}.", what)),
+            _ => Err(format!("Please provide only the {}.", what)),
+        }
+    }
+}
+
+/// Lists `choices` as alternatives: `a`, `a or b`, or `a, b, or c`.
+fn one_of(choices: &[&str]) -> String {
+    match choices.split_last() {
+        None                  => String::new(),
+        Some((last, []))      => last.to_string(),
+        Some((last, [first])) => format!("{} or {}", first, last),
+        Some((last, rest))    => format!("{}, or {}", rest.join(", "), last),
+    }
+}
@@ -0,0 +1,872 @@
+//! Lowering of typed syntax trees (see `ast`) to bytecode.
+//!
+//! Each function becomes a `Chunk`: a sequence of stack-machine
+//! `Instr`uctions, a pool of constants, and a count of local variable
+//! slots. The instructions are those of `ops.md`, plus constant loads,
+//! globals, jumps, calls, and aggregate construction.
+//!
+//! The compiler does not use the types `typeck` infers yet, so it cannot pick
+//! between, say, `iadd` and `fadd`. Operators are instead compiled as calls to
+//! globals of the same name (`a + b` is `(+) a b`), and so are ranges (`a..b`
+//! is `(..) a b`, and `a..=b` is `(..=) a b`). Top-level bindings are globals;
+//! bindings inside functions are locals, and functions do not capture the
+//! locals of enclosing functions. The names a pattern of a `case` (or of an
+//! `if` or `while` with `<-`) binds are locals wherever they are, each in a
+//! slot of its own, in scope until the end of the branch or loop. A function
+//! body, like each branch of an `if`, evaluates to its last line, so `return`
+//! is only needed to leave early; a `while` loop evaluates to unit. A `case` is
+//! a chain of tests, so its patterns can only be literals, ranges, names, `_`,
+//! and string prefix and suffix patterns, and its branches without a guard must
+//! match every value (see `exhaustive`), there being nothing to do with a value
+//! that no branch matches. A guard is tested once the names of its branch are
+//! bound, and an or-pattern by testing each alternative in turn, so it may not
+//! bind any names. A prefix is tested for with a call to `startsWith`, and the
+//! rest of the string bound with a call to `drop`; a suffix with `endsWith` and
+//! `dropEnd`.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use ast::{CaseBranch, Expr, ExprKind, FnDecl, Module, Name, Operator, Pattern,
+          PatternKind, Stmt};
+use exhaustive;
+use token::Span;
+
+
+/// A single bytecode instruction. Jump targets are indices into the
+/// enclosing chunk's `code`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instr {
+    /// `nop`
+    Nop,
+    /// `iconst_n1` to `iconst_3`: pushes an integer from -1 to 3.
+    IConst(i8),
+    /// `fconst_n1` to `fconst_3`: pushes a float from -1 to 3.
+    FConst(i8),
+    /// Pushes `True` or `False`.
+    BConst(bool),
+    /// Pushes the constant at this index of the chunk's pool.
+    Ldc(usize),
+    /// Pushes the unit value, `()`, the only value of type `Unit`.
+    Unit,
+    /// `load` and `load_0` to `load_3`
+    Load(usize),
+    /// `store` and `store_0` to `store_3`
+    Store(usize),
+    /// Pushes the global named by the string constant at this index.
+    LoadGlobal(usize),
+    /// Pops into the global named by the string constant at this index.
+    StoreGlobal(usize),
+    /// `pop`
+    Pop,
+    /// `dup`
+    Dup,
+    /// `swap`
+    Swap,
+    /// `iadd`
+    IAdd,
+    /// `fadd`
+    FAdd,
+    /// `isub`
+    ISub,
+    /// `fsub`
+    FSub,
+    /// `imul`
+    IMul,
+    /// `fmul`
+    FMul,
+    /// `idiv`
+    IDiv,
+    /// `fdiv`
+    FDiv,
+    /// `imod`
+    IMod,
+    /// `fmod`
+    FMod,
+    /// `ineg`
+    INeg,
+    /// `fneg`
+    FNeg,
+    /// `i2f`
+    I2F,
+    /// `f2i`
+    F2I,
+    /// `icmp`
+    ICmp,
+    /// `fcmpl`
+    FCmpL,
+    /// Continues at the given instruction.
+    Jump(usize),
+    /// Pops a value, and continues at the given instruction if it is
+    /// `False`. Only `False` is false: no other value, not even `0` or
+    /// `()`, stands in for a `Bool`.
+    JumpIfFalse(usize),
+    /// Calls a function with this many arguments. The function is pushed
+    /// first, then its arguments in order; all are replaced by the result.
+    Call(usize),
+    /// Returns the top of the stack to the caller.
+    Return,
+    /// Replaces a `Result` with whether it is a failure.
+    Failed,
+    /// Replaces a `Result` that is a success with what it holds.
+    Unwrap,
+    /// Replaces this many values with a tuple of them, in order.
+    MakeTuple(usize),
+    /// Replaces this many values with a list of them, in order.
+    MakeList(usize),
+    /// Replaces this many values with a set of them.
+    MakeSet(usize),
+}
+
+/// An entry in a chunk's constant pool.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Constant {
+    /// An integer too big for `IConst`.
+    Int(i64),
+    /// A float that `FConst` cannot represent.
+    Real(f64),
+    /// A character literal.
+    Char(char),
+    /// A string literal, or the name of a global.
+    Str(String),
+    /// A compiled function.
+    Function(Chunk),
+}
+
+/// The compiled form of a function, or of a whole program.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Chunk {
+    /// The function's name; `main` for the top level of a program.
+    pub name:      String,
+    /// How many parameters the function takes. They occupy the first
+    /// local slots.
+    pub arity:     usize,
+    /// How many local slots the function uses, parameters included.
+    pub locals:    usize,
+    /// The instructions.
+    pub code:      Vec<Instr>,
+    /// The constants referred to by `code`.
+    pub constants: Vec<Constant>,
+}
+
+/// Why a program could not be compiled.
+#[derive(Clone, Debug)]
+pub enum CompileError {
+    /// A construct the compiler cannot lower yet.
+    Unsupported {
+        /// What the construct is, e.g. `"case expression"`.
+        construct: &'static str,
+        /// Where it is.
+        span:      Span,
+    },
+}
+
+/// Compiles a single chunk.
+struct FnCompiler {
+    chunk:     Chunk,
+    locals:    HashMap<String, usize>,
+    top_level: bool,
+}
+
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Unsupported { construct, span } => write!(
+                f,
+                "{}:{}: cannot compile {} yet",
+                span.line,
+                span.col,
+                construct
+            ),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+impl FnCompiler {
+    fn new<S: Into<String>>(name: S, top_level: bool) -> Self {
+        FnCompiler {
+            chunk:     Chunk {
+                name:      name.into(),
+                arity:     0,
+                locals:    0,
+                code:      Vec::with_capacity(64),
+                constants: Vec::new(),
+            },
+            locals:    HashMap::new(),
+            top_level: top_level,
+        }
+    }
+
+    fn finish(self) -> Chunk {
+        self.chunk
+    }
+
+    /// Appends `instr`, returning its index.
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.chunk.code.push(instr);
+
+        self.chunk.code.len() - 1
+    }
+
+    /// Points the jump at `at` to the next instruction to be emitted.
+    fn patch(&mut self, at: usize) {
+        let target = self.chunk.code.len();
+
+        match self.chunk.code[at] {
+            Instr::Jump(ref mut t)        |
+            Instr::JumpIfFalse(ref mut t) => *t = target,
+            _ => panic!("patching a non-jump instruction"),
+        }
+    }
+
+    /// The pool index of `constant`, adding it if it is not already there.
+    fn constant(&mut self, constant: Constant) -> usize {
+        if let Some(ix) = self.chunk.constants.iter().position(|c|
+            *c == constant
+        ) {
+            return ix;
+        }
+
+        self.chunk.constants.push(constant);
+
+        self.chunk.constants.len() - 1
+    }
+
+    fn local(&mut self, name: &str) -> usize {
+        if let Some(&slot) = self.locals.get(name) {
+            return slot;
+        }
+
+        self.fresh_local(name)
+    }
+
+    /// A new slot for `name`, which hides any slot it already had until
+    /// `locals` is put back as it was before.
+    fn fresh_local(&mut self, name: &str) -> usize {
+        let slot = self.chunk.locals;
+        self.chunk.locals += 1;
+        self.locals.insert(name.to_string(), slot);
+
+        slot
+    }
+
+    fn load_name(&mut self, name: &str) {
+        let slot = self.locals.get(name).cloned();
+        let instr = match slot {
+            Some(slot) => Instr::Load(slot),
+            None        => {
+                let ix = self.constant(Constant::Str(name.to_string()));
+
+                Instr::LoadGlobal(ix)
+            },
+        };
+
+        self.emit(instr);
+    }
+
+    fn store_name(&mut self, name: &str) {
+        let slot = self.locals.get(name).cloned();
+        let instr = match slot {
+            Some(slot)             => Instr::Store(slot),
+            None if self.top_level => {
+                let ix = self.constant(Constant::Str(name.to_string()));
+
+                Instr::StoreGlobal(ix)
+            },
+            None => Instr::Store(self.local(name)),
+        };
+
+        self.emit(instr);
+    }
+
+    /// Pops the top of the stack into a fresh local slot for `name`, as
+    /// bound by a pattern that the value has matched.
+    fn store_match(&mut self, name: &str) {
+        let slot = self.fresh_local(name);
+
+        self.emit(Instr::Store(slot));
+    }
+
+    /// Pops the top of the stack into the names bound by `pattern`.
+    fn bind(&mut self, pattern: &Pattern) -> Result<(), CompileError> {
+        match pattern.kind {
+            PatternKind::Bind(ref ident) => {
+                self.store_name(&ident.name);
+
+                Ok(())
+            },
+            PatternKind::Wildcard => {
+                self.emit(Instr::Pop);
+
+                Ok(())
+            },
+            _ => Err(unsupported("destructuring pattern", pattern.span)),
+        }
+    }
+
+    fn compile_stmts(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        for stmt in stmts {
+            self.compile_stmt(stmt)?;
+            self.emit(Instr::Pop);
+        }
+
+        Ok(())
+    }
+
+    /// Compiles the lines of a block, leaving the value of the last one on
+    /// the stack, or unit if the block is empty.
+    fn compile_block(&mut self, stmts: &[Stmt]) -> Result<(), CompileError> {
+        if let Some((last, init)) = stmts.split_last() {
+            self.compile_stmts(init)?;
+            self.compile_stmt(last)
+        } else {
+            self.emit(Instr::Unit);
+
+            Ok(())
+        }
+    }
+
+    /// Compiles a line, leaving its value on the stack. The value of a
+    /// binding is the value bound.
+    fn compile_stmt(&mut self, stmt: &Stmt) -> Result<(), CompileError> {
+        match *stmt {
+            Stmt::Let(ref let_) => {
+                self.compile_expr(&let_.value)?;
+                self.emit(Instr::Dup);
+                self.bind(&let_.pattern)
+            },
+            Stmt::Fn(ref fn_decl) => {
+                let chunk = compile_fn(fn_decl)?;
+                let ix = self.constant(Constant::Function(chunk));
+
+                self.emit(Instr::Ldc(ix));
+                self.emit(Instr::Dup);
+                self.store_name(&fn_decl.name.name);
+
+                Ok(())
+            },
+            Stmt::Fixity(_) => {
+                self.emit(Instr::Unit);
+
+                Ok(())
+            },
+            Stmt::Expr(ref expr) => self.compile_expr(expr),
+        }
+    }
+
+    fn compile_expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match expr.kind {
+            ExprKind::Int(value)  => self.push_int(value),
+            ExprKind::Real(value) => self.push_real(value),
+            ExprKind::Bool(b) => {
+                self.emit(Instr::BConst(b));
+            },
+            ExprKind::Char(c) => {
+                let ix = self.constant(Constant::Char(c));
+                self.emit(Instr::Ldc(ix));
+            },
+            ExprKind::Str(ref s) => {
+                let ix = self.constant(Constant::Str(s.clone()));
+                self.emit(Instr::Ldc(ix));
+            },
+            ExprKind::Name(ref name) => match *name {
+                Name::Plain(ref ident) => self.load_name(&ident.name),
+                _ => return Err(unsupported("qualified name", name.span())),
+            },
+            ExprKind::Op(ref op) => self.load_op(op)?,
+            ExprKind::Apply(ref func, ref args) => {
+                self.compile_expr(func)?;
+
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+
+                self.emit(Instr::Call(args.len()));
+            },
+            ExprKind::BinOp(ref op, ref lhs, ref rhs) => {
+                self.load_op(op)?;
+                self.compile_expr(lhs)?;
+                self.compile_expr(rhs)?;
+                self.emit(Instr::Call(2));
+            },
+            ExprKind::Range(ref lo, ref hi, inclusive) => {
+                self.load_name(if inclusive { "..=" } else { ".." });
+                self.compile_expr(lo)?;
+                self.compile_expr(hi)?;
+                self.emit(Instr::Call(2));
+            },
+            ExprKind::Index(..) | ExprKind::Slice(..) => {
+                return Err(unsupported("indexing", expr.span));
+            },
+            ExprKind::Neg(ref operand) => {
+                self.load_name("negate");
+                self.compile_expr(operand)?;
+                self.emit(Instr::Call(1));
+            },
+            ExprKind::Tuple(ref elements) => {
+                self.compile_elements(elements)?;
+
+                let instr = if elements.is_empty() {
+                    Instr::Unit
+                } else {
+                    Instr::MakeTuple(elements.len())
+                };
+                self.emit(instr);
+            },
+            ExprKind::List(ref elements) => {
+                self.compile_elements(elements)?;
+                self.emit(Instr::MakeList(elements.len()));
+            },
+            ExprKind::Set(ref elements) => {
+                self.compile_elements(elements)?;
+                self.emit(Instr::MakeSet(elements.len()));
+            },
+            ExprKind::Return(ref value) => {
+                self.compile_expr(value)?;
+                self.emit(Instr::Return);
+            },
+            // A failure is returned as it is, and a success unwrapped.
+            ExprKind::Propagate(ref result) => {
+                self.compile_expr(result)?;
+                self.emit(Instr::Dup);
+                self.emit(Instr::Failed);
+                let to_success = self.emit(Instr::JumpIfFalse(0));
+
+                self.emit(Instr::Return);
+                self.patch(to_success);
+                self.emit(Instr::Unwrap);
+            },
+            ExprKind::If(ref cond, ref then, ref else_) => {
+                self.compile_if_else(cond, then, else_.as_ref())?;
+            },
+            ExprKind::While(ref cond, ref body) => {
+                self.compile_while(cond, body)?;
+            },
+            ExprKind::WhileLet(ref pattern, ref subject, ref body) => {
+                self.compile_while_let(pattern, subject, body)?;
+            },
+            ExprKind::Dict(..)     => {
+                return Err(unsupported("dict literal", expr.span));
+            },
+            ExprKind::ListComp(..) |
+            ExprKind::SetComp(..)  |
+            ExprKind::DictComp(..) => {
+                return Err(unsupported("comprehension", expr.span));
+            },
+            ExprKind::Lambda(..) => {
+                return Err(unsupported("anonymous function", expr.span));
+            },
+            ExprKind::Case(ref subject, ref branches) => {
+                self.compile_case(subject, branches, expr.span)?;
+            },
+            ExprKind::Try(..) => {
+                return Err(unsupported("try expression", expr.span));
+            },
+            ExprKind::For(..) => {
+                return Err(unsupported("for loop", expr.span));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn push_int(&mut self, value: i64) {
+        let instr = if (-1..=3).contains(&value) {
+            Instr::IConst(value as i8)
+        } else {
+            Instr::Ldc(self.constant(Constant::Int(value)))
+        };
+
+        self.emit(instr);
+    }
+
+    fn push_real(&mut self, value: f64) {
+        let instr = if value.fract() == 0.0 &&
+                       (-1.0..=3.0).contains(&value) &&
+                       !(value == 0.0 && value.is_sign_negative())
+        {
+            Instr::FConst(value as i8)
+        } else {
+            Instr::Ldc(self.constant(Constant::Real(value)))
+        };
+
+        self.emit(instr);
+    }
+
+    /// Pushes the function an operator stands for.
+    fn load_op(&mut self, op: &Operator) -> Result<(), CompileError> {
+        match *op {
+            Operator::Symbol(ref ident) => self.load_name(&ident.name),
+            Operator::Infixed(Name::Plain(ref ident)) => {
+                self.load_name(&ident.name);
+            },
+            Operator::Infixed(ref name) => {
+                return Err(unsupported("qualified name", name.span()));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn compile_elements(
+        &mut self,
+        elements: &[Expr]
+    ) -> Result<(), CompileError> {
+        for element in elements {
+            self.compile_expr(element)?;
+        }
+
+        Ok(())
+    }
+
+    fn compile_if_else(
+        &mut self,
+        cond:  &Expr,
+        then:  &[Stmt],
+        else_: Option<&Vec<Stmt>>
+    ) -> Result<(), CompileError> {
+        self.compile_expr(cond)?;
+        let to_else = self.emit(Instr::JumpIfFalse(0));
+
+        self.compile_block(then)?;
+        let to_end = self.emit(Instr::Jump(0));
+
+        self.patch(to_else);
+
+        match else_ {
+            Some(else_) => self.compile_block(else_)?,
+            None        => {
+                self.emit(Instr::Unit);
+            },
+        }
+
+        self.patch(to_end);
+
+        Ok(())
+    }
+
+    /// Compiles a `case` as a chain of tests of its subject, which is kept
+    /// in a local slot of its own: each branch in turn is taken if its
+    /// pattern is a name or `_`, or compares equal to the subject, or is a
+    /// range the subject is within, and its guard, if any, holds. The last
+    /// branch is taken untested unless it has a guard, so the `case` must
+    /// match every value.
+    fn compile_case(
+        &mut self,
+        subject:  &Expr,
+        branches: &[CaseBranch],
+        span:     Span
+    ) -> Result<(), CompileError> {
+        let patterns: Vec<&Pattern> = branches.iter()
+                                              .filter(|b| b.guard.is_none())
+                                              .map(|b| &b.pattern)
+                                              .collect();
+
+        if !exhaustive::is_exhaustive(&patterns) {
+            return Err(unsupported("non-exhaustive case", span));
+        }
+
+        self.compile_expr(subject)?;
+
+        // No name in the program has a space in it.
+        let slot = self.local(&format!("case {}", self.chunk.code.len()));
+        self.emit(Instr::Store(slot));
+
+        let mut to_end = Vec::with_capacity(branches.len());
+
+        for (ix, branch) in branches.iter().enumerate() {
+            let mut to_next = if ix + 1 < branches.len() ||
+                                 branch.guard.is_some()
+            {
+                self.compile_test(&branch.pattern, slot)?
+            } else {
+                Vec::new()
+            };
+
+            let outer = self.locals.clone();
+            self.bind_match(&branch.pattern, slot);
+
+            if let Some(ref guard) = branch.guard {
+                self.compile_expr(guard)?;
+                to_next.push(self.emit(Instr::JumpIfFalse(0)));
+            }
+
+            self.compile_block(&branch.body)?;
+            to_end.push(self.emit(Instr::Jump(0)));
+            self.locals = outer;
+
+            for at in to_next {
+                self.patch(at);
+            }
+        }
+
+        for at in to_end {
+            self.patch(at);
+        }
+
+        Ok(())
+    }
+
+    /// Tests whether the value in local `slot` matches `pattern`, returning
+    /// the jumps to patch to where to go if not.
+    fn compile_test(
+        &mut self,
+        pattern: &Pattern,
+        slot:    usize
+    ) -> Result<Vec<usize>, CompileError> {
+        let mut jumps = Vec::with_capacity(2);
+
+        match pattern.kind {
+            PatternKind::Bind(_) | PatternKind::Wildcard => {},
+            PatternKind::Range(lo, hi, inclusive) => {
+                self.load_name("<=");
+                self.push_int(lo);
+                self.emit(Instr::Load(slot));
+                self.emit(Instr::Call(2));
+                jumps.push(self.emit(Instr::JumpIfFalse(0)));
+
+                self.load_name(if inclusive { "<=" } else { "<" });
+                self.emit(Instr::Load(slot));
+                self.push_int(hi);
+                self.emit(Instr::Call(2));
+                jumps.push(self.emit(Instr::JumpIfFalse(0)));
+            },
+            PatternKind::Int(_)  |
+            PatternKind::Real(_) |
+            PatternKind::Bool(_) |
+            PatternKind::Char(_) |
+            PatternKind::Str(_)  => {
+                self.load_name("==");
+                self.emit(Instr::Load(slot));
+
+                match pattern.kind {
+                    PatternKind::Int(i)  => self.push_int(i),
+                    PatternKind::Real(r) => self.push_real(r),
+                    PatternKind::Bool(b) => {
+                        self.emit(Instr::BConst(b));
+                    },
+                    PatternKind::Char(c) => {
+                        let ix = self.constant(Constant::Char(c));
+                        self.emit(Instr::Ldc(ix));
+                    },
+                    PatternKind::Str(ref s) => {
+                        let ix = self.constant(Constant::Str(s.clone()));
+                        self.emit(Instr::Ldc(ix));
+                    },
+                    _ => unreachable!(),
+                }
+
+                self.emit(Instr::Call(2));
+                jumps.push(self.emit(Instr::JumpIfFalse(0)));
+            },
+            PatternKind::Prefix(ref affix, ref rest) |
+            PatternKind::Suffix(ref rest, ref affix) => {
+                match rest.kind {
+                    PatternKind::Bind(_) | PatternKind::Wildcard => {},
+                    _ => return Err(unsupported(
+                        "destructuring pattern",
+                        rest.span
+                    )),
+                }
+
+                self.load_name(match pattern.kind {
+                    PatternKind::Prefix(..) => "startsWith",
+                    _                       => "endsWith",
+                });
+                self.emit(Instr::Load(slot));
+
+                let ix = self.constant(Constant::Str(affix.clone()));
+                self.emit(Instr::Ldc(ix));
+                self.emit(Instr::Call(2));
+                jumps.push(self.emit(Instr::JumpIfFalse(0)));
+            },
+            PatternKind::As(_, ref matched) => {
+                return self.compile_test(matched, slot);
+            },
+            // Each alternative but the last goes on to the next if it does
+            // not match, and past the rest if it does.
+            PatternKind::Or(ref alternatives) => {
+                if !pattern.idents().is_empty() {
+                    return Err(unsupported(
+                        "or-pattern that binds names",
+                        pattern.span
+                    ));
+                }
+
+                let mut to_match = Vec::with_capacity(alternatives.len());
+
+                for (ix, alternative) in alternatives.iter().enumerate() {
+                    let to_next = self.compile_test(alternative, slot)?;
+
+                    if ix + 1 == alternatives.len() {
+                        jumps = to_next;
+                    } else {
+                        to_match.push(self.emit(Instr::Jump(0)));
+
+                        for at in to_next {
+                            self.patch(at);
+                        }
+                    }
+                }
+
+                for at in to_match {
+                    self.patch(at);
+                }
+            },
+            _ => return Err(unsupported("destructuring pattern", pattern.span)),
+        }
+
+        Ok(jumps)
+    }
+
+    /// Binds the names in `pattern`, which the value in local `slot` has
+    /// matched: the value itself, or what is left of the string once the
+    /// prefix or suffix of the pattern is dropped.
+    fn bind_match(&mut self, pattern: &Pattern, slot: usize) {
+        let (ident, drop, affix) = match pattern.kind {
+            PatternKind::Bind(ref ident) => {
+                self.emit(Instr::Load(slot));
+                self.store_match(&ident.name);
+
+                return;
+            },
+            PatternKind::As(ref ident, ref matched) => {
+                self.emit(Instr::Load(slot));
+                self.store_match(&ident.name);
+
+                return self.bind_match(matched, slot);
+            },
+            PatternKind::Prefix(ref affix, ref rest) => match rest.kind {
+                PatternKind::Bind(ref ident) => (ident, "drop", affix),
+                _                            => return,
+            },
+            PatternKind::Suffix(ref rest, ref affix) => match rest.kind {
+                PatternKind::Bind(ref ident) => (ident, "dropEnd", affix),
+                _                            => return,
+            },
+            _ => return,
+        };
+
+        self.load_name(drop);
+        self.push_int(affix.chars().count() as i64);
+        self.emit(Instr::Load(slot));
+        self.emit(Instr::Call(2));
+        self.store_match(&ident.name);
+    }
+
+    fn compile_while(
+        &mut self,
+        cond: &Expr,
+        body: &[Stmt]
+    ) -> Result<(), CompileError> {
+        let top = self.chunk.code.len();
+
+        self.compile_expr(cond)?;
+        let to_end = self.emit(Instr::JumpIfFalse(0));
+
+        self.compile_stmts(body)?;
+        self.emit(Instr::Jump(top));
+
+        self.patch(to_end);
+        self.emit(Instr::Unit);
+
+        Ok(())
+    }
+
+    /// Compiles `while pattern <- subject` as a `while` whose condition is
+    /// the test of the subject against the pattern (see `compile_case`).
+    fn compile_while_let(
+        &mut self,
+        pattern: &Pattern,
+        subject: &Expr,
+        body:    &[Stmt]
+    ) -> Result<(), CompileError> {
+        let top = self.chunk.code.len();
+
+        self.compile_expr(subject)?;
+
+        let slot = self.local(&format!("while {}", top));
+        self.emit(Instr::Store(slot));
+
+        let to_end = self.compile_test(pattern, slot)?;
+        let outer = self.locals.clone();
+        self.bind_match(pattern, slot);
+
+        self.compile_stmts(body)?;
+        self.emit(Instr::Jump(top));
+        self.locals = outer;
+
+        for at in to_end {
+            self.patch(at);
+        }
+        self.emit(Instr::Unit);
+
+        Ok(())
+    }
+}
+
+/// Compiles a whole program, as returned by `ast::lower`, into the chunk
+/// for its top level. Functions it declares are in the chunk's constants.
+///
+/// ```
+/// use brouwer::{Parser, ast};
+/// use brouwer::compiler::{self, Instr};
+///
+/// let src = "module Main\n\nx = 5\nif y <- x\n    y\n";
+/// let tree = Parser::from_str(src).parse().unwrap().unwrap();
+/// let main = compiler::compile(&ast::lower(&tree).unwrap()).unwrap();
+/// let globals = main.code
+///                   .iter()
+///                   .filter(|&&i| matches!(i, Instr::StoreGlobal(_)))
+///                   .count();
+///
+/// // `x` is a global, but `y` is a local of the `if`.
+/// assert_eq!(globals, 1);
+/// assert_eq!(main.locals, 2);
+/// ```
+pub fn compile(module: &Module) -> Result<Chunk, CompileError> {
+    let mut main = FnCompiler::new("main", true);
+
+    main.compile_stmts(&module.body)?;
+    main.emit(Instr::Unit);
+    main.emit(Instr::Return);
+
+    Ok(main.finish())
+}
+
+fn compile_fn(fn_decl: &FnDecl) -> Result<Chunk, CompileError> {
+    let mut f = FnCompiler::new(fn_decl.name.name.clone(), false);
+
+    for param in &fn_decl.params {
+        match param.pattern.kind {
+            PatternKind::Bind(ref ident) => f.local(&ident.name),
+            PatternKind::Wildcard        => {
+                let anon = format!("_{}", f.chunk.arity);
+
+                f.local(&anon)
+            },
+            _ => return Err(unsupported(
+                "destructuring parameter",
+                param.pattern.span
+            )),
+        };
+
+        f.chunk.arity += 1;
+    }
+
+    f.compile_block(&fn_decl.body)?;
+    f.emit(Instr::Return);
+
+    Ok(f.finish())
+}
+
+fn unsupported(construct: &'static str, span: Span) -> CompileError {
+    CompileError::Unsupported {
+        construct: construct,
+        span:      span,
+    }
+}
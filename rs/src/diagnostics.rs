@@ -0,0 +1,242 @@
+//! Rendering of errors for people: the offending source line, a caret
+//! under the problem, the error's code, and a hint.
+//!
+//! ```
+//! use brouwer::Parser;
+//! use brouwer::diagnostics::Diagnostic;
+//!
+//! let src = "module Main\n\nx = (1 +\n";
+//! let e = Parser::from_str(src).parse().err().unwrap();
+//! let rendered = Diagnostic::from(&e).render("main.bwr", src, false);
+//!
+//! assert!(rendered.starts_with("error[E0"));
+//! assert!(rendered.contains(" --> main.bwr:"));
+//! ```
+
+use std::env;
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use error::ParseError;
+use token::Span;
+
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+/// How bad a `Diagnostic` is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity {
+    /// The input cannot be used.
+    Error,
+    /// The input can be used, but is probably not what was meant.
+    Warning,
+}
+
+/// A problem with some source text, ready to be shown to a person.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    /// How bad the problem is.
+    pub severity: Severity,
+    /// A stable code for the kind of problem, e.g. `"E0003"`.
+    pub code:     &'static str,
+    /// What the problem is.
+    pub message:  String,
+    /// Where the problem is, if it is anywhere in particular.
+    pub span:     Option<Span>,
+    /// How the problem might be fixed.
+    pub hint:     Option<String>,
+}
+
+
+impl Severity {
+    fn label(&self) -> &'static str {
+        match *self {
+            Severity::Error   => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match *self {
+            Severity::Error   => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Renders the diagnostic, quoting the line of `src` that the span
+    /// starts on, with the span underlined by carets. `filename` is what
+    /// to call `src`. With `color`, the output uses ANSI escape codes. A
+    /// diagnostic whose span is not known is rendered with only the
+    /// filename, as if it had no span.
+    ///
+    /// ```text
+    /// error[E0003]: expected closing paren
+    ///  --> main.bwr:3:9
+    ///   |
+    /// 3 | x = (1 +
+    ///   |         ^
+    ///   = hint: insert `)` here
+    /// ```
+    pub fn render(&self, filename: &str, src: &str, color: bool) -> String {
+        let paint = |style: &'static str| if color { style } else { "" };
+        let reset = paint(RESET);
+        let mut out = String::with_capacity(256);
+
+        let _ = writeln!(
+            out,
+            "{}{}[{}]{}{}: {}{}",
+            paint(self.severity.color()),
+            self.severity.label(),
+            self.code,
+            reset,
+            paint(BOLD),
+            self.message,
+            reset
+        );
+
+        let span = match self.span {
+            Some(span) if span.is_known() => span,
+            _ => {
+                let _ = writeln!(
+                    out,
+                    " {}-->{} {}",
+                    paint(BLUE),
+                    reset,
+                    filename
+                );
+                self.render_hint(&mut out, "", color);
+
+                return out;
+            },
+        };
+
+        let line_text = src.lines().nth(span.line - 1).unwrap_or("");
+        let line_text = line_text.trim_end_matches('\r');
+        let line_no = span.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+
+        let _ = writeln!(
+            out,
+            "{}{}-->{} {}:{}:{}",
+            gutter,
+            paint(BLUE),
+            reset,
+            filename,
+            span.line,
+            span.col
+        );
+        let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE), reset);
+        let _ = writeln!(
+            out,
+            "{}{} |{} {}",
+            paint(BLUE),
+            line_no,
+            reset,
+            line_text
+        );
+
+        // Keep tabs in the padding, so the carets line up however wide the
+        // terminal draws them.
+        let start = line_text.char_indices()
+                             .nth(span.col.saturating_sub(1))
+                             .map_or(line_text.len(), |(i, _)| i);
+        let padding: String = line_text[..start].chars().map(|c|
+            if c == '\t' { '\t' } else { ' ' }
+        ).collect();
+        let width = line_text[start..].char_indices()
+                                      .take_while(|&(i, _)| i < span.len)
+                                      .count()
+                                      .max(1);
+
+        let _ = writeln!(
+            out,
+            "{} {}|{} {}{}{}{}",
+            gutter,
+            paint(BLUE),
+            reset,
+            padding,
+            paint(self.severity.color()),
+            "^".repeat(width),
+            reset
+        );
+
+        self.render_hint(&mut out, &gutter, color);
+
+        out
+    }
+
+    fn render_hint(&self, out: &mut String, gutter: &str, color: bool) {
+        if let Some(ref hint) = self.hint {
+            let (blue, reset) = if color { (BLUE, RESET) } else { ("", "") };
+            let _ = writeln!(
+                out,
+                "{} {}={} hint: {}",
+                gutter,
+                blue,
+                reset,
+                hint
+            );
+        }
+    }
+}
+
+impl<'a> From<&'a ParseError> for Diagnostic {
+    fn from(e: &'a ParseError) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code:     e.code(),
+            message:  e.message(),
+            span:     e.span(),
+            hint:     e.hint(),
+        }
+    }
+}
+
+/// Whether diagnostics written to stderr should be colored: only if it is
+/// a terminal, and then unless `NO_COLOR` is set or the terminal is `dumb`.
+pub fn color_enabled() -> bool {
+    io::stderr().is_terminal() &&
+        env::var_os("NO_COLOR").is_none() &&
+        env::var("TERM").map(|term| term != "dumb").unwrap_or(false)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use token::TokenType;
+
+    #[test]
+    fn unknown_span_is_rendered_without_a_line() {
+        let e = ParseError::MissingToken {
+            message:  "expected closing paren",
+            expected: vec![TokenType::RParen],
+            span:     Span::default(),
+        };
+        let rendered = Diagnostic::from(&e).render("main.bwr", "x\n", false);
+
+        assert_eq!(
+            rendered,
+            "error[E0003]: expected closing paren\n \
+              --> main.bwr\n \
+              = hint: insert `)` here\n"
+        );
+    }
+
+    #[test]
+    fn hint_names_tokens_as_they_are_written() {
+        let e = ParseError::MissingToken {
+            message:  "expected closing square bracket",
+            expected: vec![TokenType::Comma, TokenType::RSqBracket],
+            span:     Span::default(),
+        };
+
+        assert_eq!(e.hint().unwrap(), "insert `,` or `]` here");
+    }
+}
@@ -0,0 +1,175 @@
+//! Semantic comparison of two versions of a module.
+
+use std::fmt;
+
+use parser::{AST, str_repr};
+use token::TokenType;
+
+
+/// A single semantic difference between two versions of a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// An import present only in the new version.
+    ImportAdded(String),
+    /// An import present only in the old version.
+    ImportRemoved(String),
+    /// A function, by name, present only in the new version.
+    FnAdded(String),
+    /// A function, by name, present only in the old version.
+    FnRemoved(String),
+    /// A function whose parameters or return type changed.
+    SignatureChanged {
+        /// The name of the function.
+        name: String,
+        /// The old parameters and return type.
+        old:  String,
+        /// The new parameters and return type.
+        new:  String,
+    },
+    /// A function, by name, whose body changed.
+    BodyModified(String),
+}
+
+/// The parts of a top-level function declaration that are compared.
+struct FnSummary {
+    name:      String,
+    signature: String,
+    body:      Vec<String>,
+}
+
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Change::ImportAdded(ref import)   => write!(f, "+ {}", import),
+            Change::ImportRemoved(ref import) => write!(f, "- {}", import),
+            Change::FnAdded(ref name)   => write!(f, "+ fn {}", name),
+            Change::FnRemoved(ref name) => write!(f, "- fn {}", name),
+            Change::SignatureChanged { ref name, ref old, ref new } => write!(
+                f,
+                "~ fn {}: signature changed from `{}` to `{}`",
+                name,
+                old,
+                new
+            ),
+            Change::BodyModified(ref name) => {
+                write!(f, "~ fn {}: body modified", name)
+            },
+        }
+    }
+}
+
+/// Compares the imports and top-level functions of two parsed modules.
+/// Changes are listed imports first, then functions in the order they
+/// appear in `new`, followed by removed functions in the order they
+/// appeared in `old`.
+pub fn diff(old: &AST, new: &AST) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    let old_imports = imports(old);
+    let new_imports = imports(new);
+
+    for import in &old_imports {
+        if !new_imports.contains(import) {
+            changes.push(Change::ImportRemoved(import.clone()));
+        }
+    }
+
+    for import in &new_imports {
+        if !old_imports.contains(import) {
+            changes.push(Change::ImportAdded(import.clone()));
+        }
+    }
+
+    let old_fns = fn_summaries(old);
+    let new_fns = fn_summaries(new);
+
+    for new_fn in &new_fns {
+        match old_fns.iter().find(|f| f.name == new_fn.name) {
+            Some(old_fn) => {
+                if old_fn.signature != new_fn.signature {
+                    changes.push(Change::SignatureChanged {
+                        name: new_fn.name.clone(),
+                        old:  old_fn.signature.clone(),
+                        new:  new_fn.signature.clone(),
+                    });
+                }
+
+                if old_fn.body != new_fn.body {
+                    changes.push(Change::BodyModified(new_fn.name.clone()));
+                }
+            },
+            None => changes.push(Change::FnAdded(new_fn.name.clone())),
+        }
+    }
+
+    for old_fn in &old_fns {
+        if !new_fns.iter().any(|f| f.name == old_fn.name) {
+            changes.push(Change::FnRemoved(old_fn.name.clone()));
+        }
+    }
+
+    changes
+}
+
+fn top_level(ast: &AST) -> &[AST] {
+    match ast.children().first() {
+        Some(prog) => prog.children(),
+        None       => &[],
+    }
+}
+
+fn imports(ast: &AST) -> Vec<String> {
+    top_level(ast).iter()
+                  .filter(|item| item.val().type_ == TokenType::Import)
+                  .map(|import| str_repr(import).trim().to_string())
+                  .collect()
+}
+
+fn fn_summaries(ast: &AST) -> Vec<FnSummary> {
+    top_level(ast).iter()
+                  .filter_map(line_fn_decl)
+                  .map(summarize_fn)
+                  .collect()
+}
+
+/// The function declared by a top-level line, if that is all it does.
+fn line_fn_decl(line: &AST) -> Option<&AST> {
+    if line.val().type_ != TokenType::Line {
+        return None;
+    }
+
+    let expr = line.children().first()?;
+    let subexpr = expr.children().first()?;
+    let fn_decl = subexpr.children().first()?;
+
+    if fn_decl.val().type_ == TokenType::FnDecl {
+        Some(fn_decl)
+    } else {
+        None
+    }
+}
+
+fn summarize_fn(fn_decl: &AST) -> FnSummary {
+    let mut name = String::new();
+    let mut signature = Vec::new();
+    let mut body = Vec::new();
+
+    for child in fn_decl.children() {
+        match child.val().type_ {
+            // `fn f x = y` and the same body as a block are the same function.
+            TokenType::FnKeyword | TokenType::Equals => {},
+            TokenType::Ident if name.is_empty() => {
+                name = child.val().lexeme().to_string();
+            },
+            TokenType::Line => body.push(str_repr(child).trim().to_string()),
+            _ => signature.push(str_repr(child).trim().to_string()),
+        }
+    }
+
+    FnSummary {
+        name:      name,
+        signature: signature.join(" "),
+        body:      body,
+    }
+}
@@ -0,0 +1,232 @@
+//! Machine-readable dumps of parse trees, for tools outside the crate.
+//!
+//! Both formats carry the schema version they follow, and lay every node
+//! out the same way: its kind, its lexeme, its span, then its children in
+//! source order. The same tree always dumps to the same text.
+//!
+//! Tools name the version they were written against; node kinds renamed
+//! since then are dumped under the names that version used, so that a
+//! rename does not silently break them. Likewise, calls are dumped as
+//! `FnCall` nodes only from version 2; dumps for version 1 lay out the
+//! callee and arguments of a call as children of its `Expr`, as the parser
+//! used to.
+//!
+//! ```
+//! use brouwer::Parser;
+//! use brouwer::dump;
+//!
+//! let ast = Parser::from_str("module Main\n").parse().unwrap().unwrap();
+//!
+//! assert!(dump::to_json(&ast).starts_with("{\"version\":2,"));
+//! ```
+
+use std::fmt::Write;
+
+use parser::AST;
+use pretty::{self, Doc, concat, group, line, nest, text};
+use token::TokenType;
+
+
+/// The current version of the dump schema.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Node kinds renamed since the first version of the schema, as
+/// `(version, old, new)`: dumps for versions before `version` call `new`
+/// nodes `old`.
+const RENAMED_KINDS: &[(u32, &str, &str)] = &[];
+
+/// How to write out a tree.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// `{"version":2,"root":{"kind":…,"lexeme":…,"span":…,"children":[…]}}`
+    Json,
+    /// `(brouwer-ast (version 2) (Kind "lexeme" (line col offset len) …))`
+    SExpr,
+}
+
+
+/// Writes out `ast` in `format`, following version `version` of the
+/// schema. Fails if there is no such version.
+pub fn dump(ast: &AST, format: Format, version: u32) -> Result<String, String> {
+    if version == 0 || version > SCHEMA_VERSION {
+        return Err(format!(
+            "schema version {} is not supported (expected 1 to {})",
+            version,
+            SCHEMA_VERSION
+        ));
+    }
+
+    let mut out = String::with_capacity(4096);
+
+    match format {
+        Format::Json => {
+            write!(out, "{{\"version\":{},\"root\":", version).unwrap();
+            json_node(ast, version, &mut out);
+            out.push('}');
+        },
+        Format::SExpr => {
+            write!(out, "(brouwer-ast (version {}) ", version).unwrap();
+            sexpr_node(ast, version, &mut out);
+            out.push(')');
+        },
+    }
+
+    Ok(out)
+}
+
+/// Writes out `ast` as JSON, following the current version of the schema.
+pub fn to_json(ast: &AST) -> String {
+    dump(ast, Format::Json, SCHEMA_VERSION).unwrap()
+}
+
+/// Writes out `ast` as a compact S-expression, for golden tests rather
+/// than tools: `(Kind "lexeme")` for leaves and `(Kind child…)` for other
+/// nodes, broken over lines and indented only where a node does not fit
+/// on one. There are no spans and no schema version, so that an edit
+/// only changes the lines of the nodes it touches.
+///
+/// ```text
+/// (Root
+///   (Prog
+///     (ModDecl (ModuleKeyword "module") (Ident "Main"))
+///     …))
+/// ```
+pub fn to_sexpr(ast: &AST) -> String {
+    pretty::render(&sexpr_doc(ast), pretty::DEFAULT_WIDTH)
+}
+
+/// `s` as a double-quoted string, escaped for JSON.
+pub fn json_str(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"'  => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                escaped += &format!("\\u{:04x}", c as u32);
+            },
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+
+    escaped
+}
+
+fn json_node(ast: &AST, version: u32, out: &mut String) {
+    let span = ast.span();
+
+    write!(
+        out,
+        "{{\"kind\":{},\"lexeme\":{},\"span\":{{\"line\":{},\"col\":{},\
+         \"offset\":{},\"len\":{}}},\"children\":[",
+        json_str(&kind_name(ast, version)),
+        json_str(ast.val().lexeme()),
+        span.line,
+        span.col,
+        span.byte_offset,
+        span.len
+    ).unwrap();
+
+    for (i, child) in children(ast, version).into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        json_node(child, version, out);
+    }
+
+    out.push_str("]}");
+}
+
+fn sexpr_node(ast: &AST, version: u32, out: &mut String) {
+    let span = ast.span();
+
+    write!(
+        out,
+        "({} {} ({} {} {} {})",
+        kind_name(ast, version),
+        json_str(ast.val().lexeme()),
+        span.line,
+        span.col,
+        span.byte_offset,
+        span.len
+    ).unwrap();
+
+    for child in children(ast, version) {
+        out.push(' ');
+        sexpr_node(child, version, out);
+    }
+
+    out.push(')');
+}
+
+fn sexpr_doc(ast: &AST) -> Doc {
+    let kind = format!("{:?}", ast.val().type_);
+
+    if ast.children().is_empty() {
+        return text(format!("({} {})", kind, json_str(ast.val().lexeme())));
+    }
+
+    let children = ast.children()
+                      .iter()
+                      .map(|child| concat(vec![line(), sexpr_doc(child)]))
+                      .collect();
+
+    group(concat(vec![
+        text(format!("({}", kind)),
+        nest(2, concat(children)),
+        text(")"),
+    ]))
+}
+
+/// The children of the node as of schema version `version`.
+fn children(ast: &AST, version: u32) -> Vec<&AST> {
+    let mut children = Vec::with_capacity(ast.children().len());
+
+    for child in ast.children() {
+        if version < 2 && child.val().type_ == TokenType::FnCall {
+            children.extend(children_of_call(child));
+        } else {
+            children.push(child);
+        }
+    }
+
+    children
+}
+
+/// The callee of the call `fn_call`, then its arguments.
+fn children_of_call(fn_call: &AST) -> Vec<&AST> {
+    let mut children = match fn_call.children().first() {
+        Some(callee) if callee.val().type_ == TokenType::FnCall => {
+            children_of_call(callee)
+        },
+        Some(callee) => vec![callee],
+        None         => Vec::new(),
+    };
+
+    children.extend(fn_call.children().get(1));
+
+    children
+}
+
+/// The name of the node's kind as of schema version `version`.
+fn kind_name(ast: &AST, version: u32) -> String {
+    let mut name = format!("{:?}", ast.val().type_);
+
+    // Undo renames newest first, so that a kind renamed twice ends up with
+    // the name it had at `version`.
+    for &(since, old, new) in RENAMED_KINDS.iter().rev() {
+        if version < since && name == new {
+            name = old.to_string();
+        }
+    }
+
+    name
+}
@@ -0,0 +1,468 @@
+//! Applying text edits to source while preserving everything else, and
+//! writing files so that a crash never leaves one half written.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+
+/// A replacement of the bytes `start..end` of a source text with
+/// `replacement`. Insertions have `start == end`, deletions have an empty
+/// `replacement`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Byte offset of the first byte replaced.
+    pub start:       usize,
+    /// Byte offset one past the last byte replaced.
+    pub end:         usize,
+    /// The text to put in place of `start..end`.
+    pub replacement: String,
+}
+
+/// All of the edits to be made to a single file.
+#[derive(Clone, Debug)]
+pub struct FileEdits {
+    /// The file to edit.
+    pub path:  PathBuf,
+    /// The edits to apply to it.
+    pub edits: Vec<TextEdit>,
+}
+
+
+impl TextEdit {
+    /// Replaces `start..end` with `replacement`.
+    pub fn new<S: Into<String>>(
+        start:       usize,
+        end:         usize,
+        replacement: S
+    ) -> Self {
+        TextEdit {
+            start:       start,
+            end:         end,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// Inserts `text` at `offset`.
+    pub fn insert<S: Into<String>>(offset: usize, text: S) -> Self {
+        TextEdit::new(offset, offset, text)
+    }
+
+    /// Deletes `start..end`.
+    pub fn delete(start: usize, end: usize) -> Self {
+        TextEdit::new(start, end, String::new())
+    }
+
+    /// Whether this edit only inserts text, replacing nothing.
+    pub fn is_insertion(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl FileEdits {
+    /// Groups `edits` to be applied to the file at `path`.
+    pub fn new<P: AsRef<Path>>(path: P, edits: Vec<TextEdit>) -> Self {
+        FileEdits {
+            path:  path.as_ref().to_path_buf(),
+            edits: edits,
+        }
+    }
+}
+
+/// Applies `edits` to `src`, leaving every byte that no edit touches
+/// exactly as it was. The edits may be given in any order; insertions at
+/// the same offset are applied in the order given. Fails without
+/// producing any output if an edit is out of bounds, splits a UTF-8
+/// sequence, or overlaps another edit.
+pub fn apply_edits(src: &str, edits: &[TextEdit]) -> Result<String, String> {
+    let sorted = sort_edits(src, edits)?;
+
+    let mut out = String::with_capacity(src.len());
+    let mut copied_up_to = 0;
+
+    for edit in sorted {
+        out.push_str(&src[copied_up_to..edit.start]);
+        out.push_str(&edit.replacement);
+
+        copied_up_to = edit.end;
+    }
+
+    out.push_str(&src[copied_up_to..]);
+
+    Ok(out)
+}
+
+/// Checks that `edits` can be applied to `src` and returns them sorted by
+/// position.
+fn sort_edits<'a>(
+    src:   &str,
+    edits: &'a [TextEdit]
+) -> Result<Vec<&'a TextEdit>, String> {
+    for edit in edits {
+        if edit.start > edit.end {
+            return Err(format!(
+                "edit range {}..{} is backwards",
+                edit.start,
+                edit.end
+            ));
+        }
+
+        if edit.end > src.len() {
+            return Err(format!(
+                "edit range {}..{} is out of bounds for source of length {}",
+                edit.start,
+                edit.end,
+                src.len()
+            ));
+        }
+
+        if !src.is_char_boundary(edit.start) ||
+           !src.is_char_boundary(edit.end)
+        {
+            return Err(format!(
+                "edit range {}..{} does not lie on character boundaries",
+                edit.start,
+                edit.end
+            ));
+        }
+    }
+
+    // Stable, so insertions at the same offset keep their given order.
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|e| (e.start, e.end));
+
+    for pair in sorted.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+
+        let overlaps = prev.end > next.start ||
+            (prev.start == next.start &&
+             !prev.is_insertion() &&
+             !next.is_insertion());
+
+        if overlaps {
+            return Err(format!(
+                "overlapping edits at {}..{} and {}..{}",
+                prev.start,
+                prev.end,
+                next.start,
+                next.end
+            ));
+        }
+    }
+
+    Ok(sorted)
+}
+
+/// Applies edits to several files at once. Every file is read and every
+/// set of edits is checked before anything is written; the new contents
+/// are then written to temporary siblings and renamed into place, keeping
+/// the permissions of the files they replace. A file that is a symlink is
+/// edited where it points, and stays a symlink. If anything fails partway,
+/// or brouwer panics partway, files that were already replaced are
+/// restored to their original contents.
+pub fn apply_file_edits(files: &[FileEdits]) -> Result<(), String> {
+    let targets: Vec<PathBuf> = files.iter()
+                                     .map(|file| resolve_links(&file.path))
+                                     .collect();
+    let mut originals = Vec::with_capacity(files.len());
+    let mut edited = Vec::with_capacity(files.len());
+
+    for file in files {
+        let original = read_to_string(&file.path)?;
+        let new_src = apply_edits(&original, &file.edits).map_err(|e|
+            format!("{}: {}", file.path.display(), e)
+        )?;
+
+        originals.push(original);
+        edited.push(new_src);
+    }
+
+    let mut rollback = Rollback {
+        files:      files,
+        originals:  &originals,
+        temp_paths: Vec::with_capacity(files.len()),
+        renamed:    0,
+        committed:  false,
+    };
+
+    for (target, new_src) in targets.iter().zip(edited.iter()) {
+        let temp_path = temp_path_for(target);
+        rollback.temp_paths.push(temp_path.clone());
+
+        write_file(&temp_path, new_src, target)?;
+    }
+
+    let temp_paths = rollback.temp_paths.clone();
+
+    for ((file, target), temp_path) in files.iter()
+                                            .zip(&targets)
+                                            .zip(&temp_paths)
+    {
+        fs::rename(temp_path, target)
+            .map_err(|e| format!("{}: {}", file.path.display(), e))?;
+
+        rollback.renamed += 1;
+    }
+
+    rollback.committed = true;
+
+    Ok(())
+}
+
+/// Replaces the contents of the file at `path` with `contents` all at
+/// once: they are written to a temporary sibling, which is then renamed
+/// over `path`, so a failure or crash partway never leaves `path` holding
+/// only some of them. The file keeps its permissions, and if `path` is a
+/// symlink, the file it points to is the one replaced.
+pub fn write_atomic<P: AsRef<Path>>(path: P, contents: &str)
+    -> Result<(), String>
+{
+    let path = path.as_ref();
+    let target = resolve_links(path);
+    let temp_path = temp_path_for(&target);
+
+    let written = write_file(&temp_path, contents, &target).and_then(|_|
+        fs::rename(&temp_path, &target)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    );
+
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    written
+}
+
+/// Undoes a partly applied `apply_file_edits` when dropped before it is
+/// committed, which happens both when it returns early with an error and
+/// when it unwinds from a panic.
+struct Rollback<'a> {
+    files:      &'a [FileEdits],
+    originals:  &'a [String],
+    /// The temporary files written so far.
+    temp_paths: Vec<PathBuf>,
+    /// How many of the temporary files have been renamed into place.
+    renamed:    usize,
+    committed:  bool,
+}
+
+impl<'a> Drop for Rollback<'a> {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        remove_all(&self.temp_paths[self.renamed..]);
+
+        for (done, original) in self.files[..self.renamed]
+                                    .iter()
+                                    .zip(self.originals)
+        {
+            let _ = write_atomic(&done.path, original);
+        }
+    }
+}
+
+fn read_to_string(path: &Path) -> Result<String, String> {
+    let mut contents = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(contents)
+}
+
+/// Writes `contents` to a new file at `path`, which is to be renamed over
+/// `target`, giving it the permissions `target` has, if it exists. The
+/// file never has looser permissions than that, even while it is written.
+fn write_file(path: &Path, contents: &str, target: &Path)
+    -> Result<(), String>
+{
+    let permissions = fs::metadata(target).ok().map(|m| m.permissions());
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        if let Some(ref permissions) = permissions {
+            options.mode(permissions.mode() & 0o777);
+        }
+    }
+
+    options.open(path)
+        .and_then(|mut f| {
+            // A file left over from before keeps its own permissions.
+            if let Some(permissions) = permissions {
+                f.set_permissions(permissions)?;
+            }
+
+            f.write_all(contents.as_bytes())?;
+            f.sync_all()
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// The file that `path` is, after following it if it is a symlink, so that
+/// replacing it replaces what the link points to rather than the link.
+fn resolve_links(path: &Path) -> PathBuf {
+    match fs::symlink_metadata(path) {
+        Ok(ref m) if m.file_type().is_symlink() => {
+            fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        },
+        _ => path.to_path_buf(),
+    }
+}
+
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name()
+                            .map(|n| n.to_os_string())
+                            .unwrap_or_default();
+    file_name.push(".brouwer-edit");
+
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+
+    path.with_file_name(temp_name)
+}
+
+fn remove_all(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// An empty directory of the test's own, named after it.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "brouwer-edit-{}-{}",
+            name,
+            process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn contents(path: &Path) -> String {
+        read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn a_failed_write_leaves_every_file_as_it_was() {
+        let dir = scratch("write");
+        let (a, b) = (dir.join("a.bwr"), dir.join("b.bwr"));
+        fs::write(&a, "a = 1\n").unwrap();
+        fs::write(&b, "b = 2\n").unwrap();
+
+        // Nothing can be written where b's temporary file would go.
+        fs::create_dir(temp_path_for(&b)).unwrap();
+
+        let result = apply_file_edits(&[
+            FileEdits::new(&a, vec![TextEdit::new(4, 5, "10")]),
+            FileEdits::new(&b, vec![TextEdit::new(4, 5, "20")]),
+        ]);
+
+        assert!(result.is_err());
+        assert_eq!(contents(&a), "a = 1\n");
+        assert_eq!(contents(&b), "b = 2\n");
+        assert!(!temp_path_for(&a).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_rename_restores_the_files_already_replaced() {
+        let dir = scratch("rename");
+        let a = dir.join("a.bwr");
+        fs::write(&a, "a = 1\n").unwrap();
+
+        // Both edits go through the same temporary file, so the second
+        // rename finds it gone, after the first has replaced `a`.
+        let result = apply_file_edits(&[
+            FileEdits::new(&a, vec![TextEdit::new(4, 5, "10")]),
+            FileEdits::new(&a, vec![TextEdit::new(4, 5, "20")]),
+        ]);
+
+        assert!(result.is_err());
+        assert_eq!(contents(&a), "a = 1\n");
+        assert!(!temp_path_for(&a).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn edited_files_keep_their_permissions() {
+        let dir = scratch("permissions");
+        let a = dir.join("a.bwr");
+        fs::write(&a, "a = 1\n").unwrap();
+        fs::set_permissions(&a, fs::Permissions::from_mode(0o600)).unwrap();
+
+        apply_file_edits(&[
+            FileEdits::new(&a, vec![TextEdit::new(4, 5, "10")]),
+        ]).unwrap();
+
+        let mode = fs::metadata(&a).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(contents(&a), "a = 10\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_symlink_is_edited_where_it_points() {
+        use std::os::unix::fs::symlink;
+
+        let dir = scratch("symlink");
+        let (real, link) = (dir.join("real.bwr"), dir.join("link.bwr"));
+        fs::write(&real, "a = 1\n").unwrap();
+        symlink(&real, &link).unwrap();
+
+        apply_file_edits(&[
+            FileEdits::new(&link, vec![TextEdit::new(4, 5, "10")]),
+        ]).unwrap();
+
+        let link_type = fs::symlink_metadata(&link).unwrap().file_type();
+        assert!(link_type.is_symlink());
+        assert_eq!(contents(&real), "a = 10\n");
+
+        write_atomic(&link, "a = 20\n").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(contents(&real), "a = 20\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overlapping_edits_write_nothing() {
+        let dir = scratch("overlap");
+        let a = dir.join("a.bwr");
+        fs::write(&a, "a = 1\n").unwrap();
+
+        let result = apply_file_edits(&[FileEdits::new(&a, vec![
+            TextEdit::new(0, 3, "b ="),
+            TextEdit::new(2, 5, "= 2"),
+        ])]);
+
+        assert_eq!(result.unwrap_err(),
+                   format!("{}: overlapping edits at 0..3 and 2..5",
+                           a.display()));
+        assert_eq!(contents(&a), "a = 1\n");
+        assert!(!temp_path_for(&a).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
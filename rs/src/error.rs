@@ -0,0 +1,270 @@
+//! Errors produced while parsing.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use parser::spelling;
+use token::{Span, TokenType};
+
+
+/// Why a file failed to parse. Every variant except `Io` records where in
+/// the source the parser gave up, and what it would have accepted there.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A character that cannot appear where it was found.
+    UnexpectedChar {
+        /// The offending character.
+        found:    char,
+        /// What could have appeared instead.
+        expected: Vec<TokenType>,
+        /// Where the character is.
+        span:     Span,
+    },
+    /// The file ended partway through a construct.
+    UnexpectedEof {
+        /// What was being looked for, e.g. `"expected closing paren"`.
+        message:  &'static str,
+        /// What could have appeared instead of the end of the file.
+        expected: Vec<TokenType>,
+        /// Where the file ended.
+        span:     Span,
+    },
+    /// A part of a construct that is required but was not there.
+    MissingToken {
+        /// What was being looked for, e.g. `"expected closing paren"`.
+        message:  &'static str,
+        /// What could have appeared instead.
+        expected: Vec<TokenType>,
+        /// Where the missing part should have started.
+        span:     Span,
+    },
+    /// A line indented in a way that does not open, continue, or close a
+    /// block.
+    BadIndent {
+        /// What is wrong with the indentation.
+        message: &'static str,
+        /// Where the line's text starts.
+        span:    Span,
+    },
+    /// A reserved operator used as an ordinary one.
+    ReservedOp {
+        /// The operator.
+        op:   String,
+        /// Where the operator is.
+        span: Span,
+    },
+    /// A reserved word, like `True`, used as a name.
+    ReservedWord {
+        /// The word.
+        word: String,
+        /// Where the word is.
+        span: Span,
+    },
+    /// A `\x` or `\u{...}` escape that does not stand for a character.
+    BadEscape {
+        /// The escape, as written.
+        escape: String,
+        /// Where the escape is.
+        span:   Span,
+    },
+    /// Two operators of the same precedence, next to each other, that
+    /// cannot be grouped without parentheses: either one is
+    /// non-associative, or one associates left and the other right.
+    MixedFixity {
+        /// The first operator.
+        first:  String,
+        /// The second operator.
+        second: String,
+        /// Where the second operator is.
+        span:   Span,
+    },
+    /// The source file could not be read.
+    Io(io::Error),
+}
+
+
+impl ParseError {
+    /// Where in the source the error occurred, if it is a syntax error.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            ParseError::UnexpectedChar { span, .. } |
+            ParseError::UnexpectedEof { span, .. }  |
+            ParseError::MissingToken { span, .. }   |
+            ParseError::BadIndent { span, .. }      |
+            ParseError::ReservedOp { span, .. }     |
+            ParseError::ReservedWord { span, .. }   |
+            ParseError::BadEscape { span, .. }      |
+            ParseError::MixedFixity { span, .. }    => Some(span),
+            ParseError::Io(_)                       => None,
+        }
+    }
+
+    /// The tokens and constructs that would have been accepted where the
+    /// error occurred. Empty when there is no such set.
+    pub fn expected(&self) -> &[TokenType] {
+        match *self {
+            ParseError::UnexpectedChar { ref expected, .. } |
+            ParseError::UnexpectedEof { ref expected, .. }  |
+            ParseError::MissingToken { ref expected, .. }   => expected,
+            _ => &[],
+        }
+    }
+
+    /// A stable code for the kind of error, e.g. `"E0003"`, for looking
+    /// the error up in documentation and for tools to match on.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ParseError::UnexpectedChar { .. } => "E0001",
+            ParseError::UnexpectedEof { .. }  => "E0002",
+            ParseError::MissingToken { .. }   => "E0003",
+            ParseError::BadIndent { .. }      => "E0004",
+            ParseError::ReservedOp { .. }     => "E0005",
+            ParseError::MixedFixity { .. }    => "E0006",
+            ParseError::Io(_)                 => "E0007",
+            ParseError::ReservedWord { .. }   => "E0008",
+            ParseError::BadEscape { .. }      => "E0009",
+        }
+    }
+
+    /// What went wrong, without the location.
+    pub fn message(&self) -> String {
+        match *self {
+            ParseError::UnexpectedChar { found, ref expected, .. } => {
+                format!("unexpected {:?}, expected {}", found, one_of(expected))
+            },
+            ParseError::UnexpectedEof { message, .. } => {
+                format!("unexpected end of file: {}", message)
+            },
+            ParseError::MissingToken { message, .. } |
+            ParseError::BadIndent { message, .. }    => message.to_string(),
+            ParseError::ReservedOp { ref op, .. } => {
+                format!("the operator {} is reserved", op)
+            },
+            ParseError::ReservedWord { ref word, .. } => {
+                format!("{} is a reserved word, not a name", word)
+            },
+            ParseError::BadEscape { ref escape, .. } => {
+                format!("{} is not the escape of a character", escape)
+            },
+            ParseError::MixedFixity { ref first, ref second, .. } => format!(
+                "cannot use {} and {} together without parentheses",
+                first,
+                second
+            ),
+            ParseError::Io(ref e) => e.to_string(),
+        }
+    }
+
+    /// A suggestion for how to fix the error, if there is one to give.
+    pub fn hint(&self) -> Option<String> {
+        match *self {
+            ParseError::UnexpectedEof { .. } => Some(
+                "check for an unclosed bracket, string, or block".to_string()
+            ),
+            ParseError::MissingToken { ref expected, .. } => {
+                if expected.is_empty() {
+                    None
+                } else {
+                    Some(format!("insert {} here", one_of(expected)))
+                }
+            },
+            ParseError::BadIndent { .. } => Some(
+                "indent every line of a block by the same blanks, and each \
+                 nested block by more".to_string()
+            ),
+            ParseError::ReservedOp { ref op, .. } => Some(format!(
+                "{} has a built-in meaning; use another operator",
+                op
+            )),
+            ParseError::ReservedWord { ref word, .. } => Some(format!(
+                "{} has a built-in meaning; use another name",
+                word
+            )),
+            ParseError::BadEscape { ref escape, .. } => Some(
+                if escape.starts_with("\\x") {
+                    "\\x escapes go up to \\x7f; write others as \\u{...}"
+                } else {
+                    "a \\u{...} escape needs 1 to 6 hex digits, making a \
+                     code point up to 10ffff that is not a surrogate"
+                }.to_string()
+            ),
+            ParseError::MixedFixity { ref first, ref second, .. } => Some(
+                format!(
+                    "group the operands of either {} or {} in parentheses",
+                    first,
+                    second
+                )
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(span) = self.span() {
+            write!(f, "{}:{}: ", span.line, span.col)?;
+        }
+
+        write!(f, "{}", self.message())
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ParseError::Io(ref e) => Some(e),
+            _                     => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+/// `A`, `A or B`, `A or B or C`, and so on.
+fn one_of(types: &[TokenType]) -> String {
+    types.iter()
+         .map(describe)
+         .collect::<Vec<_>>()
+         .join(" or ")
+}
+
+/// A token type as a person would refer to it: `)` for `RParen`, which is
+/// always written the same way, and `string literal` for `StrLit`, which
+/// is not.
+fn describe(type_: &TokenType) -> String {
+    if let Some(text) = spelling(type_) {
+        return format!("`{}`", text);
+    }
+
+    let name = format!("{:?}", type_);
+    let mut words = Vec::new();
+    let mut start = 0;
+
+    for (i, c) in name.char_indices().skip(1) {
+        if c.is_uppercase() {
+            words.push(&name[start..i]);
+            start = i;
+        }
+    }
+    words.push(&name[start..]);
+
+    words.iter()
+         .map(|word| match *word {
+             "Chr"   => "character".to_string(),
+             "Expr"  => "expression".to_string(),
+             "Ident" => "identifier".to_string(),
+             "Lit"   => "literal".to_string(),
+             "Num"   => "numeric".to_string(),
+             "Op"    => "operator".to_string(),
+             "Str"   => "string".to_string(),
+             _       => word.to_lowercase(),
+         })
+         .collect::<Vec<_>>()
+         .join(" ")
+}
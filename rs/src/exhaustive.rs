@@ -0,0 +1,883 @@
+//! Exhaustiveness and redundancy checks for `case`.
+//!
+//! A `case` must have a branch for every value its subject can have, and
+//! every branch must match some value that no branch before it matches.
+//! Literals, lists (whose patterns each match lists of one length), sets,
+//! and dicts can only be covered by a branch that binds, or is `_`; a
+//! tuple is covered once every combination of its elements is, a `Bool`
+//! once both `True` and `False` are, and an integer once integer literals
+//! and ranges cover every 64-bit integer. A range that only partly overlaps
+//! one before it is warned about too. A branch with a guard covers
+//! nothing, since its guard may not hold, though it can still be
+//! unreachable. An or-pattern covers what each of its alternatives does,
+//! and an as-pattern what the pattern it names does. An
+//! `if pattern <- subject` without an `else` is never warned about: if the
+//! pattern cannot fail, the `else` it is given is unreachable, but it was
+//! never written.
+//!
+//! The check is the usefulness algorithm of Maranget's "Warnings for
+//! pattern matching": a pattern is worth having if there is a value that it
+//! matches and the patterns before it do not. A set or dict pattern, or a
+//! string prefix or suffix pattern, is assumed to match something that no
+//! other pattern does.
+//!
+//! ```
+//! use brouwer::{Parser, ast, exhaustive};
+//!
+//! let src = "module Main\n\n\
+//!            fn f xs\n    \
+//!                case xs\n        \
+//!                    [] => 0\n        \
+//!                    [x] => x\n        \
+//!                    [y] => y\n";
+//! let tree = Parser::from_str(src).parse().unwrap().unwrap();
+//! let problems = exhaustive::check(&ast::lower(&tree).unwrap());
+//!
+//! assert_eq!(problems.len(), 2);
+//! assert_eq!(problems[0].to_string(), "7:9: unreachable branch");
+//! assert_eq!(problems[1].to_string(),
+//!            "4:5: case does not cover every value: [_, _] is not matched");
+//! ```
+
+use std::fmt;
+
+use ast::{CaseBranch, Expr, ExprKind, Module, Pattern, PatternKind, Qualifier,
+          Stmt};
+use diagnostics::{Diagnostic, Severity};
+use token::Span;
+
+
+/// What is wrong with a `case`.
+#[derive(Clone, Debug)]
+pub enum CaseProblem {
+    /// A `case` with no branch for some values of its subject.
+    NonExhaustive {
+        /// A pattern for values that no branch matches, such as `(_, 1)`.
+        missing: String,
+        /// Where the `case` is.
+        span:    Span,
+    },
+    /// A branch whose pattern only matches values that branches before it
+    /// match, so that it is never taken.
+    Unreachable {
+        /// Where the branch's pattern is.
+        span: Span,
+    },
+    /// A range pattern that matches some of the integers that the range
+    /// of a branch before it does, but not all of them, nor all of its.
+    Overlapping {
+        /// Where the range is.
+        span:  Span,
+        /// Where the range before it is.
+        other: Span,
+    },
+}
+
+/// A pattern reduced to what the check needs: either it matches anything,
+/// or it matches values made by a constructor, whose fields it matches
+/// against the patterns it holds.
+#[derive(Clone, Debug)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    /// Any of the alternatives of an or-pattern.
+    Or(Vec<Pat>),
+}
+
+/// A way of making a value. The bits of a real are compared, so that
+/// `NaN` is the same as itself.
+#[derive(Clone, PartialEq, Debug)]
+enum Ctor {
+    Tuple(usize),
+    List(usize),
+    /// The integers from the first to the second, inclusive, of which there
+    /// are none if the first is greater: one, for an integer literal.
+    Ints(i64, i64),
+    Real(u64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    /// A set or dict pattern, which is not the same as any other.
+    Opaque(usize),
+}
+
+
+impl CaseProblem {
+    /// Where the problem is.
+    pub fn span(&self) -> Span {
+        match *self {
+            CaseProblem::NonExhaustive { span, .. } |
+            CaseProblem::Unreachable { span }       |
+            CaseProblem::Overlapping { span, .. }   => span,
+        }
+    }
+
+    /// Whether the problem keeps the program from being run, or only
+    /// means it is probably not what was meant.
+    pub fn severity(&self) -> Severity {
+        match *self {
+            CaseProblem::NonExhaustive { .. } => Severity::Error,
+            CaseProblem::Unreachable { .. }   |
+            CaseProblem::Overlapping { .. }   => Severity::Warning,
+        }
+    }
+
+    /// A stable code for the kind of problem.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            CaseProblem::NonExhaustive { .. } => "E0101",
+            CaseProblem::Unreachable { .. }   => "W0101",
+            CaseProblem::Overlapping { .. }   => "W0102",
+        }
+    }
+
+    /// What is wrong, without the location.
+    pub fn message(&self) -> String {
+        match *self {
+            CaseProblem::NonExhaustive { ref missing, .. } => format!(
+                "case does not cover every value: {} is not matched",
+                missing
+            ),
+            CaseProblem::Unreachable { .. } => {
+                "unreachable branch".to_string()
+            },
+            CaseProblem::Overlapping { other, .. } => format!(
+                "range overlaps the range at {}:{}",
+                other.line,
+                other.col
+            ),
+        }
+    }
+
+    /// How the problem might be fixed.
+    pub fn hint(&self) -> Option<String> {
+        match *self {
+            CaseProblem::NonExhaustive { ref missing, .. } => Some(format!(
+                "add a branch for {}, or a last branch for `_`",
+                missing
+            )),
+            CaseProblem::Unreachable { .. } => Some(
+                "remove the branch, or move it before the branches that \
+                 match what it does".to_string()
+            ),
+            CaseProblem::Overlapping { .. } => Some(
+                "the integers in both go to the branch before; make the \
+                 ranges meet without overlapping".to_string()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for CaseProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+
+        write!(f, "{}:{}: {}", span.line, span.col, self.message())
+    }
+}
+
+impl<'a> From<&'a CaseProblem> for Diagnostic {
+    fn from(problem: &'a CaseProblem) -> Self {
+        Diagnostic {
+            severity: problem.severity(),
+            code:     problem.code(),
+            message:  problem.message(),
+            span:     Some(problem.span()),
+            hint:     problem.hint(),
+        }
+    }
+}
+
+impl Ctor {
+    /// How many fields values made by the constructor have.
+    fn arity(&self) -> usize {
+        match *self {
+            Ctor::Tuple(n) | Ctor::List(n) => n,
+            _                              => 0,
+        }
+    }
+
+    /// Whether every value `other` makes is one this makes too.
+    fn covers(&self, other: &Ctor) -> bool {
+        match (self, other) {
+            (&Ctor::Ints(lo, hi), &Ctor::Ints(other_lo, other_hi)) => {
+                lo <= other_lo && other_hi <= hi
+            },
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Pat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (ctor, fields) = match *self {
+            Pat::Wild                      => return write!(f, "_"),
+            Pat::Ctor(ref ctor, ref fields) => (ctor, fields),
+            Pat::Or(ref alternatives)       => {
+                let joined = alternatives.iter()
+                                         .map(|p| p.to_string())
+                                         .collect::<Vec<_>>()
+                                         .join(" | ");
+
+                return write!(f, "{}", joined);
+            },
+        };
+        let joined = fields.iter()
+                           .map(|p| p.to_string())
+                           .collect::<Vec<_>>()
+                           .join(", ");
+
+        match *ctor {
+            Ctor::Tuple(_)                 => write!(f, "({})", joined),
+            Ctor::List(_)                  => write!(f, "[{}]", joined),
+            Ctor::Ints(lo, hi) if lo == hi => write!(f, "{}", lo),
+            Ctor::Ints(lo, hi)             => write!(f, "{}..={}", lo, hi),
+            Ctor::Real(bits)               => {
+                write!(f, "{}", f64::from_bits(bits))
+            },
+            Ctor::Bool(true)               => write!(f, "True"),
+            Ctor::Bool(false)              => write!(f, "False"),
+            Ctor::Char(c)                  => write!(f, "{:?}", c),
+            Ctor::Str(ref s)               => write!(f, "{:?}", s),
+            Ctor::Opaque(_)                => write!(f, "_"),
+        }
+    }
+}
+
+/// Checks every `case` in `module`, returning what is wrong in the order
+/// it is in the module.
+pub fn check(module: &Module) -> Vec<CaseProblem> {
+    let mut checker = Checker {
+        problems: Vec::new(),
+        opaque:   0,
+    };
+
+    checker.stmts(&module.body);
+
+    checker.problems
+}
+
+/// Whether a `case` whose branches have `patterns` matches every value,
+/// with the last branch if no other.
+pub fn is_exhaustive(patterns: &[&Pattern]) -> bool {
+    let mut checker = Checker {
+        problems: Vec::new(),
+        opaque:   0,
+    };
+    let rows: Vec<Vec<Pat>> = patterns.iter()
+                                      .map(|pattern| vec![checker.pat(pattern)])
+                                      .collect();
+
+    !useful(&rows, &[Pat::Wild])
+}
+
+struct Checker {
+    problems: Vec<CaseProblem>,
+    /// How many set and dict patterns have been seen, to tell them apart.
+    opaque:   usize,
+}
+
+impl Checker {
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match *stmt {
+                Stmt::Let(ref let_)  => self.expr(&let_.value),
+                Stmt::Fn(ref decl)   => self.stmts(&decl.body),
+                Stmt::Fixity(_)      => {},
+                Stmt::Expr(ref expr) => self.expr(expr),
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn qualifiers(&mut self, qualifiers: &[Qualifier]) {
+        for qualifier in qualifiers {
+            match *qualifier {
+                Qualifier::Generator(_, ref expr) |
+                Qualifier::Guard(ref expr)        => self.expr(expr),
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::Int(_)  |
+            ExprKind::Real(_) |
+            ExprKind::Bool(_) |
+            ExprKind::Char(_) |
+            ExprKind::Str(_)  |
+            ExprKind::Name(_) |
+            ExprKind::Op(_)   => {},
+            ExprKind::Apply(ref f, ref args) => {
+                self.expr(f);
+                self.exprs(args);
+            },
+            ExprKind::BinOp(_, ref lhs, ref rhs) |
+            ExprKind::Range(ref lhs, ref rhs, _) |
+            ExprKind::Index(ref lhs, ref rhs)    => {
+                self.expr(lhs);
+                self.expr(rhs);
+            },
+            ExprKind::Neg(ref operand)       |
+            ExprKind::Return(ref operand)    |
+            ExprKind::Propagate(ref operand) => self.expr(operand),
+            ExprKind::Slice(ref sliced, ref low, ref high) => {
+                self.expr(sliced);
+
+                for end in low.iter().chain(high) {
+                    self.expr(end);
+                }
+            },
+            ExprKind::Tuple(ref elems) |
+            ExprKind::List(ref elems)  |
+            ExprKind::Set(ref elems)   => self.exprs(elems),
+            ExprKind::Dict(ref entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            },
+            ExprKind::ListComp(ref elem, ref qualifiers) |
+            ExprKind::SetComp(ref elem, ref qualifiers)  => {
+                self.expr(elem);
+                self.qualifiers(qualifiers);
+            },
+            ExprKind::DictComp(ref entry, ref qualifiers) => {
+                self.expr(&entry.0);
+                self.expr(&entry.1);
+                self.qualifiers(qualifiers);
+            },
+            ExprKind::Lambda(_, ref body) => self.expr(body),
+            ExprKind::If(ref cond, ref body, ref else_body) => {
+                self.expr(cond);
+                self.stmts(body);
+
+                if let Some(ref else_body) = *else_body {
+                    self.stmts(else_body);
+                }
+            },
+            ExprKind::Case(ref subject, ref branches) => {
+                self.expr(subject);
+
+                for branch in branches {
+                    if let Some(ref guard) = branch.guard {
+                        self.expr(guard);
+                    }
+
+                    self.stmts(&branch.body);
+                }
+
+                self.case(branches, expr.span);
+            },
+            ExprKind::Try(ref body, _, ref handler) => {
+                self.stmts(body);
+                self.stmts(handler);
+            },
+            ExprKind::While(ref cond, ref body) => {
+                self.expr(cond);
+                self.stmts(body);
+            },
+            ExprKind::WhileLet(_, ref iterated, ref body) |
+            ExprKind::For(_, ref iterated, ref body) => {
+                self.expr(iterated);
+                self.stmts(body);
+            },
+        }
+    }
+
+    /// Checks the patterns of the branches of a `case` at `span`.
+    fn case(&mut self, branches: &[CaseBranch], span: Span) {
+        let mut rows: Vec<Vec<Pat>> = Vec::with_capacity(branches.len());
+        let mut ranges: Vec<(Ctor, Span)> = Vec::new();
+
+        for branch in branches {
+            let pattern = &branch.pattern;
+            let row = vec![self.pat(pattern)];
+
+            if !useful(&rows, &row) {
+                if branch.implicit {
+                    continue;
+                }
+
+                self.problems.push(CaseProblem::Unreachable {
+                    span: pattern.span,
+                });
+            } else if let PatternKind::Range(..) = pattern.kind {
+                let range = match row[0] {
+                    Pat::Ctor(ref ctor, _) => ctor.clone(),
+                    Pat::Wild | Pat::Or(_) => unreachable!(),
+                };
+                let overlapped = ranges.iter().find(|&(other, _)| {
+                    overlaps(&range, other) &&
+                        !other.covers(&range) &&
+                        !range.covers(other)
+                });
+
+                if let Some(&(_, other)) = overlapped {
+                    self.problems.push(CaseProblem::Overlapping {
+                        span:  pattern.span,
+                        other: other,
+                    });
+                }
+
+                ranges.push((range, pattern.span));
+            }
+
+            if branch.guard.is_none() {
+                rows.push(row);
+            }
+        }
+
+        if let Some(mut missing) = witness(&rows, 1) {
+            self.problems.push(CaseProblem::NonExhaustive {
+                missing: missing.remove(0).to_string(),
+                span:    span,
+            });
+        }
+    }
+
+    fn pat(&mut self, pattern: &Pattern) -> Pat {
+        match pattern.kind {
+            PatternKind::Bind(_) | PatternKind::Wildcard => Pat::Wild,
+            PatternKind::Int(i)  => Pat::Ctor(Ctor::Ints(i, i), Vec::new()),
+            PatternKind::Range(lo, hi, inclusive) => {
+                let hi = if inclusive { Some(hi) } else { hi.checked_sub(1) };
+                let ctor = match hi {
+                    Some(hi) => Ctor::Ints(lo, hi),
+                    None     => Ctor::Ints(1, 0),
+                };
+
+                Pat::Ctor(ctor, Vec::new())
+            },
+            PatternKind::Real(r) => {
+                Pat::Ctor(Ctor::Real(r.to_bits()), Vec::new())
+            },
+            PatternKind::Bool(b) => Pat::Ctor(Ctor::Bool(b), Vec::new()),
+            PatternKind::Char(c) => Pat::Ctor(Ctor::Char(c), Vec::new()),
+            PatternKind::Str(ref s) => {
+                Pat::Ctor(Ctor::Str(s.clone()), Vec::new())
+            },
+            PatternKind::Tuple(ref elems) => Pat::Ctor(
+                Ctor::Tuple(elems.len()),
+                elems.iter().map(|p| self.pat(p)).collect()
+            ),
+            PatternKind::List(ref elems) => Pat::Ctor(
+                Ctor::List(elems.len()),
+                elems.iter().map(|p| self.pat(p)).collect()
+            ),
+            PatternKind::As(_, ref matched) => self.pat(matched),
+            PatternKind::Or(ref alternatives) => Pat::Or(
+                alternatives.iter().map(|p| self.pat(p)).collect()
+            ),
+            PatternKind::Set(_)     |
+            PatternKind::Dict(_)    |
+            PatternKind::Prefix(..) |
+            PatternKind::Suffix(..) => {
+                self.opaque += 1;
+
+                Pat::Ctor(Ctor::Opaque(self.opaque), Vec::new())
+            },
+        }
+    }
+}
+
+/// Whether some value matched by the patterns of `row` is matched by no
+/// row of `rows`.
+fn useful(rows: &[Vec<Pat>], row: &[Pat]) -> bool {
+    let rows = &expand(rows);
+    let (first, rest) = match row.split_first() {
+        Some(split) => split,
+        None        => return rows.is_empty(),
+    };
+
+    match *first {
+        Pat::Or(ref alternatives) => alternatives.iter().any(|alternative| {
+            let mut alternative_row = vec![alternative.clone()];
+            alternative_row.extend_from_slice(rest);
+
+            useful(rows, &alternative_row)
+        }),
+        Pat::Ctor(ref ctor, ref fields) => {
+            let mut specialized_row = fields.clone();
+            specialized_row.extend_from_slice(rest);
+
+            split(ctor, &head_ctors(rows)).iter().any(|piece| {
+                useful(&specialize(rows, piece), &specialized_row)
+            })
+        },
+        Pat::Wild => {
+            let ctors = head_ctors(rows);
+
+            if is_complete(&ctors) {
+                pieces(&ctors).iter().any(|ctor| {
+                    let mut specialized_row = vec![Pat::Wild; ctor.arity()];
+                    specialized_row.extend_from_slice(rest);
+
+                    useful(&specialize(rows, ctor), &specialized_row)
+                })
+            } else {
+                useful(&default_rows(rows), rest)
+            }
+        },
+    }
+}
+
+/// Patterns, `width` of them, matching values of which no row of `rows`
+/// matches every one, if there are any such values.
+fn witness(rows: &[Vec<Pat>], width: usize) -> Option<Vec<Pat>> {
+    let rows = &expand(rows);
+
+    if width == 0 {
+        return if rows.is_empty() { Some(Vec::new()) } else { None };
+    }
+
+    let ctors = head_ctors(rows);
+
+    if is_complete(&ctors) {
+        for ctor in &pieces(&ctors) {
+            let arity = ctor.arity();
+
+            if let Some(mut found) = witness(
+                &specialize(rows, ctor),
+                arity + width - 1
+            ) {
+                let rest = found.split_off(arity);
+                let mut missing = vec![Pat::Ctor(ctor.clone(), found)];
+                missing.extend(rest);
+
+                return Some(missing);
+            }
+        }
+
+        return None;
+    }
+
+    witness(&default_rows(rows), width - 1).map(|rest| {
+        let mut missing = vec![unmatched(&ctors)];
+        missing.extend(rest);
+
+        missing
+    })
+}
+
+/// The constructors of the first patterns of `rows`, each once, in the
+/// order they first appear.
+fn head_ctors(rows: &[Vec<Pat>]) -> Vec<Ctor> {
+    let mut ctors = Vec::new();
+
+    for row in rows {
+        if let Pat::Ctor(ref ctor, _) = row[0] {
+            if !ctors.contains(ctor) {
+                ctors.push(ctor.clone());
+            }
+        }
+    }
+
+    ctors
+}
+
+/// Whether `ctors` are every way of making a value of their type. Only a
+/// tuple can be made just one way, and integers only by ranges that cover
+/// all of them; there are always more other literals, lists of other
+/// lengths, and other sets and dicts.
+fn is_complete(ctors: &[Ctor]) -> bool {
+    let tuple = ctors.iter().any(|ctor| match *ctor {
+        Ctor::Tuple(_) => true,
+        _              => false,
+    });
+
+    tuple ||
+        ctors.contains(&Ctor::Bool(true)) &&
+            ctors.contains(&Ctor::Bool(false)) ||
+        uncovered_int(ctors).is_none() &&
+            ctors.iter().any(|ctor| match *ctor {
+                Ctor::Ints(..) => true,
+                _              => false,
+            })
+}
+
+/// `ctors`, which are complete, with their ranges of integers replaced by
+/// the pieces (see `split`) that all integers are cut into by them.
+fn pieces(ctors: &[Ctor]) -> Vec<Ctor> {
+    let mut pieces: Vec<Ctor> = ctors.iter()
+                                     .filter(|ctor| match **ctor {
+                                         Ctor::Ints(..) => false,
+                                         _              => true,
+                                     })
+                                     .cloned()
+                                     .collect();
+
+    if pieces.len() < ctors.len() {
+        pieces.extend(split(&Ctor::Ints(i64::MIN, i64::MAX), ctors));
+    }
+
+    pieces
+}
+
+/// `ctor`, cut into pieces that each range of integers among `heads`
+/// covers the whole of or none of. Only a range of integers is cut; an
+/// empty one is no pieces at all.
+fn split(ctor: &Ctor, heads: &[Ctor]) -> Vec<Ctor> {
+    let (lo, hi) = match *ctor {
+        Ctor::Ints(lo, hi) if lo <= hi => (lo, hi),
+        Ctor::Ints(..)                 => return Vec::new(),
+        _                              => return vec![ctor.clone()],
+    };
+
+    // Where each piece starts: where the range does, and where any range
+    // among `heads` starts or has just ended within it.
+    let mut starts = vec![lo];
+
+    for head in heads {
+        if let Ctor::Ints(head_lo, head_hi) = *head {
+            if head_lo > head_hi {
+                continue;
+            }
+
+            for &start in &[Some(head_lo), head_hi.checked_add(1)] {
+                match start {
+                    Some(start) if lo < start && start <= hi => {
+                        starts.push(start);
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+
+    starts.sort();
+    starts.dedup();
+
+    starts.iter()
+          .enumerate()
+          .map(|(ix, &start)| {
+              let end = starts.get(ix + 1).map_or(hi, |next| next - 1);
+
+              Ctor::Ints(start, end)
+          })
+          .collect()
+}
+
+/// The integer nearest to 0 that no range of integers among `ctors`
+/// covers, if there is one.
+fn uncovered_int(ctors: &[Ctor]) -> Option<i64> {
+    split(&Ctor::Ints(i64::MIN, i64::MAX), ctors)
+        .into_iter()
+        .filter(|piece| !ctors.iter().any(|ctor| ctor.covers(piece)))
+        .filter_map(|piece| match piece {
+            Ctor::Ints(lo, hi) => Some(if lo > 0 {
+                lo
+            } else if hi < 0 {
+                hi
+            } else {
+                0
+            }),
+            _ => None,
+        })
+        .min_by_key(|i| i.checked_abs().unwrap_or(i64::MAX))
+}
+
+/// Whether `a` and `b` are ranges of integers with some in common.
+fn overlaps(a: &Ctor, b: &Ctor) -> bool {
+    match (a, b) {
+        (&Ctor::Ints(a_lo, a_hi), &Ctor::Ints(b_lo, b_hi)) => {
+            a_lo.max(b_lo) <= a_hi.min(b_hi)
+        },
+        _ => false,
+    }
+}
+
+/// A pattern for values made by none of `ctors`, which are not complete.
+fn unmatched(ctors: &[Ctor]) -> Pat {
+    if let Some(&Ctor::Bool(b)) = ctors.iter().find(|ctor| match **ctor {
+        Ctor::Bool(_) => true,
+        _             => false,
+    }) {
+        return Pat::Ctor(Ctor::Bool(!b), Vec::new());
+    }
+
+    let lists = ctors.iter().filter_map(|ctor| match *ctor {
+        Ctor::List(n) => Some(n),
+        _             => None,
+    }).collect::<Vec<_>>();
+
+    if lists.is_empty() {
+        return match uncovered_int(ctors) {
+            Some(i) if ctors.iter().any(|ctor| match *ctor {
+                Ctor::Ints(..) => true,
+                _              => false,
+            }) => Pat::Ctor(Ctor::Ints(i, i), Vec::new()),
+            _ => Pat::Wild,
+        };
+    }
+
+    let len = (0..).find(|n| !lists.contains(n)).unwrap_or(0);
+
+    Pat::Ctor(Ctor::List(len), vec![Pat::Wild; len])
+}
+
+/// The rows of `rows` that can match a value made by `ctor`, with their
+/// first pattern replaced by patterns for its fields.
+fn specialize(rows: &[Vec<Pat>], ctor: &Ctor) -> Vec<Vec<Pat>> {
+    let mut specialized = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut new_row = match row[0] {
+            Pat::Ctor(ref c, ref fields) if c.covers(ctor) => fields.clone(),
+            Pat::Ctor(..) => continue,
+            Pat::Wild     => vec![Pat::Wild; ctor.arity()],
+            // Taken apart by `expand` before the rows get here.
+            Pat::Or(_)    => unreachable!(),
+        };
+        new_row.extend_from_slice(&row[1..]);
+
+        specialized.push(new_row);
+    }
+
+    specialized
+}
+
+/// `rows`, with each whose first pattern is an or-pattern replaced by a
+/// row for each of its alternatives.
+fn expand(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    let mut expanded = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        match row.first() {
+            Some(Pat::Or(alternatives)) => {
+                let alternative_rows: Vec<Vec<Pat>> = alternatives
+                    .iter()
+                    .map(|alternative| {
+                        let mut alternative_row = vec![alternative.clone()];
+                        alternative_row.extend_from_slice(&row[1..]);
+
+                        alternative_row
+                    })
+                    .collect();
+
+                expanded.extend(expand(&alternative_rows));
+            },
+            _ => expanded.push(row.clone()),
+        }
+    }
+
+    expanded
+}
+
+/// The rows of `rows` whose first pattern matches anything, without it.
+fn default_rows(rows: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    rows.iter()
+        .filter(|row| match row[0] {
+            Pat::Wild => true,
+            _         => false,
+        })
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast;
+    use parser::Parser;
+
+    fn problems(src: &str) -> Vec<CaseProblem> {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        check(&ast::lower(&tree).unwrap())
+    }
+
+    /// The problems with `case x` given `branches`, one to a line.
+    fn case(branches: &[&str]) -> Vec<String> {
+        let mut src = "module Main\n\nfn f x\n    case x\n".to_string();
+        for branch in branches {
+            src.push_str(&format!("        {}\n", branch));
+        }
+
+        problems(&src).iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn a_bool_needs_both_true_and_false() {
+        assert_eq!(
+            case(&["True => 1"]),
+            vec!["4:5: case does not cover every value: False is not matched"]
+        );
+        assert!(case(&["True => 1", "False => 0"]).is_empty());
+    }
+
+    #[test]
+    fn a_tuple_needs_every_combination_of_its_elements() {
+        assert_eq!(
+            case(&["(True, _) => 1", "(_, True) => 2"]),
+            vec!["4:5: case does not cover every value: \
+                  (False, False) is not matched"]
+        );
+    }
+
+    #[test]
+    fn a_branch_with_a_guard_covers_nothing() {
+        assert_eq!(
+            case(&["n if n > 0 => 1"]),
+            vec!["4:5: case does not cover every value: _ is not matched"]
+        );
+    }
+
+    #[test]
+    fn integer_ranges_must_cover_every_integer() {
+        let problems = case(&["0..=9 => 1", "10 => 2", "_ if True => 3"]);
+
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].ends_with("is not matched"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn ranges_that_partly_overlap_are_warned_about() {
+        let src = "module Main\n\n\
+                   fn f x\n    case x\n        1..=5 => 1\n        \
+                   3..=8 => 2\n        _ => 3\n";
+        let problems = problems(src);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].code(), "W0102");
+        assert_eq!(problems[0].to_string(),
+                   "6:9: range overlaps the range at 5:9");
+    }
+
+    #[test]
+    fn branches_after_one_that_matches_anything_are_unreachable() {
+        let problems = problems(
+            "module Main\n\n\
+             fn f x\n    case x\n        _ => 1\n        0 => 2\n"
+        );
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].code(), "W0101");
+        assert_eq!(problems[0].span().line, 6);
+    }
+
+    #[test]
+    fn the_else_an_if_pattern_never_wrote_is_not_unreachable() {
+        let src = "module Main\n\n\
+                   fn f pair\n    if (a, b) <- pair\n        a + b\n";
+
+        assert!(problems(src).is_empty(), "{:?}", problems(src));
+    }
+
+    #[test]
+    fn a_written_else_after_an_if_pattern_that_cannot_fail_is() {
+        let src = "module Main\n\n\
+                   fn f pair\n    if (a, b) <- pair\n        a + b\n    \
+                   else\n        0\n";
+        let problems = problems(src);
+
+        assert_eq!(problems.len(), 1, "{:?}", problems);
+        assert_eq!(problems[0].to_string(), "6:5: unreachable branch");
+    }
+}
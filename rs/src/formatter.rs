@@ -0,0 +1,757 @@
+//! Printing parse trees back out as canonical source, for `brouwer fmt`.
+//!
+//! Every construct is laid out one way: blocks indented by a fixed number
+//! of spaces; one space between tokens, except inside brackets, before
+//! commas and colons, and within literals and qualified names; and a blank
+//! line after the module declaration and after the imports. Other blank
+//! lines are kept, though never more than one in a row, and so are
+//! comments, except that a comment between tokens on a line is moved to
+//! the end of the line. A block comment over several lines is kept as it
+//! is. An import list, or a tuple, list, dict, or set, too long for its
+//! line is wrapped one item per line; a comprehension too long for its line
+//! has its expression, then what it draws from, on lines of their own.
+//! Nothing else is broken over lines. Lines end in `\r\n` if the first
+//! line of the source does, and in `\n` otherwise.
+//!
+//! ```
+//! use brouwer::formatter::{self, FormatConfig};
+//!
+//! let src = "module Main\nimport   List (map,filter)\n-- xs\nx   =  [1,2]\n";
+//! let formatted = formatter::format(src, &FormatConfig::default()).unwrap();
+//!
+//! assert_eq!(
+//!     formatted,
+//!     "module Main\n\nimport List (map, filter)\n\n-- xs\nx = [1, 2]\n"
+//! );
+//!
+//! let crlf = "module Main\r\nx = 1\r\n";
+//! let formatted = formatter::format(crlf, &FormatConfig::default()).unwrap();
+//!
+//! assert_eq!(formatted, "module Main\r\n\r\nx = 1\r\n");
+//! ```
+
+use std::ops::Range;
+
+use error::ParseError;
+use lexer;
+use parser::{AST, Parser};
+use pretty::{self, Doc, bracketed, concat, group, hardline, join, line,
+             nest, softline, text};
+use scan;
+use token::{Span, TokenType};
+
+
+/// How `format` lays source out.
+#[derive(Clone, Debug)]
+pub struct FormatConfig {
+    /// Columns to fit lines within, where they can be broken.
+    pub width:  usize,
+    /// Spaces to indent each block by.
+    pub indent: usize,
+}
+
+/// A comment, and the lines it starts and ends on.
+#[derive(Clone)]
+struct Comment {
+    line:       usize,
+    end_line:   usize,
+    text:       String,
+    /// Whether there is code before the comment on its first line.
+    trailing:   bool,
+    /// Whether there is code after the comment on its last line.
+    code_after: bool,
+}
+
+/// Builds the document for a file, putting each of its comments back in
+/// as the code around it is reached.
+struct Formatter<'a> {
+    config:      &'a FormatConfig,
+    src:         &'a str,
+    line_starts: Vec<usize>,
+    comments:    Vec<Comment>,
+    next:        usize,
+    /// The last line of the source that has been laid out.
+    last_line:   usize,
+}
+
+/// Part of a line: a token; a document (a block, or a line break) that
+/// the tokens around it are not spaced from; or a document in brackets,
+/// spaced as its opening and closing brackets are.
+enum Piece {
+    Token(TokenType, String),
+    Doc(Doc),
+    Bracketed(TokenType, Doc, TokenType),
+}
+
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        FormatConfig {
+            width:  pretty::DEFAULT_WIDTH,
+            indent: 4,
+        }
+    }
+}
+
+/// Formats the source `src`. Fails if it does not parse.
+pub fn format(src: &str, config: &FormatConfig) -> Result<String, ParseError> {
+    let ast = match Parser::from_str(src).parse()? {
+        Some(ast) => ast,
+        None      => return Err(ParseError::MissingToken {
+            message:  "expected module declaration",
+            expected: vec![TokenType::ModuleKeyword],
+            span:     Span {
+                line: 1,
+                col:  1,
+                ..Span::default()
+            },
+        }),
+    };
+
+    let line_starts = line_starts(src);
+    let comments = find_comments(src, &line_starts)?;
+
+    let mut formatter = Formatter {
+        config:      config,
+        src:         src,
+        line_starts: line_starts,
+        comments:    comments,
+        next:        0,
+        last_line:   0,
+    };
+
+    let doc = match ast.children().first() {
+        Some(prog) => formatter.items(prog.children(), true),
+        None       => pretty::nil(),
+    };
+
+    let mut formatted = pretty::render(&doc, config.width);
+    let trimmed_len = formatted.trim_end().len();
+    formatted.truncate(trimmed_len);
+    formatted.push('\n');
+
+    // Block comments are kept as they are, so may have either line ending.
+    match src.find('\n') {
+        Some(ix) if src[..ix].ends_with('\r') => {
+            Ok(formatted.replace("\r\n", "\n").replace('\n', "\r\n"))
+        },
+        _ => Ok(formatted.replace("\r\n", "\n")),
+    }
+}
+
+impl<'a> Formatter<'a> {
+    /// Lays out a run of lines, or of case branches, one per line. At the
+    /// top level, `items` are the module declaration, imports, and lines
+    /// of a program, and any comments left over go after them.
+    fn items(&mut self, items: &[AST], top_level: bool) -> Doc {
+        let mut docs = Vec::with_capacity(2 * items.len());
+        let mut last_type = None;
+
+        for item in items.iter().filter(|item| !item.children().is_empty()) {
+            let type_ = item.val().type_.clone();
+            let start = item.line();
+            let end = self.line_of(item.span().end().saturating_sub(1));
+
+            let mut blank = top_level && match last_type {
+                Some(TokenType::ModDecl) => true,
+                Some(TokenType::Import)  => type_ != TokenType::Import,
+                _                        => false,
+            };
+
+            while let Some(comment) = self.take_comment(start, false) {
+                self.put_comment(&mut docs, comment, blank);
+                blank = false;
+            }
+
+            self.separate(&mut docs, start, blank);
+            docs.push(self.item(item));
+            self.last_line = end;
+
+            while let Some(comment) = self.take_comment(end + 1, false) {
+                self.put_comment(&mut docs, comment, false);
+            }
+
+            last_type = Some(type_);
+        }
+
+        if top_level {
+            let eof = usize::MAX;
+
+            while let Some(comment) = self.take_comment(eof, false) {
+                self.put_comment(&mut docs, comment, false);
+            }
+        }
+
+        concat(docs)
+    }
+
+    fn item(&mut self, item: &AST) -> Doc {
+        if item.val().type_ == TokenType::Import {
+            return self.import(item);
+        }
+
+        let mut pieces = Vec::with_capacity(16);
+        self.pieces(item, &mut pieces);
+
+        line_doc(pieces)
+    }
+
+    /// `import Name (a, b, c)`, with the list wrapped if it is too long.
+    fn import(&mut self, import: &AST) -> Doc {
+        let children = import.children();
+        let l_paren = children.iter()
+                              .position(|c| c.val().type_ == TokenType::LParen);
+
+        let mut head = Vec::with_capacity(4);
+
+        for child in &children[..l_paren.unwrap_or(children.len())] {
+            self.pieces(child, &mut head);
+        }
+
+        let l_paren = if let Some(ix) = l_paren {
+            ix
+        } else {
+            return line_doc(head);
+        };
+
+        let names = children[l_paren + 1..]
+            .iter()
+            .filter(|c| c.val().type_ == TokenType::Ident)
+            .map(|c| text(c.val().lexeme().to_string()))
+            .collect();
+
+        concat(vec![
+            line_doc(head),
+            text(" "),
+            bracketed("(", names, ")", self.config.indent),
+        ])
+    }
+
+    /// Appends the pieces of `ast` to `out`.
+    fn pieces(&mut self, ast: &AST, out: &mut Vec<Piece>) {
+        let type_ = ast.val().type_.clone();
+
+        if ast.children().is_empty() {
+            if !ast.val().lexeme().is_empty() {
+                out.push(Piece::Token(type_, ast.val().lexeme().to_string()));
+            }
+
+            return;
+        }
+
+        match type_ {
+            // A triple-quoted string as written, since its leaves leave out
+            // the indentation that it strips.
+            TokenType::StrLit if is_text_block(ast) => {
+                out.push(Piece::Token(type_, self.written_span(ast)));
+            },
+            // Written as one token, with nothing between the parts.
+            TokenType::StrLit      |
+            TokenType::ChrLit      |
+            TokenType::MemberIdent |
+            TokenType::ScopedIdent |
+            TokenType::Infixed     => {
+                out.push(Piece::Token(type_, leaf_text(ast)));
+            },
+            // As written, since the lexemes of its leaves leave out any
+            // underscores.
+            TokenType::NumLit => {
+                out.push(Piece::Token(type_, self.written(ast)));
+            },
+            // With nothing around the brackets or the colon of a slice,
+            // since `xs [i]` would apply `xs` to a list.
+            TokenType::Index => {
+                for child in ast.children() {
+                    match child.val().type_ {
+                        TokenType::LSqBracket | TokenType::Colon => {
+                            let bracket = child.val().lexeme().to_string();
+                            out.push(Piece::Doc(text(bracket)));
+                        },
+                        _ => self.pieces(child, out),
+                    }
+                }
+            },
+            // With nothing before the `?`, since `x ? y` is an operator.
+            TokenType::Propagate => {
+                if let Some(operand) = ast.children().first() {
+                    self.pieces(operand, out);
+                }
+
+                out.push(Piece::Doc(text("?".to_string())));
+            },
+            TokenType::TupleLit |
+            TokenType::ListLit  |
+            TokenType::DictLit  |
+            TokenType::SetLit   => out.push(self.collection(ast)),
+            TokenType::ListComp |
+            TokenType::DictComp |
+            TokenType::SetComp  => out.push(self.comprehension(ast)),
+            TokenType::UnaryOp => {
+                out.push(Piece::Token(TokenType::Minus, "-".to_string()));
+
+                if let Some(operand) = ast.children().get(1) {
+                    self.pieces(operand, out);
+                }
+            },
+            TokenType::FnDecl     |
+            TokenType::IfElse     |
+            TokenType::Try        |
+            TokenType::While      |
+            TokenType::For        |
+            TokenType::Case       |
+            TokenType::CaseBranch => self.block_pieces(ast, out),
+            _ => {
+                for child in ast.children() {
+                    self.pieces(child, out);
+                }
+            },
+        }
+    }
+
+    /// A tuple, list, dict, or set, wrapped one item per line if it is too
+    /// long for its line.
+    fn collection(&mut self, ast: &AST) -> Piece {
+        let children = ast.children();
+        let (open, close) = (&children[0], &children[children.len() - 1]);
+        let items = children[1..children.len() - 1]
+            .iter()
+            .filter(|c| c.val().type_ != TokenType::Comma)
+            .map(|c| self.item(c))
+            .collect();
+
+        Piece::Bracketed(
+            open.val().type_.clone(),
+            bracketed(
+                open.val().lexeme(),
+                items,
+                close.val().lexeme(),
+                self.config.indent
+            ),
+            close.val().type_.clone()
+        )
+    }
+
+    /// A comprehension, `[e | x <- xs, c]`. If it is too long for its
+    /// line, `e` and then what follows the `|` go on lines of their own,
+    /// and the generators and conditions are wrapped one per line if they
+    /// are still too long.
+    fn comprehension(&mut self, ast: &AST) -> Piece {
+        let children = ast.children();
+        let (open, close) = (&children[0], &children[children.len() - 1]);
+        let expr = self.item(&children[1]);
+        let quals = children[3..children.len() - 1]
+            .iter()
+            .filter(|c| c.val().type_ != TokenType::Comma)
+            .map(|c| self.item(c))
+            .collect();
+
+        let doc = group(concat(vec![
+            text(open.val().lexeme()),
+            nest(self.config.indent, concat(vec![
+                softline(),
+                expr,
+                line(),
+                text("| "),
+                nest(2, group(join(quals, concat(vec![text(","), line()])))),
+            ])),
+            softline(),
+            text(close.val().lexeme()),
+        ]));
+
+        Piece::Bracketed(
+            open.val().type_.clone(),
+            doc,
+            close.val().type_.clone()
+        )
+    }
+
+    /// Appends the pieces of a construct with a body (a `fn`, `if`, `try`,
+    /// loop, `case`, or branch of a `case`) to `out`: its header and any
+    /// inline bodies, then each block on the lines after the header it
+    /// belongs to. A branch's block comes after its `=>`, on the lines
+    /// below it.
+    fn block_pieces(&mut self, ast: &AST, out: &mut Vec<Piece>) {
+        let children = ast.children();
+        let mut i = 0;
+
+        while i < children.len() {
+            let child = &children[i];
+
+            match child.val().type_ {
+                TokenType::Line | TokenType::CaseBranch
+                    if i == 0 ||
+                       !is_body_sep(&children[i - 1]) ||
+                       child.line() > children[i - 1].line() =>
+                {
+                    let run = children[i..]
+                        .iter()
+                        .take_while(|c| {
+                            c.val().type_ == TokenType::Line ||
+                            c.val().type_ == TokenType::CaseBranch
+                        })
+                        .count();
+
+                    out.push(Piece::Doc(self.block(&children[i..i + run])));
+                    i += run;
+
+                    continue;
+                },
+                TokenType::ElseKeyword | TokenType::CatchKeyword => {
+                    let mut docs = Vec::with_capacity(2);
+
+                    while let Some(comment) = self.take_comment(
+                        child.line(),
+                        false
+                    ) {
+                        self.put_comment(&mut docs, comment, false);
+                    }
+
+                    docs.push(hardline());
+                    out.push(Piece::Doc(concat(docs)));
+                    self.pieces(child, out);
+                },
+                _ => self.pieces(child, out),
+            }
+
+            i += 1;
+        }
+    }
+
+    /// The lines of a block, each on its own line below the header and
+    /// indented. A comment after the header goes with the header.
+    fn block(&mut self, items: &[AST]) -> Doc {
+        let mut header = Vec::with_capacity(1);
+
+        if let Some(first) = items.first() {
+            while let Some(comment) = self.take_comment(first.line(), true) {
+                self.put_comment(&mut header, comment, false);
+            }
+        }
+
+        header.push(nest(self.config.indent, concat(vec![
+            hardline(),
+            self.items(items, false),
+        ])));
+
+        concat(header)
+    }
+
+    /// Takes the next comment if it is on a line before `line`, or starts
+    /// `line` with code after it, and, if `trailing_only`, if there is code
+    /// before it on its line.
+    fn take_comment(
+        &mut self,
+        line:          usize,
+        trailing_only: bool
+    ) -> Option<Comment> {
+        let comment = match self.comments.get(self.next) {
+            Some(c) if c.line < line && (c.trailing || !trailing_only) => {
+                c.clone()
+            },
+            Some(c) if c.line == line && c.code_after && !c.trailing &&
+                       !trailing_only => c.clone(),
+            _ => return None,
+        };
+
+        self.next += 1;
+
+        Some(comment)
+    }
+
+    /// Puts `comment` after what is in `docs`: at the end of the line, if
+    /// it was after code in the source, and otherwise on a line of its own.
+    fn put_comment(
+        &mut self,
+        docs:    &mut Vec<Doc>,
+        comment: Comment,
+        blank:   bool
+    ) {
+        if comment.trailing {
+            docs.push(text(" "));
+        } else {
+            self.separate(docs, comment.line, blank);
+        }
+
+        docs.push(text(comment.text));
+        self.last_line = comment.end_line;
+    }
+
+    /// The source text of the leaves of `ast`, with nothing between them.
+    fn written(&self, ast: &AST) -> String {
+        if ast.children().is_empty() {
+            let span = ast.val().span;
+
+            return self.src[span.byte_offset..span.end()].to_string();
+        }
+
+        ast.children().iter().map(|child| self.written(child)).collect()
+    }
+
+    /// The source text from the start of `ast` to its end, including what
+    /// is between its leaves.
+    fn written_span(&self, ast: &AST) -> String {
+        let span = ast.span();
+
+        self.src[span.byte_offset..span.end()].to_string()
+    }
+
+    /// Starts a new line for something starting on source line `line`,
+    /// after what is in `docs`, with a blank line first if `blank` or if
+    /// there was one in the source.
+    fn separate(&self, docs: &mut Vec<Doc>, line: usize, blank: bool) {
+        if docs.is_empty() {
+            return;
+        }
+
+        docs.push(hardline());
+
+        if blank || line > self.last_line + 1 {
+            docs.push(hardline());
+        }
+    }
+
+    /// The line the byte at `offset` is on.
+    fn line_of(&self, offset: usize) -> usize {
+        line_at(&self.line_starts, offset)
+    }
+}
+
+/// Lays out the pieces of one line, putting a space between tokens where
+/// one belongs.
+fn line_doc(pieces: Vec<Piece>) -> Doc {
+    let mut docs = Vec::with_capacity(2 * pieces.len());
+    let mut last = None;
+
+    for piece in pieces {
+        match piece {
+            Piece::Token(type_, lexeme) => {
+                if last.is_some_and(|l| spaced(l, &type_)) {
+                    docs.push(text(" "));
+                }
+
+                docs.push(text(lexeme));
+                last = Some(type_);
+            },
+            Piece::Doc(doc) => {
+                docs.push(doc);
+                last = None;
+            },
+            Piece::Bracketed(open, doc, close) => {
+                if last.is_some_and(|l| spaced(l, &open)) {
+                    docs.push(text(" "));
+                }
+
+                docs.push(doc);
+                last = Some(close);
+            },
+        }
+    }
+
+    concat(docs)
+}
+
+/// Whether a token of type `next` is spaced from a token of type `last`
+/// before it.
+fn spaced(last: TokenType, next: &TokenType) -> bool {
+    match last {
+        TokenType::LParen        |
+        TokenType::LSqBracket    |
+        TokenType::LCurlyBracket |
+        TokenType::Backslash     |
+        TokenType::Minus         |
+        TokenType::DotDot        |
+        TokenType::DotDotEquals  => return false,
+        _                        => {},
+    }
+
+    match *next {
+        TokenType::RParen        |
+        TokenType::RSqBracket    |
+        TokenType::RCurlyBracket |
+        TokenType::Comma         |
+        TokenType::Colon         |
+        TokenType::DotDot        |
+        TokenType::DotDotEquals  => false,
+        _                        => true,
+    }
+}
+
+/// Whether `ast` is the `=` or `=>` before an inline body.
+fn is_body_sep(ast: &AST) -> bool {
+    ast.val().type_ == TokenType::Equals ||
+        ast.val().type_ == TokenType::FatRArrow
+}
+
+/// The lexemes of the leaves below `ast`, run together.
+fn leaf_text(ast: &AST) -> String {
+    if ast.children().is_empty() {
+        return ast.val().lexeme().to_string();
+    }
+
+    ast.children().iter().map(leaf_text).collect()
+}
+
+/// Whether the `StrLit` `str_lit` is triple-quoted.
+fn is_text_block(str_lit: &AST) -> bool {
+    str_lit.children()
+           .first()
+           .is_some_and(|quote| quote.val().type_ == TokenType::TripleQuote)
+}
+
+/// The byte offset at which each line of `src` starts.
+fn line_starts(src: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+
+    for (i, c) in src.char_indices() {
+        if c == '\n' {
+            starts.push(i + 1);
+        }
+    }
+
+    starts
+}
+
+/// The line that byte `offset` is on, given where each line starts.
+fn line_at(line_starts: &[usize], offset: usize) -> usize {
+    match line_starts.binary_search(&offset) {
+        Ok(ix)  => ix + 1,
+        Err(ix) => ix,
+    }
+}
+
+/// Every comment in `src`, in order: a line comment runs from `--` to the
+/// end of its line, and a block comment from `{-` to the `-}` closing it.
+/// Comments are found in the gaps the tokens leave.
+fn find_comments(
+    src:         &str,
+    line_starts: &[usize]
+) -> Result<Vec<Comment>, ParseError> {
+    let mut comments = Vec::new();
+    let mut gap_start = 0;
+    let mut code_line = None;
+
+    for token in lexer::tokenize(src)? {
+        match token.type_ {
+            TokenType::Newline |
+            TokenType::Indent  |
+            TokenType::Dedent  => continue,
+            _                  => {},
+        }
+
+        gap_comments(
+            src,
+            line_starts,
+            gap_start..token.span.byte_offset,
+            code_line,
+            Some(token.span.line),
+            &mut comments
+        );
+
+        gap_start = token.span.end();
+        code_line = Some(line_at(line_starts, gap_start - 1));
+    }
+
+    gap_comments(
+        src,
+        line_starts,
+        gap_start..src.len(),
+        code_line,
+        None,
+        &mut comments
+    );
+
+    Ok(comments)
+}
+
+/// The comments in `gap`, a stretch of `src` between the end of the code
+/// on `code_before`, if any, and the start of the code on `code_after`.
+fn gap_comments(
+    src:         &str,
+    line_starts: &[usize],
+    gap:         Range<usize>,
+    code_before: Option<usize>,
+    code_after:  Option<usize>,
+    comments:    &mut Vec<Comment>
+) {
+    let mut pos = gap.start;
+
+    while pos < gap.end {
+        let rest = &src[pos..gap.end];
+
+        let len = if rest.starts_with("--") {
+            scan::line_len(rest.as_bytes())
+        } else if rest.starts_with("{-") {
+            lexer::block_comment_len(rest).unwrap_or(rest.len())
+        } else {
+            pos += rest.chars().next().map_or(1, char::len_utf8);
+
+            continue;
+        };
+
+        let line = line_at(line_starts, pos);
+        let end_line = line_at(line_starts, pos + len - 1);
+
+        comments.push(Comment {
+            line:       line,
+            end_line:   end_line,
+            text:       rest[..len].trim_end().to_string(),
+            trailing:   code_before == Some(line),
+            code_after: code_after == Some(end_line),
+        });
+
+        pos += len;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn narrow(src: &str) -> String {
+        let config = FormatConfig {
+            width: 24,
+            ..FormatConfig::default()
+        };
+
+        format(src, &config).unwrap()
+    }
+
+    #[test]
+    fn long_collections_wrap_one_item_per_line() {
+        let src = "module Main\n\n\
+                   xs = [100000, 200000, 300000]\n\
+                   t = f (100000, 200000, 300000)\n\
+                   ys = [1, 2]\n";
+
+        assert_eq!(
+            narrow(src),
+            "module Main\n\n\
+             xs = [\n    100000,\n    200000,\n    300000\n]\n\
+             t = f (\n    100000,\n    200000,\n    300000\n)\n\
+             ys = [1, 2]\n"
+        );
+    }
+
+    #[test]
+    fn long_comprehension_wraps_before_the_bar() {
+        let src = "module Main\n\nys = [x * 2 | x <- xs, x > 100000]\n";
+
+        assert_eq!(
+            narrow(src),
+            "module Main\n\n\
+             ys = [\n    x * 2\n    | x <- xs,\n      x > 100000\n]\n"
+        );
+    }
+
+    #[test]
+    fn wrapped_output_formats_to_itself() {
+        let src = "module Main\n\n\
+                   xs = [100000, 200000, 300000]\n\
+                   ys = [x * 2 | x <- xs, x > 100000]\n\
+                   s = {100000, 200000, 300000}\n";
+        let once = narrow(src);
+
+        assert_eq!(narrow(&once), once);
+    }
+}
@@ -0,0 +1,420 @@
+//! The module graph: which modules import which.
+//!
+//! The graph is built from module headers (the module declaration and the
+//! imports) rather than from whole parse trees, so that a file which does
+//! not parse still takes part in it. `recover_header` reads a header one
+//! line at a time, keeping whatever it can make sense of: a mistake in the
+//! body costs nothing, and a mistake in one import costs at most that
+//! import.
+//!
+//! ```
+//! use brouwer::graph::{self, ModuleGraph};
+//!
+//! let main = graph::recover_header(
+//!     "module Main\nimport Util (f)\nimport List as L\n\nf (((\n"
+//! );
+//! let util = graph::recover_header("module Util exposing f\n\nfn f x = x\n");
+//!
+//! let mut modules = ModuleGraph::new();
+//! modules.insert(main);
+//! modules.insert(util);
+//!
+//! assert_eq!(modules.imports_of("Main"), vec!["Util", "List"]);
+//! assert_eq!(modules.importers_of("Util"), vec!["Main"]);
+//! assert_eq!(modules.unresolved().len(), 1);
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ast::{Ident, Import, ImportNames};
+use error::ParseError;
+use lexer::Lexer;
+use parser::{is_blank, is_op_char};
+use token::{Span, Token, TokenType};
+
+
+/// What could be recovered of a module's header.
+#[derive(Debug, Default)]
+pub struct Header {
+    /// The name in the module declaration, if there is one that can be
+    /// read.
+    pub module:  Option<Ident>,
+    /// Every import whose module name can be read, in order.
+    pub imports: Vec<Import>,
+    /// What was wrong with the header, in order. The body is not checked.
+    pub errors:  Vec<ParseError>,
+}
+
+/// The imports between a set of modules, each known by its header.
+#[derive(Debug, Default)]
+pub struct ModuleGraph {
+    modules: BTreeMap<String, Header>,
+}
+
+
+impl ModuleGraph {
+    /// A graph with no modules.
+    pub fn new() -> Self {
+        ModuleGraph::default()
+    }
+
+    /// Adds a module, replacing any earlier header for a module of the
+    /// same name. Returns `false`, and adds nothing, if the header has no
+    /// module name.
+    pub fn insert(&mut self, header: Header) -> bool {
+        let name = match header.module {
+            Some(ref ident) => ident.name.clone(),
+            None            => return false,
+        };
+
+        self.modules.insert(name, header);
+
+        true
+    }
+
+    /// Removes a module, returning its header.
+    pub fn remove(&mut self, module: &str) -> Option<Header> {
+        self.modules.remove(module)
+    }
+
+    /// The header of `module`, if it is in the graph.
+    pub fn header(&self, module: &str) -> Option<&Header> {
+        self.modules.get(module)
+    }
+
+    /// The name of every module in the graph, in sorted order.
+    pub fn modules(&self) -> Vec<&str> {
+        self.modules.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// The modules that `module` imports, in the order it imports them,
+    /// whether or not they are in the graph.
+    pub fn imports_of(&self, module: &str) -> Vec<&str> {
+        let mut imported: Vec<&str> = Vec::new();
+
+        if let Some(header) = self.modules.get(module) {
+            for import in &header.imports {
+                if !imported.contains(&import.module.name.as_str()) {
+                    imported.push(&import.module.name);
+                }
+            }
+        }
+
+        imported
+    }
+
+    /// The modules in the graph that import `module`, in sorted order.
+    pub fn importers_of(&self, module: &str) -> Vec<&str> {
+        self.modules
+            .iter()
+            .filter(|&(_, header)|
+                header.imports.iter().any(|i| i.module.name == module)
+            )
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Every import of a module that is not in the graph, with the name of
+    /// the module that makes it.
+    pub fn unresolved(&self) -> Vec<(&str, &Import)> {
+        let mut unresolved = Vec::new();
+
+        for (name, header) in &self.modules {
+            for import in &header.imports {
+                if !self.modules.contains_key(&import.module.name) {
+                    unresolved.push((name.as_str(), import));
+                }
+            }
+        }
+
+        unresolved
+    }
+}
+
+/// Reads the module declaration and imports at the top of `src`, however
+/// broken it, or the rest of the file, is.
+///
+/// Each line of the header is read on its own. A line that does not lex
+/// is skipped, with its error recorded, and the header goes on. The header
+/// ends at the first line that lexes but is neither a module declaration
+/// nor an import.
+pub fn recover_header(src: &str) -> Header {
+    let mut header = Header::default();
+    let shared: Arc<str> = src.into();
+    let mut pos = 0;
+    // The end of the last line read, which is further on than the line it
+    // started on if an import list was wrapped or a block comment ran over
+    // more than one line.
+    let mut read_to = 0;
+
+    for (i, text) in src.split('\n').enumerate() {
+        let line = i + 1;
+        let start = pos;
+        pos += text.len() + 1;
+
+        if start < read_to || is_blank_line(text) ||
+           text.starts_with(is_blank)
+        {
+            continue;
+        }
+
+        let tokens = match line_tokens(&shared, start, line) {
+            Ok((tokens, end)) => {
+                read_to = end;
+
+                tokens
+            },
+            Err(e) => {
+                header.errors.push(e);
+
+                continue;
+            },
+        };
+
+        let first = match tokens.first() {
+            Some(first) => first,
+            None        => continue,
+        };
+
+        match first.type_ {
+            TokenType::ModuleKeyword => {
+                if header.module.is_some() || !header.imports.is_empty() {
+                    header.errors.push(missing(
+                        "expected one module declaration, before any imports",
+                        vec![TokenType::ImportKeyword],
+                        first.span
+                    ));
+                } else {
+                    recover_mod_decl(&tokens, &mut header);
+                }
+            },
+            TokenType::ImportKeyword => {
+                if header.module.is_none() && header.imports.is_empty() {
+                    header.errors.push(missing(
+                        "expected module declaration before imports",
+                        vec![TokenType::ModuleKeyword],
+                        first.span
+                    ));
+                }
+
+                recover_import(&tokens, &mut header);
+            },
+            _ => break,
+        }
+    }
+
+    header
+}
+
+fn recover_mod_decl(tokens: &[Token], header: &mut Header) {
+    match tokens.get(1) {
+        Some(name) if name.type_ == TokenType::Ident => {
+            header.module = Some(ident(name));
+        },
+        _ => header.errors.push(missing(
+            "expected name of module to be plain identifier",
+            vec![TokenType::Ident],
+            after(tokens, 1)
+        )),
+    }
+}
+
+fn recover_import(tokens: &[Token], header: &mut Header) {
+    let module = match tokens.get(1) {
+        Some(name) if name.type_ == TokenType::Ident => ident(name),
+        _ => {
+            header.errors.push(missing(
+                "expected module name after import keyword",
+                vec![TokenType::Ident],
+                after(tokens, 1)
+            ));
+
+            return;
+        },
+    };
+
+    let names = match tokens.get(2).map(|t| &t.type_) {
+        Some(&TokenType::AsKeyword) => match tokens.get(3) {
+            Some(alias) if alias.type_ == TokenType::Ident => {
+                ImportNames::Alias(ident(alias))
+            },
+            _ => {
+                header.errors.push(missing(
+                    "expected namespace alias after as keyword",
+                    vec![TokenType::Ident],
+                    after(tokens, 3)
+                ));
+
+                ImportNames::Exposing(Vec::new())
+            },
+        },
+        Some(&TokenType::HidingKeyword) => {
+            ImportNames::Hiding(recover_import_list(tokens, 3, header))
+        },
+        _ => ImportNames::Exposing(recover_import_list(tokens, 2, header)),
+    };
+
+    let last = &tokens[tokens.len() - 1].span;
+    let span = Span {
+        len: last.byte_offset + last.len - tokens[0].span.byte_offset,
+        ..tokens[0].span
+    };
+
+    header.imports.push(Import {
+        module: module,
+        names:  names,
+        span:   span,
+    });
+}
+
+/// The identifiers of the parenthesized import list starting at `start`
+/// in the tokens of an import line, recording an error if the parentheses
+/// are missing.
+fn recover_import_list(
+    tokens: &[Token],
+    start:  usize,
+    header: &mut Header
+) -> Vec<Ident> {
+    let mut names = Vec::new();
+
+    match tokens.get(start) {
+        Some(l_paren) if l_paren.type_ == TokenType::LParen => {},
+        _ => {
+            header.errors.push(missing(
+                "expected left paren to start import list",
+                vec![TokenType::LParen],
+                after(tokens, start)
+            ));
+
+            return names;
+        },
+    }
+
+    for token in &tokens[start + 1..] {
+        match token.type_ {
+            TokenType::Ident  => names.push(ident(token)),
+            TokenType::RParen => return names,
+            _                 => {},
+        }
+    }
+
+    header.errors.push(missing(
+        "expected right paren to terminate import list",
+        vec![TokenType::Comma, TokenType::RParen],
+        after(tokens, tokens.len())
+    ));
+
+    names
+}
+
+/// The tokens of the line starting at byte `pos` of `src`, without the
+/// layout tokens that end it, and the byte offset the line ends at, past
+/// any block comment it runs into. A line that does not lex is an error,
+/// but a later line that does not is not this line's problem.
+fn line_tokens(
+    src:  &Arc<str>,
+    pos:  usize,
+    line: usize
+) -> Result<(Vec<Token>, usize), ParseError> {
+    let mut tokens = Vec::new();
+    let mut end = pos;
+
+    for token in Lexer::at_line(src.clone(), pos, line) {
+        match token {
+            Ok(token) => match token.type_ {
+                TokenType::Newline => {
+                    end = token.span.end();
+
+                    break;
+                },
+                TokenType::Indent |
+                TokenType::Dedent => break,
+                _                 => {
+                    end = token.span.end();
+                    tokens.push(token);
+                },
+            },
+            Err(e) => {
+                if e.span().is_none_or(|span| span.line == line) {
+                    return Err(e);
+                }
+
+                break;
+            },
+        }
+    }
+
+    Ok((tokens, end))
+}
+
+/// Whether a line has nothing on it but blanks and a line comment.
+fn is_blank_line(text: &str) -> bool {
+    let text = text.trim_start_matches(is_blank).trim_end_matches('\r');
+
+    text.is_empty() ||
+        text.starts_with("--") && !text[2..].starts_with(is_op_char)
+}
+
+/// Where the token at `ix` is, or just past the last token if there is
+/// none there.
+fn after(tokens: &[Token], ix: usize) -> Span {
+    if let Some(token) = tokens.get(ix) {
+        return token.span;
+    }
+
+    let last = &tokens[tokens.len() - 1].span;
+
+    Span {
+        col:         last.col + last.len,
+        byte_offset: last.byte_offset + last.len,
+        len:         0,
+        ..*last
+    }
+}
+
+fn missing(
+    message:  &'static str,
+    expected: Vec<TokenType>,
+    span:     Span
+) -> ParseError {
+    ParseError::MissingToken {
+        message:  message,
+        expected: expected,
+        span:     span,
+    }
+}
+
+fn ident(token: &Token) -> Ident {
+    Ident {
+        name: token.lexeme().to_string(),
+        span: token.span,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use diagnostics::Diagnostic;
+
+    #[test]
+    fn import_without_list_is_reported_after_module_name() {
+        let src = "module A\n\nimport B\n";
+        let header = recover_header(src);
+
+        assert_eq!(header.module.map(|m| m.name), Some("A".to_string()));
+        assert_eq!(header.imports.len(), 1);
+        assert_eq!(header.errors.len(), 1);
+
+        let span = header.errors[0].span().unwrap();
+
+        assert_eq!((span.line, span.col), (3, 9));
+
+        let rendered = Diagnostic::from(&header.errors[0])
+            .render("A.bwr", src, false);
+
+        assert!(rendered.contains("A.bwr:3:9"), "{}", rendered);
+        assert!(rendered.contains("3 | import B"), "{}", rendered);
+    }
+}
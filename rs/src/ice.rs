@@ -0,0 +1,256 @@
+//! Reporting of internal compiler errors (panics within brouwer itself).
+//!
+//! Each stage records what it is working on with `enter_pass`, so that if
+//! it panics, the hook installed by `install_hook` can tell the user what
+//! was being processed and leave behind enough state to reproduce the bug.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+
+const ISSUE_TRACKER: &str = "https://github.com/AugmentedFifth/brouwer/issues";
+
+/// What brouwer was doing at the time of a panic.
+#[derive(Clone, Debug, Default)]
+pub struct IceContext {
+    /// The name of the pass that was running, e.g. `"parse"`.
+    pub pass:     Option<&'static str>,
+    /// The source file being processed.
+    pub file:     Option<PathBuf>,
+    /// The line and column the pass had got to in the source, if known.
+    pub location: Option<(usize, usize)>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<IceContext> = RefCell::new(IceContext::default());
+}
+
+
+/// Records that `pass` is now running over `file`.
+pub fn enter_pass<P: AsRef<Path>>(pass: &'static str, file: P) {
+    CONTEXT.with(|ctx| {
+        if let Ok(mut ctx) = ctx.try_borrow_mut() {
+            ctx.pass = Some(pass);
+            ctx.file = Some(file.as_ref().to_path_buf());
+            ctx.location = None;
+        }
+    });
+}
+
+/// Records how far through the source the current pass has got.
+pub fn set_location(line: usize, col: usize) {
+    CONTEXT.with(|ctx| {
+        if let Ok(mut ctx) = ctx.try_borrow_mut() {
+            ctx.location = Some((line, col));
+        }
+    });
+}
+
+/// A copy of the current thread's context.
+pub fn current_context() -> IceContext {
+    CONTEXT.with(|ctx|
+        ctx.try_borrow().map(|ctx| ctx.clone()).unwrap_or_default()
+    )
+}
+
+/// Replaces the default panic hook with one that reports an internal
+/// compiler error politely, instead of printing a raw Rust panic message,
+/// and writes a dump file with the state needed to reproduce it.
+///
+/// The dump holds a copy of the source file being processed, so it is
+/// created in the temporary directory readable only by the user (on Unix;
+/// elsewhere, with the directory's permissions), under a name no other
+/// dump has, and the user is told what is in it before they report it.
+///
+/// A panic from printing to a closed pipe, as when output is piped into
+/// `head`, is not a bug: the process exits quietly instead, though not
+/// with 0, as its output was cut short. On Unix it exits with 141, as if
+/// `SIGPIPE` had killed it, as it would have a C program; elsewhere, 1.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let ctx = current_context();
+        let message = panic_message(info);
+
+        if is_closed_pipe(&message) {
+            process::exit(if cfg!(unix) { 141 } else { 1 });
+        }
+
+        eprintln!("error: internal compiler error: {}", message);
+
+        if let Some(ref file) = ctx.file {
+            match ctx.location {
+                Some((line, col)) => {
+                    eprintln!(" --> {}:{}:{}", file.display(), line, col);
+                },
+                None => eprintln!(" --> {}", file.display()),
+            }
+        }
+
+        if let Some(pass) = ctx.pass {
+            eprintln!("note: brouwer panicked during the `{}` pass", pass);
+        }
+
+        eprintln!(
+            "note: this is a bug in brouwer, not in your program; please \
+             report it at {}",
+            ISSUE_TRACKER
+        );
+
+        match write_dump(&ctx, info, &message) {
+            Ok(path) => eprintln!(
+                "note: the state needed to reproduce this, including a copy \
+                 of the source file, was written to {}; check that it holds \
+                 nothing private before attaching it",
+                path.display()
+            ),
+            Err(e) => eprintln!("note: could not write a crash dump: {}", e),
+        }
+    }));
+}
+
+/// Whether `message` is that of the panic `print!` makes when the pipe
+/// it prints to has been closed: `EPIPE` on Unix, and `ERROR_BROKEN_PIPE`
+/// or `ERROR_NO_DATA` on Windows.
+fn is_closed_pipe(message: &str) -> bool {
+    let closed = if cfg!(windows) {
+        &["(os error 109)", "(os error 232)"][..]
+    } else {
+        &["(os error 32)"][..]
+    };
+
+    message.starts_with("failed printing to std") &&
+        closed.iter().any(|code| message.ends_with(code))
+}
+
+/// What a panic with `payload` said, as for one caught with
+/// `std::panic::catch_unwind`.
+pub fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    payload_message(info.payload())
+}
+
+fn write_dump(
+    ctx:     &IceContext,
+    info:    &PanicHookInfo,
+    message: &str
+) -> Result<PathBuf, String> {
+    let (path, mut file) = create_dump()?;
+
+    let mut dump = String::new();
+    dump += &format!("brouwer {}\n", env!("CARGO_PKG_VERSION"));
+    dump += &format!(
+        "args: {}\n",
+        env::args_os().map(|arg| arg.to_string_lossy().into_owned())
+                      .collect::<Vec<_>>()
+                      .join(" ")
+    );
+    dump += &format!("pass: {}\n", ctx.pass.unwrap_or("unknown"));
+
+    if let Some((line, col)) = ctx.location {
+        dump += &format!("location: {}:{}\n", line, col);
+    }
+
+    dump += &format!("panic: {}\n", message);
+
+    if let Some(loc) = info.location() {
+        dump += &format!("panicked at: {}:{}\n", loc.file(), loc.line());
+    }
+
+    if let Some(ref file) = ctx.file {
+        let mut src = String::new();
+
+        dump += &format!("file: {}\n", file.display());
+
+        match File::open(file).and_then(|mut f| f.read_to_string(&mut src)) {
+            Ok(_) => {
+                dump += "--- source ---\n";
+                dump += &src;
+            },
+            Err(e) => dump += &format!("(could not read source: {})\n", e),
+        }
+    }
+
+    file.write_all(dump.as_bytes())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
+/// Creates a dump file in the temporary directory, named after the time
+/// and the process, that only the user can read, and that did not exist:
+/// a file already there, from another panic or put there by anyone else,
+/// is never written to.
+fn create_dump() -> Result<(PathBuf, File), String> {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH)
+                                .map(|d| d.as_secs())
+                                .unwrap_or(0);
+    let stem = format!("brouwer-ice-{}-{}", secs, process::id());
+
+    for n in 0..100 {
+        let name = match n {
+            0 => format!("{}.txt", stem),
+            _ => format!("{}-{}.txt", stem, n),
+        };
+        let path = env::temp_dir().join(name);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {},
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    Err(format!(
+        "{}: every name for a dump file is taken",
+        env::temp_dir().join(stem).display()
+    ))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn dumps_never_share_a_file() {
+        let (first, _) = create_dump().unwrap();
+        let (second, _) = create_dump().unwrap();
+
+        assert_ne!(first, second);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let mode = fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        fs::remove_file(first).unwrap();
+        fs::remove_file(second).unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+//! Keeping the tokens of a source text up to date as it is edited, for
+//! editors that highlight as the user types.
+//!
+//! The lexer records its state at the start of every line (see
+//! `LineState`). After an edit, lexing restarts at the last line that
+//! starts before the edit, and stops as soon as it reaches a line after
+//! the edit in the same state as before: from there on, the old tokens are
+//! still right, once moved to where their text now is.
+//!
+//! ```
+//! use brouwer::edit::TextEdit;
+//! use brouwer::incremental::LexedSource;
+//!
+//! let mut lexed = LexedSource::new("module Main\n\nx = 1\ny = 2\n".into());
+//! let before = lexed.tokens().len();
+//!
+//! // Only `x = 1` needs lexing again.
+//! let relexed = lexed.edit(&TextEdit::new(17, 18, "10")).unwrap();
+//! assert!(relexed.len() < before);
+//!
+//! let lexemes: Vec<_> = lexed.tokens().iter().map(|t| t.lexeme()).collect();
+//! assert!(lexemes.contains(&"10"));
+//! assert_eq!(lexed.tokens().last().unwrap().span.line, 4);
+//! ```
+
+use std::mem;
+use std::ops::Range;
+use std::slice;
+use std::sync::Arc;
+
+use edit::{self, TextEdit};
+use error::ParseError;
+use lexer::{LineState, Lexer};
+use token::{Span, Token};
+
+
+/// A source text together with its tokens and the lexer's state at the
+/// start of each of its lines.
+#[derive(Debug)]
+pub struct LexedSource {
+    src:    Arc<str>,
+    tokens: Vec<Token>,
+    lines:  Vec<LineState>,
+    error:  Option<ParseError>,
+}
+
+/// How far the text after an edit has moved.
+#[derive(Clone, Copy)]
+struct Shift {
+    lines: isize,
+    bytes: isize,
+}
+
+
+impl LexedSource {
+    /// Lexes the whole of `src`.
+    pub fn new(src: String) -> Self {
+        let mut lexed = LexedSource {
+            src:    "".into(),
+            tokens: Vec::new(),
+            lines:  Vec::new(),
+            error:  None,
+        };
+
+        lexed.relex(src.into(), 0, None);
+
+        lexed
+    }
+
+    /// The source text.
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// The tokens of the source, as `lexer::tokenize` would give them, up
+    /// to the first error, if any.
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    /// The error that lexing stopped at, if it did not reach the end.
+    pub fn error(&self) -> Option<&ParseError> {
+        self.error.as_ref()
+    }
+
+    /// The lexer's state at the start of line `line`, if lexing got that
+    /// far.
+    pub fn line_state(&self, line: usize) -> Option<&LineState> {
+        self.lines
+            .binary_search_by_key(&line, |state| state.line)
+            .ok()
+            .map(|i| &self.lines[i])
+    }
+
+    /// Applies `edit` to the source, lexing again only as much as it has
+    /// to. Returns the indices, into the new `tokens`, of the tokens that
+    /// were lexed again; every token outside them is one that was there
+    /// before the edit, moved but otherwise unchanged.
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<Range<usize>, String> {
+        let src = edit::apply_edits(&self.src, slice::from_ref(edit))?;
+
+        // The last line to start at or before the edit. There is always
+        // one, for the first line.
+        let restart = self.lines
+                          .iter()
+                          .rposition(|state| state.pos <= edit.start)
+                          .unwrap_or(0);
+
+        let removed = &self.src[edit.start..edit.end];
+        let shift = Shift {
+            lines: newlines(&edit.replacement) as isize -
+                   newlines(removed) as isize,
+            bytes: edit.replacement.len() as isize - removed.len() as isize,
+        };
+        let new_end = edit.start + edit.replacement.len();
+
+        Ok(self.relex(src.into(), restart, Some((edit.end, new_end, shift))))
+    }
+
+    /// Replaces the source with `src`, which is the same as the old one
+    /// up to the start of the line `lines[restart]`, and lexes it
+    /// again from there. Given where an edit ended in the old and new
+    /// source, and how far it moved the text after it, stops as soon as a
+    /// line after the edit is in the same state as it was before.
+    fn relex(
+        &mut self,
+        src:     Arc<str>,
+        restart: usize,
+        edit:    Option<(usize, usize, Shift)>
+    ) -> Range<usize> {
+        self.src = src;
+
+        let LexedSource {
+            ref src,
+            ref mut tokens,
+            ref mut lines,
+            ref mut error,
+        } = *self;
+
+        let old_tokens = mem::take(tokens);
+        let old_error = error.take();
+        let old_lines = lines.split_off(restart);
+
+        let mut lexer = match old_lines.first() {
+            Some(state) => Lexer::resume(src.clone(), state),
+            None        => Lexer::recording(src.clone()),
+        };
+        let first = old_lines.first().map_or(0, |state| state.tokens_before);
+
+        tokens.extend_from_slice(&old_tokens[..first]);
+
+        let mut seen = 0;
+
+        loop {
+            // Any lines this reaches are recorded before it is returned.
+            let token = lexer.next();
+
+            if let Some((old_end, new_end, shift)) = edit {
+                let new_lines = lexer.lines().unwrap_or(&[]);
+
+                for (j, state) in new_lines.iter().enumerate().skip(seen) {
+                    if state.pos < new_end {
+                        continue;
+                    }
+
+                    let old = old_lines.iter().position(|old|
+                        old.pos >= old_end && same_line(old, state, shift)
+                    );
+
+                    if let Some(i) = old {
+                        lines.extend_from_slice(&new_lines[..j]);
+                        catch_up(
+                            src,
+                            tokens,
+                            lines,
+                            &old_lines[i..],
+                            &old_tokens,
+                            state,
+                            shift
+                        );
+                        *error = old_error.map(|e| shift_error(e, shift));
+
+                        return first..state.tokens_before;
+                    }
+                }
+
+                seen = new_lines.len();
+            }
+
+            match token {
+                Some(Ok(token)) => tokens.push(token),
+                Some(Err(e))    => {
+                    *error = Some(e);
+
+                    break;
+                },
+                None => break,
+            }
+        }
+
+        lines.extend_from_slice(lexer.lines().unwrap_or(&[]));
+
+        first..tokens.len()
+    }
+}
+
+/// Appends the old tokens and line states from the old line `old[0]`
+/// onwards, moved by `shift` to where they are in `src`, for when `new` is
+/// in the same state as it.
+fn catch_up(
+    src:        &Arc<str>,
+    tokens:     &mut Vec<Token>,
+    lines:      &mut Vec<LineState>,
+    old:        &[LineState],
+    old_tokens: &[Token],
+    new:        &LineState,
+    shift:      Shift
+) {
+    for token in &old_tokens[old[0].tokens_before..] {
+        let mut token = token.clone();
+        let span = shift_span(token.span, shift);
+        token.move_to(src, span);
+
+        tokens.push(token);
+    }
+
+    let moved_by = new.tokens_before as isize - old[0].tokens_before as isize;
+
+    for state in old {
+        let mut state = state.clone();
+        state.pos = shift_by(state.pos, shift.bytes);
+        state.line = shift_by(state.line, shift.lines);
+        state.tokens_before = shift_by(state.tokens_before, moved_by);
+        state.line_end = shift_span(state.line_end, shift);
+
+        lines.push(state);
+    }
+}
+
+/// Whether the old line `old`, moved by `shift`, starts where `new` does,
+/// in the same state.
+fn same_line(old: &LineState, new: &LineState, shift: Shift) -> bool {
+    shift_by(old.pos, shift.bytes) == new.pos &&
+        shift_by(old.line, shift.lines) == new.line &&
+        old.indents == new.indents &&
+        old.in_string == new.in_string &&
+        old.bracket_depth == new.bracket_depth &&
+        old.at_start == new.at_start &&
+        shift_span(old.line_end, shift) == new.line_end
+}
+
+fn shift_by(n: usize, by: isize) -> usize {
+    (n as isize + by) as usize
+}
+
+fn shift_span(span: Span, shift: Shift) -> Span {
+    if !span.is_known() {
+        return span;
+    }
+
+    Span {
+        line:        shift_by(span.line, shift.lines),
+        byte_offset: shift_by(span.byte_offset, shift.bytes),
+        ..span
+    }
+}
+
+fn shift_error(mut e: ParseError, shift: Shift) -> ParseError {
+    match e {
+        ParseError::UnexpectedChar { ref mut span, .. } |
+        ParseError::UnexpectedEof { ref mut span, .. }  |
+        ParseError::MissingToken { ref mut span, .. }   |
+        ParseError::BadIndent { ref mut span, .. }      |
+        ParseError::ReservedOp { ref mut span, .. }     |
+        ParseError::ReservedWord { ref mut span, .. }   |
+        ParseError::BadEscape { ref mut span, .. }      |
+        ParseError::MixedFixity { ref mut span, .. }    => {
+            *span = shift_span(*span, shift);
+        },
+        ParseError::Io(_) => {},
+    }
+
+    e
+}
+
+fn newlines(text: &str) -> usize {
+    text.bytes().filter(|&b| b == b'\n').count()
+}
@@ -0,0 +1,1389 @@
+//! Splitting source text into a stream of tokens.
+//!
+//! The lexer produces the same leaves that the parser puts in its trees,
+//! each with its span, plus the `Newline`, `Indent`, and `Dedent` layout
+//! tokens. Blanks, blank lines, comments, and line breaks within brackets
+//! are skipped.
+//!
+//! Block comments, `{- ... -}`, nest, and count as blanks wherever they
+//! are, line breaks in them included. A line that starts with one is
+//! indented by the blanks before it.
+//!
+//! ```
+//! use brouwer::lexer;
+//! use brouwer::{Kind, TokenKind};
+//!
+//! let tokens = lexer::tokenize("module Main\n").unwrap();
+//!
+//! assert_eq!(tokens[0].kind(), Kind::Token(TokenKind::ModuleKeyword));
+//! assert_eq!(tokens[1].lexeme(), "Main");
+//! ```
+
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+
+use error::ParseError;
+use parser::{is_newline, is_op_char};
+use scan;
+use token::{Span, Token, TokenType};
+
+
+/// The indentation of every block enclosing the current line, outermost
+/// first. The bottom level is the empty string, unless the stack is for a
+/// fragment of code from inside a block (see `with_base`).
+///
+/// Blocks nest to any depth, and one line can close several at once:
+///
+/// ```
+/// use std::collections::VecDeque;
+/// use brouwer::TokenType;
+/// use brouwer::lexer::IndentStack;
+///
+/// let mut indents = IndentStack::new();
+/// let mut layout = VecDeque::new();
+///
+/// // A `for`, an `if` inside it, and a `fn` inside that.
+/// for indent in &["    ", "        ", "            "] {
+///     indents.issue(indent.to_string(), &mut layout).unwrap();
+/// }
+/// assert_eq!(indents.depth(), 3);
+///
+/// // Returning to the top level closes all three.
+/// layout.clear();
+/// indents.issue(String::new(), &mut layout).unwrap();
+///
+/// let dedents = layout.iter().filter(|t| **t == TokenType::Dedent).count();
+/// assert_eq!(dedents, 3);
+/// assert_eq!(indents.depth(), 0);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndentStack {
+    levels: Vec<String>,
+}
+
+/// Lazily tokenizes a source string. Yields `Err` at most once, after which
+/// it stops. The leaves it yields share the source rather than copying
+/// their lexemes out of it.
+pub struct Lexer {
+    src:       Arc<str>,
+    pos:       usize,
+    line:      usize,
+    col:       usize,
+    indents:   IndentStack,
+    pending:   VecDeque<Token>,
+    line_end:  Span,
+    at_start:  bool,
+    at_bol:    bool,
+    done:      bool,
+    in_string: bool,
+    brackets:  usize,
+    yielded:   usize,
+    lines:     Option<Vec<LineState>>,
+}
+
+/// Everything the lexer carries over from one line to the next, recorded
+/// at the start of a line so that lexing can be restarted there (see
+/// `Lexer::resume`) without going over the lines before it again.
+#[derive(Clone, Debug)]
+pub struct LineState {
+    /// Byte offset of the start of the line.
+    pub pos:           usize,
+    /// The line's number.
+    pub line:          usize,
+    /// How many tokens the lines before this one lexed to.
+    pub tokens_before: usize,
+    /// The blocks open at the start of the line.
+    pub indents:       IndentStack,
+    /// Whether the line starts inside a string literal.
+    pub in_string:     bool,
+    /// How many brackets are open at the start of the line.
+    pub bracket_depth: usize,
+    /// The newline ending the last line with any code on it, which is
+    /// where the `Newline` token that ends that line is.
+    pub line_end:      Span,
+    /// Whether no line before this one has any code on it.
+    pub at_start:      bool,
+}
+
+/// Why a number literal is malformed, as `number_len` finds it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BadNumber {
+    /// The byte offset in the literal of where it goes wrong.
+    pub at:         usize,
+    /// What should have been there, e.g. `"expected a digit after _"`.
+    pub message:    &'static str,
+    /// Whether the character at `at` is itself the mistake, as the `2` of
+    /// `0b12` is, rather than in the place of something missing.
+    pub unexpected: bool,
+}
+
+/// Where a token began: its byte offset, line, and column.
+#[derive(Clone, Copy)]
+struct Mark {
+    pos:  usize,
+    line: usize,
+    col:  usize,
+}
+
+
+impl IndentStack {
+    /// A stack holding only the outermost, unindented level.
+    pub fn new() -> Self {
+        IndentStack {
+            levels: vec![String::new()],
+        }
+    }
+
+    /// A stack whose outermost level is `base`, for code lifted out of a
+    /// block indented by `base`. No line may be indented less than that.
+    pub fn with_base(base: String) -> Self {
+        IndentStack {
+            levels: vec![base],
+        }
+    }
+
+    /// Pops every level but the outermost.
+    pub fn clear(&mut self) {
+        self.levels.truncate(1);
+    }
+
+    /// The indentation of the outermost level.
+    pub fn base(&self) -> &str {
+        &self.levels[0]
+    }
+
+    /// How many blocks enclose the current line.
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Queues onto `out` the layout tokens for a new line indented by
+    /// `indent`: a `Newline`, then an `Indent` if it opens a block, or a
+    /// `Dedent` and `Newline` for each block it closes, so that the line
+    /// holding each closed block's header is ended as well.
+    pub fn issue(
+        &mut self,
+        indent: String,
+        out:    &mut VecDeque<TokenType>
+    ) -> Result<(), &'static str> {
+        out.push_back(TokenType::Newline);
+
+        let deeper = {
+            let top = &self.levels[self.levels.len() - 1];
+
+            if indent == *top {
+                return Ok(());
+            }
+
+            indent.starts_with(top.as_str())
+        };
+
+        if deeper {
+            self.levels.push(indent);
+            out.push_back(TokenType::Indent);
+
+            return Ok(());
+        }
+
+        while self.levels.len() > 1 &&
+              self.levels[self.levels.len() - 1].len() > indent.len()
+        {
+            self.levels.pop();
+            out.push_back(TokenType::Dedent);
+            out.push_back(TokenType::Newline);
+        }
+
+        if self.levels[self.levels.len() - 1] != indent {
+            return Err("indentation does not match any enclosing block");
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for IndentStack {
+    fn default() -> Self {
+        IndentStack::new()
+    }
+}
+
+impl Lexer {
+    /// A lexer over the whole of `src`.
+    pub fn new<S: Into<Arc<str>>>(src: S) -> Self {
+        Lexer {
+            src:       src.into(),
+            pos:       0,
+            line:      1,
+            col:       1,
+            indents:   IndentStack::new(),
+            pending:   VecDeque::with_capacity(4),
+            line_end:  Span::default(),
+            at_start:  true,
+            at_bol:    true,
+            done:      false,
+            in_string: false,
+            brackets:  0,
+            yielded:   0,
+            lines:     None,
+        }
+    }
+
+    /// A lexer over `src` that records the state it is in at the start of
+    /// every line it reaches, for `lines`.
+    pub fn recording<S: Into<Arc<str>>>(src: S) -> Self {
+        let mut lexer = Lexer::new(src);
+        lexer.lines = Some(Vec::new());
+
+        lexer
+    }
+
+    /// A recording lexer over `src`, restarted at the start of a line in
+    /// the state recorded there. The line must start at the same place in
+    /// `src` as it did in the source the state was recorded from, and the
+    /// text before it must be the same.
+    pub fn resume<S: Into<Arc<str>>>(src: S, state: &LineState) -> Self {
+        let mut lexer = Lexer::recording(src);
+        lexer.pos = state.pos;
+        lexer.line = state.line;
+        lexer.indents = state.indents.clone();
+        lexer.line_end = state.line_end;
+        lexer.at_start = state.at_start;
+        lexer.at_bol = !state.in_string && state.bracket_depth == 0;
+        lexer.in_string = state.in_string;
+        lexer.brackets = state.bracket_depth;
+        lexer.yielded = state.tokens_before;
+        lexer.record_line();
+
+        lexer
+    }
+
+    /// The state at the start of every line reached so far, in order, if
+    /// this lexer is recording. A line is not reached until the token
+    /// after the line before it has been yielded.
+    pub fn lines(&self) -> Option<&[LineState]> {
+        self.lines.as_deref()
+    }
+
+    /// Records the current state, if recording and at the start of a line
+    /// not already recorded.
+    fn record_line(&mut self) {
+        let at_line_start = self.pos == 0 ||
+                            self.src[..self.pos].ends_with('\n');
+
+        if !at_line_start {
+            return;
+        }
+
+        let state = LineState {
+            pos:           self.pos,
+            line:          self.line,
+            tokens_before: self.yielded,
+            indents:       self.indents.clone(),
+            in_string:     self.in_string,
+            bracket_depth: self.brackets,
+            line_end:      self.line_end,
+            at_start:      self.at_start,
+        };
+
+        if let Some(ref mut lines) = self.lines {
+            if lines.last().is_none_or(|last| last.pos != state.pos) {
+                lines.push(state);
+            }
+        }
+    }
+
+    /// A lexer over `src` from byte offset `pos`, which starts line `line`,
+    /// as though the source began there. Spans are still those of `src`.
+    pub fn at_line<S: Into<Arc<str>>>(
+        src:  S,
+        pos:  usize,
+        line: usize
+    ) -> Self {
+        let mut lexer = Lexer::new(src);
+        lexer.pos = pos;
+        lexer.line = line;
+
+        lexer
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+
+        Some(c)
+    }
+
+    /// Moves past the next `n` bytes, which are on the current line.
+    fn bump_within_line(&mut self, n: usize) {
+        let text = &self.src.as_bytes()[self.pos..self.pos + n];
+        self.col += scan::char_count(text);
+        self.pos += n;
+    }
+
+    /// The bytes from here on.
+    fn rest(&self) -> &[u8] {
+        &self.src.as_bytes()[self.pos..]
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            pos:  self.pos,
+            line: self.line,
+            col:  self.col,
+        }
+    }
+
+    fn span_from(&self, start: Mark) -> Span {
+        Span {
+            line:        start.line,
+            col:         start.col,
+            byte_offset: start.pos,
+            len:         self.pos - start.pos,
+        }
+    }
+
+    /// A leaf of the text from `start` to here.
+    fn leaf(&self, type_: TokenType, start: Mark) -> Token {
+        Token::in_source(type_, &self.src, self.span_from(start))
+    }
+
+    /// The text from `start` to here.
+    fn text_from(&self, start: Mark) -> &str {
+        &self.src[start.pos..self.pos]
+    }
+
+    fn layout_token(&self, type_: TokenType, span: Span) -> Token {
+        let mut token = Token::new(type_, String::new());
+        token.span = span;
+
+        token
+    }
+
+    /// Whether a `{-` block comment starts here.
+    fn at_block_comment(&self) -> bool {
+        self.src[self.pos..].starts_with("{-")
+    }
+
+    /// Skips the block comment starting here, and any nested in it.
+    fn skip_block_comment(&mut self) -> Result<(), ParseError> {
+        let end = block_comment_len(&self.src[self.pos..]).map(|len|
+            self.pos + len
+        );
+
+        while end.is_none_or(|end| self.pos < end) {
+            if self.bump().is_none() {
+                return Err(self.eof(
+                    "expected -} to close block comment",
+                    Vec::new()
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a `--` comment starts here.
+    fn at_comment(&self) -> bool {
+        self.src[self.pos..].starts_with("--") &&
+            !self.peek_nth(2).is_some_and(is_op_char)
+    }
+
+    fn skip_blanks(&mut self) {
+        let n = scan::blank_run(self.rest());
+        self.bump_within_line(n);
+    }
+
+    fn skip_comment(&mut self) {
+        let n = scan::line_len(self.rest());
+        self.bump_within_line(n);
+    }
+
+    /// Reads the indentation of the next line with any text on it, and
+    /// queues the layout tokens for it.
+    fn start_line(&mut self) -> Result<(), ParseError> {
+        loop {
+            self.record_line();
+
+            let start = self.mark();
+            self.skip_blanks();
+            let indent = self.text_from(start).to_string();
+            let indent_span = self.span_from(start);
+
+            while self.at_block_comment() {
+                self.skip_block_comment()?;
+                self.skip_blanks();
+            }
+
+            if self.at_comment() {
+                self.skip_comment();
+            }
+
+            match self.peek() {
+                Some(c) if is_newline(c) => {
+                    self.bump();
+
+                    continue;
+                },
+                None => return Ok(()),
+                Some(_) => {},
+            }
+
+            self.at_bol = false;
+
+            if self.at_start {
+                self.at_start = false;
+
+                return if indent.is_empty() {
+                    Ok(())
+                } else {
+                    Err(ParseError::BadIndent {
+                        message: "source must not start with leading \
+                                  whitespace",
+                        span:    indent_span,
+                    })
+                };
+            }
+
+            let mut types = VecDeque::with_capacity(4);
+
+            if let Err(message) = self.indents.issue(indent, &mut types) {
+                return Err(ParseError::BadIndent {
+                    message: message,
+                    span:    indent_span,
+                });
+            }
+
+            for type_ in types {
+                let span = match type_ {
+                    TokenType::Newline => self.line_end,
+                    _                  => indent_span,
+                };
+                let token = self.layout_token(type_, span);
+
+                self.pending.push_back(token);
+            }
+
+            return Ok(());
+        }
+    }
+
+    /// Queues the layout tokens that end the last line and close every
+    /// open block.
+    fn finish(&mut self) {
+        self.done = true;
+
+        if self.at_start {
+            return;
+        }
+
+        let mut types = VecDeque::with_capacity(4);
+        let eof_span = self.span_from(self.mark());
+
+        self.indents
+            .issue(String::new(), &mut types)
+            .expect("the outermost level is always unindented");
+
+        for type_ in types {
+            let span = match type_ {
+                TokenType::Newline => self.line_end,
+                _                  => eof_span,
+            };
+            let token = self.layout_token(type_, span);
+
+            self.pending.push_back(token);
+        }
+    }
+
+    fn lex_word(&mut self) -> Token {
+        let start = self.mark();
+
+        loop {
+            let n = scan::ident_run(self.rest());
+            self.bump_within_line(n);
+
+            // Non-ASCII letters and digits, a character at a time.
+            match self.peek() {
+                Some(c) if c.is_alphanumeric() => { self.bump(); },
+                _                              => break,
+            }
+        }
+
+        let type_ = keyword(self.text_from(start))
+            .unwrap_or(TokenType::Ident);
+
+        self.leaf(type_, start)
+    }
+
+    fn lex_number(&mut self) -> Result<Token, ParseError> {
+        let start = self.mark();
+        let len = match number_len(&self.src[self.pos..]) {
+            Ok(len)  => len,
+            Err(bad) => return Err(self.bad_number(bad)),
+        };
+
+        let literal = &self.src[self.pos..self.pos + len];
+        let has_radix = literal.get(..2).and_then(radix).is_some();
+
+        // Unlike the parser's, the lexemes keep any underscores, since
+        // they are the text of the source where they are.
+        if has_radix {
+            self.bump_within_line(2);
+
+            let prefix = self.leaf(TokenType::Radix, start);
+            let digits = self.mark();
+
+            self.bump_within_line(len - 2);
+            self.pending.push_back(self.leaf(TokenType::AbsInt, digits));
+
+            return Ok(prefix);
+        }
+
+        self.bump_within_line(len);
+
+        let real = self.text_from(start).contains(['.', 'e', 'E']);
+        let type_ = if real {
+            TokenType::AbsReal
+        } else {
+            TokenType::AbsInt
+        };
+
+        Ok(self.leaf(type_, start))
+    }
+
+    /// The error for the malformed number literal starting here that
+    /// `number_len` found.
+    fn bad_number(&mut self, bad: BadNumber) -> ParseError {
+        self.bump_within_line(bad.at);
+
+        let expected = vec![TokenType::AbsInt];
+
+        match self.peek() {
+            None => self.eof(bad.message, expected),
+            Some(c) if bad.unexpected => {
+                let at = self.mark();
+                self.bump();
+
+                ParseError::UnexpectedChar {
+                    found:    c,
+                    expected: expected,
+                    span:     self.span_from(at),
+                }
+            },
+            Some(_) => ParseError::MissingToken {
+                message:  bad.message,
+                expected: expected,
+                span:     self.span_from(self.mark()),
+            },
+        }
+    }
+
+    fn lex_op(&mut self) -> Token {
+        let start = self.mark();
+
+        while self.peek().is_some_and(is_op_char) {
+            self.bump();
+        }
+
+        let type_ = match self.text_from(start) {
+            "="  => TokenType::Equals,
+            ":"  => TokenType::Colon,
+            "::" => TokenType::DoubleColon,
+            "->" => TokenType::RArrow,
+            "=>" => TokenType::FatRArrow,
+            "<-" => TokenType::LArrow,
+            "|"  => TokenType::Bar,
+            "@"  => TokenType::At,
+            "\\" => TokenType::Backslash,
+            _    => TokenType::Op,
+        };
+
+        self.leaf(type_, start)
+    }
+
+    /// Lexes one character of a character or string literal, closed by
+    /// `quote`, into a leaf of type `type_`.
+    fn lex_literal_char(
+        &mut self,
+        type_: TokenType,
+        quote: char
+    ) -> Result<Option<Token>, ParseError> {
+        let start = self.mark();
+
+        match self.peek() {
+            Some('\\') => {},
+            Some(c) if c != quote => {
+                self.bump();
+
+                return Ok(Some(self.leaf(type_, start)));
+            },
+            _ => return Ok(None),
+        }
+
+        self.bump();
+
+        match self.peek() {
+            Some('x') | Some('u') => self.lex_code_escape(type_, start),
+            Some(c) if "'\"tvnrb0".contains(c) => {
+                self.bump();
+
+                Ok(Some(self.leaf(type_, start)))
+            },
+            Some(c) => Err(ParseError::UnexpectedChar {
+                found:    c,
+                expected: vec![type_],
+                span:     self.span_from(self.mark()),
+            }),
+            None => Err(self.eof("expected escaped character", vec![type_])),
+        }
+    }
+
+    /// Lexes the rest of a `\x41` or `\u{1F600}` escape, after the `\` at
+    /// `start`, into a `HexEscape` or `UnicodeEscape` in place of a leaf of
+    /// type `type_`.
+    fn lex_code_escape(
+        &mut self,
+        type_: TokenType,
+        start: Mark
+    ) -> Result<Option<Token>, ParseError> {
+        let is_hex = |c: Option<char>| c.is_some_and(|c| c.is_ascii_hexdigit());
+        let escape_type = if self.bump() == Some('x') {
+            for _ in 0..2 {
+                if is_hex(self.peek()) {
+                    self.bump();
+                }
+            }
+
+            TokenType::HexEscape
+        } else {
+            if self.peek() == Some('{') {
+                self.bump();
+
+                while is_hex(self.peek()) {
+                    self.bump();
+                }
+
+                if self.peek() == Some('}') {
+                    self.bump();
+                }
+            }
+
+            TokenType::UnicodeEscape
+        };
+
+        if code_escape(self.text_from(start)).is_some() {
+            return Ok(Some(self.leaf(escape_type, start)));
+        }
+
+        match self.peek() {
+            Some(_) => Err(ParseError::BadEscape {
+                escape: self.text_from(start).to_string(),
+                span:   self.span_from(start),
+            }),
+            None => Err(self.eof("expected escaped character", vec![type_])),
+        }
+    }
+
+    fn lex_closing_quote(
+        &mut self,
+        type_:   TokenType,
+        quote:   char,
+        message: &'static str
+    ) -> Result<Token, ParseError> {
+        let start = self.mark();
+
+        match self.bump() {
+            Some(c) if c == quote => Ok(self.leaf(type_, start)),
+            Some(c) => Err(ParseError::UnexpectedChar {
+                found:    c,
+                expected: vec![type_],
+                span:     self.span_from(start),
+            }),
+            None => Err(self.eof(message, vec![type_])),
+        }
+    }
+
+    fn lex_chr_lit(&mut self) -> Result<Token, ParseError> {
+        let start = self.mark();
+        self.bump();
+        let open = self.leaf(TokenType::SingleQuote, start);
+
+        let chr = match self.lex_literal_char(TokenType::ChrChr, '\'')? {
+            Some(chr) => chr,
+            None      => {
+                let message = "expected character after '";
+
+                return Err(match self.peek() {
+                    Some(_) => ParseError::MissingToken {
+                        message:  message,
+                        expected: vec![TokenType::ChrChr],
+                        span:     self.span_from(self.mark()),
+                    },
+                    None => self.eof(message, vec![TokenType::ChrChr]),
+                });
+            },
+        };
+
+        let close = self.lex_closing_quote(
+            TokenType::SingleQuote,
+            '\'',
+            "expected closing '"
+        )?;
+
+        self.pending.push_back(chr);
+        self.pending.push_back(close);
+
+        Ok(open)
+    }
+
+    /// Lexes the opening quote of a string literal. The rest of it is
+    /// lexed a character at a time by `lex_in_string`, so that the lexer
+    /// can stop at, and be resumed from, a line break inside it.
+    fn lex_str_lit(&mut self) -> Token {
+        let start = self.mark();
+        self.bump();
+        self.in_string = true;
+
+        self.leaf(TokenType::DoubleQuote, start)
+    }
+
+    /// Lexes a triple-quoted string whole, since which of its characters
+    /// are in it depends on all of its lines. The leaves after its opening
+    /// quotes are queued up, and no line within it is recorded.
+    fn lex_text_block(&mut self) -> Result<Token, ParseError> {
+        let start = self.mark();
+
+        for _ in 0..3 {
+            self.bump();
+        }
+
+        let open = self.leaf(TokenType::TripleQuote, start);
+        let body = self.pos;
+        let end = match text_block_len(&self.src[body..]) {
+            Some(len) => body + len,
+            None      => {
+                while self.bump().is_some() {}
+
+                return Err(self.eof(
+                    "expected closing \"\"\"",
+                    vec![TokenType::TripleQuote]
+                ));
+            },
+        };
+        let skips = text_block_skips(&self.src[body..end]);
+
+        while self.pos < end {
+            let at = self.pos - body;
+
+            if skips.iter().any(|skip| skip.contains(&at)) {
+                self.bump();
+
+                continue;
+            }
+
+            let chr_start = self.mark();
+            let str_chr = match self.lex_literal_char(TokenType::StrChr, '"')? {
+                Some(str_chr) => str_chr,
+                // A quote, which needs no escape here.
+                None          => {
+                    self.bump();
+                    self.leaf(TokenType::StrChr, chr_start)
+                },
+            };
+
+            self.pending.push_back(str_chr);
+        }
+
+        let close = self.mark();
+
+        for _ in 0..3 {
+            self.bump();
+        }
+
+        self.pending.push_back(self.leaf(TokenType::TripleQuote, close));
+
+        Ok(open)
+    }
+
+    /// Lexes the next character of a string literal, or its closing quote.
+    fn lex_in_string(&mut self) -> Result<Token, ParseError> {
+        if let Some(str_chr) = self.lex_literal_char(TokenType::StrChr, '"')? {
+            return Ok(str_chr);
+        }
+
+        self.in_string = false;
+
+        self.lex_closing_quote(
+            TokenType::DoubleQuote,
+            '"',
+            "expected closing \""
+        )
+    }
+
+    fn lex_token(&mut self, c: char) -> Result<Token, ParseError> {
+        let start = self.mark();
+
+        if self.src[self.pos..].starts_with("..") {
+            let type_ = if self.src[self.pos..].starts_with("..=") {
+                self.bump_within_line(3);
+
+                TokenType::DotDotEquals
+            } else {
+                self.bump_within_line(2);
+
+                TokenType::DotDot
+            };
+
+            return Ok(self.leaf(type_, start));
+        }
+        let punctuation = match c {
+            '(' => Some(TokenType::LParen),
+            ')' => Some(TokenType::RParen),
+            '[' => Some(TokenType::LSqBracket),
+            ']' => Some(TokenType::RSqBracket),
+            '{' => Some(TokenType::LCurlyBracket),
+            '}' => Some(TokenType::RCurlyBracket),
+            ',' => Some(TokenType::Comma),
+            '.' => Some(TokenType::Dot),
+            '`' => Some(TokenType::Backtick),
+            _   => None,
+        };
+
+        if let Some(type_) = punctuation {
+            self.bump();
+
+            match c {
+                '(' | '[' | '{' => self.brackets += 1,
+                ')' | ']' | '}' => {
+                    self.brackets = self.brackets.saturating_sub(1);
+                },
+                _ => {},
+            }
+
+            Ok(self.leaf(type_, start))
+        } else if c == '_' || c.is_alphabetic() {
+            Ok(self.lex_word())
+        } else if c.is_ascii_digit() {
+            self.lex_number()
+        } else if is_op_char(c) {
+            Ok(self.lex_op())
+        } else if c == '\'' {
+            self.lex_chr_lit()
+        } else if self.src[self.pos..].starts_with("\"\"\"") {
+            self.lex_text_block()
+        } else if c == '"' {
+            Ok(self.lex_str_lit())
+        } else {
+            Err(ParseError::UnexpectedChar {
+                found:    c,
+                expected: Vec::new(),
+                span:     Span {
+                    line:        start.line,
+                    col:         start.col,
+                    byte_offset: start.pos,
+                    len:         c.len_utf8(),
+                },
+            })
+        }
+    }
+
+    fn eof(
+        &self,
+        message:  &'static str,
+        expected: Vec<TokenType>
+    ) -> ParseError {
+        ParseError::UnexpectedEof {
+            message:  message,
+            expected: expected,
+            span:     self.span_from(self.mark()),
+        }
+    }
+}
+
+impl Iterator for Lexer {
+    type Item = Result<Token, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let token = self.next_token();
+
+        if let Some(Ok(_)) = token {
+            self.yielded += 1;
+        }
+
+        token
+    }
+}
+
+impl Lexer {
+    fn next_token(&mut self) -> Option<Result<Token, ParseError>> {
+        loop {
+            if let Some(token) = self.pending.pop_front() {
+                return Some(Ok(token));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if self.in_string {
+                // The line a line break in the string started, if it did.
+                self.record_line();
+
+                let token = self.lex_in_string();
+                if token.is_err() {
+                    self.done = true;
+                }
+
+                return Some(token);
+            }
+
+            if self.at_bol {
+                if let Err(e) = self.start_line() {
+                    self.done = true;
+
+                    return Some(Err(e));
+                }
+
+                if self.at_bol {
+                    self.finish();
+                }
+
+                continue;
+            }
+
+            self.skip_blanks();
+
+            if self.at_block_comment() {
+                if let Err(e) = self.skip_block_comment() {
+                    self.done = true;
+
+                    return Some(Err(e));
+                }
+
+                continue;
+            }
+
+            if self.at_comment() {
+                self.skip_comment();
+            }
+
+            let c = match self.peek() {
+                Some(c) => c,
+                None    => {
+                    self.finish();
+
+                    continue;
+                },
+            };
+
+            if is_newline(c) {
+                let start = self.mark();
+                self.bump();
+
+                // A line break within brackets, as in a wrapped import
+                // list, does not end the line.
+                if self.brackets > 0 {
+                    self.record_line();
+
+                    continue;
+                }
+
+                self.line_end = self.span_from(start);
+                self.at_bol = true;
+
+                continue;
+            }
+
+            let token = self.lex_token(c);
+
+            if token.is_err() {
+                self.pending.clear();
+                self.done = true;
+            }
+
+            return Some(token);
+        }
+    }
+}
+
+/// The keyword that `word` is, or `None` if it is an identifier. `_` counts
+/// as a keyword here.
+///
+/// ```
+/// use brouwer::TokenType;
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::keyword("while"), Some(TokenType::WhileKeyword));
+/// assert_eq!(lexer::keyword("whilst"), None);
+/// ```
+pub fn keyword(word: &str) -> Option<TokenType> {
+    match word {
+        "_"        => Some(TokenType::Underscore),
+        "module"   => Some(TokenType::ModuleKeyword),
+        "exposing" => Some(TokenType::ExposingKeyword),
+        "hiding"   => Some(TokenType::HidingKeyword),
+        "import"   => Some(TokenType::ImportKeyword),
+        "as"       => Some(TokenType::AsKeyword),
+        "fn"       => Some(TokenType::FnKeyword),
+        "case"     => Some(TokenType::CaseKeyword),
+        "if"       => Some(TokenType::IfKeyword),
+        "else"     => Some(TokenType::ElseKeyword),
+        "try"      => Some(TokenType::TryKeyword),
+        "catch"    => Some(TokenType::CatchKeyword),
+        "while"    => Some(TokenType::WhileKeyword),
+        "for"      => Some(TokenType::ForKeyword),
+        "in"       => Some(TokenType::InKeyword),
+        "var"      => Some(TokenType::VarKeyword),
+        "NaN"      => Some(TokenType::NanKeyword),
+        "Infinity" => Some(TokenType::InfinityKeyword),
+        "True"     => Some(TokenType::TrueKeyword),
+        "False"    => Some(TokenType::FalseKeyword),
+        "return"   => Some(TokenType::ReturnKeyword),
+        "infixl"   => Some(TokenType::InfixlKeyword),
+        "infixr"   => Some(TokenType::InfixrKeyword),
+        "infix"    => Some(TokenType::InfixKeyword),
+        _          => None,
+    }
+}
+
+/// The radix of an integer literal with the prefix `prefix` (`0x`, `0o`, or
+/// `0b`), or `None` if that is not a radix prefix.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::radix("0x"), Some(16));
+/// assert_eq!(lexer::radix("0d"), None);
+/// ```
+pub fn radix(prefix: &str) -> Option<u32> {
+    let mut chars = prefix.chars();
+
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('0'), Some(c), None) => radix_of_prefix(c),
+        _                          => None,
+    }
+}
+
+/// The radix that a letter after a `0` gives an integer literal.
+fn radix_of_prefix(c: char) -> Option<u32> {
+    match c {
+        'x' => Some(16),
+        'o' => Some(8),
+        'b' => Some(2),
+        _   => None,
+    }
+}
+
+/// The length in bytes of the number literal at the start of `src`, which
+/// must start with a digit, or why it is malformed. The parser reads
+/// number literals by this too, so that it and the lexer agree on them.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::number_len("1_000.5e-3 + x"), Ok(10));
+/// assert_eq!(lexer::number_len("0xff..n"), Ok(4));
+/// assert_eq!(lexer::number_len("1..n"), Ok(1));
+///
+/// let bad = lexer::number_len("1__0").unwrap_err();
+/// assert_eq!((bad.at, bad.message), (2, "expected a digit after _"));
+///
+/// let bad = lexer::number_len("2e+x").unwrap_err();
+/// assert_eq!((bad.at, bad.message), (3, "expected a digit in exponent"));
+/// ```
+pub fn number_len(src: &str) -> Result<usize, BadNumber> {
+    let bytes = src.as_bytes();
+
+    if let Some(radix) = src.get(..2).and_then(radix) {
+        let message = match radix {
+            16 => "expected a hexadecimal digit after 0x",
+            8  => "expected an octal digit after 0o",
+            _  => "expected a binary digit after 0b",
+        };
+        let end = digits_end(bytes, 2, radix, message)?;
+
+        // `0b12` and `0xfg` are mistakes, not two tokens.
+        return if src[end..].starts_with(char::is_alphanumeric) {
+            Err(BadNumber {
+                at:         end,
+                message:    message,
+                unexpected: true,
+            })
+        } else {
+            Ok(end)
+        };
+    }
+
+    let mut end = digits_end(bytes, 0, 10, "expected a digit")?;
+
+    // A second `.` begins a range, not a fraction.
+    if bytes.get(end) == Some(&b'.') && bytes.get(end + 1) != Some(&b'.') {
+        end = digits_end(
+            bytes,
+            end + 1,
+            10,
+            "expected at least one digit after decimal point"
+        )?;
+    }
+
+    // `1e` is a mistake, not `1` applied to `e`.
+    if bytes.get(end) == Some(&b'e') || bytes.get(end) == Some(&b'E') {
+        let signed = match bytes.get(end + 1) {
+            Some(b'+') | Some(b'-') => 1,
+            _                       => 0,
+        };
+
+        end = digits_end(
+            bytes,
+            end + 1 + signed,
+            10,
+            "expected a digit in exponent"
+        )?;
+    }
+
+    Ok(end)
+}
+
+/// The byte offset in `bytes` of the end of the run of digits in radix
+/// `radix` from `start` on, with single underscores between them. Fails
+/// with `message` if there is no digit at `start`.
+fn digits_end(
+    bytes:   &[u8],
+    start:   usize,
+    radix:   u32,
+    message: &'static str
+) -> Result<usize, BadNumber> {
+    let is_digit = |i: usize| {
+        bytes.get(i).is_some_and(|&b| (b as char).is_digit(radix))
+    };
+    let missing = |at, message| BadNumber {
+        at:         at,
+        message:    message,
+        unexpected: false,
+    };
+
+    if !is_digit(start) {
+        return Err(missing(start, message));
+    }
+
+    let mut end = start + 1;
+
+    loop {
+        if bytes.get(end) == Some(&b'_') {
+            if !is_digit(end + 1) {
+                return Err(missing(end + 1, "expected a digit after _"));
+            }
+
+            end += 2;
+        } else if is_digit(end) {
+            end += 1;
+        } else {
+            return Ok(end);
+        }
+    }
+}
+
+/// The character that a `\x41` or `\u{1F600}` escape stands for, or
+/// `None` if it is not one of those or does not stand for a character.
+/// A `\x` escape has two hex digits, and goes up to `\x7f`; a `\u` escape
+/// has one to six, making a code point that is not a surrogate.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::code_escape("\\x41"), Some('A'));
+/// assert_eq!(lexer::code_escape("\\u{1F600}"), Some('\u{1F600}'));
+/// assert_eq!(lexer::code_escape("\\xff"), None);
+/// assert_eq!(lexer::code_escape("\\u{D800}"), None);
+/// ```
+pub fn code_escape(escape: &str) -> Option<char> {
+    let (digits, max) = if let Some(digits) = escape.strip_prefix("\\x") {
+        (digits, 0x7f)
+    } else if escape.starts_with("\\u{") && escape.ends_with('}') {
+        (&escape[3..escape.len() - 1], 0x10ffff)
+    } else {
+        return None;
+    };
+
+    let len_ok = if max == 0x7f {
+        digits.len() == 2
+    } else {
+        !digits.is_empty() && digits.len() <= 6
+    };
+
+    if !len_ok || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    match u32::from_str_radix(digits, 16) {
+        Ok(code) if code <= max => ::std::char::from_u32(code),
+        _                       => None,
+    }
+}
+
+/// The length in bytes of the body of the triple-quoted string that `src`
+/// starts just inside of, up to the `"""` that closes it, or `None` if
+/// nothing does. A character after a `\` does not close it.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::text_block_len("a\\\"b\"\"\" x"), Some(4));
+/// assert_eq!(lexer::text_block_len("a\"\" x"), None);
+/// ```
+pub fn text_block_len(src: &str) -> Option<usize> {
+    let bytes = src.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i..].starts_with(b"\"\"\"") {
+            return Some(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    None
+}
+
+/// The byte ranges of `body`, the text between the quotes of a
+/// triple-quoted string, that are not part of the string, in order: a line
+/// break just after the opening quotes, and one before a last line of only
+/// blanks, along with the blanks on those lines; the indentation common to
+/// every line after the first with anything but blanks on it; and the `\r`
+/// of each `\r\n`.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// let body = "\n    a\n      b\n    ";
+/// let kept: String = body.char_indices()
+///     .filter(|&(i, _)| {
+///         !lexer::text_block_skips(body).iter().any(|r| r.contains(&i))
+///     })
+///     .map(|(_, c)| c)
+///     .collect();
+///
+/// assert_eq!(kept, "a\n  b");
+/// ```
+pub fn text_block_skips(body: &str) -> Vec<Range<usize>> {
+    let is_blank = |c| c == ' ' || c == '\t';
+
+    // Each line, up to but not including its line break.
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+
+    for (i, c) in body.char_indices() {
+        if c == '\n' {
+            let end = if body[..i].ends_with('\r') { i - 1 } else { i };
+
+            lines.push(line_start..end);
+            line_start = i + 1;
+        }
+    }
+
+    lines.push(line_start..body.len());
+
+    let blank_line = |line: &Range<usize>| {
+        body[line.clone()].chars().all(is_blank)
+    };
+    let mut skips = Vec::new();
+    let mut content = 0..lines.len();
+
+    if lines.len() > 1 {
+        if blank_line(&lines[0]) {
+            skips.push(0..lines[1].start);
+            content.start = 1;
+        }
+
+        if blank_line(&lines[lines.len() - 1]) {
+            skips.push(lines[lines.len() - 2].end..body.len());
+            content.end -= 1;
+        }
+    }
+
+    let leading = |line: &Range<usize>| {
+        let text = &body[line.clone()];
+
+        text.len() - text.trim_start_matches(is_blank).len()
+    };
+    let indented = &lines[content.start.max(1)..content.end.max(1)];
+    let indent = indented.iter()
+                         .filter(|line| !blank_line(line))
+                         .map(&leading)
+                         .min()
+                         .unwrap_or(0);
+
+    for line in indented {
+        skips.push(line.start..line.start + indent.min(leading(line)));
+    }
+
+    for line in &lines[content.clone()] {
+        if body[line.end..].starts_with("\r\n") {
+            skips.push(line.end..line.end + 1);
+        }
+    }
+
+    skips.retain(|skip| !skip.is_empty());
+    skips.sort_by_key(|skip| skip.start);
+
+    skips
+}
+
+/// The length in bytes of the block comment at the start of `src`, from
+/// its `{-` to the `-}` that closes it, past any nested in it, or `None` if
+/// nothing does.
+///
+/// ```
+/// use brouwer::lexer;
+///
+/// assert_eq!(lexer::block_comment_len("{- a {- b -} -} x"), Some(15));
+/// assert_eq!(lexer::block_comment_len("{- a {- b -} x"), None);
+/// ```
+pub fn block_comment_len(src: &str) -> Option<usize> {
+    let bytes = src.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (b'{', b'-') => {
+                depth += 1;
+                i += 2;
+            },
+            (b'-', b'}') if depth > 0 => {
+                depth -= 1;
+                i += 2;
+
+                if depth == 0 {
+                    return Some(i);
+                }
+            },
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+/// Tokenizes the whole of `src`.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, ParseError> {
+    Lexer::new(src).collect()
+}
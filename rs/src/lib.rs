@@ -0,0 +1,78 @@
+#![cfg_attr(feature="clippy", feature(plugin))]
+#![cfg_attr(feature="clippy", plugin(clippy))]
+
+#![deny(missing_docs)]
+// Struct literals name every field, `Span { line: line, .. }`, as the crate
+// has since before field init shorthand. `Parser::from_str` cannot fail,
+// so it is a constructor rather than `FromStr`. Errors are returned as
+// they are, however big: `ParseError` and `LoadError` are made once, when
+// parsing or loading stops, so moving them costs nothing worth a box. Tests
+// of a value's shape stay `match`es with their arms lined up, `=> true`
+// above `=> false`, rather than `matches!`.
+#![allow(clippy::redundant_field_names)]
+#![allow(clippy::match_like_matches_macro)]
+#![allow(clippy::should_implement_trait)]
+#![allow(clippy::result_large_err)]
+
+//! Parser (and bytecode compiler/interpreter) for the brouwer language.
+//!
+//! The parser turns a source file into a `Tree<Token>` (an `AST`):
+//!
+//! ```no_run
+//! use brouwer::Parser;
+//!
+//! let mut parser = Parser::new("main.bwr").unwrap();
+//!
+//! if let Ok(Some(ast)) = parser.parse() {
+//!     brouwer::parser::log_depth_first(&ast, 0);
+//! }
+//! ```
+//!
+//! Source already in memory needs no file:
+//!
+//! ```
+//! use brouwer::Parser;
+//!
+//! let mut parser = Parser::from_str("module Main\n\nprint \"hi\"\n");
+//!
+//! assert!(parser.parse().unwrap().is_some());
+//! ```
+
+pub mod ast;
+pub mod backend;
+pub mod cache;
+pub mod check;
+pub mod compiler;
+pub mod diagnostics;
+pub mod diff;
+pub mod dump;
+pub mod edit;
+pub mod error;
+pub mod exhaustive;
+pub mod formatter;
+pub mod graph;
+pub mod ice;
+pub mod incremental;
+pub mod lexer;
+pub mod lint;
+pub mod loader;
+pub mod minimize;
+pub mod parser;
+pub mod plugin;
+pub mod pretty;
+pub mod query;
+pub mod resolve;
+pub mod runtime;
+pub mod schedule;
+pub mod scan;
+pub mod server;
+pub mod stats;
+pub mod token;
+pub mod tree;
+pub mod typeck;
+pub mod vfs;
+
+pub use error::ParseError;
+pub use parser::{AST, Items, Parser};
+pub use token::{Kind, NodeKind, Token, TokenKind, TokenType};
+pub use tree::Tree;
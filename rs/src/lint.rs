@@ -0,0 +1,602 @@
+//! Lints over the parse tree, and over the names that `resolve` finds.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use ast::{self, ImportNames, Module, TypeExpr};
+use diagnostics::{Diagnostic, Severity};
+use parser::{AST, str_repr};
+use resolve::{Resolution, SymbolKind};
+use stats::decision_points;
+use token::{Span, TokenType};
+use typeck;
+
+
+/// Thresholds above which the lints warn.
+#[derive(Clone, Debug)]
+pub struct LintConfig {
+    /// Highest cyclomatic complexity a function may have.
+    pub max_complexity: usize,
+    /// Deepest a function's blocks may be nested.
+    pub max_nesting:    usize,
+}
+
+/// A problem found by a lint.
+#[derive(Clone, Debug)]
+pub struct Warning {
+    /// The name of the lint that fired.
+    pub lint:     &'static str,
+    /// The function the problem is in, if it is in one that is known.
+    pub function: Option<String>,
+    /// What the problem is.
+    pub message:  String,
+    /// Where the problem is, if it is anywhere in particular: for a lint
+    /// about a whole function, its header, from `fn` to its return type.
+    pub span:     Option<Span>,
+}
+
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            max_complexity: 10,
+            max_nesting:    4,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(span) = self.span {
+            write!(f, "{}:{}: ", span.line, span.col)?;
+        }
+
+        match self.function {
+            Some(ref function) => write!(
+                f,
+                "warning[{}]: in fn {}: {}",
+                self.lint,
+                function,
+                self.message
+            ),
+            None => write!(f, "warning[{}]: {}", self.lint, self.message),
+        }
+    }
+}
+
+impl Warning {
+    /// A stable code for the lint that fired.
+    pub fn code(&self) -> &'static str {
+        match self.lint {
+            "complexity"    => "W0301",
+            "nesting"       => "W0302",
+            "unused-result" => "W0303",
+            _               => "W0300",
+        }
+    }
+}
+
+impl<'a> From<&'a Warning> for Diagnostic {
+    fn from(warning: &'a Warning) -> Self {
+        let message = match warning.function {
+            Some(ref function) => {
+                format!("in fn {}: {}", function, warning.message)
+            },
+            None => warning.message.clone(),
+        };
+
+        Diagnostic {
+            severity: Severity::Warning,
+            code:     warning.code(),
+            message:  message,
+            span:     warning.span,
+            hint:     None,
+        }
+    }
+}
+
+/// Runs every lint over `ast`.
+///
+/// ```
+/// use brouwer::Parser;
+/// use brouwer::lint::{self, LintConfig};
+///
+/// let src = "module Main\n\nfn f x\n    if x\n        if x\n            1\n";
+/// let tree = Parser::from_str(src).parse().unwrap().unwrap();
+/// let config = LintConfig { max_nesting: 1, ..LintConfig::default() };
+/// let warnings = lint::lint(&tree, &config);
+///
+/// assert_eq!(warnings[0].to_string(),
+///            "3:1: warning[nesting]: in fn f: blocks are nested 2 deep \
+///             (max 1)");
+/// assert_eq!(warnings[0].span.unwrap().len, "fn f x".len());
+/// ```
+pub fn lint(ast: &AST, config: &LintConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut ret_types = HashMap::new();
+
+    collect_ret_types(ast, &mut ret_types);
+    check_fns(ast, config, &ret_types, &mut warnings);
+
+    warnings
+}
+
+/// Warns about each name that is bound by a `var`, a parameter, or a
+/// pattern, or brought in by an import, but never read. Names starting
+/// with `_` are never warned about, nor are the names an `import ...
+/// hiding` brings in, which are not known. The warnings about names bound
+/// by patterns are `resolve`'s own (W0201); those about other variables and
+/// parameters are W0202, and those about imports W0203.
+///
+/// ```
+/// use brouwer::{Parser, ast, lint, resolve};
+///
+/// let src = "module Main\nimport List (map, filter)\n\n\
+///            fn f x _y\n    var z = 1\n    map x\n";
+/// let tree = Parser::from_str(src).parse().unwrap().unwrap();
+/// let module = ast::lower(&tree).unwrap();
+/// let warnings = lint::unused(&module, &resolve::resolve(&module));
+/// let found: Vec<_> = warnings.iter()
+///                             .map(|w| (w.code, w.span.unwrap().line))
+///                             .collect();
+///
+/// assert_eq!(found, vec![("W0203", 2), ("W0202", 5)]);
+/// assert_eq!(warnings[1].message, "variable z is never read");
+/// ```
+pub fn unused(module: &Module, resolution: &Resolution) -> Vec<Diagnostic> {
+    let mut found: Vec<Diagnostic> = resolution.warnings
+                                               .iter()
+                                               .map(Diagnostic::from)
+                                               .collect();
+
+    for symbol in resolution.symbols() {
+        let what = match symbol.kind {
+            SymbolKind::Var    => "variable",
+            SymbolKind::Param  => "parameter",
+            SymbolKind::Import => {
+                if symbol.uses.is_empty() {
+                    found.push(unused_import(
+                        symbol.span,
+                        format!("{} is imported but never used", symbol.name)
+                    ));
+                }
+
+                continue;
+            },
+            _ => continue,
+        };
+
+        if symbol.uses.is_empty() && !symbol.name.starts_with('_') {
+            found.push(Diagnostic {
+                severity: Severity::Warning,
+                code:     "W0202",
+                message:  format!("{} {} is never read", what, symbol.name),
+                span:     Some(symbol.span),
+                hint:     Some(format!(
+                    "call it `_{}` if it is unused on purpose",
+                    symbol.name
+                )),
+            });
+        }
+    }
+
+    // An alias is a module symbol, as is the module an import names, which
+    // need not be used when what is imported from it is.
+    let top = &resolution.scopes[0];
+
+    for import in &module.imports {
+        if let ImportNames::Alias(ref alias) = import.names {
+            let used = top.get(&alias.name, true)
+                          .is_none_or(|ix| !top.symbols[ix].uses.is_empty());
+
+            if !used {
+                found.push(unused_import(
+                    alias.span,
+                    format!(
+                        "{} (module {}) is imported but never used",
+                        alias.name,
+                        import.module.name
+                    )
+                ));
+            }
+        }
+    }
+
+    found.sort_by_key(|d| d.span.map(|span| (span.line, span.col)));
+
+    found
+}
+
+fn unused_import(span: Span, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        code:     "W0203",
+        message:  message,
+        span:     Some(span),
+        hint:     Some("remove it from the import".to_string()),
+    }
+}
+
+fn check_fns(
+    ast:       &AST,
+    config:    &LintConfig,
+    ret_types: &HashMap<String, TypeExpr>,
+    warnings:  &mut Vec<Warning>
+) {
+    if ast.val().type_ == TokenType::FnDecl {
+        let name = fn_name(ast);
+
+        // A body's last line is the function's result, unless the function
+        // is declared to return `Unit`.
+        let discard_last = ret_types.get(&name).is_some_and(typeck::is_unit);
+        check_discarded(
+            ast.children(),
+            discard_last,
+            &name,
+            ret_types,
+            warnings
+        );
+
+        let complexity = 1 + fn_decision_points(ast);
+        if complexity > config.max_complexity {
+            warnings.push(Warning {
+                lint:     "complexity",
+                function: Some(name.clone()),
+                message:  format!(
+                    "cyclomatic complexity is {} (max {})",
+                    complexity,
+                    config.max_complexity
+                ),
+                span:     Some(header_span(ast)),
+            });
+        }
+
+        let nesting = nesting_depth(ast, false);
+        if nesting > config.max_nesting {
+            warnings.push(Warning {
+                lint:     "nesting",
+                function: Some(name),
+                message:  format!(
+                    "blocks are nested {} deep (max {})",
+                    nesting,
+                    config.max_nesting
+                ),
+                span:     Some(header_span(ast)),
+            });
+        }
+    }
+
+    for child in ast.children() {
+        check_fns(child, config, ret_types, warnings);
+    }
+}
+
+/// Records the declared return type of every function below `ast` that
+/// has one.
+fn collect_ret_types(ast: &AST, ret_types: &mut HashMap<String, TypeExpr>) {
+    if ast.val().type_ == TokenType::FnDecl {
+        let children = ast.children();
+
+        if let Some(arrow) = children.iter().position(|c|
+            c.val().type_ == TokenType::RArrow
+        ) {
+            if let Some(Ok(ret_type)) = children.get(arrow + 1)
+                                                .map(ast::lower_type)
+            {
+                ret_types.insert(fn_name(ast), ret_type);
+            }
+        }
+    }
+
+    for child in ast.children() {
+        collect_ret_types(child, ret_types);
+    }
+}
+
+/// Warns about each line among `block` whose value is thrown away even
+/// though it is known not to be `Unit`. Every line but the last is a
+/// statement; the last is too if `discard_last`.
+fn check_discarded(
+    block:        &[AST],
+    discard_last: bool,
+    function:     &str,
+    ret_types:    &HashMap<String, TypeExpr>,
+    warnings:     &mut Vec<Warning>
+) {
+    let lines: Vec<&AST> = block.iter()
+                                .filter(|c| c.val().type_ == TokenType::Line)
+                                .collect();
+
+    for (i, line) in lines.iter().enumerate() {
+        let expr = if let Some(expr) = line.children().first() {
+            expr
+        } else {
+            continue;
+        };
+        let discarded = discard_last || i + 1 < lines.len();
+
+        if discarded && has_value(expr, ret_types) {
+            let repr = str_repr(expr).split_whitespace()
+                                     .collect::<Vec<_>>()
+                                     .join(" ");
+
+            warnings.push(Warning {
+                lint:     "unused-result",
+                function: Some(function.to_string()),
+                message:  format!(
+                    "the result of `{}` is discarded; write `_ = {}` if \
+                     that is intended",
+                    repr,
+                    repr
+                ),
+                span:     Some(line.span()),
+            });
+        }
+
+        check_nested_discarded(expr, discarded, function, ret_types, warnings);
+    }
+}
+
+/// Checks the blocks of the control flow in `expr`, whose own value is
+/// thrown away if `discarded`.
+fn check_nested_discarded(
+    expr:      &AST,
+    discarded: bool,
+    function:  &str,
+    ret_types: &HashMap<String, TypeExpr>,
+    warnings:  &mut Vec<Warning>
+) {
+    for subexpr in applied(expr) {
+        if let Some(inner) = subexpr.children().first() {
+            check_control_discarded(
+                inner,
+                discarded,
+                function,
+                ret_types,
+                warnings
+            );
+        }
+    }
+}
+
+fn check_control_discarded(
+    inner:     &AST,
+    discarded: bool,
+    function:  &str,
+    ret_types: &HashMap<String, TypeExpr>,
+    warnings:  &mut Vec<Warning>
+) {
+    let children = inner.children();
+
+    match inner.val().type_ {
+        TokenType::Var | TokenType::Assign => {
+            if let Some(value) = children.last() {
+                check_nested_discarded(
+                    value,
+                    false,
+                    function,
+                    ret_types,
+                    warnings
+                );
+            }
+        },
+        TokenType::IfElse => {
+            let else_at = children.iter()
+                                  .position(|c|
+                                      c.val().type_ == TokenType::ElseKeyword
+                                  )
+                                  .unwrap_or(children.len());
+
+            check_discarded(
+                &children[..else_at],
+                discarded,
+                function,
+                ret_types,
+                warnings
+            );
+            check_discarded(
+                &children[else_at..],
+                discarded,
+                function,
+                ret_types,
+                warnings
+            );
+
+            if let Some(else_if) = children.get(else_at + 1) {
+                check_control_discarded(
+                    else_if,
+                    discarded,
+                    function,
+                    ret_types,
+                    warnings
+                );
+            }
+        },
+        TokenType::Try => {
+            let catch_at = children.iter()
+                                   .position(|c|
+                                       c.val().type_ == TokenType::CatchKeyword
+                                   )
+                                   .unwrap_or(children.len());
+
+            check_discarded(
+                &children[..catch_at],
+                discarded,
+                function,
+                ret_types,
+                warnings
+            );
+            check_discarded(
+                &children[catch_at..],
+                discarded,
+                function,
+                ret_types,
+                warnings
+            );
+        },
+        TokenType::Case => {
+            for branch in children {
+                check_discarded(
+                    branch.children(),
+                    discarded,
+                    function,
+                    ret_types,
+                    warnings
+                );
+            }
+        },
+        TokenType::While | TokenType::For => check_discarded(
+            children,
+            true,
+            function,
+            ret_types,
+            warnings
+        ),
+        _ => {},
+    }
+}
+
+/// Whether `expr` is known to have a value other than `Unit`: a literal, a
+/// range, an arithmetic, comparison, or logical operation, or a call to a
+/// function declared to return something else. Calls to anything else, and
+/// other operators (like `:>`), might be procedures, so are given the
+/// benefit of the doubt.
+fn has_value(expr: &AST, ret_types: &HashMap<String, TypeExpr>) -> bool {
+    let subexprs = applied(expr);
+
+    match subexprs.first().map(|s| &s.val().type_) {
+        Some(&TokenType::BinOp) => {
+            let op = &subexprs[0].children()[1].children()[0];
+
+            return op.val().type_ == TokenType::Op &&
+                   is_pure_op(op.val().lexeme());
+        },
+        Some(&TokenType::UnaryOp) |
+        Some(&TokenType::Range)   => return true,
+        _ => {},
+    }
+
+    let head = match subexprs.first().and_then(|s| s.children().first()) {
+        Some(head) => head,
+        None       => return false,
+    };
+
+    match head.val().type_ {
+        TokenType::NumLit   |
+        TokenType::BoolLit  |
+        TokenType::ChrLit   |
+        TokenType::StrLit   |
+        TokenType::ListLit  |
+        TokenType::ListComp |
+        TokenType::DictLit  |
+        TokenType::DictComp |
+        TokenType::SetLit   |
+        TokenType::SetComp  |
+        TokenType::Lambda   => true,
+        // `()` is the unit value.
+        TokenType::TupleLit => head.children().len() > 2,
+        TokenType::Parened  => head.children()
+                                   .get(1)
+                                   .is_some_and(|e| has_value(e, ret_types)),
+        TokenType::QualIdent => {
+            let name = str_repr(head).trim().to_string();
+
+            ret_types.get(&name).is_some_and(|t| !typeck::is_unit(t))
+        },
+        _ => false,
+    }
+}
+
+/// The children of `expr`, except that a call is taken apart into its
+/// callee and then its arguments, as they are written.
+fn applied(expr: &AST) -> Vec<&AST> {
+    let mut subexprs = Vec::new();
+    let mut next = expr.children().first();
+
+    while let Some(node) = next {
+        if node.val().type_ != TokenType::FnCall {
+            subexprs.push(node);
+
+            break;
+        }
+
+        subexprs.extend(node.children().get(1));
+        next = node.children().first();
+    }
+
+    subexprs.reverse();
+
+    subexprs
+}
+
+fn fn_name(fn_decl: &AST) -> String {
+    fn_decl.children()
+           .iter()
+           .find(|c| c.val().type_ == TokenType::Ident)
+           .map(|ident| ident.val().lexeme().to_string())
+           .unwrap_or_default()
+}
+
+/// Where the header of `fn_decl` is: everything before its body.
+fn header_span(fn_decl: &AST) -> Span {
+    let children = fn_decl.children();
+    let header = children.iter()
+                         .take_while(|c| c.val().type_ != TokenType::Line)
+                         .last();
+
+    match (children.first(), header) {
+        (Some(first), Some(last)) => first.span().to(&last.span()),
+        _                         => fn_decl.span(),
+    }
+}
+
+/// Decision points in the body of a function, not counting those of any
+/// functions nested within it, which are linted on their own.
+fn fn_decision_points(ast: &AST) -> usize {
+    ast.children().iter().map(|child|
+        if child.val().type_ == TokenType::FnDecl {
+            0
+        } else {
+            decision_points(child) + fn_decision_points(child)
+        }
+    ).sum()
+}
+
+/// The deepest nesting of blocks below `ast`. An `if` that is the `else`
+/// branch of another `if` continues the same chain rather than nesting.
+fn nesting_depth(ast: &AST, is_else_if: bool) -> usize {
+    let opens_block = match ast.val().type_ {
+        TokenType::IfElse => !is_else_if,
+        TokenType::While  |
+        TokenType::For    |
+        TokenType::Try    |
+        TokenType::Case   => true,
+        _ => false,
+    };
+
+    let deepest_child = ast.children().iter().map(|child|
+        match child.val().type_ {
+            TokenType::FnDecl => 0,
+            TokenType::IfElse => nesting_depth(
+                child,
+                ast.val().type_ == TokenType::IfElse
+            ),
+            _ => nesting_depth(child, false),
+        }
+    ).max().unwrap_or(0);
+
+    if opens_block {
+        deepest_child + 1
+    } else {
+        deepest_child
+    }
+}
+
+fn is_pure_op(op: &str) -> bool {
+    match op {
+        "+"  | "-"  | "*"  | "/"  | "%"  | "^"  |
+        "==" | "!=" | "/=" | "<"  | "<=" | ">"  | ">=" |
+        "&&" | "||" => true,
+        _           => false,
+    }
+}
@@ -0,0 +1,602 @@
+//! Loading a whole program: a module, the modules it imports, the modules
+//! those import, and so on.
+//!
+//! `import Foo` is found as `Foo.bwr` in the first of the loader's search
+//! paths to have one, and the file found must declare `module Foo`. Each
+//! module is loaded once, however many modules import it, and a module
+//! that imports itself, directly or not, is an error.
+//!
+//! Paths are written however the platform writes them (see `PathStyle`):
+//! on Windows, with either slash, a drive letter, or as a UNC path. Every
+//! path the loader gives back is `normalize`d.
+//!
+//! ```
+//! use std::env;
+//! use std::fs;
+//! use brouwer::loader::Loader;
+//!
+//! let dir = env::temp_dir().join("brouwer-loader-example");
+//! fs::create_dir_all(&dir).unwrap();
+//! fs::write(dir.join("Main.bwr"), "module Main\nimport Util (f)\n\nf 1\n")
+//!     .unwrap();
+//! fs::write(dir.join("Util.bwr"), "module Util exposing f\n\nfn f x = x\n")
+//!     .unwrap();
+//!
+//! let mut loader = Loader::new(vec![dir.clone()]);
+//! let program = loader.load(dir.join("Main.bwr")).unwrap();
+//! let names: Vec<&str> = program.modules
+//!                               .iter()
+//!                               .map(|m| m.module.name.name.as_str())
+//!                               .collect();
+//!
+//! assert_eq!(names, vec!["Util", "Main"]);
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use ast::{self, Ident, LowerError, Module};
+use error::ParseError;
+use ice;
+use parser::{AST, Parser};
+use vfs::{FileSystem, RealFs};
+
+
+/// The extension of brouwer source files.
+pub const EXTENSION: &str = "bwr";
+
+/// Finds, parses, and lowers modules, one file after another with the
+/// same parser, reading them from a `FileSystem`: by default, the disk.
+pub struct Loader<F = RealFs> {
+    fs:           F,
+    search_paths: Vec<PathBuf>,
+    parser:       Parser,
+    /// The files the last `load` read or looked for.
+    looked_at:    Vec<PathBuf>,
+}
+
+/// Every module of a program.
+pub struct Program {
+    /// The modules, each after every module it imports, so the one the
+    /// program was loaded from is last.
+    pub modules: Vec<LoadedModule>,
+}
+
+/// A module of a program, and the file it was loaded from.
+pub struct LoadedModule {
+    /// The file.
+    pub path:   PathBuf,
+    /// What was read from the file.
+    pub source: String,
+    /// The parse tree.
+    pub tree:   AST,
+    /// The typed syntax tree lowered from `tree`.
+    pub module: Module,
+}
+
+/// Why a program could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file that could not be read.
+    Io {
+        /// The file.
+        path:  PathBuf,
+        /// What went wrong.
+        error: io::Error,
+    },
+    /// A file that did not parse.
+    Parse {
+        /// The file.
+        path:   PathBuf,
+        /// What was read from it, for showing where the error is.
+        source: String,
+        /// What was wrong.
+        error:  ParseError,
+    },
+    /// A file that parsed, but not into a program.
+    NotAProgram {
+        /// The file.
+        path: PathBuf,
+    },
+    /// A file whose parse tree could not be lowered.
+    Lower {
+        /// The file.
+        path:  PathBuf,
+        /// What was wrong.
+        error: LowerError,
+    },
+    /// An import of a module that is in none of the search paths.
+    NotFound {
+        /// The file with the import in it.
+        importer: PathBuf,
+        /// The name of the imported module, where it is imported.
+        module:   Ident,
+        /// The files that were looked for, in order.
+        searched: Vec<PathBuf>,
+    },
+    /// A file that declares a module other than the one it was imported
+    /// as.
+    WrongName {
+        /// The file.
+        path:     PathBuf,
+        /// The name it was imported as.
+        expected: String,
+        /// The name in its module declaration.
+        found:    Ident,
+    },
+    /// An import of a module that is, directly or not, importing the
+    /// module the import is in.
+    Cycle {
+        /// The file with the import in it.
+        importer: PathBuf,
+        /// The name of the imported module, where it is imported.
+        module:   Ident,
+        /// The modules in the cycle, in the order they import each other,
+        /// starting and ending with the imported one.
+        cycle:    Vec<String>,
+    },
+}
+
+
+impl Loader {
+    /// A loader that reads from the disk, and looks for imported modules
+    /// in each of `search_paths`, in order.
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Loader::with_fs(RealFs, search_paths)
+    }
+}
+
+impl<F: FileSystem> Loader<F> {
+    /// A loader that reads from `fs`, and looks for imported modules in
+    /// each of `search_paths`, in order.
+    pub fn with_fs(fs: F, search_paths: Vec<PathBuf>) -> Self {
+        Loader {
+            fs:           fs,
+            search_paths: search_paths,
+            parser:       Parser::from_str(""),
+            looked_at:    Vec::new(),
+        }
+    }
+
+    /// The filesystem read from.
+    pub fn fs(&self) -> &F {
+        &self.fs
+    }
+
+    /// The filesystem read from, mutably.
+    pub fn fs_mut(&mut self) -> &mut F {
+        &mut self.fs
+    }
+
+    /// Where imported modules are looked for, in order.
+    pub fn search_paths(&self) -> &[PathBuf] {
+        &self.search_paths
+    }
+
+    /// Adds `path` to the end of the search paths, unless it is one
+    /// already.
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+
+        if !self.search_paths.iter().any(|p| p == path) {
+            self.search_paths.push(path.to_path_buf());
+        }
+    }
+
+    /// The files that the last `load` read, or looked for an imported
+    /// module in, whether or not they were there, in the order it did, so
+    /// that what it loaded, or why it failed, is only out of date once one
+    /// of them changes.
+    pub fn looked_at(&self) -> &[PathBuf] {
+        &self.looked_at
+    }
+
+    /// The files that `module` would be found in, one per search path, in
+    /// the order they are tried.
+    pub fn candidates(&self, module: &str) -> Vec<PathBuf> {
+        self.search_paths
+            .iter()
+            .map(|dir| normalize(&dir.join(module).with_extension(EXTENSION)))
+            .collect()
+    }
+
+    /// The file `module` is found in, if it is found.
+    pub fn find(&self, module: &str) -> Option<PathBuf> {
+        self.candidates(module)
+            .into_iter()
+            .find(|path| self.fs.is_file(path))
+    }
+
+    /// Loads the module in the file at `path`, and every module it
+    /// imports, directly or not. Stops at the first error.
+    pub fn load<P: AsRef<Path>>(
+        &mut self,
+        path: P
+    ) -> Result<Program, LoadError> {
+        let mut program = Program { modules: Vec::new() };
+        let mut importing = Vec::new();
+        self.looked_at.clear();
+
+        self.visit(
+            &normalize(path.as_ref()),
+            None,
+            &mut importing,
+            &mut program
+        )?;
+
+        Ok(program)
+    }
+
+    /// Loads the module at `path`, then each module it imports that is not
+    /// loaded yet, then adds it to `program`. `importing` holds the names
+    /// of the modules partway through being loaded, each imported by the
+    /// one before it.
+    fn visit(
+        &mut self,
+        path:      &Path,
+        expected:  Option<&str>,
+        importing: &mut Vec<String>,
+        program:   &mut Program
+    ) -> Result<(), LoadError> {
+        let loaded = self.load_module(path)?;
+        let name = loaded.module.name.name.clone();
+
+        if let Some(expected) = expected {
+            if name != expected {
+                return Err(LoadError::WrongName {
+                    path:     path.to_path_buf(),
+                    expected: expected.to_string(),
+                    found:    loaded.module.name.clone(),
+                });
+            }
+        }
+
+        importing.push(name);
+
+        for import in &loaded.module.imports {
+            let imported = &import.module.name;
+
+            if let Some(ix) = importing.iter().position(|m| m == imported) {
+                let mut cycle = importing[ix..].to_vec();
+                cycle.push(imported.clone());
+
+                return Err(LoadError::Cycle {
+                    importer: path.to_path_buf(),
+                    module:   import.module.clone(),
+                    cycle:    cycle,
+                });
+            }
+
+            if program.get(imported).is_some() {
+                continue;
+            }
+
+            for candidate in self.candidates(imported) {
+                self.look_at(&candidate);
+            }
+
+            let found = match self.find(imported) {
+                Some(found) => found,
+                None        => return Err(LoadError::NotFound {
+                    importer: path.to_path_buf(),
+                    module:   import.module.clone(),
+                    searched: self.candidates(imported),
+                }),
+            };
+
+            self.visit(&found, Some(imported), importing, program)?;
+        }
+
+        importing.pop();
+        program.modules.push(loaded);
+
+        Ok(())
+    }
+
+    fn look_at(&mut self, path: &Path) {
+        if !self.looked_at.iter().any(|p| p == path) {
+            self.looked_at.push(path.to_path_buf());
+        }
+    }
+
+    /// Reads, parses, and lowers the file at `path`.
+    fn load_module(&mut self, path: &Path) -> Result<LoadedModule, LoadError> {
+        ice::enter_pass("parse", path);
+        self.look_at(path);
+
+        let source = self.fs.read(path).map_err(|e| LoadError::Io {
+            path:  path.to_path_buf(),
+            error: e,
+        })?;
+
+        self.parser.reset(&source);
+
+        let tree = match self.parser.parse() {
+            Ok(Some(tree)) => tree,
+            Ok(None)       => return Err(LoadError::NotAProgram {
+                path: path.to_path_buf(),
+            }),
+            Err(e)         => return Err(LoadError::Parse {
+                path:   path.to_path_buf(),
+                source: source,
+                error:  e,
+            }),
+        };
+
+        ice::enter_pass("lower", path);
+
+        let module = ast::lower(&tree).map_err(|e| LoadError::Lower {
+            path:  path.to_path_buf(),
+            error: e,
+        })?;
+
+        Ok(LoadedModule {
+            path:   path.to_path_buf(),
+            source: source,
+            tree:   tree,
+            module: module,
+        })
+    }
+}
+
+impl Program {
+    /// The module the program was loaded from.
+    pub fn root(&self) -> &LoadedModule {
+        self.modules.last().expect("a program has at least one module")
+    }
+
+    /// The module called `name`, if it is part of the program.
+    pub fn get(&self, name: &str) -> Option<&LoadedModule> {
+        self.modules.iter().find(|m| m.module.name.name == name)
+    }
+}
+
+/// `path` without any `.`, and without any `..` that follows a directory
+/// name, as the directory and the `..` cancel out. Unlike
+/// `fs::canonicalize`, this does not look at the filesystem, so it does not
+/// follow links, does not fail if `path` does not exist, and does not turn
+/// a Windows path into a `\\?\` one.
+///
+/// A path is taken apart as the platform writes paths: see `PathStyle`,
+/// for how each platform's are normalized.
+///
+/// ```
+/// use std::path::Path;
+/// use brouwer::loader::normalize;
+///
+/// assert_eq!(normalize(Path::new("./src/../lib/./Util.bwr")),
+///            Path::new("lib/Util.bwr"));
+/// assert_eq!(normalize(Path::new("../lib/Util.bwr")),
+///            Path::new("../lib/Util.bwr"));
+/// ```
+pub fn normalize(path: &Path) -> PathBuf {
+    match path.to_str() {
+        Some(path) => PathBuf::from(PathStyle::native().normalize(path)),
+        None       => normalize_components(path),
+    }
+}
+
+/// `normalize` for a path that is not UTF-8, which `std::path` takes apart
+/// for the platform brouwer is running on.
+fn normalize_components(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir    => {},
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                },
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {},
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component.as_os_str()),
+        }
+    }
+
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+
+    normalized
+}
+
+/// How a platform writes paths: what separates the names in them, and
+/// what may come before the first name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Names are separated by `/`, and a path that starts with one is
+    /// absolute.
+    Unix,
+    /// Names are separated by `\` or `/`, and a path may start with a
+    /// drive, `C:`, or a share, `\\server\share`.
+    Windows,
+}
+
+impl PathStyle {
+    /// The style of the platform brouwer is running on.
+    pub fn native() -> PathStyle {
+        if cfg!(windows) {
+            PathStyle::Windows
+        } else {
+            PathStyle::Unix
+        }
+    }
+
+    /// The separator this style writes.
+    pub fn separator(self) -> char {
+        match self {
+            PathStyle::Unix    => '/',
+            PathStyle::Windows => '\\',
+        }
+    }
+
+    /// Whether this style reads `c` as a separator.
+    pub fn is_separator(self, c: char) -> bool {
+        match self {
+            PathStyle::Unix    => c == '/',
+            PathStyle::Windows => c == '/' || c == '\\',
+        }
+    }
+
+    /// `path` as `normalize` would give it back on a platform with this
+    /// style, with every separator written as `separator` writes it. A
+    /// `..` never climbs out of the root, or out of a drive or share.
+    ///
+    /// ```
+    /// use brouwer::loader::PathStyle::{Unix, Windows};
+    ///
+    /// assert_eq!(Unix.normalize("/../src/./Main.bwr"), "/src/Main.bwr");
+    /// assert_eq!(Unix.normalize("src/.."), ".");
+    /// assert_eq!(Unix.normalize(r"a\..\b"), r"a\..\b");
+    ///
+    /// assert_eq!(Windows.normalize(r"C:\src\.\lib\..\Main.bwr"),
+    ///            r"C:\src\Main.bwr");
+    /// assert_eq!(Windows.normalize(r"\\server\share\src/../../Main.bwr"),
+    ///            r"\\server\share\Main.bwr");
+    /// assert_eq!(Windows.normalize("C:../lib"), r"C:..\lib");
+    /// assert_eq!(Windows.normalize("lib/../../Main.bwr"), r"..\Main.bwr");
+    /// ```
+    pub fn normalize(self, path: &str) -> String {
+        let (prefix, rest) = self.split_prefix(path);
+        let rooted = rest.starts_with(|c| self.is_separator(c));
+        let mut names: Vec<&str> = Vec::new();
+
+        for name in rest.split(|c| self.is_separator(c)) {
+            match name {
+                "" | "." => {},
+                ".." => match names.last() {
+                    Some(&last) if last != ".." => {
+                        names.pop();
+                    },
+                    _ if rooted => {},
+                    _           => names.push(".."),
+                },
+                _ => names.push(name),
+            }
+        }
+
+        let separator = self.separator().to_string();
+        let mut normalized: String = prefix
+            .chars()
+            .map(|c| if self.is_separator(c) { self.separator() } else { c })
+            .collect();
+
+        if rooted {
+            normalized.push(self.separator());
+        }
+
+        normalized += &names.join(&separator);
+
+        if normalized.is_empty() {
+            normalized.push('.');
+        }
+
+        normalized
+    }
+
+    /// Splits `path` into what comes before its first name, if anything
+    /// but a separator does, and the rest: on Windows, a drive, `C:`, or a
+    /// share, `\\server\share`, which is always followed by the root.
+    fn split_prefix(self, path: &str) -> (&str, &str) {
+        if self == PathStyle::Unix {
+            return ("", path);
+        }
+
+        let mut chars = path.char_indices();
+
+        match (chars.next(), chars.next()) {
+            (Some((_, a)), Some((_, b)))
+                if self.is_separator(a) && self.is_separator(b) =>
+            {
+                let mut names = 0;
+
+                for (ix, c) in path.char_indices().skip(2) {
+                    if self.is_separator(c) {
+                        names += 1;
+
+                        if names == 2 {
+                            return path.split_at(ix);
+                        }
+                    }
+                }
+
+                (path, r"\")
+            },
+            (Some((_, drive)), Some((colon, ':')))
+                if drive.is_ascii_alphabetic() =>
+            {
+                path.split_at(colon + 1)
+            },
+            _ => ("", path),
+        }
+    }
+}
+
+impl LoadError {
+    /// The file the error is in.
+    pub fn path(&self) -> &Path {
+        match *self {
+            LoadError::Io { ref path, .. }          |
+            LoadError::Parse { ref path, .. }       |
+            LoadError::NotAProgram { ref path }     |
+            LoadError::Lower { ref path, .. }       |
+            LoadError::WrongName { ref path, .. }   => path,
+            LoadError::NotFound { ref importer, .. } |
+            LoadError::Cycle { ref importer, .. }   => importer,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:", self.path().display())?;
+
+        match *self {
+            LoadError::Io { ref error, .. } => write!(f, " {}", error),
+            LoadError::Parse { ref error, .. } => write!(f, "{}", error),
+            LoadError::NotAProgram { .. } => write!(f, " not a program"),
+            LoadError::Lower { ref error, .. } => write!(f, "{}", error),
+            LoadError::NotFound { ref module, ref searched, .. } => write!(
+                f,
+                "{}:{}: cannot find module {} (looked for {})",
+                module.span.line,
+                module.span.col,
+                module.name,
+                searched.iter()
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+            ),
+            LoadError::WrongName { ref expected, ref found, .. } => write!(
+                f,
+                "{}:{}: expected module {}, found module {}",
+                found.span.line,
+                found.span.col,
+                expected,
+                found.name
+            ),
+            LoadError::Cycle { ref module, ref cycle, .. } => write!(
+                f,
+                "{}:{}: import cycle: {}",
+                module.span.line,
+                module.span.col,
+                cycle.join(" -> ")
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            LoadError::Io { ref error, .. }    => Some(error),
+            LoadError::Parse { ref error, .. } => Some(error),
+            LoadError::Lower { ref error, .. } => Some(error),
+            _                                  => None,
+        }
+    }
+}
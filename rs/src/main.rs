@@ -1,54 +1,1360 @@
 #![cfg_attr(feature="clippy", feature(plugin))]
 #![cfg_attr(feature="clippy", plugin(clippy))]
+// Struct literals name every field, as the library's do.
+#![allow(clippy::redundant_field_names)]
 
-#![deny(missing_docs)]
+//! Command-line interface to the brouwer library.
 
-#![feature(collection_placement)]
-#![feature(io)]
-#![feature(placement_in_syntax)]
+extern crate brouwer;
 
-//! Parser (and bytecode compiler/interpreter) for the brouwer language.
+mod cli;
 
-mod parser;
-mod token;
-mod tree;
+use brouwer::{AST, ParseError, Parser};
+use brouwer::{ast, cache, check, diagnostics, diff, dump, edit, formatter,
+              graph, ice, lexer, lint, minimize, query, resolve, schedule,
+              server, stats, typeck};
+use brouwer::backend::Backends;
+use brouwer::cache::{Cache, Key};
+use brouwer::loader::{LoadError, Loader};
+use brouwer::diagnostics::{Diagnostic, Severity};
+use brouwer::edit::{FileEdits, TextEdit};
+use brouwer::formatter::FormatConfig;
+use brouwer::lint::LintConfig;
+use brouwer::minimize::Predicate;
+use brouwer::parser::{log_depth_first, str_repr};
+use brouwer::plugin::Plugins;
+use brouwer::server::{Listener, Server};
 
-use parser::{Parser, log_depth_first};
+use cli::{Args, Flag};
 
 use std::env;
-use std::process;
+use std::ffi::OsString;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+
+
+/// A subcommand of `brouwer`.
+struct Command {
+    name:    &'static str,
+    /// What the subcommand takes, for the usage message.
+    usage:   &'static str,
+    /// The flags it takes, besides `cli::SHARED_FLAGS`.
+    flags:   &'static [Flag],
+    /// What it can print with `--format`, the default first.
+    formats: &'static [&'static str],
+    run:     fn(&Args, &mut Session) -> i32,
+}
+
+/// Every subcommand. `brouwer <file>` is short for `brouwer parse <file>`.
+const COMMANDS: &[Command] = &[
+    Command {
+        name:    "parse",
+        usage:   "<file>",
+        flags:   &[],
+        formats: &["tree", "json", "sexpr"],
+        run:     run_parse,
+    },
+    Command {
+        name:    "check",
+        usage:   "<file>... [--path DIR]... [--deny-warnings] [--jobs N]",
+        flags:   &[
+            Flag { name: "--path",          value: Some("a directory") },
+            Flag { name: "--deny-warnings", value: None },
+            Flag { name: "--jobs",          value: Some("a number") },
+        ],
+        formats: &[],
+        run:     run_check,
+    },
+    Command {
+        name:    "compile",
+        usage:   "<file> [--backend NAME] [--output FILE] [--cache DIR]",
+        flags:   &[
+            Flag { name: "--backend", value: Some("a backend name") },
+            Flag { name: "--output",  value: Some("a file name") },
+            Flag { name: "--cache",   value: Some("a directory") },
+        ],
+        formats: &[],
+        run:     run_compile,
+    },
+    Command {
+        name:    "fmt",
+        usage:   "<file>... [--check] [--width N] [--indent N]",
+        flags:   &[
+            Flag { name: "--check",  value: None },
+            Flag { name: "--width",  value: Some("a number") },
+            Flag { name: "--indent", value: Some("a number") },
+        ],
+        formats: &[],
+        run:     run_fmt,
+    },
+    Command {
+        name:    "tokens",
+        usage:   "<file>",
+        flags:   &[],
+        formats: &["text", "json"],
+        run:     run_tokens,
+    },
+    Command {
+        name:    "diff",
+        usage:   "<old> <new>",
+        flags:   &[],
+        formats: &[],
+        run:     run_diff,
+    },
+    Command {
+        name:    "dump",
+        usage:   "<file> [--schema-version N]",
+        flags:   &[
+            Flag { name: "--sexpr",          value: None },
+            Flag { name: "--schema-version", value: Some("a number") },
+        ],
+        formats: &["json", "sexpr"],
+        run:     run_dump,
+    },
+    Command {
+        name:    "graph",
+        usage:   "<dir>",
+        flags:   &[],
+        formats: &[],
+        run:     run_graph,
+    },
+    Command {
+        name:    "lint",
+        usage:   "<file> [--max-complexity N] [--max-nesting N] \
+                  [--deny-warnings]",
+        flags:   &[
+            Flag { name: "--max-complexity", value: Some("a number") },
+            Flag { name: "--max-nesting",    value: Some("a number") },
+            Flag { name: "--deny-warnings",  value: None },
+        ],
+        formats: &[],
+        run:     run_lint,
+    },
+    Command {
+        name:    "minimize",
+        usage:   "<file> (--panics | --fails | --error-contains MSG)",
+        flags:   &[
+            Flag { name: "--panics",         value: None },
+            Flag { name: "--fails",          value: None },
+            Flag { name: "--error-contains", value: Some("the error text") },
+        ],
+        formats: &[],
+        run:     run_minimize,
+    },
+    Command {
+        name:    "query",
+        usage:   "<selector> <file>",
+        flags:   &[],
+        formats: &[],
+        run:     run_query,
+    },
+    Command {
+        name:    "stats",
+        usage:   "<dir>",
+        flags:   &[],
+        formats: &["table", "json"],
+        run:     run_stats,
+    },
+    Command {
+        name:    "serve",
+        usage:   "[--socket PATH | --port N] [--daemon]",
+        flags:   &[
+            Flag { name: "--socket", value: Some("a file name") },
+            Flag { name: "--port",   value: Some("a port number") },
+            Flag { name: "--daemon", value: None },
+        ],
+        formats: &[],
+        run:     run_serve,
+    },
+    Command {
+        name:    "cache",
+        usage:   "gc --max-size SIZE [--dir DIR]",
+        flags:   &[
+            Flag { name: "--max-size", value: Some("a size, like 100M") },
+            Flag { name: "--dir",      value: Some("a directory") },
+        ],
+        formats: &[],
+        run:     run_cache,
+    },
+];
+
+/// What every subcommand shares: the settings of the shared flags, and a
+/// count of the errors reported.
+struct Session {
+    /// What `--format` chose, or the subcommand's default.
+    format:     &'static str,
+    /// Whether to print nothing but errors.
+    quiet:      bool,
+    /// How many errors to print before keeping count of the rest.
+    max_errors: Option<usize>,
+    errors:     usize,
+    /// The parser every file is parsed with, reset for each.
+    parser:     Parser,
+}
+
+impl Session {
+    fn new(args: &Args, command: &Command) -> Result<Session, String> {
+        if command.formats.is_empty() && args.has("--format") {
+            return Err(format!(
+                "brouwer {} does not take --format",
+                command.name
+            ));
+        }
+
+        let format = args.choice("--format", command.formats)?;
+
+        Ok(Session {
+            format:     format.or(command.formats.first().cloned())
+                              .unwrap_or(""),
+            quiet:      args.has("--quiet"),
+            max_errors: args.number("--max-errors")?,
+            errors:     0,
+            parser:     Parser::from_str(""),
+        })
+    }
+
+    /// Counts an error, returning whether to print it.
+    fn count_error(&mut self) -> bool {
+        self.errors += 1;
+
+        self.max_errors.is_none_or(|max| self.errors <= max)
+    }
+
+    /// Prints an error on stderr.
+    fn error<D: fmt::Display>(&mut self, message: D) {
+        if self.count_error() {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Prints a parse error in `src`, which was read from `filename`, on
+    /// stderr.
+    fn report(&mut self, filename: &Path, src: &str, e: &ParseError) {
+        self.diagnose(filename, src, &Diagnostic::from(e));
+    }
+
+    /// Prints a diagnostic about `src`, which was read from `filename`, on
+    /// stderr.
+    fn diagnose(
+        &mut self,
+        filename:   &Path,
+        src:        &str,
+        diagnostic: &Diagnostic
+    ) {
+        if self.count_error() {
+            eprint!(
+                "{}",
+                diagnostic.render(
+                    &filename.to_string_lossy(),
+                    src,
+                    diagnostics::color_enabled()
+                )
+            );
+        }
+    }
+
+    /// Says how many errors went unprinted, if any did.
+    fn finish(&self) {
+        match self.max_errors {
+            Some(max) if self.errors > max => eprintln!(
+                "{} more errors not shown (--max-errors {})",
+                self.errors - max,
+                max
+            ),
+            _ => {},
+        }
+    }
+}
 
 
 fn main() {
-    if let Some(filename) = env::args().nth(1) {
-        let mut parser = match Parser::new(filename) {
-            Ok(parser) => parser,
-            Err(e) => {
-                eprintln!("{}", e);
+    ice::install_hook();
+
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+
+    let first = args.first().map(|a| a.to_str().unwrap_or(""));
+
+    let (command, args) = match first {
+        None | Some("help") | Some("--help") => {
+            print_usage();
+
+            process::exit(if args.is_empty() { 1 } else { 0 });
+        },
+        Some("--tokens") => (command("tokens"), &args[1..]),
+        Some(name) => match COMMANDS.iter().find(|c| c.name == name) {
+            Some(command) => (command, &args[1..]),
+            None          => (command("parse"), &args[..]),
+        },
+    };
+
+    let parsed = Args::parse(args, command.flags).and_then(|parsed|
+        Session::new(&parsed, command).map(|session| (parsed, session))
+    );
+    let (args, mut session) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("Usage: brouwer {} {}", command.name, command.usage);
+
+            process::exit(1);
+        },
+    };
+
+    let status = (command.run)(&args, &mut session);
+    session.finish();
+
+    process::exit(status);
+}
+
+/// The subcommand called `name`.
+fn command(name: &str) -> &'static Command {
+    COMMANDS.iter()
+            .find(|c| c.name == name)
+            .expect("no such subcommand")
+}
+
+/// Prints every subcommand, with what it takes, on stderr.
+fn print_usage() {
+    eprintln!("Usage: brouwer <command> [--format FORMAT] [--quiet] \
+               [--max-errors N] ...\n");
+
+    for command in COMMANDS {
+        eprintln!("    brouwer {} {}", command.name, command.usage);
+    }
+
+    eprintln!("\n`brouwer <file>` is short for `brouwer parse <file>`.");
+}
+
+/// `brouwer parse <file> [--format tree|json|sexpr]`
+///
+/// Prints the parse tree: as an indented tree, by default; as a JSON dump
+/// in the newest version of the schema; or as a compact S-expression.
+fn run_parse(args: &Args, session: &mut Session) -> i32 {
+    let filename = match args.operand("source file") {
+        Ok(filename) => Path::new(filename),
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let ast = match parse_file(session, filename) {
+        Ok(ast) => ast,
+        Err(status) => return status,
+    };
+
+    if session.quiet {
+        return 0;
+    }
+
+    match session.format {
+        "json"  => println!("{}", dump::to_json(&ast)),
+        "sexpr" => println!("{}", dump::to_sexpr(&ast)),
+        _       => {
+            log_depth_first(&ast, 0);
+            println!();
+        },
+    }
+
+    0
+}
+
+/// `brouwer check <file>... [--path DIR]... [--deny-warnings] [--jobs N]`
+///
+/// Loads each file with every module it imports (see `loader`), looking
+/// for them in the file's own directory and then in each `--path`. Then
+/// resolves the names in each module loaded (see `resolve`), infers its
+/// types (see `typeck`), and checks its `case`s (see `exhaustive`),
+/// reporting what is wrong with each, and exits with 1 if anything is.
+/// Unreachable `case` branches are warned about, but do not count unless
+/// `--deny-warnings` is given. A module imported by more than one file is
+/// checked once.
+///
+/// Each pass is followed by the hooks of the plugins registered (see
+/// `plugin`), whose warnings count the same way. None are built in: the
+/// hooks are for tools that check code with the library instead.
+///
+/// Modules are checked on `--jobs` threads (by default, one per core; see
+/// `schedule`), but reported on in the order they were loaded.
+fn run_check(args: &Args, session: &mut Session) -> i32 {
+    if args.operands.is_empty() {
+        eprintln!("Please provide the source file.");
+
+        return 1;
+    }
+
+    let jobs = match args.number("--jobs") {
+        Ok(jobs) => jobs.unwrap_or_else(schedule::default_jobs),
+        Err(e)   => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let deny_warnings = args.has("--deny-warnings");
+    let plugins = Plugins::new();
+    let mut status = 0;
+    let mut checked = HashSet::new();
+
+    for filename in &args.operands {
+        let dir = Path::new(filename).parent()
+                                     .unwrap_or_else(|| Path::new(""));
+        let mut loader = Loader::new(vec![dir.to_path_buf()]);
+
+        for path in args.paths("--path") {
+            loader.add_search_path(path);
+        }
+
+        let program = match loader.load(filename) {
+            Ok(program) => program,
+            Err(e)      => {
+                match e {
+                    LoadError::Parse { ref path, ref source, ref error } => {
+                        session.report(path, source, error);
+                    },
+                    _ => session.error(&e),
+                }
+
+                status = 1;
+                continue;
+            },
+        };
+
+        let mut fresh = HashSet::new();
+
+        for loaded in &program.modules {
+            if checked.insert(loaded.path.clone()) {
+                fresh.insert(loaded.path.as_path());
+            }
+        }
+
+        let reports = schedule::run(&program, jobs, |loaded, _| {
+            if fresh.contains(loaded.path.as_path()) {
+                Some(check::check_module(&plugins, &program, loaded))
+            } else {
+                None
+            }
+        });
+
+        for (loaded, report) in program.modules.iter().zip(reports) {
+            let report = match report {
+                Some(report) => report,
+                None         => continue,
+            };
+
+            let filename = loaded.path.display();
+
+            for e in &report.errors {
+                session.error(format!("{}:{}", filename, e));
+            }
+
+            let mut diagnosed_errors = 0;
+
+            for diagnostic in report.diagnostics() {
+                if diagnostic.severity == Severity::Error || deny_warnings {
+                    diagnosed_errors += 1;
+                }
+
+                session.diagnose(&loaded.path, &loaded.source, &diagnostic);
+            }
 
-                process::exit(1);
+            if !report.errors.is_empty() || diagnosed_errors > 0 {
+                status = 1;
+            } else if !session.quiet {
+                println!("{}: ok", filename);
+            }
+        }
+    }
+
+    status
+}
+
+/// `brouwer compile <file> [--backend NAME] [--output FILE] [--cache DIR]`
+///
+/// Prints what the backend named (see `backend`) generates for the file:
+/// by default, the bytecode it compiles to, one chunk at a time, each
+/// function after the chunk it is declared in. With `--output`, writes it
+/// to the file instead, replacing the file only once all of it is written.
+/// With `--cache`, reuses the artifact stored in the artifact store in
+/// `DIR` (see `cache`) for a file that has been compiled with the same
+/// backend before, and stores it there otherwise.
+fn run_compile(args: &Args, session: &mut Session) -> i32 {
+    let filename = match args.operand("source file") {
+        Ok(filename) => Path::new(filename),
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let backends = Backends::new();
+    let backend = match backends.get(args.value("--backend")
+                                         .as_deref()
+                                         .unwrap_or("bytecode"))
+    {
+        Some(backend) => backend,
+        None          => {
+            eprintln!(
+                "--backend must be one of: {}",
+                backends.names().join(", ")
+            );
+
+            return 1;
+        },
+    };
+
+    let cached = args.path("--cache").map(|dir| {
+        let mut src = String::new();
+        let read = File::open(filename).and_then(|mut f|
+            f.read_to_string(&mut src)
+        );
+
+        (Cache::open(dir), read.ok().map(|_|
+            Key::new(backend.name(), &[env!("CARGO_PKG_VERSION"), &src])
+        ))
+    });
+
+    let hit = match cached {
+        Some((ref cache, Some(ref key))) => cache.get(key),
+        _                                => None,
+    };
+
+    let artifact = match hit {
+        Some(artifact) => artifact,
+        None           => {
+            let module = match lower_file(session, filename) {
+                Ok(module)  => module,
+                Err(status) => return status,
+            };
+
+            ice::enter_pass("typeck", filename);
+            let typing = typeck::infer(&module);
+
+            ice::enter_pass("codegen", filename);
+            let artifact = match backend.emit(&module, &typing) {
+                Ok(artifact) => artifact,
+                Err(e)       => {
+                    session.error(format!("{}:{}", filename.display(), e));
+
+                    return 1;
+                },
+            };
+
+            if let Some((ref cache, Some(ref key))) = cached {
+                ice::enter_pass("cache", filename);
+
+                if let Err(e) = cache.put(key, &artifact) {
+                    session.error(e);
+
+                    return 1;
+                }
+            }
+
+            artifact
+        },
+    };
+
+    if let Some(output) = args.path("--output") {
+        ice::enter_pass("write", output);
+
+        if let Err(e) = edit::write_atomic(output, &artifact) {
+            session.error(e);
+
+            return 1;
+        }
+    } else if !session.quiet {
+        print!("{}", artifact);
+    }
+
+    0
+}
+
+/// Reads the file, reporting any failure on stderr.
+fn read_file(session: &mut Session, filename: &Path) -> Option<String> {
+    let mut src = String::new();
+
+    match File::open(filename).and_then(|mut f| f.read_to_string(&mut src)) {
+        Ok(_) => Some(src),
+        Err(e) => {
+            session.error(format!("{}: {}", filename.display(), e));
+
+            None
+        },
+    }
+}
+
+/// Parses the file, reporting any failure on stderr and returning the exit
+/// status to use.
+fn parse_file(session: &mut Session, filename: &Path) -> Result<AST, i32> {
+    match read_file(session, filename) {
+        Some(src) => parse_src(session, filename, &src),
+        None      => Err(1),
+    }
+}
+
+/// Parses `src`, which is called `filename`, reporting any failure on
+/// stderr and returning the exit status to use.
+fn parse_src(
+    session:  &mut Session,
+    filename: &Path,
+    src:      &str
+) -> Result<AST, i32> {
+    ice::enter_pass("parse", filename);
+    session.parser.reset(src);
+
+    match session.parser.parse() {
+        Ok(Some(ast)) => Ok(ast),
+        Ok(_) => {
+            session.error("Parse failed!");
+
+            Err(2)
+        },
+        Err(e) => {
+            session.report(filename, src, &e);
+
+            Err(1)
+        },
+    }
+}
+
+/// Parses the file and lowers it to a typed syntax tree, reporting any
+/// failure on stderr and returning the exit status to use.
+fn lower_file(
+    session:  &mut Session,
+    filename: &Path
+) -> Result<ast::Module, i32> {
+    let tree = parse_file(session, filename)?;
+
+    ice::enter_pass("lower", filename);
+    ast::lower(&tree).map_err(|e| {
+        session.error(format!("{}:{}", filename.display(), e));
+
+        1
+    })
+}
+
+/// `brouwer diff <old> <new>`
+///
+/// Exits with 0 if the two files are semantically the same and 1 if they
+/// differ, like `diff`.
+fn run_diff(args: &Args, session: &mut Session) -> i32 {
+    if args.operands.len() != 2 {
+        eprintln!("Please provide the old and new source files.");
+
+        return 2;
+    }
+
+    let old = match parse_file(session, Path::new(&args.operands[0])) {
+        Ok(ast) => ast,
+        Err(_)  => return 2,
+    };
+    let new = match parse_file(session, Path::new(&args.operands[1])) {
+        Ok(ast) => ast,
+        Err(_)  => return 2,
+    };
+
+    ice::enter_pass("diff", &args.operands[1]);
+    let changes = diff::diff(&old, &new);
+
+    if !session.quiet {
+        for change in &changes {
+            println!("{}", change);
+        }
+    }
+
+    if changes.is_empty() { 0 } else { 1 }
+}
+
+/// `brouwer dump <file> [--format json|sexpr] [--schema-version N]`
+///
+/// Prints the parse tree as JSON, or as an S-expression, following the
+/// given version of the dump schema (by default the newest). `--sexpr` is
+/// the same as `--format sexpr`.
+fn run_dump(args: &Args, session: &mut Session) -> i32 {
+    let format = if args.has("--sexpr") || session.format == "sexpr" {
+        dump::Format::SExpr
+    } else {
+        dump::Format::Json
+    };
+
+    let (filename, version) = match (
+        args.operand("source file"),
+        args.number("--schema-version")
+    ) {
+        (Ok(filename), Ok(version)) => {
+            (Path::new(filename), version.unwrap_or(dump::SCHEMA_VERSION))
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let ast = match parse_file(session, filename) {
+        Ok(ast) => ast,
+        Err(status) => return status,
+    };
+
+    ice::enter_pass("dump", filename);
+    match dump::dump(&ast, format, version) {
+        Ok(dumped) => {
+            if !session.quiet {
+                println!("{}", dumped);
+            }
+
+            0
+        },
+        Err(e) => {
+            session.error(e);
+
+            1
+        },
+    }
+}
+
+/// `brouwer fmt <file>... [--check] [--width N] [--indent N]`
+///
+/// Rewrites each file in canonical form. With `--check`, writes nothing,
+/// but lists the files that are not in canonical form and exits with 1 if
+/// there are any.
+fn run_fmt(args: &Args, session: &mut Session) -> i32 {
+    let mut config = FormatConfig::default();
+    let check = args.has("--check");
+
+    let settings = args.number("--width").and_then(|width|
+        args.number("--indent").map(|indent| (width, indent))
+    );
+
+    match settings {
+        Ok((width, indent)) => {
+            config.width = width.unwrap_or(config.width);
+            config.indent = indent.unwrap_or(config.indent);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    }
+
+    if args.operands.is_empty() {
+        eprintln!("Please provide the source file.");
+
+        return 1;
+    }
+
+    let mut status = 0;
+
+    for filename in &args.operands {
+        let filename = Path::new(filename);
+        let src = match read_file(session, filename) {
+            Some(src) => src,
+            None      => {
+                status = 2;
+                continue;
             },
         };
 
-        match parser.parse() {
-            Ok(Some(ast)) => {
-                log_depth_first(&ast, 0);
-                println!();
+        ice::enter_pass("fmt", filename);
+        let formatted = match formatter::format(&src, &config) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                session.report(filename, &src, &e);
+
+                status = 2;
+                continue;
             },
-            Ok(_) => {
-                eprintln!("Parse failed!");
+        };
+
+        if formatted == src {
+            continue;
+        }
+
+        if check {
+            if !session.quiet {
+                println!("{}", filename.display());
+            }
+
+            if status == 0 {
+                status = 1;
+            }
+
+            continue;
+        }
+
+        let edits = FileEdits::new(
+            filename,
+            vec![TextEdit::new(0, src.len(), formatted)]
+        );
+
+        if let Err(e) = edit::apply_file_edits(&[edits]) {
+            session.error(e);
+
+            status = 2;
+        }
+    }
+
+    status
+}
+
+/// `brouwer graph <dir>`
+///
+/// Prints each module under the directory with the modules it imports,
+/// one `Importer -> Imported` edge per line. Headers are recovered from
+/// files that do not parse; what was wrong with them goes to stderr, and
+/// makes the exit status 2, as does a file with no module declaration.
+fn run_graph(args: &Args, session: &mut Session) -> i32 {
+    let dir = match args.operand("directory to report on") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let sources = match stats::find_sources(dir) {
+        Ok(sources) => sources,
+        Err(e) => {
+            session.error(e);
+
+            return 1;
+        },
+    };
 
-                process::exit(2);
+    let mut modules = graph::ModuleGraph::new();
+    let mut status = 0;
+
+    for source in sources {
+        let src = match read_file(session, &source) {
+            Some(src) => src,
+            None      => {
+                status = 2;
+                continue;
             },
+        };
+
+        ice::enter_pass("graph", &source);
+        let header = graph::recover_header(&src);
+
+        for e in &header.errors {
+            session.report(&source, &src, e);
+
+            status = 2;
+        }
+
+        if !modules.insert(header) {
+            session.error(format!(
+                "{}: no module declaration",
+                source.display()
+            ));
+
+            status = 2;
+        }
+    }
+
+    if !session.quiet {
+        for module in modules.modules() {
+            for imported in modules.imports_of(module) {
+                println!("{} -> {}", module, imported);
+            }
+        }
+    }
+
+    status
+}
+
+/// `brouwer lint <file> [--max-complexity N] [--max-nesting N]
+/// [--deny-warnings]`
+///
+/// Reports what `lint::lint` and `lint::unused` warn about on stderr, in
+/// the order they are in the file, as `brouwer check` does its warnings.
+/// With `--deny-warnings`, exits with 1 if there are any warnings.
+fn run_lint(args: &Args, session: &mut Session) -> i32 {
+    let mut config = LintConfig::default();
+
+    let settings = args.number("--max-complexity").and_then(|complexity|
+        args.number("--max-nesting").map(|nesting| (complexity, nesting))
+    );
+
+    match settings {
+        Ok((complexity, nesting)) => {
+            config.max_complexity =
+                complexity.unwrap_or(config.max_complexity);
+            config.max_nesting = nesting.unwrap_or(config.max_nesting);
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    }
+
+    let filename = match args.operand("source file") {
+        Ok(filename) => Path::new(filename),
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let src = match read_file(session, filename) {
+        Some(src) => src,
+        None      => return 1,
+    };
+    let ast = match parse_src(session, filename, &src) {
+        Ok(ast) => ast,
+        Err(status) => return status,
+    };
+
+    ice::enter_pass("lower", filename);
+    let module = match ast::lower(&ast) {
+        Ok(module) => module,
+        Err(e) => {
+            session.error(format!("{}:{}", filename.display(), e));
+
+            return 1;
+        },
+    };
+
+    ice::enter_pass("resolve", filename);
+    let resolution = resolve::resolve(&module);
+
+    ice::enter_pass("lint", filename);
+    let warnings = lint::lint(&ast, &config);
+    let unused = lint::unused(&module, &resolution);
+
+    let mut diagnostics: Vec<Diagnostic> = warnings.iter()
+                                                   .map(Diagnostic::from)
+                                                   .chain(unused)
+                                                   .collect();
+    diagnostics.sort_by_key(|d| d.span.map(|span| (span.line, span.col)));
+
+    for diagnostic in &diagnostics {
+        session.diagnose(filename, &src, diagnostic);
+    }
+
+    if args.has("--deny-warnings") && !diagnostics.is_empty() {
+        1
+    } else {
+        0
+    }
+}
+
+/// `brouwer minimize <file> (--panics | --fails | --error-contains MSG)`
+///
+/// Prints the smallest program found that still reproduces the failure.
+fn run_minimize(args: &Args, session: &mut Session) -> i32 {
+    let predicate = if args.has("--panics") {
+        Some(Predicate::Panics)
+    } else if args.has("--fails") {
+        Some(Predicate::ParseFails)
+    } else {
+        args.value("--error-contains")
+            .map(|needle| Predicate::ErrorContains(needle.to_string()))
+    };
+
+    let filename = args.operand("source file");
+
+    let (filename, predicate) = match (filename, predicate) {
+        (Ok(f), Some(p)) => (Path::new(f), p),
+        _ => {
+            eprintln!(
+                "Please provide the source file and one of --panics, \
+                 --fails, or --error-contains MSG."
+            );
+
+            return 1;
+        },
+    };
+
+    let src = match read_file(session, filename) {
+        Some(src) => src,
+        None      => return 1,
+    };
+
+    ice::enter_pass("minimize", filename);
+    match minimize::minimize(&src, &predicate) {
+        Ok(minimized) => {
+            if !session.quiet {
+                print!("{}", minimized);
+            }
+
+            0
+        },
+        Err(e) => {
+            session.error(e);
+
+            1
+        },
+    }
+}
+
+/// `brouwer query <selector> <file>`
+///
+/// Prints every node the selector selects (see `query`), one per line,
+/// with where it starts.
+fn run_query(args: &Args, session: &mut Session) -> i32 {
+    if args.operands.len() != 2 {
+        eprintln!("Please provide the selector and the source file.");
+
+        return 1;
+    }
+
+    let filename = Path::new(&args.operands[1]);
+
+    let selector = match query::Selector::parse(
+        &args.operands[0].to_string_lossy()
+    ) {
+        Ok(selector) => selector,
+        Err(e) => {
+            session.error(e);
+
+            return 1;
+        },
+    };
+
+    let ast = match parse_file(session, filename) {
+        Ok(ast) => ast,
+        Err(status) => return status,
+    };
+
+    ice::enter_pass("query", filename);
+    if !session.quiet {
+        for node in selector.select(&ast) {
+            println!(
+                "{}:{}:{}: {}",
+                filename.display(),
+                node.line(),
+                node.col(),
+                str_repr(node).trim()
+            );
+        }
+    }
+
+    0
+}
+
+/// `brouwer stats <dir> [--format table|json]`
+fn run_stats(args: &Args, session: &mut Session) -> i32 {
+    let dir = match args.operand("directory to report on") {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let sources = match stats::find_sources(dir) {
+        Ok(sources) => sources,
+        Err(e) => {
+            session.error(e);
+
+            return 1;
+        },
+    };
+
+    let mut all = Vec::with_capacity(sources.len());
+    let mut status = 0;
+
+    for source in sources {
+        ice::enter_pass("stats", &source);
+
+        match stats::module_stats(&source) {
+            Ok(module_stats) => all.push(module_stats),
             Err(e) => {
-                eprintln!("Parser error:\n    {}", e);
+                session.error(e);
 
-                process::exit(1);
+                status = 2;
             },
         }
+    }
+
+    if session.quiet {
+        return status;
+    }
+
+    if session.format == "json" {
+        println!("{}", stats::to_json(&all));
     } else {
-        eprintln!("Please provide the source file.");
+        print!("{}", stats::to_table(&all));
+    }
+
+    status
+}
+
+/// `brouwer tokens <file> [--format text|json]`
+///
+/// Prints the file's tokens with where each starts: one per line, or as a
+/// JSON array. `brouwer --tokens <file>` is the same.
+fn run_tokens(args: &Args, session: &mut Session) -> i32 {
+    let filename = match args.operand("source file") {
+        Ok(filename) => Path::new(filename),
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    let src = match read_file(session, filename) {
+        Some(src) => src,
+        None      => return 1,
+    };
+
+    ice::enter_pass("lex", filename);
+    let tokens = match lexer::tokenize(&src) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            session.report(filename, &src, &e);
+
+            return 1;
+        },
+    };
+
+    if session.quiet {
+        return 0;
+    }
+
+    if session.format == "json" {
+        let entries: Vec<String> = tokens.iter().map(|token| format!(
+            "{{\"type\":{},\"lexeme\":{},\"line\":{},\"col\":{}}}",
+            dump::json_str(&format!("{:?}", token.type_)),
+            dump::json_str(token.lexeme()),
+            token.span.line,
+            token.span.col
+        )).collect();
+
+        println!("[{}]", entries.join(","));
+    } else {
+        for token in &tokens {
+            println!(
+                "{}:{}\t{:?}\t{}",
+                token.span.line,
+                token.span.col,
+                token.type_,
+                token.lexeme()
+            );
+        }
+    }
+
+    0
+}
+
+/// `brouwer cache gc --max-size SIZE [--dir DIR]`
+///
+/// Removes the artifacts used least recently from the artifact store in
+/// `DIR` (by default `.brouwer-cache`; see `cache`) until it takes up no
+/// more than `SIZE` bytes. `SIZE` may end in `K`, `M`, or `G`.
+fn run_cache(args: &Args, session: &mut Session) -> i32 {
+    match args.operand("cache command").map(|c| c.to_string_lossy()) {
+        Ok(ref command) if command == "gc" => {},
+        Ok(other) => {
+            eprintln!("Unknown cache command {}; expected gc.", other);
+
+            return 1;
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    }
+
+    let max_size = match args.value("--max-size")
+                             .map(|size| cache::parse_size(&size))
+    {
+        Some(Ok(max_size)) => max_size,
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+        None => {
+            eprintln!("Please provide --max-size.");
+
+            return 1;
+        },
+    };
+
+    let cache = Cache::open(
+        args.path("--dir").unwrap_or_else(|| Path::new(cache::DEFAULT_DIR))
+    );
+
+    match cache.gc(max_size) {
+        Ok(report) => {
+            if !session.quiet {
+                println!(
+                    "removed {} artifacts, freeing {} bytes; {} bytes remain",
+                    report.removed,
+                    report.freed,
+                    report.remaining
+                );
+            }
+
+            0
+        },
+        Err(e) => {
+            session.error(format!("{}: {}", cache.dir().display(), e));
+
+            1
+        },
+    }
+}
+
+/// `brouwer serve [--socket PATH | --port N] [--daemon]`
+///
+/// Runs a compile server (see `server`) until it is asked to shut down. It
+/// listens on a Unix socket that only the user can connect to: the one at
+/// `PATH`, or else the one `server::default_socket` names. With `--port`,
+/// it listens on TCP port `N` of `127.0.0.1` instead, which every user of
+/// the machine can connect to, as on systems without Unix sockets. With
+/// `--daemon`, starts the server as a process of its own in the
+/// background, and exits as soon as it has.
+fn run_serve(args: &Args, session: &mut Session) -> i32 {
+    let port = match args.number("--port") {
+        Ok(port) => port,
+        Err(e)   => {
+            eprintln!("{}", e);
+
+            return 1;
+        },
+    };
+
+    if let (Some(_), Some(_)) = (port, args.path("--socket")) {
+        eprintln!("--port and --socket cannot be used together");
+
+        return 1;
+    }
+
+    #[cfg(unix)]
+    {
+        if port.is_none() {
+            let path = args.path("--socket")
+                           .map(PathBuf::from)
+                           .unwrap_or_else(server::default_socket);
+            let listener = match server::bind(&path) {
+                Ok(listener) => listener,
+                Err(e)       => {
+                    session.error(format!(
+                        "cannot listen on {}: {}",
+                        path.display(),
+                        e
+                    ));
+
+                    return 1;
+                },
+            };
+
+            let address = path.display().to_string();
+            let status = serve_on(
+                listener,
+                &address,
+                &["--socket", &address],
+                args,
+                session
+            );
+
+            if !args.has("--daemon") {
+                let _ = fs::remove_file(&path);
+            }
+
+            return status;
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        if args.has("--socket") {
+            eprintln!("--socket needs Unix sockets, which this system lacks");
+
+            return 1;
+        }
+    }
+
+    let port = port.unwrap_or(server::DEFAULT_PORT);
+
+    match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => serve_on(
+            listener,
+            &format!("127.0.0.1:{}", port),
+            &["--port", &port.to_string()],
+            args,
+            session
+        ),
+        Err(e) => {
+            session.error(format!("cannot listen on port {}: {}", port, e));
+
+            1
+        },
+    }
+}
+
+/// Serves on `listener`, which is at `address`, or, with `--daemon`,
+/// starts a server of its own process listening there, passing it `flags`
+/// to tell it where.
+fn serve_on<L: Listener>(
+    listener: L,
+    address:  &str,
+    flags:    &[&str],
+    args:     &Args,
+    session:  &mut Session
+) -> i32 {
+    if args.has("--daemon") {
+        // The server listens there again for itself.
+        drop(listener);
+
+        let daemon = env::current_exe().and_then(|exe|
+            process::Command::new(exe)
+                .arg("serve")
+                .args(flags)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+        );
+
+        return match daemon {
+            Ok(child) => {
+                if !session.quiet {
+                    println!(
+                        "serving on {} as process {}",
+                        address,
+                        child.id()
+                    );
+                }
+
+                0
+            },
+            Err(e) => {
+                session.error(format!("cannot start the server: {}", e));
+
+                1
+            },
+        };
+    }
+
+    if !session.quiet {
+        println!("serving on {}", address);
+    }
+
+    match Server::new(FormatConfig::default()).serve(listener) {
+        Ok(()) => 0,
+        Err(e) => {
+            session.error(format!("server failed: {}", e));
 
-        process::exit(1);
+            1
+        },
     }
 }
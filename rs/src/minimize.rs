@@ -0,0 +1,144 @@
+//! Delta-debugging minimizer for inputs that make the parser misbehave.
+
+use std::panic;
+
+use parser::Parser;
+
+
+/// What it means for an input to still reproduce the bug being minimized.
+#[derive(Clone, Debug)]
+pub enum Predicate {
+    /// The parser panics.
+    Panics,
+    /// The parser returns an error whose message contains the given text.
+    ErrorContains(String),
+    /// The parser reports that the parse failed without an error.
+    ParseFails,
+}
+
+enum Outcome {
+    Parsed,
+    Failed,
+    Error(String),
+    Panicked,
+}
+
+
+impl Predicate {
+    fn holds(&self, outcome: &Outcome) -> bool {
+        match (self, outcome) {
+            (&Predicate::Panics, &Outcome::Panicked)  => true,
+            (&Predicate::ParseFails, &Outcome::Failed) => true,
+            (Predicate::ErrorContains(needle), Outcome::Error(e)) =>
+                e.contains(needle.as_str()),
+            _ => false,
+        }
+    }
+}
+
+/// Shrinks `src` to a smaller program for which `predicate` still holds,
+/// by repeatedly deleting blocks (a line together with every more-indented
+/// line under it, i.e. one subtree of the parse) and keeping each deletion
+/// that preserves the failure. Returns an error if `src` itself does not
+/// satisfy `predicate`.
+pub fn minimize(src: &str, predicate: &Predicate) -> Result<String, String> {
+    let mut lines: Vec<&str> = src.lines().collect();
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let result = if predicate.holds(&run(&lines)) {
+        shrink(&mut lines, predicate);
+
+        let mut minimized = lines.join("\n");
+        minimized.push('\n');
+
+        Ok(minimized)
+    } else {
+        Err("the input does not reproduce the failure".to_string())
+    };
+
+    panic::set_hook(default_hook);
+
+    result
+}
+
+fn shrink(lines: &mut Vec<&str>, predicate: &Predicate) {
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        // Whole blocks first, largest (outermost) first, then lone lines.
+        let mut candidates = Vec::new();
+        for start in 0..lines.len() {
+            let end = block_end(lines, start);
+
+            candidates.push((start, end));
+
+            if end > start + 1 {
+                candidates.push((start, start + 1));
+            }
+        }
+        candidates.sort_by(|&(a_start, a_end), &(b_start, b_end)|
+            (b_end - b_start).cmp(&(a_end - a_start))
+                             .then(a_start.cmp(&b_start))
+        );
+
+        for (start, end) in candidates {
+            if end > lines.len() {
+                continue;
+            }
+
+            let mut candidate = lines.clone();
+            candidate.drain(start..end);
+
+            if candidate.iter().all(|l| l.trim().is_empty()) {
+                continue;
+            }
+
+            if predicate.holds(&run(&candidate)) {
+                *lines = candidate;
+                changed = true;
+
+                break;
+            }
+        }
+    }
+}
+
+/// The index one past the last line of the block that starts at `start`.
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indent_of(lines[start]);
+    let mut end = start + 1;
+
+    while end < lines.len() &&
+          (lines[end].trim().is_empty() || indent_of(lines[end]) > indent)
+    {
+        end += 1;
+    }
+
+    end
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+fn run(lines: &[&str]) -> Outcome {
+    let mut src = lines.join("\n");
+    src.push('\n');
+
+    let outcome = panic::catch_unwind(move || {
+        match Parser::from_str(&src).parse() {
+            Ok(Some(_)) => Outcome::Parsed,
+            Ok(None)    => Outcome::Failed,
+            Err(e)      => Outcome::Error(e.to_string()),
+        }
+    });
+
+    match outcome {
+        Ok(result) => result,
+        Err(_)     => Outcome::Panicked,
+    }
+}
@@ -1,48 +1,258 @@
-use std::collections::VecDeque;
+//! A scannerless recursive-descent parser for brouwer source files.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::convert::AsRef;
-use std::error::Error;
 use std::fs::File;
 use std::io;
-use std::io::{Chars, Read};
+use std::io::Read;
+use std::iter::{self, Peekable};
 use std::path::Path;
-
-use token::{Token, TokenType};
+use std::sync::Arc;
+use std::vec::IntoIter;
+
+use error::ParseError;
+use ice;
+use lexer::{self, IndentStack};
+use scan;
+use token::{Span, Token, TokenType};
 use tree::Tree;
 
 
+/// A parse tree, as produced by `Parser::parse`.
 pub type AST = Tree<Token>;
 
+/// Parses a single source file, or any other source of text, into an
+/// `AST`. The leaves of the tree share the source, rather than copying
+/// their lexemes out of it.
 pub struct Parser {
-    charstream:    Chars<File>,
+    source:        Arc<str>,
+    read_to:       usize,
+    read_error:    Option<io::Error>,
     eof:           bool,
     charhistory:   VecDeque<char>,
     ch:            char,
-    currentindent: String,
+    indents:       IndentStack,
+    layout:        VecDeque<TokenType>,
+    line_starts:   Vec<usize>,
+    fixities:      HashMap<String, Fixity>,
+    /// How many of the brackets of tuples, lists, dicts, sets, and
+    /// comprehensions are open, within which line breaks are blanks.
+    brackets:      usize,
+    /// The word last looked up as a keyword: where it starts, the
+    /// character it starts with, and the keyword it is, if any. Every
+    /// keyword tried at one place is checked against this one lookup.
+    word:          Option<(usize, char, Option<TokenType>)>,
+}
+
+/// The top-level items of a source file, parsed one at a time as they are
+/// asked for: see `Parser::items`.
+pub struct Items<'a> {
+    parser: &'a mut Parser,
+    next:   ItemKind,
+}
+
+/// What `Items` is to look for next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum ItemKind {
+    ModDecl,
+    Import,
+    Line,
+    Done,
+}
+
+/// The length of the longest keyword, `exposing` or `Infinity`.
+const MAX_KEYWORD_LEN: usize = 8;
+
+/// How a token that is always spelled the same (see `fixed_token`) is
+/// matched. Words and operators munch maximally: `::` is never a `:`
+/// followed by another, nor `import` the start of `imports`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Munch {
+    /// A single character, whatever follows it.
+    Char,
+    /// Punctuation of more than one character, whatever follows it. Any
+    /// longer token it starts is looked for first.
+    Punct,
+    /// A whole word: not followed by a letter, digit, or `_`.
+    Word,
+    /// A whole operator: not followed by another operator character.
+    Op,
+}
+
+/// Which way a chain of operators of the same precedence groups.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Assoc {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+    /// `a == b == c` is an error.
+    NonAssoc,
+}
+
+/// How tightly an infix operator binds, from 0 to 9, and which way it
+/// groups.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fixity {
+    /// The precedence; higher binds tighter. Application binds tighter
+    /// than any operator.
+    pub prec:  u8,
+    /// The associativity.
+    pub assoc: Assoc,
 }
 
 
 impl Parser {
+    /// Opens the file at `filename` for parsing.
     pub fn new<P: AsRef<Path>>(filename: P) -> io::Result<Self> {
         let file = File::open(filename)?;
 
-        Ok(Parser {
-            charstream:    file.chars(),
+        Ok(Parser::from_reader(file))
+    }
+
+    /// Parses whatever `reader` yields, which is read all at once. Read
+    /// errors, including invalid UTF-8, are reported by `parse` as
+    /// `ParseError::Io`.
+    pub fn from_reader<R: Read>(mut reader: R) -> Self {
+        let mut bytes = Vec::with_capacity(4096);
+        let read = reader.read_to_end(&mut bytes).and_then(|_|
+            String::from_utf8(bytes).map_err(|_| io::Error::new(
+                io::ErrorKind::InvalidData,
+                "source is not valid UTF-8"
+            ))
+        );
+
+        match read {
+            Ok(src) => Parser::from_source(src.into(), None),
+            Err(e)  => Parser::from_source("".into(), Some(e)),
+        }
+    }
+
+    /// Parses the source code `src`.
+    #[cfg_attr(feature="clippy", allow(should_implement_trait))]
+    pub fn from_str(src: &str) -> Self {
+        Parser::from_source(src.into(), None)
+    }
+
+    /// Parses `source`, or if reading it failed, reports `read_error`.
+    fn from_source(source: Arc<str>, read_error: Option<io::Error>) -> Self {
+        Parser {
+            source:        source,
+            read_to:       0,
+            read_error:    read_error,
             eof:           false,
             charhistory:   VecDeque::with_capacity(20),
             ch:            ' ', // Dummy value.
-            currentindent: String::with_capacity(32),
-        })
+            indents:       IndentStack::new(),
+            layout:        VecDeque::with_capacity(4),
+            line_starts:   vec![0],
+            fixities:      HashMap::new(),
+            brackets:      0,
+            word:          None,
+        }
+    }
+
+    /// Starts over on the source code `src`, as if the parser were made by
+    /// `Parser::from_str(src)`, but keeping the buffers it has grown, so
+    /// that parsing many files with one parser allocates little more than
+    /// the trees it returns.
+    ///
+    /// ```
+    /// use brouwer::Parser;
+    ///
+    /// let mut parser = Parser::from_str("module A\n\nx = (\n");
+    /// assert!(parser.parse().is_err());
+    ///
+    /// parser.reset("module B\n\nx = 1\n");
+    /// assert!(parser.parse().unwrap().is_some());
+    /// ```
+    pub fn reset(&mut self, src: &str) {
+        self.source = src.into();
+        self.read_to = 0;
+        self.read_error = None;
+        self.eof = false;
+        self.charhistory.clear();
+        self.ch = ' '; // Dummy value.
+        self.indents.clear();
+        self.layout.clear();
+        self.line_starts.clear();
+        self.line_starts.push(0);
+        self.fixities.clear();
+        self.brackets = 0;
+        self.word = None;
+    }
+
+    /// Parses the whole file. Returns `Ok(None)` if the file is not a
+    /// program at all, and `Err` on a syntax error or if the file cannot be
+    /// read.
+    pub fn parse(&mut self) -> Result<Option<AST>, ParseError> {
+        self.start()?;
+
+        let mut main_ast = new_ast_node(TokenType::Root);
+        let prog = if let Some(p) = self.parse_prog()? {
+            p
+        } else {
+            return Ok(None);
+        };
+
+        main_ast.add_child(prog);
+        propagate_spans(&mut main_ast);
+
+        Ok(Some(main_ast))
+    }
+
+    /// Parses the file one top-level item at a time, as the items are
+    /// asked for: the `ModDecl`, then each `Import`, then each `Line`.
+    /// Nothing past the end of an item is parsed until the next one is
+    /// asked for.
+    ///
+    /// A syntax error is yielded in place of the item it is in, after
+    /// which parsing goes on from the next line that is not indented, or,
+    /// for an error within brackets, which may have run on past the end of
+    /// the item, from the next such line after the one the item starts on.
+    /// Iteration ends after an error reading the file.
+    ///
+    /// ```
+    /// use brouwer::{Parser, TokenType};
+    ///
+    /// let mut parser = Parser::from_str("module Main\n\nx = (\ny = 2\n");
+    /// let items: Vec<_> = parser.items().collect();
+    ///
+    /// assert_eq!(items.len(), 3);
+    /// assert_eq!(items[0].as_ref().ok().unwrap().val().type_,
+    ///            TokenType::ModDecl);
+    /// assert!(items[1].is_err());
+    /// assert_eq!(items[2].as_ref().ok().unwrap().line(), 4);
+    /// ```
+    pub fn items(&mut self) -> Items<'_> {
+        Items {
+            parser: self,
+            next:   ItemKind::ModDecl,
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Option<AST>, String> {
+    /// Reads up to the first character of the source that is not
+    /// whitespace.
+    fn start(&mut self) -> Result<(), ParseError> {
         let mut last_ch = '\0'; // Dummy value.
         let mut hit_eof = true;
+        // Blanks after a block comment do not indent the line it is on.
+        let mut after_comment = false;
 
-        while let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
+        while let Some(c) = self.next_char()? {
+            self.ch = c;
+
+            if self.consume_block_comment()? {
+                if self.eof && self.charhistory.is_empty() {
+                    break;
+                }
+
+                after_comment = true;
+                self.charhistory.push_front(self.ch);
+
+                continue;
+            }
 
             if !self.ch.is_whitespace() {
                 hit_eof = false;
@@ -50,7 +260,13 @@ impl Parser {
                 break;
             }
 
-            last_ch = self.ch;
+            if is_newline(self.ch) {
+                after_comment = false;
+            }
+
+            if !after_comment {
+                last_ch = self.ch;
+            }
         }
 
         if hit_eof {
@@ -58,25 +274,84 @@ impl Parser {
         }
 
         if last_ch != '\0' && !is_newline(last_ch) {
-            return Err(
-                "source must not start with leading whitespace".to_string()
-            );
+            return Err(self.bad_indent(
+                "source must not start with leading whitespace"
+            ));
         }
 
-        let mut main_ast = new_ast_node(TokenType::Root, 1);
-        let prog = if let Some(p) = self.parse_prog()? {
-            p
-        } else {
-            return Ok(None);
-        };
+        Ok(())
+    }
 
-        main_ast.add_child(prog);
+    /// After a syntax error, skips the rest of the line it was on, and any
+    /// indented lines after that, to the next line with code on it at the
+    /// top level. Returns `false` if there is no such line.
+    fn skip_to_top_level(&mut self) -> Result<bool, ParseError> {
+        // The error may have come after the end of its line was scanned,
+        // leaving the next line's layout tokens waiting.
+        if !self.layout.is_empty() {
+            let top_level = self.indents.depth() == 0;
+            self.layout.clear();
+
+            if top_level {
+                return Ok(!self.eof || !self.charhistory.is_empty());
+            }
+        }
 
-        Ok(Some(main_ast))
+        loop {
+            while !is_newline(self.ch) {
+                match self.next_char()? {
+                    Some(c) => self.ch = c,
+                    None    => {
+                        self.eof = true;
+
+                        return Ok(false);
+                    },
+                }
+            }
+
+            self.layout.clear();
+            self.indents.clear();
+            self.scan_layout()?;
+
+            if self.eof && self.charhistory.is_empty() {
+                return Ok(false);
+            }
+
+            // An indented line is still part of the item with the error.
+            if self.expect_layout(TokenType::Newline) &&
+               self.layout.is_empty()
+            {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Parses the lines of a fragment, from the first line with any code
+    /// on it, into a `Prog` without a module declaration or imports.
+    fn parse_fragment(&mut self) -> Result<AST, ParseError> {
+        let mut fragment = new_ast_node(TokenType::Prog);
+
+        // Pretend to be at the end of a line, so that the first line's
+        // indentation is measured like any other's.
+        self.ch = '\n';
+        self.scan_layout()?;
+        self.expect_layout(TokenType::Newline);
+
+        while !self.eof || !self.charhistory.is_empty() {
+            if let Some(line) = self.parse_line(true)? {
+                fragment.add_child(line);
+            } else {
+                break;
+            }
+        }
+
+        propagate_spans(&mut fragment);
+
+        Ok(fragment)
     }
 
-    fn parse_prog(&mut self) -> Result<Option<AST>, String> {
-        let mut prog = new_ast_node(TokenType::Prog, 24);
+    fn parse_prog(&mut self) -> Result<Option<AST>, ParseError> {
+        let mut prog = new_ast_node(TokenType::Prog);
 
         if let Some(mod_decl) = self.parse_mod_decl()? {
             prog.add_child(mod_decl);
@@ -103,8 +378,8 @@ impl Parser {
         Ok(Some(prog))
     }
 
-    fn parse_mod_decl(&mut self) -> Result<Option<AST>, String> {
-        let mut mod_decl = new_ast_node(TokenType::ModDecl, 8);
+    fn parse_mod_decl(&mut self) -> Result<Option<AST>, ParseError> {
+        let mut mod_decl = new_ast_node(TokenType::ModDecl);
 
         if let Some(mod_kwd) = self.parse_module_keyword()? {
             mod_decl.add_child(mod_kwd);
@@ -115,9 +390,10 @@ impl Parser {
         if let Some(mod_name) = self.parse_ident()? {
             mod_decl.add_child(mod_name);
         } else {
-            return Err(
-                "expected name of module to be plain identifier".to_string()
-            );
+            return Err(self.missing(
+                "expected name of module to be plain identifier",
+                vec![TokenType::Ident]
+            ));
         }
 
         self.consume_blanks()?;
@@ -137,10 +413,10 @@ impl Parser {
             if let Some(first_ident) = self.parse_ident()? {
                 mod_decl.add_child(first_ident);
             } else {
-                return Err(
-                    "expected at least one item in module export/hide list"
-                        .to_string()
-                );
+                return Err(self.missing(
+                    "expected at least one item in module export/hide list",
+                    vec![TokenType::Ident]
+                ));
             }
 
             self.consume_blanks()?;
@@ -158,14 +434,17 @@ impl Parser {
         }
 
         if !self.expect_newline()? {
-            Err("expected newline after module declaration".to_string())
+            Err(self.missing(
+                "expected newline after module declaration",
+                vec![TokenType::Newline]
+            ))
         } else {
             Ok(Some(mod_decl))
         }
     }
 
-    fn parse_import(&mut self) -> Result<Option<AST>, String> {
-        let mut import = new_ast_node(TokenType::Import, 10);
+    fn parse_import(&mut self) -> Result<Option<AST>, ParseError> {
+        let mut import = new_ast_node(TokenType::Import);
 
         if let Some(import_kwd) = self.parse_import_keyword()? {
             import.add_child(import_kwd);
@@ -176,9 +455,10 @@ impl Parser {
         if let Some(mod_name) = self.parse_ident()? {
             import.add_child(mod_name);
         } else {
-            return Err(
-                "expected module name after import keyword".to_string()
-            );
+            return Err(self.missing(
+                "expected module name after import keyword",
+                vec![TokenType::Ident]
+            ));
         }
 
         self.consume_blanks()?;
@@ -189,9 +469,10 @@ impl Parser {
             if let Some(qual_name) = self.parse_ident()? {
                 import.add_child(qual_name);
             } else {
-                return Err(
-                    "expected namespace alias after as keyword".to_string()
-                );
+                return Err(self.missing(
+                    "expected namespace alias after as keyword",
+                    vec![TokenType::Ident]
+                ));
             }
         } else {
             if let Some(hiding_kwd) = self.parse_hiding_keyword()? {
@@ -203,46 +484,56 @@ impl Parser {
             if let Some(l_paren) = self.parse_l_paren()? {
                 import.add_child(l_paren);
             } else {
-                return Err(
-                    "expected left paren to start import list".to_string()
-                );
+                return Err(self.missing(
+                    "expected left paren to start import list",
+                    vec![TokenType::LParen]
+                ));
             }
 
+            // The list may be wrapped over several lines.
+            self.consume_whitespace()?;
+
             if let Some(first_import_item) = self.parse_ident()? {
                 import.add_child(first_import_item);
             } else {
-                return Err(
-                    "expected at least one import item in import list"
-                        .to_string()
-                );
+                return Err(self.missing(
+                    "expected at least one import item in import list",
+                    vec![TokenType::Ident]
+                ));
             }
 
-            self.consume_blanks()?;
+            self.consume_whitespace()?;
 
             while let Some(comma) = self.parse_comma()? {
+                self.consume_whitespace()?;
+
                 if let Some(import_item) = self.parse_ident()? {
                     import.add_child(comma);
                     import.add_child(import_item);
 
-                    self.consume_blanks()?;
+                    self.consume_whitespace()?;
                 } else {
                     break;
                 }
             }
 
-            self.consume_blanks()?;
+            self.consume_whitespace()?;
 
             if let Some(r_paren) = self.parse_r_paren()? {
                 import.add_child(r_paren);
             } else {
-                return Err(
-                    "expected right paren to terminate import list".to_string()
-                );
+                return Err(self.missing(
+                    "expected right paren to terminate import list",
+                    vec![TokenType::Comma, TokenType::RParen]
+                ));
             }
         }
 
         if !self.expect_newline()? {
-            Err("expected newline after import statement".to_string())
+            Err(self.missing(
+                "expected newline after import statement",
+                vec![TokenType::Newline]
+            ))
         } else {
             Ok(Some(import))
         }
@@ -251,17 +542,22 @@ impl Parser {
     fn parse_line(
         &mut self,
         consume_newline: bool
-    ) -> Result<Option<AST>, String> {
+    ) -> Result<Option<AST>, ParseError> {
+        if self.expect_layout(TokenType::Indent) {
+            return Err(self.bad_indent("unexpected indentation"));
+        }
+
         self.consume_blanks()?;
 
-        let mut line = new_ast_node(TokenType::Line, 1);
+        let here = self.span_at(self.offset(), 0);
+        ice::set_location(here.line, here.col);
+
+        let mut line = new_ast_node(TokenType::Line);
 
         if let Some(expr) = self.parse_expr()? {
             line.add_child(expr);
         }
 
-        self.consume_line_comment(consume_newline)?;
-
         if consume_newline {
             self.expect_newline()?;
         }
@@ -269,80 +565,246 @@ impl Parser {
         Ok(Some(line))
     }
 
-    fn consume_line_comment(
-        &mut self,
-        consume_newline: bool
-    ) -> Result<bool, String> {
-        self.consume_blanks()?;
+    /// Parses an expression: operators and their operands, or, looser than
+    /// any operator, a range between two of those, `a..b` or `a..=b`.
+    fn parse_expr(&mut self) -> Result<Option<AST>, ParseError> {
+        let lhs = match self.parse_op_expr()? {
+            Some(lhs) => lhs,
+            None      => return Ok(None),
+        };
 
-        if !self.consume_line_comment_op()? {
-            return Ok(false);
+        if !self.layout.is_empty() {
+            return Ok(Some(lhs));
         }
 
-        if is_newline(self.ch) {
-            if consume_newline {
-                self.expect_newline()?;
-            }
+        self.consume_blanks()?;
 
-            return Ok(true);
-        }
+        let range_op = match self.parse_range_op()? {
+            Some(range_op) => range_op,
+            None           => return Ok(Some(lhs)),
+        };
+        let rhs = match self.parse_op_expr()? {
+            Some(rhs) => rhs,
+            None      => return Err(self.missing(
+                "expected the end of the range",
+                vec![TokenType::Expr]
+            )),
+        };
 
-        while let Some(&front_ch) = self.charhistory.front() {
-            self.ch = front_ch;
-            self.charhistory.pop_front();
+        let mut range = new_ast_node(TokenType::Range);
+        range.add_child(lhs);
+        range.add_child(range_op);
+        range.add_child(rhs);
 
-            if is_newline(self.ch) {
-                if consume_newline {
-                    self.expect_newline()?;
+        let mut expr = new_ast_node(TokenType::Expr);
+        expr.add_child(range);
+
+        Ok(Some(expr))
+    }
+
+    /// Parses operators and their operands, grouped by `nest_ops`.
+    fn parse_op_expr(&mut self) -> Result<Option<AST>, ParseError> {
+        self.consume_blanks()?;
+
+        let mut items = Vec::with_capacity(6);
+
+        loop {
+            let mut item = None;
+
+            // After an operand, `-` is subtraction rather than the sign of
+            // a literal: `n - 1` is not `n` applied to `-1`.
+            if items.last().is_some_and(|i| !is_operator(i)) &&
+               self.layout.is_empty()
+            {
+                self.consume_blanks()?;
+
+                if self.ch == '-' {
+                    if let Some(op) = self.parse_op()? {
+                        let mut subexpr = new_ast_node(TokenType::Subexpr);
+                        subexpr.add_child(op);
+
+                        item = Some(subexpr);
+                    }
                 }
+            }
 
-                return Ok(true);
+            if item.is_none() {
+                item = self.parse_subexpr()?;
             }
+
+            if let Some(i) = item {
+                items.push(i);
+            } else {
+                break;
+            }
+        }
+
+        if items.is_empty() {
+            return Ok(None);
+        }
+
+        // A lone operator, as in `(+)`, is just a value.
+        if items.len() == 1 {
+            let mut expr = new_ast_node(TokenType::Expr);
+            expr.add_child(items.remove(0));
+
+            return Ok(Some(expr));
         }
 
-        while let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
+        let mut items = items.into_iter().peekable();
+
+        self.nest_ops(&mut items, 0, None).map(Some)
+    }
+
+    /// Groups the operands and operators of an expression into `BinOp`s
+    /// and `UnaryOp`s by precedence climbing, taking operators no looser
+    /// than `min_prec`. `parent` is the operator whose right operand this
+    /// is, if any, to catch chains that cannot be grouped.
+    fn nest_ops(
+        &self,
+        items:    &mut Peekable<IntoIter<AST>>,
+        min_prec: u8,
+        parent:   Option<(String, Fixity)>
+    ) -> Result<AST, ParseError> {
+        let mut lhs = self.nest_operand(items)?;
+        let mut last = parent;
+
+        while let Some(op) = items.peek() {
+            let name = op_name(&op.children()[0]);
+            let fixity = self.fixity(&name);
+            let span = first_leaf_span(op);
+
+            if fixity.prec < min_prec {
+                break;
+            }
+
+            if let Some((ref last_name, ref last_fixity)) = last {
+                if last_fixity.prec == fixity.prec &&
+                   (last_fixity.assoc != fixity.assoc ||
+                    fixity.assoc == Assoc::NonAssoc)
+                {
+                    return Err(ParseError::MixedFixity {
+                        first:  last_name.clone(),
+                        second: name,
+                        span:   span,
+                    });
+                }
+            }
+
+            let op = if let Some(op) = items.next() {
+                op
+            } else {
+                break;
+            };
+            let rhs_min = if fixity.assoc == Assoc::Right {
+                fixity.prec
+            } else {
+                fixity.prec + 1
             };
+            let rhs = self.nest_ops(
+                items,
+                rhs_min,
+                Some((name.clone(), fixity))
+            )?;
 
-            if is_newline(self.ch) {
-                if consume_newline {
-                    self.expect_newline()?;
+            let mut bin_op = new_ast_node(TokenType::BinOp);
+            bin_op.add_child(lhs);
+            bin_op.add_child(op);
+            bin_op.add_child(rhs);
+
+            lhs = new_ast_node(TokenType::Expr);
+            lhs.add_child(bin_op);
+            last = Some((name, fixity));
+        }
+
+        Ok(lhs)
+    }
+
+    /// Takes the next operand from `items`: a run of subexpressions, the
+    /// first applied to the rest as nested `FnCall`s, or a negation.
+    fn nest_operand(
+        &self,
+        items: &mut Peekable<IntoIter<AST>>
+    ) -> Result<AST, ParseError> {
+        let negation = match items.peek() {
+            Some(item) if is_operator(item) => {
+                if op_name(&item.children()[0]) != "-" {
+                    return Err(ParseError::MissingToken {
+                        message:  "expected an operand before operator",
+                        expected: vec![TokenType::Expr],
+                        span:     first_leaf_span(item),
+                    });
                 }
 
-                return Ok(true);
+                true
+            },
+            Some(_) => false,
+            None    => return Err(self.missing(
+                "expected an operand after operator",
+                vec![TokenType::Expr]
+            )),
+        };
+
+        if negation {
+            let mut unary_op = new_ast_node(TokenType::UnaryOp);
+
+            if let Some(minus) = items.next() {
+                unary_op.add_child(minus);
             }
-        }
 
-        self.eof = true;
+            // Negation binds like binary `-`, so `-a * b` is `-(a * b)`
+            // but `-a + b` is `(-a) + b`.
+            let operand = self.nest_ops(items, 7, None)?;
+            unary_op.add_child(operand);
 
-        Ok(true)
-    }
+            let mut expr = new_ast_node(TokenType::Expr);
+            expr.add_child(unary_op);
 
-    fn parse_expr(&mut self) -> Result<Option<AST>, String> {
-        self.consume_blanks()?;
+            return Ok(expr);
+        }
 
-        let first_subexpr = if let Some(subexpr) = self.parse_subexpr()? {
-            subexpr
-        } else {
-            return Ok(None);
+        // Application is left-associative: `f x y` is `(f x) y`.
+        let mut application = match items.next() {
+            Some(callee) => callee,
+            None         => return Err(self.missing(
+                "expected an operand after operator",
+                vec![TokenType::Expr]
+            )),
         };
 
-        let mut expr = new_ast_node(TokenType::Expr, 6);
-        expr.add_child(first_subexpr);
+        while items.peek().is_some_and(|i| !is_operator(i)) {
+            if let Some(arg) = items.next() {
+                let mut fn_call = new_ast_node(TokenType::FnCall);
+                fn_call.add_child(application);
+                fn_call.add_child(arg);
 
-        while let Some(subexpr) = self.parse_subexpr()? {
-            expr.add_child(subexpr);
+                application = fn_call;
+            }
         }
 
-        Ok(Some(expr))
+        let mut expr = new_ast_node(TokenType::Expr);
+        expr.add_child(application);
+
+        Ok(expr)
     }
 
-    fn parse_subexpr(&mut self) -> Result<Option<AST>, String> {
+    /// The fixity of the operator `name`, as declared earlier in the file
+    /// or else by default.
+    fn fixity(&self, name: &str) -> Fixity {
+        self.fixities
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| default_fixity(name))
+    }
+
+    fn parse_subexpr(&mut self) -> Result<Option<AST>, ParseError> {
+        if !self.layout.is_empty() {
+            return Ok(None);
+        }
+
         self.consume_blanks()?;
 
-        let mut subexpr = new_ast_node(TokenType::Subexpr, 1);
+        let mut subexpr = new_ast_node(TokenType::Subexpr);
 
         if let Some(var) = self.parse_var()? {
             subexpr.add_child(var);
@@ -350,7 +812,9 @@ impl Parser {
             subexpr.add_child(assign);
         } else if let Some(fn_decl) = self.parse_fn_decl()? {
             subexpr.add_child(fn_decl);
-        } else if let Some(parened) = self.parse_parened()? {
+        } else if let Some(fixity_decl) = self.parse_fixity_decl()? {
+            subexpr.add_child(fixity_decl);
+        } else if let Some(parened) = self.bracketed(Parser::parse_parened)? {
             subexpr.add_child(parened);
         } else if let Some(return_) = self.parse_return()? {
             subexpr.add_child(return_);
@@ -366,20 +830,22 @@ impl Parser {
             subexpr.add_child(for_);
         } else if let Some(lambda) = self.parse_lambda()? {
             subexpr.add_child(lambda);
-        } else if let Some(tuple_lit) = self.parse_tuple_lit()? {
-            subexpr.add_child(tuple_lit);
-        } else if let Some(list_lit) = self.parse_list_lit()? {
-            subexpr.add_child(list_lit);
-        } else if let Some(list_comp) = self.parse_list_comp()? {
-            subexpr.add_child(list_comp);
-        } else if let Some(dict_lit) = self.parse_dict_lit()? {
-            subexpr.add_child(dict_lit);
-        } else if let Some(dict_comp) = self.parse_dict_comp()? {
-            subexpr.add_child(dict_comp);
-        } else if let Some(set_lit) = self.parse_set_lit()? {
-            subexpr.add_child(set_lit);
-        } else if let Some(set_comp) = self.parse_set_comp()? {
-            subexpr.add_child(set_comp);
+        } else if let Some(tuple) = self.bracketed(Parser::parse_tuple_lit)? {
+            subexpr.add_child(tuple);
+        } else if let Some(list) = self.bracketed(Parser::parse_list_lit)? {
+            subexpr.add_child(list);
+        } else if let Some(comp) = self.bracketed(Parser::parse_list_comp)? {
+            subexpr.add_child(comp);
+        } else if let Some(dict) = self.bracketed(Parser::parse_dict_lit)? {
+            subexpr.add_child(dict);
+        } else if let Some(comp) = self.bracketed(Parser::parse_dict_comp)? {
+            subexpr.add_child(comp);
+        } else if let Some(set) = self.bracketed(Parser::parse_set_lit)? {
+            subexpr.add_child(set);
+        } else if let Some(comp) = self.bracketed(Parser::parse_set_comp)? {
+            subexpr.add_child(comp);
+        } else if let Some(bool_lit) = self.parse_bool_lit()? {
+            subexpr.add_child(bool_lit);
         } else if let Some(qual_ident) = self.parse_qual_ident()? {
             subexpr.add_child(qual_ident);
         } else if let Some(infixed) = self.parse_infixed()? {
@@ -396,10 +862,88 @@ impl Parser {
             return Ok(None);
         }
 
+        // A `[` right after an operand, with nothing between, indexes it:
+        // `xs[i]`, where `f [i]` applies `f` to a list. A `?` right after
+        // one, and not the start of an operator, returns it if it failed.
+        // What follows the `?` is another operand, so `x?y` is `(x?) y`
+        // while `x ? y` is still the operator `?`.
+        while !(self.eof && self.charhistory.is_empty()) &&
+              is_indexable(&subexpr)
+        {
+            if self.ch == '[' {
+                subexpr = self.parse_index(subexpr)?;
+            } else if self.ch == '?' {
+                let start = self.offset();
+
+                if !self.expect_whole("?", is_op_char)? {
+                    break;
+                }
+
+                let mut propagate = new_ast_node(TokenType::Propagate);
+                propagate.add_child(subexpr);
+                propagate.add_child(self.new_leaf(TokenType::Op, "?", start));
+
+                subexpr = new_ast_node(TokenType::Subexpr);
+                subexpr.add_child(propagate);
+            } else {
+                break;
+            }
+        }
+
         Ok(Some(subexpr))
     }
 
-    fn parse_var(&mut self) -> Result<Option<AST>, String> {
+    /// Parses the `[i]` or `[a:b]` after `indexed`, a `Subexpr`, into a
+    /// `Subexpr` holding the `Index`.
+    fn parse_index(&mut self, indexed: AST) -> Result<AST, ParseError> {
+        let mut index = new_ast_node(TokenType::Index);
+        index.add_child(indexed);
+
+        if let Some(l_sq_bracket) = self.parse_l_sq_bracket()? {
+            index.add_child(l_sq_bracket);
+        }
+
+        let low = self.parse_expr()?;
+        self.consume_blanks()?;
+        let sliced = low.is_none() || self.ch == ':';
+
+        if let Some(low) = low {
+            index.add_child(low);
+        }
+
+        if sliced {
+            if let Some(colon) = self.parse_colon()? {
+                index.add_child(colon);
+            } else {
+                return Err(self.missing(
+                    "expected an index or slice",
+                    vec![TokenType::Expr, TokenType::Colon]
+                ));
+            }
+
+            if let Some(high) = self.parse_expr()? {
+                index.add_child(high);
+            }
+
+            self.consume_blanks()?;
+        }
+
+        if let Some(r_sq_bracket) = self.parse_r_sq_bracket()? {
+            index.add_child(r_sq_bracket);
+        } else {
+            return Err(self.missing(
+                "left square bracket in index requires ]",
+                vec![TokenType::RSqBracket]
+            ));
+        }
+
+        let mut subexpr = new_ast_node(TokenType::Subexpr);
+        subexpr.add_child(index);
+
+        Ok(subexpr)
+    }
+
+    fn parse_var(&mut self) -> Result<Option<AST>, ParseError> {
         let var_keyword = if let Some(var_kwd) = self.parse_var_keyword()? {
             var_kwd
         } else {
@@ -409,15 +953,15 @@ impl Parser {
         let pattern = if let Some(pat) = self.parse_pattern()? {
             pat
         } else {
-            return Err(
-                "left-hand side of var assignment must be a pattern"
-                    .to_string()
-            );
+            return Err(self.missing(
+                "left-hand side of var assignment must be a pattern",
+                vec![TokenType::Pattern]
+            ));
         };
 
         self.consume_blanks()?;
 
-        let mut var = new_ast_node(TokenType::Var, 6);
+        let mut var = new_ast_node(TokenType::Var);
         var.add_child(var_keyword);
         var.add_child(pattern);
 
@@ -426,26 +970,29 @@ impl Parser {
                 var.add_child(colon);
                 var.add_child(type_);
             } else {
-                return Err(
-                    "type of var binding must be a valid type identifier"
-                        .to_string()
-                );
+                return Err(self.missing(
+                    "type of var binding must be a valid type identifier",
+                    vec![TokenType::TypeIdent]
+                ));
             }
         }
 
         let equals = if let Some(eq) = self.parse_equals()? {
             eq
         } else {
-            return Err("var assignment must use =".to_string());
+            return Err(self.missing(
+                "var assignment must use =",
+                vec![TokenType::Equals]
+            ));
         };
 
         let expr = if let Some(xpr) = self.parse_expr()? {
             xpr
         } else {
-            return Err(
-                "right-hand side of var assignment must be a valid expression"
-                    .to_string()
-            );
+            return Err(self.missing(
+                "right-hand side of var assignment must be a valid expression",
+                vec![TokenType::Expr]
+            ));
         };
 
         var.add_child(equals);
@@ -454,25 +1001,35 @@ impl Parser {
         Ok(Some(var))
     }
 
-    fn parse_assign(&mut self) -> Result<Option<AST>, String> {
-        let pattern = if let Some(pat) = self.parse_pattern()? {
-            pat
-        } else {
-            return Ok(None);
+    fn parse_assign(&mut self) -> Result<Option<AST>, ParseError> {
+        self.consume_blanks()?;
+
+        let start = self.offset();
+        let pattern = match self.parse_pattern() {
+            Ok(Some(pat)) => pat,
+            Ok(None)      => return Ok(None),
+            // Not a pattern, as in `[x | x <- xs]`, so not a binding.
+            Err(_)        => {
+                self.unread_from(start);
+
+                return Ok(None);
+            },
         };
 
         self.consume_blanks()?;
 
-        let mut assign = new_ast_node(TokenType::Assign, 5);
-        assign.add_child(pattern.clone());
+        let mut assign = new_ast_node(TokenType::Assign);
+        assign.add_child(pattern);
 
         if let Some(colon) = self.parse_colon()? {
-            let type_ = if let Some(ty) = self.parse_type_ident()? {
-                ty
-            } else {
-                return Err(
-                    "type of binding must be a valid identifier".to_string()
-                );
+            let type_ = match self.parse_type_ident() {
+                Ok(Some(ty)) => ty,
+                // Not a binding, as in the slice `xs[1:5]`.
+                _            => {
+                    self.unread_from(start);
+
+                    return Ok(None);
+                },
             };
 
             assign.add_child(colon);
@@ -484,20 +1041,9 @@ impl Parser {
         let equals = if let Some(eq) = self.parse_equals()? {
             eq
         } else {
-            self.charhistory.push_front(self.ch);
-            self.charhistory.push_front(' ');
-
-            let mut consumed_pattern = str_repr(&pattern);
-
-            while consumed_pattern.len() > 1 {
-                if let Some(consumed_ch) = consumed_pattern.pop() {
-                    self.charhistory.push_front(consumed_ch);
-                }
-            }
-
-            if let Some(c) = consumed_pattern.pop() {
-                self.ch = c;
-            }
+            // Not a binding after all, so it can be parsed again as an
+            // expression.
+            self.unread_from(start);
 
             return Ok(None);
         };
@@ -505,10 +1051,10 @@ impl Parser {
         let expr = if let Some(xpr) = self.parse_expr()? {
             xpr
         } else {
-            return Err(
-                "right-hand side of assignment must be a valid expression"
-                    .to_string()
-            );
+            return Err(self.missing(
+                "right-hand side of assignment must be a valid expression",
+                vec![TokenType::Expr]
+            ));
         };
 
         assign.add_child(equals);
@@ -517,7 +1063,26 @@ impl Parser {
         Ok(Some(assign))
     }
 
-    fn parse_fn_decl(&mut self) -> Result<Option<AST>, String> {
+    /// Puts back exactly what was read since byte offset `start`.
+    fn unread_from(&mut self, start: usize) {
+        let consumed: Vec<char> = self.source[start..self.offset()]
+                                      .chars()
+                                      .collect();
+
+        if consumed.is_empty() {
+            return;
+        }
+
+        self.charhistory.push_front(self.ch);
+
+        for &c in consumed[1..].iter().rev() {
+            self.charhistory.push_front(c);
+        }
+
+        self.ch = consumed[0];
+    }
+
+    fn parse_fn_decl(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let fn_keyword = if let Some(fn_kwd) = self.parse_fn_keyword()? {
@@ -531,12 +1096,15 @@ impl Parser {
         let fn_name = if let Some(f_name) = self.parse_ident()? {
             f_name
         } else {
-            return Err("expected function name".to_string());
+            return Err(self.missing(
+                "expected function name",
+                vec![TokenType::Ident]
+            ));
         };
 
         self.consume_blanks()?;
 
-        let mut fn_decl = new_ast_node(TokenType::FnDecl, 18);
+        let mut fn_decl = new_ast_node(TokenType::FnDecl);
         fn_decl.add_child(fn_keyword);
         fn_decl.add_child(fn_name);
 
@@ -552,39 +1120,128 @@ impl Parser {
             let ret_type = if let Some(ret_ty) = self.parse_qual_ident()? {
                 ret_ty
             } else {
-                return Err("expected type after arrow".to_string());
+                return Err(self.missing(
+                    "expected type after arrow",
+                    vec![TokenType::QualIdent]
+                ));
             };
 
             fn_decl.add_child(ret_type);
         }
 
-        self.get_block(&mut fn_decl, TokenType::Line)?;
+        self.get_body(&mut fn_decl, TokenType::Equals)?;
 
         Ok(Some(fn_decl))
     }
 
-    fn parse_parened(&mut self) -> Result<Option<AST>, String> {
+    fn parse_fixity_decl(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let l_paren = if let Some(l_prn) = self.parse_l_paren()? {
-            l_prn
-        } else {
-            return Ok(None);
-        };
+        let (keyword, assoc) =
+            if let Some(kwd) = self.parse_infixl_keyword()? {
+                (kwd, Assoc::Left)
+            } else if let Some(kwd) = self.parse_infixr_keyword()? {
+                (kwd, Assoc::Right)
+            } else if let Some(kwd) = self.parse_infix_keyword()? {
+                (kwd, Assoc::NonAssoc)
+            } else {
+                return Ok(None);
+            };
 
-        let expr = if let Some(xpr) = self.parse_expr()? {
-            xpr
+        self.consume_blanks()?;
+
+        let start = self.offset();
+        let prec = if let Some(digit) = self.ch.to_digit(10) {
+            self.advance()?;
+
+            digit as u8
         } else {
-            return Err("expected expression within parens".to_string());
+            return Err(self.missing(
+                "expected precedence from 0 to 9 after fixity keyword",
+                vec![TokenType::AbsInt]
+            ));
         };
 
-        let r_paren = if let Some(r_prn) = self.parse_r_paren()? {
+        if self.ch.is_ascii_digit() {
+            return Err(self.missing(
+                "precedence must be from 0 to 9",
+                vec![TokenType::AbsInt]
+            ));
+        }
+
+        self.consume_blanks()?;
+
+        let op = if let Some(o) = self.parse_op()? {
+            o
+        } else if let Some(infixed) = self.parse_infixed()? {
+            infixed
+        } else {
+            return Err(self.missing(
+                "expected operator after precedence",
+                vec![TokenType::Op, TokenType::Infixed]
+            ));
+        };
+
+        self.fixities.insert(op_name(&op), Fixity {
+            prec:  prec,
+            assoc: assoc,
+        });
+
+        let mut fixity_decl = new_ast_node(TokenType::FixityDecl);
+        fixity_decl.add_child(keyword);
+        fixity_decl.add_child(
+            self.new_leaf(TokenType::AbsInt, prec.to_string(), start)
+        );
+        fixity_decl.add_child(op);
+
+        Ok(Some(fixity_decl))
+    }
+
+    fn parse_parened(&mut self) -> Result<Option<AST>, ParseError> {
+        self.consume_blanks()?;
+
+        let start = self.offset();
+        let l_paren = if let Some(l_prn) = self.parse_l_paren()? {
+            l_prn
+        } else {
+            return Ok(None);
+        };
+
+        self.brackets += 1;
+
+        let expr = if let Some(xpr) = self.parse_expr()? {
+            xpr
+        } else if self.ch == ')' {
+            // The empty tuple, which is parsed again as one.
+            self.unread_from(start);
+
+            return Ok(None);
+        } else {
+            return Err(self.missing(
+                "expected expression within parens",
+                vec![TokenType::Expr]
+            ));
+        };
+
+        self.consume_blanks()?;
+
+        // A tuple, which is parsed again as one.
+        if self.ch == ',' {
+            self.unread_from(start);
+
+            return Ok(None);
+        }
+
+        let r_paren = if let Some(r_prn) = self.parse_r_paren()? {
             r_prn
         } else {
-            return Err("expected closing paren".to_string());
+            return Err(self.missing(
+                "expected closing paren",
+                vec![TokenType::RParen]
+            ));
         };
 
-        let mut parened = new_ast_node(TokenType::Parened, 3);
+        let mut parened = new_ast_node(TokenType::Parened);
         parened.add_child(l_paren);
         parened.add_child(expr);
         parened.add_child(r_paren);
@@ -592,7 +1249,7 @@ impl Parser {
         Ok(Some(parened))
     }
 
-    fn parse_return(&mut self) -> Result<Option<AST>, String> {
+    fn parse_return(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let return_keyword =
@@ -605,17 +1262,20 @@ impl Parser {
         let expr = if let Some(xpr) = self.parse_expr()? {
             xpr
         } else {
-            return Err("expected expression to return".to_string());
+            return Err(self.missing(
+                "expected expression to return",
+                vec![TokenType::Expr]
+            ));
         };
 
-        let mut return_ = new_ast_node(TokenType::Return, 2);
+        let mut return_ = new_ast_node(TokenType::Return);
         return_.add_child(return_keyword);
         return_.add_child(expr);
 
         Ok(Some(return_))
     }
 
-    fn parse_case(&mut self) -> Result<Option<AST>, String> {
+    fn parse_case(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let case_keyword = if let Some(case_kwd) = self.parse_case_keyword()? {
@@ -629,10 +1289,13 @@ impl Parser {
         let subject_expr = if let Some(subj_expr) = self.parse_expr()? {
             subj_expr
         } else {
-            return Err("expected subject expression for case".to_string());
+            return Err(self.missing(
+                "expected subject expression for case",
+                vec![TokenType::Expr]
+            ));
         };
 
-        let mut case = new_ast_node(TokenType::Case, 6);
+        let mut case = new_ast_node(TokenType::Case);
         case.add_child(case_keyword);
         case.add_child(subject_expr);
 
@@ -641,7 +1304,7 @@ impl Parser {
         Ok(Some(case))
     }
 
-    fn parse_case_branch(&mut self) -> Result<Option<AST>, String> {
+    fn parse_case_branch(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let pattern = if let Some(pat) = self.parse_pattern()? {
@@ -650,27 +1313,75 @@ impl Parser {
             return Ok(None);
         };
 
+        self.consume_blanks()?;
+
+        let guard = self.parse_guard()?;
+
+        self.consume_blanks()?;
+
         let fat_r_arrow = if let Some(fat_r_arr) = self.parse_fat_r_arrow()? {
             fat_r_arr
         } else {
-            return Err("expected => while parsing case branch".to_string());
+            return Err(self.missing(
+                "expected => while parsing case branch",
+                vec![TokenType::FatRArrow]
+            ));
         };
 
+        let mut case_branch = new_ast_node(TokenType::CaseBranch);
+        case_branch.add_child(pattern);
+        if let Some(guard) = guard {
+            case_branch.add_child(guard);
+        }
+        case_branch.add_child(fat_r_arrow);
+
+        // Nothing but a comment after the `=>` on its line, so a block
+        // below it.
+        self.consume_blanks()?;
+
+        if is_newline(self.ch) {
+            self.get_block(&mut case_branch, TokenType::Line)?;
+
+            return Ok(Some(case_branch));
+        }
+
         let line = if let Some(l) = self.parse_line(false)? {
             l
         } else {
-            return Err("expected expression(s) after =>".to_string());
+            return Err(self.missing(
+                "expected expression(s) after =>",
+                vec![TokenType::Line]
+            ));
         };
-
-        let mut case_branch = new_ast_node(TokenType::CaseBranch, 3);
-        case_branch.add_child(pattern);
-        case_branch.add_child(fat_r_arrow);
         case_branch.add_child(line);
 
         Ok(Some(case_branch))
     }
 
-    fn parse_if_else(&mut self) -> Result<Option<AST>, String> {
+    fn parse_guard(&mut self) -> Result<Option<AST>, ParseError> {
+        let if_keyword = if let Some(if_kwd) = self.parse_if_keyword()? {
+            if_kwd
+        } else {
+            return Ok(None);
+        };
+
+        let cond = if let Some(c) = self.parse_expr()? {
+            c
+        } else {
+            return Err(self.missing(
+                "expected expression as guard",
+                vec![TokenType::Expr]
+            ));
+        };
+
+        let mut guard = new_ast_node(TokenType::Guard);
+        guard.add_child(if_keyword);
+        guard.add_child(cond);
+
+        Ok(Some(guard))
+    }
+
+    fn parse_if_else(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let if_keyword = if let Some(if_kwd) = self.parse_if_keyword()? {
@@ -681,23 +1392,30 @@ impl Parser {
 
         self.consume_blanks()?;
 
-        let if_condition = if let Some(if_cond) = self.parse_expr()? {
+        // `if pattern <- expr` matches rather than tests.
+        let if_condition = if let Some(generator) = self.parse_generator()? {
+            generator
+        } else if let Some(if_cond) = self.parse_expr()? {
             if_cond
         } else {
-            return Err("expected expression as if condition".to_string());
+            return Err(self.missing(
+                "expected expression as if condition",
+                vec![TokenType::Expr]
+            ));
         };
 
-        let mut if_else = new_ast_node(TokenType::IfElse, 9);
+        let mut if_else = new_ast_node(TokenType::IfElse);
         if_else.add_child(if_keyword);
         if_else.add_child(if_condition);
 
-        let start_indent = self.get_block(&mut if_else, TokenType::Line)?;
+        self.get_body(&mut if_else, TokenType::FatRArrow)?;
 
-        if self.currentindent != start_indent {
+        if !self.continues_header_line() {
             return Ok(Some(if_else));
         }
 
         if let Some(else_kwd) = self.parse_else_keyword()? {
+            self.expect_layout(TokenType::Newline);
             if_else.add_child(else_kwd);
         } else {
             return Ok(Some(if_else));
@@ -709,15 +1427,15 @@ impl Parser {
             return Ok(Some(if_else));
         }
 
-        self.get_block(&mut if_else, TokenType::Line)?;
+        self.get_body(&mut if_else, TokenType::FatRArrow)?;
 
         Ok(Some(if_else))
     }
 
-    fn parse_try(&mut self) -> Result<Option<AST>, String> {
+    fn parse_try(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut try = new_ast_node(TokenType::Try, 8);
+        let mut try = new_ast_node(TokenType::Try);
 
         if let Some(try_kwd) = self.parse_try_keyword()? {
             self.consume_blanks()?;
@@ -727,35 +1445,42 @@ impl Parser {
             return Ok(None);
         }
 
-        let start_indent = self.get_block(&mut try, TokenType::Line)?;
+        self.get_body(&mut try, TokenType::FatRArrow)?;
 
-        if self.currentindent != start_indent {
-            return Err(
+        if !self.continues_header_line() {
+            return Err(self.bad_indent(
                 "try must have corresponsing catch on same indent level"
-                    .to_string()
-            );
+            ));
         }
 
         let catch_keyword =
             if let Some(catch_kwd) = self.parse_catch_keyword()? {
+                self.expect_layout(TokenType::Newline);
+
                 catch_kwd
             } else {
-                return Err("try must have corresponding catch".to_string());
+                return Err(self.missing(
+                    "try must have corresponding catch",
+                    vec![TokenType::CatchKeyword]
+                ));
             };
 
         if let Some(exception_ident) = self.parse_ident()? {
             try.add_child(catch_keyword);
             try.add_child(exception_ident);
 
-            self.get_block(&mut try, TokenType::Line)?;
+            self.get_body(&mut try, TokenType::FatRArrow)?;
 
             Ok(Some(try))
         } else {
-            Err("catch must name the caught exception".to_string())
+            Err(self.missing(
+                "catch must name the caught exception",
+                vec![TokenType::Ident]
+            ))
         }
     }
 
-    fn parse_while(&mut self) -> Result<Option<AST>, String> {
+    fn parse_while(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let while_keyword =
@@ -767,20 +1492,29 @@ impl Parser {
 
         self.consume_blanks()?;
 
-        if let Some(while_condition) = self.parse_expr()? {
-            let mut while_ = new_ast_node(TokenType::While, 8);
+        // `while pattern <- expr` loops for as long as the value matches.
+        let condition = match self.parse_generator()? {
+            Some(generator) => Some(generator),
+            None            => self.parse_expr()?,
+        };
+
+        if let Some(while_condition) = condition {
+            let mut while_ = new_ast_node(TokenType::While);
             while_.add_child(while_keyword);
             while_.add_child(while_condition);
 
-            self.get_block(&mut while_, TokenType::Line)?;
+            self.get_body(&mut while_, TokenType::FatRArrow)?;
 
             Ok(Some(while_))
         } else {
-            Err("expected expression as while condition".to_string())
+            Err(self.missing(
+                "expected expression as while condition",
+                vec![TokenType::Expr]
+            ))
         }
     }
 
-    fn parse_for(&mut self) -> Result<Option<AST>, String> {
+    fn parse_for(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let for_keyword = if let Some(for_kwd) = self.parse_for_keyword()? {
@@ -794,9 +1528,10 @@ impl Parser {
         let for_pattern = if let Some(for_pat) = self.parse_pattern()? {
             for_pat
         } else {
-            return Err(
-                "expected pattern as first part of for header".to_string()
-            );
+            return Err(self.missing(
+                "expected pattern as first part of for header",
+                vec![TokenType::Pattern]
+            ));
         };
 
         self.consume_blanks()?;
@@ -804,27 +1539,33 @@ impl Parser {
         let in_keyword = if let Some(in_kwd) = self.parse_in_keyword()? {
             in_kwd
         } else {
-            return Err("missing in keyword of for loop".to_string());
+            return Err(self.missing(
+                "missing in keyword of for loop",
+                vec![TokenType::InKeyword]
+            ));
         };
 
         let iterated = if let Some(itrd) = self.parse_expr()? {
             itrd
         } else {
-            return Err("for must iterate over an expression".to_string());
+            return Err(self.missing(
+                "for must iterate over an expression",
+                vec![TokenType::Expr]
+            ));
         };
 
-        let mut for_ = new_ast_node(TokenType::For, 9);
+        let mut for_ = new_ast_node(TokenType::For);
         for_.add_child(for_keyword);
         for_.add_child(for_pattern);
         for_.add_child(in_keyword);
         for_.add_child(iterated);
 
-        self.get_block(&mut for_, TokenType::Line)?;
+        self.get_body(&mut for_, TokenType::FatRArrow)?;
 
         Ok(Some(for_))
     }
 
-    fn parse_lambda(&mut self) -> Result<Option<AST>, String> {
+    fn parse_lambda(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let backslash = if let Some(bkslsh) = self.parse_backslash()? {
@@ -836,10 +1577,13 @@ impl Parser {
         let first_param = if let Some(fst_param) = self.parse_param()? {
             fst_param
         } else {
-            return Err("lambda expression requires 1+ args".to_string());
+            return Err(self.missing(
+                "lambda expression requires 1+ args",
+                vec![TokenType::Param]
+            ));
         };
 
-        let mut lambda = new_ast_node(TokenType::Lambda, 8);
+        let mut lambda = new_ast_node(TokenType::Lambda);
         lambda.add_child(backslash);
         lambda.add_child(first_param);
 
@@ -859,7 +1603,10 @@ impl Parser {
         let r_arrow = if let Some(r_arr) = self.parse_r_arrow()? {
             r_arr
         } else {
-            return Err("lambda expression requires ->".to_string());
+            return Err(self.missing(
+                "lambda expression requires ->",
+                vec![TokenType::RArrow]
+            ));
         };
 
         if let Some(expr) = self.parse_expr()? {
@@ -868,11 +1615,14 @@ impl Parser {
 
             Ok(Some(lambda))
         } else {
-            Err("lambda body must be expression".to_string())
+            Err(self.missing(
+                "lambda body must be expression",
+                vec![TokenType::Expr]
+            ))
         }
     }
 
-    fn parse_tuple_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_tuple_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let l_paren = if let Some(l_prn) = self.parse_l_paren()? {
@@ -881,7 +1631,9 @@ impl Parser {
             return Ok(None);
         };
 
-        let mut tuple_lit = new_ast_node(TokenType::TupleLit, 9);
+        self.brackets += 1;
+
+        let mut tuple_lit = new_ast_node(TokenType::TupleLit);
         tuple_lit.add_child(l_paren);
 
         self.consume_blanks()?;
@@ -892,17 +1644,19 @@ impl Parser {
             if let Some(first_comma) = self.parse_comma()? {
                 tuple_lit.add_child(first_comma);
             } else {
-                return Err(
-                    "expected comma after first tuple element".to_string()
-                );
+                return Err(self.missing(
+                    "expected comma after first tuple element",
+                    vec![TokenType::Comma]
+                ));
             }
 
             if let Some(second_expr) = self.parse_expr()? {
                 tuple_lit.add_child(second_expr);
             } else {
-                return Err(
-                    "expected 0 or at least 2 elements in tuple".to_string()
-                );
+                return Err(self.missing(
+                    "expected 0 or at least 2 elements in tuple",
+                    vec![TokenType::Expr]
+                ));
             }
 
             self.consume_blanks()?;
@@ -924,17 +1678,22 @@ impl Parser {
 
             Ok(Some(tuple_lit))
         } else {
-            Err("expected right paren to terminate tuple".to_string())
+            Err(self.missing(
+                "expected right paren to terminate tuple",
+                vec![TokenType::Comma, TokenType::RParen]
+            ))
         }
     }
 
-    fn parse_list_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_list_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut list_lit = new_ast_node(TokenType::ListLit, 12);
+        let start = self.offset();
+        let mut list_lit = new_ast_node(TokenType::ListLit);
 
         if let Some(l_sq_bracket) = self.parse_l_sq_bracket()? {
             list_lit.add_child(l_sq_bracket);
+            self.brackets += 1;
         } else {
             return Ok(None);
         }
@@ -944,6 +1703,13 @@ impl Parser {
 
             self.consume_blanks()?;
 
+            // A list comprehension, which is parsed again as one.
+            if self.ch == '|' {
+                self.unread_from(start);
+
+                return Ok(None);
+            }
+
             while let Some(comma) = self.parse_comma()? {
                 if let Some(expr) = self.parse_expr()? {
                     list_lit.add_child(comma);
@@ -961,11 +1727,14 @@ impl Parser {
 
             Ok(Some(list_lit))
         } else {
-            Err("left square bracket in list literal requires ]".to_string())
+            Err(self.missing(
+                "left square bracket in list literal requires ]",
+                vec![TokenType::Comma, TokenType::RSqBracket]
+            ))
         }
     }
 
-    fn parse_list_comp(&mut self) -> Result<Option<AST>, String> {
+    fn parse_list_comp(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let l_sq_bracket =
@@ -975,22 +1744,27 @@ impl Parser {
                 return Ok(None);
             };
 
+        self.brackets += 1;
+
         let expr = if let Some(xpr) = self.parse_expr()? {
             xpr
         } else {
-            return Err(
-                "expected expression on left-hand side of list comprehension"
-                    .to_string()
-            );
+            return Err(self.missing(
+                "expected expression on left-hand side of list comprehension",
+                vec![TokenType::Expr]
+            ));
         };
 
         let bar_ = if let Some(br) = self.parse_bar()? {
             br
         } else {
-            return Err("expected | for list comprehension".to_string());
+            return Err(self.missing(
+                "expected | for list comprehension",
+                vec![TokenType::Bar]
+            ));
         };
 
-        let mut list_comp = new_ast_node(TokenType::ListComp, 12);
+        let mut list_comp = new_ast_node(TokenType::ListComp);
         list_comp.add_child(l_sq_bracket);
         list_comp.add_child(expr);
         list_comp.add_child(bar_);
@@ -1029,24 +1803,46 @@ impl Parser {
 
             Ok(Some(list_comp))
         } else {
-            Err("expected ] to terminate list comprehension".to_string())
+            Err(self.missing(
+                "expected ] to terminate list comprehension",
+                vec![TokenType::Comma, TokenType::RSqBracket]
+            ))
         }
     }
 
-    fn parse_dict_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_dict_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut dict_lit = new_ast_node(TokenType::DictLit, 16);
+        let start = self.offset();
+        let mut dict_lit = new_ast_node(TokenType::DictLit);
 
         if let Some(l_curly_bracket) = self.parse_l_curly_bracket()? {
             dict_lit.add_child(l_curly_bracket);
+            self.brackets += 1;
         } else {
             return Ok(None);
         }
 
-        if let Some(first_entry) = self.parse_dict_entry()? {
+        self.consume_blanks()?;
+
+        // A set, or a comprehension, which is parsed again as one.
+        let first_entry = self.parse_dict_entry()?;
+
+        if first_entry.is_none() && self.ch != '}' {
+            self.unread_from(start);
+
+            return Ok(None);
+        }
+
+        if let Some(first_entry) = first_entry {
             self.consume_blanks()?;
 
+            if self.ch == '|' {
+                self.unread_from(start);
+
+                return Ok(None);
+            }
+
             dict_lit.add_child(first_entry);
 
             while let Some(comma) = self.parse_comma()? {
@@ -1066,13 +1862,17 @@ impl Parser {
 
             Ok(Some(dict_lit))
         } else {
-            Err("left curly bracket in dict literal requires }".to_string())
+            Err(self.missing(
+                "left curly bracket in dict literal requires }",
+                vec![TokenType::Comma, TokenType::RCurlyBracket]
+            ))
         }
     }
 
-    fn parse_dict_comp(&mut self) -> Result<Option<AST>, String> {
+    fn parse_dict_comp(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
+        let start = self.offset();
         let l_curly_bracket =
             if let Some(l_curly_bckt) = self.parse_l_curly_bracket()? {
                 l_curly_bckt
@@ -1080,22 +1880,27 @@ impl Parser {
                 return Ok(None);
             };
 
+        self.brackets += 1;
+
         let dict_entry = if let Some(dict_ent) = self.parse_dict_entry()? {
             dict_ent
         } else {
-            return Err(
-                "expected entry on left-hand side of dict comprehension"
-                    .to_string()
-            );
+            // A set, or a set comprehension, which is parsed again as one.
+            self.unread_from(start);
+
+            return Ok(None);
         };
 
         let bar_ = if let Some(br) = self.parse_bar()? {
             br
         } else {
-            return Err("expected | for dict comprehension".to_string());
+            return Err(self.missing(
+                "expected | for dict comprehension",
+                vec![TokenType::Bar]
+            ));
         };
 
-        let mut dict_comp = new_ast_node(TokenType::DictComp, 12);
+        let mut dict_comp = new_ast_node(TokenType::DictComp);
         dict_comp.add_child(l_curly_bracket);
         dict_comp.add_child(dict_entry);
         dict_comp.add_child(bar_);
@@ -1132,17 +1937,22 @@ impl Parser {
 
             Ok(Some(dict_comp))
         } else {
-            Err("expected } to terminate dict comprehension".to_string())
+            Err(self.missing(
+                "expected } to terminate dict comprehension",
+                vec![TokenType::Comma, TokenType::RCurlyBracket]
+            ))
         }
     }
 
-    fn parse_set_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_set_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut set_lit = new_ast_node(TokenType::SetLit, 12);
+        let start = self.offset();
+        let mut set_lit = new_ast_node(TokenType::SetLit);
 
         if let Some(l_curly_bracket) = self.parse_l_curly_bracket()? {
             set_lit.add_child(l_curly_bracket);
+            self.brackets += 1;
         } else {
             return Ok(None);
         }
@@ -1150,6 +1960,13 @@ impl Parser {
         if let Some(first_expr) = self.parse_expr()? {
             self.consume_blanks()?;
 
+            // A set comprehension, which is parsed again as one.
+            if self.ch == '|' {
+                self.unread_from(start);
+
+                return Ok(None);
+            }
+
             set_lit.add_child(first_expr);
 
             while let Some(comma) = self.parse_comma()? {
@@ -1169,11 +1986,14 @@ impl Parser {
 
             Ok(Some(set_lit))
         } else {
-            Err("left curly bracket in set literal requires }".to_string())
+            Err(self.missing(
+                "left curly bracket in set literal requires }",
+                vec![TokenType::Comma, TokenType::RCurlyBracket]
+            ))
         }
     }
 
-    fn parse_set_comp(&mut self) -> Result<Option<AST>, String> {
+    fn parse_set_comp(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let l_curly_bracket =
@@ -1183,22 +2003,27 @@ impl Parser {
                 return Ok(None);
             };
 
+        self.brackets += 1;
+
         let expr = if let Some(xpr) = self.parse_expr()? {
             xpr
         } else {
-            return Err(
-                "expected expression on left-hand side of set comprehension"
-                    .to_string()
-            );
+            return Err(self.missing(
+                "expected expression on left-hand side of set comprehension",
+                vec![TokenType::Expr]
+            ));
         };
 
         let bar_ = if let Some(br) = self.parse_bar()? {
             br
         } else {
-            return Err("expected | for set comprehension".to_string());
+            return Err(self.missing(
+                "expected | for set comprehension",
+                vec![TokenType::Bar]
+            ));
         };
 
-        let mut set_comp = new_ast_node(TokenType::SetComp, 12);
+        let mut set_comp = new_ast_node(TokenType::SetComp);
         set_comp.add_child(l_curly_bracket);
         set_comp.add_child(expr);
         set_comp.add_child(bar_);
@@ -1235,29 +2060,32 @@ impl Parser {
 
             Ok(Some(set_comp))
         } else {
-            Err("expected } to terminate set comprehension".to_string())
+            Err(self.missing(
+                "expected } to terminate set comprehension",
+                vec![TokenType::Comma, TokenType::RCurlyBracket]
+            ))
         }
     }
 
-    fn parse_qual_ident(&mut self) -> Result<Option<AST>, String> {
+    fn parse_qual_ident(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         if let Some(member_ident) = self.parse_member_ident()? {
-            let mut qual_ident = new_ast_node(TokenType::QualIdent, 1);
+            let mut qual_ident = new_ast_node(TokenType::QualIdent);
             qual_ident.add_child(member_ident);
 
             return Ok(Some(qual_ident));
         }
 
         if let Some(scoped_ident) = self.parse_scoped_ident()? {
-            let mut qual_ident = new_ast_node(TokenType::QualIdent, 1);
+            let mut qual_ident = new_ast_node(TokenType::QualIdent);
             qual_ident.add_child(scoped_ident);
 
             return Ok(Some(qual_ident));
         }
 
         if let Some(ident) = self.parse_ident()? {
-            let mut qual_ident = new_ast_node(TokenType::QualIdent, 1);
+            let mut qual_ident = new_ast_node(TokenType::QualIdent);
             qual_ident.add_child(ident);
 
             return Ok(Some(qual_ident));
@@ -1266,24 +2094,20 @@ impl Parser {
         Ok(None)
     }
 
-    fn parse_namespaced_ident(&mut self) -> Result<Option<AST>, String> {
+    fn parse_namespaced_ident(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         if let Some(scoped_ident) = self.parse_scoped_ident()? {
-            let mut namespaced_ident = new_ast_node(
-                TokenType::NamespacedIdent,
-                1
-            );
+            let mut namespaced_ident =
+                new_ast_node(TokenType::NamespacedIdent);
             namespaced_ident.add_child(scoped_ident);
 
             return Ok(Some(namespaced_ident));
         }
 
         if let Some(ident) = self.parse_ident()? {
-            let mut namespaced_ident = new_ast_node(
-                TokenType::NamespacedIdent,
-                1
-            );
+            let mut namespaced_ident =
+                new_ast_node(TokenType::NamespacedIdent);
             namespaced_ident.add_child(ident);
 
             return Ok(Some(namespaced_ident));
@@ -1292,13 +2116,24 @@ impl Parser {
         Ok(None)
     }
 
-    fn parse_ident(&mut self) -> Result<Option<AST>, String> {
+    fn parse_ident(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         if self.ch != '_' && !self.ch.is_alphabetic() {
             return Ok(None);
         }
 
+        let start = self.offset();
+
+        let reserved = match self.keyword_here() {
+            Some(TokenType::TrueKeyword)  => Some("True"),
+            Some(TokenType::FalseKeyword) => Some("False"),
+            _                             => None,
+        };
+
+        if let Some(word) = reserved {
+            return Err(self.reserved_word(word, start));
+        }
         let mut id = String::with_capacity(16);
 
         if self.ch == '_' {
@@ -1321,10 +2156,10 @@ impl Parser {
             }
         }
 
-        Ok(Some(new_ast_leaf(TokenType::Ident, id)))
+        Ok(Some(self.new_leaf(TokenType::Ident, id, start)))
     }
 
-    fn parse_member_ident(&mut self) -> Result<Option<AST>, String> {
+    fn parse_member_ident(&mut self) -> Result<Option<AST>, ParseError> {
         let first_ident = if let Some(fst_ident) = self.parse_ident()? {
             fst_ident
         } else {
@@ -1332,18 +2167,19 @@ impl Parser {
         };
 
         if let Some(dot) = self.parse_dot()? {
-            if let Some(second_ident) = self.parse_ident()? {
-                let mut member_ident = new_ast_node(TokenType::MemberIdent, 3);
-                member_ident.add_child(first_ident);
-                member_ident.add_child(dot);
-                member_ident.add_child(second_ident);
+            let mut member_ident = new_ast_node(TokenType::MemberIdent);
+            member_ident.add_child(first_ident);
+            member_ident.add_child(dot);
+            member_ident.add_child(self.parse_member()?);
 
-                Ok(Some(member_ident))
-            } else {
-                Err("expected identifier after dot operator".to_string())
+            while let Some(dot) = self.parse_dot()? {
+                member_ident.add_child(dot);
+                member_ident.add_child(self.parse_member()?);
             }
+
+            Ok(Some(member_ident))
         } else {
-            let mut first_ident_lex = first_ident.val().lexeme.clone();
+            let mut first_ident_lex = first_ident.val().lexeme().to_string();
 
             self.charhistory.push_front(self.ch);
 
@@ -1361,7 +2197,7 @@ impl Parser {
         }
     }
 
-    fn parse_scoped_ident(&mut self) -> Result<Option<AST>, String> {
+    fn parse_scoped_ident(&mut self) -> Result<Option<AST>, ParseError> {
         let first_ident = if let Some(fst_ident) = self.parse_ident()? {
             fst_ident
         } else {
@@ -1369,18 +2205,19 @@ impl Parser {
         };
 
         if let Some(double_colon) = self.parse_double_colon()? {
-            if let Some(second_ident) = self.parse_ident()? {
-                let mut scoped_ident = new_ast_node(TokenType::ScopedIdent, 3);
-                scoped_ident.add_child(first_ident);
-                scoped_ident.add_child(double_colon);
-                scoped_ident.add_child(second_ident);
+            let mut scoped_ident = new_ast_node(TokenType::ScopedIdent);
+            scoped_ident.add_child(first_ident);
+            scoped_ident.add_child(double_colon);
+            scoped_ident.add_child(self.parse_scoped()?);
 
-                Ok(Some(scoped_ident))
-            } else {
-                Err("expected identifier after dot operator".to_string())
+            while let Some(double_colon) = self.parse_double_colon()? {
+                scoped_ident.add_child(double_colon);
+                scoped_ident.add_child(self.parse_scoped()?);
             }
+
+            Ok(Some(scoped_ident))
         } else {
-            let mut first_ident_lex = first_ident.val().lexeme.clone();
+            let mut first_ident_lex = first_ident.val().lexeme().to_string();
 
             self.charhistory.push_front(self.ch);
 
@@ -1398,16 +2235,40 @@ impl Parser {
         }
     }
 
-    fn parse_type_ident(&mut self) -> Result<Option<AST>, String> {
+    /// The identifier after a dot in a member identifier.
+    fn parse_member(&mut self) -> Result<AST, ParseError> {
+        if let Some(ident) = self.parse_ident()? {
+            Ok(ident)
+        } else {
+            Err(self.missing(
+                "expected identifier after dot operator",
+                vec![TokenType::Ident]
+            ))
+        }
+    }
+
+    /// The identifier after a `::` in a scoped identifier.
+    fn parse_scoped(&mut self) -> Result<AST, ParseError> {
+        if let Some(ident) = self.parse_ident()? {
+            Ok(ident)
+        } else {
+            Err(self.missing(
+                "expected identifier after ::",
+                vec![TokenType::Ident]
+            ))
+        }
+    }
+
+    fn parse_type_ident(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         if let Some(namespaced_ident) = self.parse_namespaced_ident()? {
-            let mut type_ident = new_ast_node(TokenType::TypeIdent, 1);
+            let mut type_ident = new_ast_node(TokenType::TypeIdent);
             type_ident.add_child(namespaced_ident);
 
             Ok(Some(type_ident))
         } else if let Some(l_paren) = self.parse_l_paren()? {
-            let mut type_ident = new_ast_node(TokenType::TypeIdent, 7);
+            let mut type_ident = new_ast_node(TokenType::TypeIdent);
             type_ident.add_child(l_paren);
 
             if let Some(first_ident) = self.parse_type_ident()? {
@@ -1416,20 +2277,20 @@ impl Parser {
                 let first_comma = if let Some(fst_cma) = self.parse_comma()? {
                     fst_cma
                 } else {
-                    return Err(
-                        "expected comma after first type tuple element"
-                            .to_string()
-                    );
+                    return Err(self.missing(
+                        "expected comma after first type tuple element",
+                        vec![TokenType::Comma]
+                    ));
                 };
 
                 let second_ident =
                     if let Some(snd_ident) = self.parse_type_ident()? {
                         snd_ident
                     } else {
-                        return Err(
-                            "expected 0 or at least 2 elements in type tuple"
-                                .to_string()
-                        );
+                        return Err(self.missing(
+                            "expected 0 or at least 2 elements in type tuple",
+                            vec![TokenType::TypeIdent]
+                        ));
                     };
 
                 type_ident.add_child(first_ident);
@@ -1453,9 +2314,10 @@ impl Parser {
             if let Some(r_paren) = self.parse_r_paren()? {
                 type_ident.add_child(r_paren);
             } else {
-                return Err(
-                    "expected right paren to terminate type tuple".to_string()
-                );
+                return Err(self.missing(
+                    "expected right paren to terminate type tuple",
+                    vec![TokenType::Comma, TokenType::RParen]
+                ));
             }
 
             Ok(Some(type_ident))
@@ -1463,17 +2325,23 @@ impl Parser {
             let ident = if let Some(id) = self.parse_type_ident()? {
                 id
             } else {
-                return Err("expected type identifier after [".to_string());
+                return Err(self.missing(
+                    "expected type identifier after [",
+                    vec![TokenType::TypeIdent]
+                ));
             };
 
             let r_sq_bracket =
                 if let Some(r_sq_bckt) = self.parse_r_sq_bracket()? {
                     r_sq_bckt
                 } else {
-                    return Err("expected closing ] of list type".to_string());
+                    return Err(self.missing(
+                        "expected closing ] of list type",
+                        vec![TokenType::RSqBracket]
+                    ));
                 };
 
-            let mut type_ident = new_ast_node(TokenType::TypeIdent, 3);
+            let mut type_ident = new_ast_node(TokenType::TypeIdent);
             type_ident.add_child(l_sq_bracket);
             type_ident.add_child(ident);
             type_ident.add_child(r_sq_bracket);
@@ -1483,12 +2351,15 @@ impl Parser {
             let ident = if let Some(id) = self.parse_type_ident()? {
                 id
             } else {
-                return Err("expected type identifier after {".to_string());
+                return Err(self.missing(
+                    "expected type identifier after {",
+                    vec![TokenType::TypeIdent]
+                ));
             };
 
             self.consume_blanks()?;
 
-            let mut type_ident = new_ast_node(TokenType::TypeIdent, 5);
+            let mut type_ident = new_ast_node(TokenType::TypeIdent);
             type_ident.add_child(l_curly_bracket);
             type_ident.add_child(ident);
 
@@ -1497,7 +2368,10 @@ impl Parser {
                     type_ident.add_child(comma);
                     type_ident.add_child(second_ident);
                 } else {
-                    return Err("expected type identifier after ,".to_string());
+                    return Err(self.missing(
+                        "expected type identifier after ,",
+                        vec![TokenType::TypeIdent]
+                    ));
                 }
             }
 
@@ -1506,16 +2380,20 @@ impl Parser {
 
                 Ok(Some(type_ident))
             } else {
-                Err("expected closing } of dict/set type".to_string())
+                Err(self.missing(
+                    "expected closing } of dict/set type",
+                    vec![TokenType::Comma, TokenType::RCurlyBracket]
+                ))
             }
         } else {
             Ok(None)
         }
     }
 
-    fn parse_op(&mut self) -> Result<Option<AST>, String> {
+    fn parse_op(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
+        let start = self.offset();
         let mut op = String::with_capacity(4);
 
         while let Some(op_char) = self.expect_char_op()? {
@@ -1523,56 +2401,85 @@ impl Parser {
         }
 
         if op.is_empty() {
+            Ok(None)
+        } else if op == "=>" {
+            // Ends the header of an inline body, as in `if c => x`.
+            if !self.eof {
+                self.charhistory.push_front(self.ch);
+            }
+
+            self.charhistory.push_front('>');
+            self.ch = '=';
+
+            Ok(None)
+        } else if op == "|" || op == ":" {
+            // Ends the element of a comprehension, as in `[x | x <- xs]`,
+            // or the start of a slice, as in `xs[1:5]`.
+            if !self.eof {
+                self.charhistory.push_front(self.ch);
+            }
+
+            self.ch = op.chars().next().unwrap_or('|');
+
             Ok(None)
         } else if is_reserved_op(&op) {
-            Err(format!("the operator {} is reserved", op))
+            let span = self.span_at(start, op.len());
+
+            Err(ParseError::ReservedOp {
+                op:   op,
+                span: span,
+            })
         } else {
-            Ok(Some(new_ast_leaf(TokenType::Op, op)))
+            Ok(Some(self.new_leaf(TokenType::Op, op, start)))
         }
     }
 
-    fn parse_num_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_num_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let mut minus = None;
+        let mut start = self.offset();
 
         if self.expect_op("-")? {
-            minus = Some(new_ast_leaf(TokenType::Minus, "-"));
+            minus = Some(self.new_leaf(TokenType::Minus, "-", start));
 
             self.consume_blanks()?;
+            start = self.offset();
         }
 
-        if self.expect_keyword("NaN")? {
-            let mut num_lit = new_ast_node(TokenType::NumLit, 1);
-            let mut real_lit = new_ast_node(TokenType::RealLit, 2);
+        if self.expect_keyword(TokenType::NanKeyword)? {
+            let mut num_lit = new_ast_node(TokenType::NumLit);
+            let mut real_lit = new_ast_node(TokenType::RealLit);
 
             if let Some(m) = minus {
                 real_lit.add_child(m);
             }
 
-            real_lit.add_child(new_ast_leaf(TokenType::NanKeyword, "NaN"));
+            real_lit.add_child(
+                self.new_leaf(TokenType::NanKeyword, "NaN", start)
+            );
             num_lit.add_child(real_lit);
 
             return Ok(Some(num_lit));
         }
 
-        if self.expect_keyword("Infinity")? {
-            let mut num_lit = new_ast_node(TokenType::NumLit, 1);
-            let mut real_lit = new_ast_node(TokenType::RealLit, 2);
+        if self.expect_keyword(TokenType::InfinityKeyword)? {
+            let mut num_lit = new_ast_node(TokenType::NumLit);
+            let mut real_lit = new_ast_node(TokenType::RealLit);
 
             if let Some(m) = minus {
                 real_lit.add_child(m);
             }
 
             real_lit.add_child(
-                new_ast_leaf(TokenType::InfinityKeyword, "Infinity")
+                self.new_leaf(TokenType::InfinityKeyword, "Infinity", start)
             );
             num_lit.add_child(real_lit);
 
             return Ok(Some(num_lit));
         }
 
-        if !self.ch.is_digit(10) {
+        if !self.ch.is_ascii_digit() {
             if minus.is_some() {
                 //self.charhistory.push_front(' ');
                 self.charhistory.push_front(self.ch);
@@ -1582,61 +2489,115 @@ impl Parser {
             return Ok(None);
         }
 
-        let mut s = String::with_capacity(10);
+        let scanned = {
+            let upcoming = self.upcoming_line();
 
-        while self.ch.is_digit(10) {
-            s.push(self.ch);
+            lexer::number_len(&upcoming).map(|len| upcoming[..len].to_owned())
+        };
+        let literal = match scanned {
+            Ok(literal) => literal,
+            Err(bad)    => return Err(self.bad_number(start, bad)),
+        };
 
-            if self.advance()? {
-                break;
-            }
+        for _ in 0..literal.len() {
+            self.advance()?;
         }
 
-        if self.ch != '.' {
-            let mut num_lit = new_ast_node(TokenType::NumLit, 1);
-            let mut int_lit = new_ast_node(TokenType::IntLit, 2);
+        let mut num_lit = new_ast_node(TokenType::NumLit);
+
+        // The lexemes leave out underscores, and write an exponent as `e`,
+        // an optional `-`, and its digits, so that they can be parsed as
+        // they are; their spans are of the literal as written.
+        if let Some(prefix) = literal.get(..2).filter(|p| {
+            lexer::radix(p).is_some()
+        }) {
+            let digits: String = literal[2..].chars()
+                                             .filter(|&c| c != '_')
+                                             .collect();
+            let mut int_lit = new_ast_node(TokenType::IntLit);
 
             if let Some(m) = minus {
                 int_lit.add_child(m);
             }
 
-            int_lit.add_child(new_ast_leaf(TokenType::AbsInt, s));
+            int_lit.add_child(self.new_leaf(TokenType::Radix, prefix, start));
+            int_lit.add_child(self.normalized_leaf(
+                TokenType::AbsInt,
+                digits,
+                start + 2,
+                literal.len() - 2
+            ));
             num_lit.add_child(int_lit);
 
             return Ok(Some(num_lit));
         }
 
-        s.push(self.ch);
-        self.advance()?;
+        let s: String = literal.chars()
+                               .filter(|&c| c != '_' && c != '+')
+                               .map(|c| if c == 'E' { 'e' } else { c })
+                               .collect();
+        let real = s.contains(['.', 'e']);
 
-        if !self.ch.is_digit(10) {
-            return Err(
-                "expected at least one digit after decimal point".to_string()
-            );
+        let (lit_type, abs_type) = if real {
+            (TokenType::RealLit, TokenType::AbsReal)
+        } else {
+            (TokenType::IntLit, TokenType::AbsInt)
+        };
+
+        let mut lit = new_ast_node(lit_type);
+
+        if let Some(m) = minus {
+            lit.add_child(m);
         }
 
-        while self.ch.is_digit(10) {
-            s.push(self.ch);
+        lit.add_child(self.normalized_leaf(abs_type, s, start, literal.len()));
+        num_lit.add_child(lit);
 
-            if self.advance()? {
-                break;
+        Ok(Some(num_lit))
+    }
+
+    /// The error for the malformed number literal at byte offset `start`
+    /// that `lexer::number_len` found, at the character it went wrong at.
+    fn bad_number(
+        &mut self,
+        start: usize,
+        bad:   lexer::BadNumber
+    ) -> ParseError {
+        let expected = vec![TokenType::AbsInt];
+
+        while self.offset() < start + bad.at {
+            match self.advance() {
+                Ok(false) => {},
+                Ok(true)  => break,
+                Err(e)    => return e,
             }
         }
 
-        let mut num_lit = new_ast_node(TokenType::NumLit, 1);
-        let mut real_lit = new_ast_node(TokenType::RealLit, 2);
-
-        if let Some(m) = minus {
-            real_lit.add_child(m);
+        if bad.unexpected {
+            self.unexpected(bad.message, expected)
+        } else {
+            self.missing(bad.message, expected)
         }
+    }
 
-        real_lit.add_child(new_ast_leaf(TokenType::AbsReal, s));
-        num_lit.add_child(real_lit);
+    fn parse_bool_lit(&mut self) -> Result<Option<AST>, ParseError> {
+        self.consume_blanks()?;
 
-        Ok(Some(num_lit))
+        let keyword = if let Some(true_) = self.parse_true_keyword()? {
+            true_
+        } else if let Some(false_) = self.parse_false_keyword()? {
+            false_
+        } else {
+            return Ok(None);
+        };
+
+        let mut bool_lit = new_ast_node(TokenType::BoolLit);
+        bool_lit.add_child(keyword);
+
+        Ok(Some(bool_lit))
     }
 
-    fn parse_chr_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_chr_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let init_single_quote =
@@ -1649,16 +2610,22 @@ impl Parser {
         let the_char = if let Some(ch_ch) = self.parse_chr_chr()? {
             ch_ch
         } else {
-            return Err("unexpected ' or EOF".to_string());
+            return Err(self.missing(
+                "expected character after '",
+                vec![TokenType::ChrChr]
+            ));
         };
 
         let end_single_quote = if let Some(s_qt) = self.parse_single_quote()? {
             s_qt
         } else {
-            return Err(format!("expected ', got: {}", self.ch));
+            return Err(self.unexpected(
+                "expected closing '",
+                vec![TokenType::SingleQuote]
+            ));
         };
 
-        let mut chr_lit = new_ast_node(TokenType::ChrLit, 3);
+        let mut chr_lit = new_ast_node(TokenType::ChrLit);
         chr_lit.add_child(init_single_quote);
         chr_lit.add_child(the_char);
         chr_lit.add_child(end_single_quote);
@@ -1666,10 +2633,16 @@ impl Parser {
         Ok(Some(chr_lit))
     }
 
-    fn parse_str_lit(&mut self) -> Result<Option<AST>, String> {
+    fn parse_str_lit(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut str_lit = new_ast_node(TokenType::StrLit, 16);
+        let start = self.offset();
+
+        if self.expect_whole("\"\"\"", |_| false)? {
+            return self.parse_text_block(start).map(Some);
+        }
+
+        let mut str_lit = new_ast_node(TokenType::StrLit);
 
         if let Some(init_double_quote) = self.parse_double_quote()? {
             str_lit.add_child(init_double_quote);
@@ -1686,11 +2659,82 @@ impl Parser {
 
             Ok(Some(str_lit))
         } else {
-            Err(format!("expected \", got: {}", self.ch))
+            Err(self.unexpected(
+                "expected closing \"",
+                vec![TokenType::DoubleQuote]
+            ))
+        }
+    }
+
+    /// Parses the rest of a triple-quoted string, after its `"""` at byte
+    /// offset `start`, leaving out what `lexer::text_block_skips` says is
+    /// not part of it.
+    fn parse_text_block(&mut self, start: usize) -> Result<AST, ParseError> {
+        let mut str_lit = new_ast_node(TokenType::StrLit);
+        str_lit.add_child(
+            self.new_leaf(TokenType::TripleQuote, "\"\"\"", start)
+        );
+
+        let body = self.offset();
+        let len = if self.eof && self.charhistory.is_empty() {
+            None
+        } else {
+            lexer::text_block_len(&self.source[body..])
+        };
+        let end = match len {
+            Some(len) => body + len,
+            None      => {
+                while !(self.eof && self.charhistory.is_empty()) {
+                    self.advance()?;
+                }
+
+                return Err(self.missing(
+                    "expected closing \"\"\"",
+                    vec![TokenType::TripleQuote]
+                ));
+            },
+        };
+        let skips = lexer::text_block_skips(&self.source[body..end]);
+
+        while self.offset() < end {
+            let at = self.offset() - body;
+
+            if skips.iter().any(|skip| skip.contains(&at)) {
+                self.advance()?;
+
+                continue;
+            }
+
+            // Quotes and line breaks need no escape here.
+            if self.ch == '"' || is_newline(self.ch) {
+                let str_chr = self.new_leaf(
+                    TokenType::StrChr,
+                    self.ch.to_string(),
+                    self.offset()
+                );
+                self.advance()?;
+
+                str_lit.add_child(str_chr);
+            } else if let Some(str_chr) = self.parse_str_chr()? {
+                str_lit.add_child(str_chr);
+            } else {
+                return Err(self.unexpected(
+                    "expected escaped character",
+                    vec![TokenType::StrChr]
+                ));
+            }
         }
+
+        let close = self.offset();
+        self.expect_whole("\"\"\"", |_| false)?;
+        str_lit.add_child(
+            self.new_leaf(TokenType::TripleQuote, "\"\"\"", close)
+        );
+
+        Ok(str_lit)
     }
 
-    fn parse_infixed(&mut self) -> Result<Option<AST>, String> {
+    fn parse_infixed(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         let first_backtick = if let Some(bcktck) = self.parse_backtick()? {
@@ -1702,16 +2746,22 @@ impl Parser {
         let ident = if let Some(id) = self.parse_qual_ident()? {
             id
         } else {
-            return Err("expected identifier after `".to_string());
+            return Err(self.missing(
+                "expected identifier after `",
+                vec![TokenType::QualIdent]
+            ));
         };
 
         let second_backtick = if let Some(bcktck) = self.parse_backtick()? {
             bcktck
         } else {
-            return Err("expected closing `".to_string());
+            return Err(self.missing(
+                "expected closing `",
+                vec![TokenType::Backtick]
+            ));
         };
 
-        let mut infixed = new_ast_node(TokenType::Infixed, 3);
+        let mut infixed = new_ast_node(TokenType::Infixed);
         infixed.add_child(first_backtick);
         infixed.add_child(ident);
         infixed.add_child(second_backtick);
@@ -1719,15 +2769,87 @@ impl Parser {
         Ok(Some(infixed))
     }
 
-    fn parse_pattern(&mut self) -> Result<Option<AST>, String> {
+    fn parse_pattern(&mut self) -> Result<Option<AST>, ParseError> {
+        let first = if let Some(alt) = self.parse_alternative()? {
+            alt
+        } else {
+            return Ok(None);
+        };
+
+        let mut alternatives = Vec::new();
+
+        loop {
+            self.consume_blanks()?;
+
+            let start = self.offset();
+
+            // Not the start of `||`, or any other operator.
+            if !self.expect_whole("|", is_op_char)? {
+                break;
+            }
+
+            let bar = self.new_leaf(TokenType::Bar, "|", start);
+
+            if let Some(alternative) = self.parse_alternative()? {
+                alternatives.push((bar, alternative));
+            } else {
+                // Not an or-pattern, but maybe the end of the element of a
+                // comprehension, as in `[x | even x]`.
+                self.unread_from(start);
+
+                break;
+            }
+        }
+
+        if alternatives.is_empty() {
+            return Ok(Some(first));
+        }
+
+        let mut pattern = new_ast_node(TokenType::Pattern);
+        pattern.add_child(first);
+
+        for (bar, alternative) in alternatives {
+            pattern.add_child(bar);
+            pattern.add_child(alternative);
+        }
+
+        Ok(Some(pattern))
+    }
+
+    /// Parses a pattern that is not an or-pattern, though one may be within
+    /// it, between brackets. `@` and `++` bind tighter than `|`, so
+    /// `n @ 0 | 1` is `(n @ 0) | 1`.
+    fn parse_alternative(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
-        let mut pattern = new_ast_node(TokenType::Pattern, 8);
+        let mut pattern = new_ast_node(TokenType::Pattern);
 
-        if let Some(ident) = self.parse_ident()? {
-            pattern.add_child(ident);
+        if let Some(bool_lit) = self.parse_bool_lit()? {
+            pattern.add_child(bool_lit);
 
             Ok(Some(pattern))
+        } else if let Some(ident) = self.parse_ident()? {
+            pattern.add_child(ident);
+
+            self.consume_blanks()?;
+
+            if let Some(at) = self.parse_at()? {
+                let matched = if let Some(pat) = self.parse_alternative()? {
+                    pat
+                } else {
+                    return Err(self.missing(
+                        "expected pattern after @",
+                        vec![TokenType::Pattern]
+                    ));
+                };
+
+                pattern.add_child(at);
+                pattern.add_child(matched);
+
+                return Ok(Some(pattern));
+            }
+
+            self.parse_suffix_pattern(pattern).map(Some)
         } else if let Some(chr_lit) = self.parse_chr_lit()? {
             pattern.add_child(chr_lit);
 
@@ -1735,15 +2857,45 @@ impl Parser {
         } else if let Some(str_lit) = self.parse_str_lit()? {
             pattern.add_child(str_lit);
 
+            self.consume_blanks()?;
+
+            let concat_start = self.offset();
+
+            if let Some(concat) = self.parse_concat_op()? {
+                if let Some(rest) = self.parse_alternative()? {
+                    pattern.add_child(concat);
+                    pattern.add_child(rest);
+                } else {
+                    // Not a prefix pattern, but maybe the start of an
+                    // expression, as in `"a" ++ (b)`.
+                    self.unread_from(concat_start);
+                }
+            }
+
             Ok(Some(pattern))
         } else if let Some(num_lit) = self.parse_num_lit()? {
             pattern.add_child(num_lit);
 
+            self.consume_blanks()?;
+
+            let range_start = self.offset();
+
+            if let Some(range_op) = self.parse_range_op()? {
+                if let Some(high) = self.parse_num_lit()? {
+                    pattern.add_child(range_op);
+                    pattern.add_child(high);
+                } else {
+                    // Not a range pattern, but maybe the start of a range
+                    // expression, as in `0..n`.
+                    self.unread_from(range_start);
+                }
+            }
+
             Ok(Some(pattern))
         } else if let Some(underscore) = self.parse_underscore()? {
             pattern.add_child(underscore);
 
-            Ok(Some(pattern))
+            self.parse_suffix_pattern(pattern).map(Some)
         } else if let Some(l_paren) = self.parse_l_paren()? {
             pattern.add_child(l_paren);
 
@@ -1751,19 +2903,19 @@ impl Parser {
                 let first_comma = if let Some(cma) = self.parse_comma()? {
                     cma
                 } else {
-                    return Err(
-                        "expected comma after first element of pattern tuple"
-                            .to_string()
-                    );
+                    return Err(self.missing(
+                        "expected comma after first element of pattern tuple",
+                        vec![TokenType::Comma]
+                    ));
                 };
 
                 let second_pattern = if let Some(pat) = self.parse_pattern()? {
                     pat
                 } else {
-                    return Err(
-                        "expected 0 or at least 2 elements in pattern tuple"
-                            .to_string()
-                    );
+                    return Err(self.missing(
+                        "expected 0 or at least 2 elements in pattern tuple",
+                        vec![TokenType::Pattern]
+                    ));
                 };
 
                 pattern.add_child(first_pattern);
@@ -1789,7 +2941,10 @@ impl Parser {
 
                 Ok(Some(pattern))
             } else {
-                Err("left paren in pattern requires )".to_string())
+                Err(self.missing(
+                    "left paren in pattern requires )",
+                    vec![TokenType::Comma, TokenType::RParen]
+                ))
             }
         } else if let Some(l_sq_bracket) = self.parse_l_sq_bracket()? {
             pattern.add_child(l_sq_bracket);
@@ -1816,7 +2971,10 @@ impl Parser {
 
                 Ok(Some(pattern))
             } else {
-                Err("left square bracket in pattern requires ]".to_string())
+                Err(self.missing(
+                    "left square bracket in pattern requires ]",
+                    vec![TokenType::Comma, TokenType::RSqBracket]
+                ))
             }
         } else if let Some(l_curly_bracket) = self.parse_l_curly_bracket()? {
             pattern.add_child(l_curly_bracket);
@@ -1829,10 +2987,11 @@ impl Parser {
                         if let Some(fst_val) = self.parse_pattern()? {
                             fst_val
                         } else {
-                            return Err(
-                                "expected value pattern after \
-                                 first = of dict pattern".to_string(),
-                            );
+                            return Err(self.missing(
+                                "expected value pattern after first = of \
+                                 dict pattern",
+                                vec![TokenType::Pattern]
+                            ));
                         };
 
                     pattern.add_child(first_key);
@@ -1849,19 +3008,20 @@ impl Parser {
                                 if let Some(eq) = self.parse_equals()? {
                                     eq
                                 } else {
-                                    return Err(
-                                        "expected = after key of dict pattern"
-                                            .to_string()
-                                    );
+                                    return Err(self.missing(
+                                        "expected = after key of dict pattern",
+                                        vec![TokenType::Equals]
+                                    ));
                                 };
 
                             let val = if let Some(v) = self.parse_pattern()? {
                                 v
                             } else {
-                                return Err(
-                                    "expected value pattern after = \
-                                     of dict pattern".to_string(),
-                                );
+                                return Err(self.missing(
+                                    "expected value pattern after = of dict \
+                                     pattern",
+                                    vec![TokenType::Pattern]
+                                ));
                             };
 
                             pattern.add_child(comma);
@@ -1897,46 +3057,155 @@ impl Parser {
 
                 Ok(Some(pattern))
             } else {
-                Err("left curly bracket in pattern requires }".to_string())
+                Err(self.missing(
+                    "left curly bracket in pattern requires }",
+                    vec![TokenType::Comma, TokenType::RCurlyBracket]
+                ))
             }
         } else {
             Ok(None)
         }
     }
 
-    fn parse_chr_chr(&mut self) -> Result<Option<AST>, String> {
+    /// Parses the `++ "suffix"` that may follow `rest`, a pattern that is a
+    /// name or `_`, into a suffix pattern, or else gives back `rest`.
+    fn parse_suffix_pattern(&mut self, rest: AST) -> Result<AST, ParseError> {
+        self.consume_blanks()?;
+
+        let start = self.offset();
+        let concat = match self.parse_concat_op()? {
+            Some(concat) => concat,
+            None         => return Ok(rest),
+        };
+        let suffix = match self.parse_str_lit()? {
+            Some(suffix) => suffix,
+            None         => {
+                // Not a suffix pattern, but maybe the start of an
+                // expression, as in `s ++ t`.
+                self.unread_from(start);
+
+                return Ok(rest);
+            },
+        };
+
+        let mut pattern = new_ast_node(TokenType::Pattern);
+        pattern.add_child(rest);
+        pattern.add_child(concat);
+        pattern.add_child(suffix);
+
+        Ok(pattern)
+    }
+
+    fn parse_chr_chr(&mut self) -> Result<Option<AST>, ParseError> {
+        let start = self.offset();
+
         if let Some(char_) = self.expect_char_not_chr_ctrl()? {
-            Ok(Some(new_ast_leaf(TokenType::ChrChr, char_.to_string())))
+            Ok(Some(
+                self.new_leaf(TokenType::ChrChr, char_.to_string(), start)
+            ))
         } else if !self.expect_char('\\')? {
             Ok(None)
+        } else if let Some(code_escape) = self.parse_code_escape(start)? {
+            Ok(Some(code_escape))
         } else if let Some(esc_char) = self.expect_char_esc()? {
             let mut escaped = String::with_capacity(2);
             escaped.push('\\');
             escaped.push(esc_char);
 
-            Ok(Some(new_ast_leaf(TokenType::ChrChr, escaped)))
+            Ok(Some(self.new_leaf(TokenType::ChrChr, escaped, start)))
         } else {
             Ok(None)
         }
     }
 
-    fn parse_str_chr(&mut self) -> Result<Option<AST>, String> {
+    fn parse_str_chr(&mut self) -> Result<Option<AST>, ParseError> {
+        let start = self.offset();
+
         if let Some(char_) = self.expect_char_not_str_ctrl()? {
-            Ok(Some(new_ast_leaf(TokenType::StrChr, char_.to_string())))
+            Ok(Some(
+                self.new_leaf(TokenType::StrChr, char_.to_string(), start)
+            ))
         } else if !self.expect_char('\\')? {
             Ok(None)
+        } else if let Some(code_escape) = self.parse_code_escape(start)? {
+            Ok(Some(code_escape))
         } else if let Some(esc_char) = self.expect_char_esc()? {
             let mut escaped = String::with_capacity(2);
             escaped.push('\\');
             escaped.push(esc_char);
 
-            Ok(Some(new_ast_leaf(TokenType::StrChr, escaped)))
+            Ok(Some(self.new_leaf(TokenType::StrChr, escaped, start)))
         } else {
             Ok(None)
         }
     }
 
-    fn parse_param(&mut self) -> Result<Option<AST>, String> {
+    /// Parses the rest of a `\x41` or `\u{1F600}` escape, after its `\`,
+    /// which is at byte offset `start`.
+    fn parse_code_escape(
+        &mut self,
+        start: usize
+    ) -> Result<Option<AST>, ParseError> {
+        let (type_, message) = match self.ch {
+            'x' => (TokenType::HexEscape, "expected two hex digits after \\x"),
+            'u' => (
+                TokenType::UnicodeEscape,
+                "expected hex digits in braces after \\u"
+            ),
+            _   => return Ok(None),
+        };
+
+        let mut escape = String::with_capacity(10);
+        escape.push('\\');
+        escape.push(self.ch);
+        self.advance()?;
+
+        let is_hex: fn(char) -> bool = |c| c.is_ascii_hexdigit();
+
+        if type_ == TokenType::HexEscape {
+            self.escape_char(&mut escape, is_hex, message, &type_)?;
+            self.escape_char(&mut escape, is_hex, message, &type_)?;
+        } else {
+            self.escape_char(&mut escape, |c| c == '{', message, &type_)?;
+
+            while self.ch != '}' {
+                self.escape_char(&mut escape, is_hex, message, &type_)?;
+            }
+
+            self.escape_char(&mut escape, |c| c == '}', message, &type_)?;
+        }
+
+        if lexer::code_escape(&escape).is_none() {
+            return Err(ParseError::BadEscape {
+                span:   self.span_at(start, escape.len()),
+                escape: escape,
+            });
+        }
+
+        Ok(Some(self.new_leaf(type_, escape, start)))
+    }
+
+    /// Consumes `self.ch` onto the end of `escape` if it is what `wanted`
+    /// accepts. Otherwise fails with `message`, as a part of an escape of
+    /// type `type_` that is missing.
+    fn escape_char(
+        &mut self,
+        escape:  &mut String,
+        wanted:  fn(char) -> bool,
+        message: &'static str,
+        type_:   &TokenType
+    ) -> Result<(), ParseError> {
+        if (self.eof && self.charhistory.is_empty()) || !wanted(self.ch) {
+            return Err(self.missing(message, vec![type_.clone()]));
+        }
+
+        escape.push(self.ch);
+        self.advance()?;
+
+        Ok(())
+    }
+
+    fn parse_param(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
         if let Some(l_paren) = self.parse_l_paren()? {
@@ -1957,16 +3226,22 @@ impl Parser {
             let type_ident = if let Some(ty_id) = self.parse_type_ident()? {
                 ty_id
             } else {
-                return Err("expected type".to_string());
+                return Err(self.missing(
+                    "expected type",
+                    vec![TokenType::TypeIdent]
+                ));
             };
 
             let r_paren = if let Some(r_prn) = self.parse_r_paren()? {
                 r_prn
             } else {
-                return Err("expected ) after type".to_string());
+                return Err(self.missing(
+                    "expected ) after type",
+                    vec![TokenType::RParen]
+                ));
             };
 
-            let mut param = new_ast_node(TokenType::Param, 5);
+            let mut param = new_ast_node(TokenType::Param);
             param.add_child(l_paren);
             param.add_child(pattern);
             param.add_child(colon);
@@ -1975,7 +3250,7 @@ impl Parser {
 
             Ok(Some(param))
         } else if let Some(pattern) = self.parse_pattern()? {
-            let mut param = new_ast_node(TokenType::Param, 1);
+            let mut param = new_ast_node(TokenType::Param);
             param.add_child(pattern);
 
             Ok(Some(param))
@@ -1984,47 +3259,48 @@ impl Parser {
         }
     }
 
-    fn parse_generator(&mut self) -> Result<Option<AST>, String> {
-        let pattern = if let Some(pat) = self.parse_pattern()? {
-            pat
-        } else {
-            return Ok(None);
+    fn parse_generator(&mut self) -> Result<Option<AST>, ParseError> {
+        self.consume_blanks()?;
+
+        let start = self.offset();
+        let pattern = match self.parse_pattern() {
+            Ok(Some(pat)) => pat,
+            Ok(None)      => return Ok(None),
+            // Not a pattern, so a condition rather than a generator.
+            Err(_)        => {
+                self.unread_from(start);
+
+                return Ok(None);
+            },
         };
 
+        self.consume_blanks()?;
+
         if let Some(l_arrow) = self.parse_l_arrow()? {
             if let Some(expr) = self.parse_expr()? {
-                let mut generator = new_ast_node(TokenType::Generator, 3);
+                let mut generator = new_ast_node(TokenType::Generator);
                 generator.add_child(pattern);
                 generator.add_child(l_arrow);
                 generator.add_child(expr);
 
                 Ok(Some(generator))
             } else {
-                Err("expected expression after <-".to_string())
+                Err(self.missing(
+                    "expected expression after <-",
+                    vec![TokenType::Expr]
+                ))
             }
         } else {
-            self.charhistory.push_front(self.ch);
-            self.charhistory.push_front(' ');
-
-            let mut consumed_pattern = str_repr(&pattern);
-
-            while consumed_pattern.len() > 1 {
-                if let Some(consumed_pattern_pop) = consumed_pattern.pop() {
-                    self.charhistory.push_front(consumed_pattern_pop);
-                }
-            }
-
-            if let Some(last_consumed_ch) = consumed_pattern.pop() {
-                self.ch = last_consumed_ch;
-            }
+            self.unread_from(start);
 
             Ok(None)
         }
     }
 
-    fn parse_dict_entry(&mut self) -> Result<Option<AST>, String> {
+    fn parse_dict_entry(&mut self) -> Result<Option<AST>, ParseError> {
         self.consume_blanks()?;
 
+        let start = self.offset();
         let key = if let Some(ky) = self.parse_expr()? {
             ky
         } else {
@@ -2036,18 +3312,22 @@ impl Parser {
         let equals = if let Some(eq) = self.parse_equals()? {
             eq
         } else {
+            // An element of a set, which is parsed again as one.
+            self.unread_from(start);
+
             return Ok(None);
         };
 
         let val = if let Some(vl) = self.parse_expr()? {
             vl
         } else {
-            return Err(
-                "expected expression to be assigned to dict key".to_string()
-            );
+            return Err(self.missing(
+                "expected expression to be assigned to dict key",
+                vec![TokenType::Expr]
+            ));
         };
 
-        let mut dict_entry = new_ast_node(TokenType::DictEntry, 3);
+        let mut dict_entry = new_ast_node(TokenType::DictEntry);
         dict_entry.add_child(key);
         dict_entry.add_child(equals);
         dict_entry.add_child(val);
@@ -2055,311 +3335,398 @@ impl Parser {
         Ok(Some(dict_entry))
     }
 
-    fn parse_equals(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('=')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Equals, "=")))
-        }
+    fn parse_equals(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Equals)
     }
 
-    fn parse_single_quote(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('\'')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::SingleQuote, "'")))
-        }
+    fn parse_single_quote(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::SingleQuote)
     }
 
-    fn parse_double_quote(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('"')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::DoubleQuote, "\"")))
-        }
+    fn parse_double_quote(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::DoubleQuote)
     }
 
-    fn parse_fn_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("fn")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::FnKeyword, "fn")))
-        }
+    fn parse_fn_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::FnKeyword)
     }
 
-    fn parse_case_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("case")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::CaseKeyword, "case")))
-        }
+    fn parse_case_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::CaseKeyword)
     }
 
-    fn parse_if_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("if")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::IfKeyword, "if")))
-        }
+    fn parse_if_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::IfKeyword)
     }
 
-    fn parse_else_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("else")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ElseKeyword, "else")))
-        }
+    fn parse_else_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ElseKeyword)
     }
 
-    fn parse_try_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("try")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::TryKeyword, "try")))
-        }
+    fn parse_try_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::TryKeyword)
     }
 
-    fn parse_catch_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("catch")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::CatchKeyword, "catch")))
-        }
+    fn parse_catch_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::CatchKeyword)
     }
 
-    fn parse_while_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("while")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::WhileKeyword, "while")))
-        }
+    fn parse_while_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::WhileKeyword)
     }
 
-    fn parse_for_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("for")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ForKeyword, "for")))
-        }
+    fn parse_for_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ForKeyword)
     }
 
-    fn parse_in_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("in")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::InKeyword, "in")))
-        }
+    fn parse_in_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::InKeyword)
     }
 
-    fn parse_var_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("var")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::VarKeyword, "var")))
-        }
+    fn parse_var_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::VarKeyword)
     }
 
-    fn parse_module_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("module")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ModuleKeyword, "module")))
-        }
+    fn parse_module_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ModuleKeyword)
     }
 
-    fn parse_exposing_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("exposing")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ExposingKeyword, "exposing")))
-        }
+    fn parse_exposing_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ExposingKeyword)
     }
 
-    fn parse_hiding_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("hiding")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::HidingKeyword, "hiding")))
-        }
+    fn parse_hiding_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::HidingKeyword)
     }
 
-    fn parse_import_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("import")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ImportKeyword, "import")))
-        }
+    fn parse_import_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ImportKeyword)
     }
 
-    fn parse_as_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("as")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::AsKeyword, "as")))
-        }
+    fn parse_as_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::AsKeyword)
     }
 
-    fn parse_return_keyword(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("return")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::ReturnKeyword, "return")))
-        }
+    fn parse_return_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::ReturnKeyword)
     }
 
-    fn consume_line_comment_op(&mut self) -> Result<bool, String> {
-        self.expect_op("--")
+    fn parse_infixl_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::InfixlKeyword)
     }
 
-    fn parse_dot(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op(".")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Dot, ".")))
-        }
+    fn parse_infixr_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::InfixrKeyword)
     }
 
-    fn parse_comma(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char(',')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Comma, ",")))
-        }
+    fn parse_infix_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::InfixKeyword)
     }
 
-    fn parse_colon(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op(":")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Colon, ":")))
-        }
+    fn parse_true_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::TrueKeyword)
     }
 
-    fn parse_double_colon(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op("::")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::DoubleColon, "::")))
-        }
+    fn parse_false_keyword(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::FalseKeyword)
     }
 
-    fn parse_underscore(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_keyword("_")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Underscore, "_")))
+    /// Parses the `..` or `..=` between the ends of a range.
+    fn parse_range_op(&mut self) -> Result<Option<AST>, ParseError> {
+        match self.parse_fixed(TokenType::DotDotEquals)? {
+            Some(dot_dot_equals) => Ok(Some(dot_dot_equals)),
+            None                 => self.parse_fixed(TokenType::DotDot),
         }
     }
 
-    fn parse_l_arrow(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op("<-")? {
-            Ok(None)
+    /// Parses the `++` between the parts of a prefix or suffix pattern.
+    fn parse_concat_op(&mut self) -> Result<Option<AST>, ParseError> {
+        let start = self.offset();
+
+        if self.expect_op("++")? {
+            Ok(Some(self.new_leaf(TokenType::Op, "++", start)))
         } else {
-            Ok(Some(new_ast_leaf(TokenType::LArrow, "<-")))
+            Ok(None)
         }
     }
 
-    fn parse_r_arrow(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op("->")? {
-            Ok(None)
+    fn parse_dot(&mut self) -> Result<Option<AST>, ParseError> {
+        let start = self.offset();
+
+        // Not the start of a `..` or `..=`, which is the middle of a range.
+        if self.expect_whole(".", |c| c == '.' || is_op_char(c))? {
+            Ok(Some(self.new_leaf(TokenType::Dot, ".", start)))
         } else {
-            Ok(Some(new_ast_leaf(TokenType::RArrow, "->")))
+            Ok(None)
         }
     }
 
-    fn parse_fat_r_arrow(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_op("=>")? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::FatRArrow, "=>")))
-        }
+    fn parse_comma(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Comma)
     }
 
-    fn parse_l_paren(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('(')? {
-            Ok(None)
+    fn parse_colon(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Colon)
+    }
+
+    fn parse_double_colon(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::DoubleColon)
+    }
+
+    fn parse_underscore(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Underscore)
+    }
+
+    fn parse_l_arrow(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::LArrow)
+    }
+
+    fn parse_r_arrow(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::RArrow)
+    }
+
+    fn parse_fat_r_arrow(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::FatRArrow)
+    }
+
+    fn parse_l_paren(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::LParen)
+    }
+
+    fn parse_r_paren(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::RParen)
+    }
+
+    fn parse_l_sq_bracket(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::LSqBracket)
+    }
+
+    fn parse_r_sq_bracket(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::RSqBracket)
+    }
+
+    fn parse_l_curly_bracket(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::LCurlyBracket)
+    }
+
+    fn parse_r_curly_bracket(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::RCurlyBracket)
+    }
+
+    fn parse_backslash(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Backslash)
+    }
+
+    fn parse_bar(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Bar)
+    }
+
+    fn parse_backtick(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::Backtick)
+    }
+
+    fn parse_at(&mut self) -> Result<Option<AST>, ParseError> {
+        self.parse_fixed(TokenType::At)
+    }
+
+    /// Parses a token that is always spelled the same, like a keyword or
+    /// a bracket, into a leaf of type `type_`.
+    #[inline]
+    fn parse_fixed(
+        &mut self,
+        type_: TokenType
+    ) -> Result<Option<AST>, ParseError> {
+        let (text, munch) = fixed_token(&type_);
+        let start = self.offset();
+
+        if !text.starts_with(self.ch) {
+            return Ok(None);
+        }
+
+        let matched = match munch {
+            Munch::Char => {
+                self.advance()?;
+
+                true
+            },
+            Munch::Word  => self.expect_keyword(type_.clone())?,
+            Munch::Punct => self.expect_whole(text, |_| false)?,
+            Munch::Op    => self.expect_op(text)?,
+        };
+
+        if matched {
+            Ok(Some(self.new_leaf(type_, text, start)))
         } else {
-            Ok(Some(new_ast_leaf(TokenType::LParen, "(")))
+            Ok(None)
         }
     }
 
-    fn parse_r_paren(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char(')')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::RParen, ")")))
+    fn consume_line_comment_op(&mut self) -> Result<bool, ParseError> {
+        self.expect_op("--")
+    }
+
+    /// Reads the next character of the source, noting where each line
+    /// starts so that spans can be worked out.
+    #[inline]
+    fn read_char(&mut self) -> Option<Result<char, ParseError>> {
+        if let Some(e) = self.read_error.take() {
+            return Some(Err(ParseError::Io(e)));
+        }
+
+        let c = self.source[self.read_to..].chars().next()?;
+        self.read_to += c.len_utf8();
+
+        if c == '\n' {
+            self.line_starts.push(self.read_to);
         }
+
+        Some(Ok(c))
     }
 
-    fn parse_l_sq_bracket(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('[')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::LSqBracket, "[")))
+    /// The byte offset of `self.ch`: everything read from the source, less
+    /// whatever is waiting in `self.charhistory`.
+    fn offset(&self) -> usize {
+        let pending = self.charhistory
+                          .iter()
+                          .fold(self.ch.len_utf8(), |n, c| n + c.len_utf8());
+
+        self.read_to.saturating_sub(pending)
+    }
+
+    /// The span of `len` bytes starting at byte offset `start`.
+    fn span_at(&self, start: usize, len: usize) -> Span {
+        let line_ix = match self.line_starts.binary_search(&start) {
+            Ok(ix)  => ix,
+            Err(ix) => ix - 1,
+        };
+        let line_start = self.line_starts[line_ix];
+        let col = match self.source.get(line_start..start) {
+            Some(before) => before.chars().count(),
+            None         => start - line_start,
+        };
+
+        Span {
+            line:        line_ix + 1,
+            col:         col + 1,
+            byte_offset: start,
+            len:         len,
         }
     }
 
-    fn parse_r_sq_bracket(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char(']')? {
-            Ok(None)
+    /// An error for a required part of a construct, described by `message`,
+    /// that should have started at the current character.
+    fn missing(
+        &self,
+        message:  &'static str,
+        expected: Vec<TokenType>
+    ) -> ParseError {
+        let span = self.span_at(self.offset(), 0);
+
+        if self.eof && self.charhistory.is_empty() {
+            ParseError::UnexpectedEof {
+                message:  message,
+                expected: expected,
+                span:     span,
+            }
         } else {
-            Ok(Some(new_ast_leaf(TokenType::RSqBracket, "]")))
+            ParseError::MissingToken {
+                message:  message,
+                expected: expected,
+                span:     span,
+            }
         }
     }
 
-    fn parse_l_curly_bracket(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('{')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::LCurlyBracket, "{")))
+    /// Like `missing`, but blames the current character itself.
+    fn unexpected(
+        &self,
+        message:  &'static str,
+        expected: Vec<TokenType>
+    ) -> ParseError {
+        if self.eof && self.charhistory.is_empty() {
+            return self.missing(message, expected);
+        }
+
+        ParseError::UnexpectedChar {
+            found:    self.ch,
+            expected: expected,
+            span:     self.span_at(self.offset(), self.ch.len_utf8()),
         }
     }
 
-    fn parse_r_curly_bracket(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('}')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::RCurlyBracket, "}")))
+    /// An error for a line indented wrongly, at the current character.
+    fn bad_indent(&self, message: &'static str) -> ParseError {
+        ParseError::BadIndent {
+            message: message,
+            span:    self.span_at(self.offset(), 0),
         }
     }
 
-    fn parse_backslash(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('\\')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Backslash, "\\")))
+    /// An error for the reserved word `word`, starting at byte offset
+    /// `start`, where a name should be.
+    fn reserved_word(&self, word: &str, start: usize) -> ParseError {
+        ParseError::ReservedWord {
+            word: word.to_string(),
+            span: self.span_at(start, word.len()),
         }
     }
 
-    fn parse_bar(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('|')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Bar, "|")))
+    /// Creates a leaf like `new_ast_leaf`, whose lexeme starts at byte
+    /// offset `start`. The leaf shares the source if the lexeme is the
+    /// text there, as it should be.
+    fn new_leaf<S: AsRef<str>>(
+        &self,
+        token_type: TokenType,
+        s:          S,
+        start:      usize
+    ) -> AST {
+        let s = s.as_ref();
+        let span = self.span_at(start, s.len());
+
+        if self.source.get(start..span.end()) == Some(s) {
+            let token = Token::in_source(token_type, &self.source, span);
+
+            return AST::new(token, 0);
         }
+
+        let mut leaf = new_ast_leaf(token_type, s);
+        leaf.val_mut().span = span;
+
+        leaf
     }
 
-    fn parse_backtick(&mut self) -> Result<Option<AST>, String> {
-        if !self.expect_char('`')? {
-            Ok(None)
-        } else {
-            Ok(Some(new_ast_leaf(TokenType::Backtick, "`")))
+    /// Creates a leaf like `new_leaf` whose lexeme, `s`, is a normalized
+    /// form of the `len` bytes of source at byte offset `start`, such as a
+    /// number with the underscores left out.
+    fn normalized_leaf<S: AsRef<str>>(
+        &self,
+        token_type: TokenType,
+        s:          S,
+        start:      usize,
+        len:        usize
+    ) -> AST {
+        let s = s.as_ref();
+
+        if s.len() == len {
+            return self.new_leaf(token_type, s, start);
         }
+
+        let mut leaf = new_ast_leaf(token_type, s);
+        leaf.val_mut().span = self.span_at(start, len);
+
+        leaf
     }
 
     /// Returns `true` when the EOF is reached and `self.charhistory` is
     /// consumed, otherwise returns `false`.
     #[inline]
-    fn advance(&mut self) -> Result<bool, String> {
+    fn advance(&mut self) -> Result<bool, ParseError> {
         if let Some(first_history) = self.charhistory.pop_front() {
             self.ch = first_history;
 
             Ok(self.charhistory.is_empty() && self.eof)
-        } else if let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
+        } else if let Some(temp_ch) = self.read_char() {
+            self.ch = temp_ch?;
 
             Ok(false)
         } else {
@@ -2369,12 +3736,61 @@ impl Parser {
         }
     }
 
+    /// Runs `parse`, which parses something in brackets, and then forgets
+    /// any brackets it opened. Line breaks, and line comments before them,
+    /// are blanks within the brackets, as the lexer has them; once `parse`
+    /// has returned, they end lines again. A syntax error leaves the
+    /// brackets open, for `Items` to see that it was within them.
+    fn bracketed(
+        &mut self,
+        parse: fn(&mut Parser) -> Result<Option<AST>, ParseError>
+    ) -> Result<Option<AST>, ParseError> {
+        let brackets = self.brackets;
+        let parsed = parse(self)?;
+        self.brackets = brackets;
+
+        Ok(parsed)
+    }
+
+    /// Skips blanks and comments, as the lexer does, and, within brackets
+    /// (see `bracketed`), line breaks. Returns whether there were any.
     #[inline]
-    fn consume_blanks(&mut self) -> Result<bool, String> {
-        if !is_blank(self.ch) {
-            return Ok(false);
+    fn consume_blanks(&mut self) -> Result<bool, ParseError> {
+        let mut consumed = false;
+
+        loop {
+            if is_blank(self.ch) {
+                consumed = true;
+
+                if !self.consume_blank_run()? {
+                    return Ok(true);
+                }
+            } else if self.ch == '{' && self.consume_block_comment()? {
+                consumed = true;
+            } else if self.brackets > 0 && is_newline(self.ch) {
+                consumed = true;
+
+                if self.advance()? {
+                    return Ok(true);
+                }
+            } else if self.ch == '-' && self.consume_line_comment_op()? {
+                consumed = true;
+
+                while !is_newline(self.ch) {
+                    if self.advance()? {
+                        return Ok(true);
+                    }
+                }
+            } else {
+                return Ok(consumed);
+            }
         }
+    }
 
+    /// Skips the blanks from `self.ch` on. Returns `false` if they run to
+    /// the end of the file.
+    #[inline]
+    fn consume_blank_run(&mut self) -> Result<bool, ParseError> {
         while let Some(first_history) = self.charhistory.pop_front() {
             self.ch = first_history;
 
@@ -2383,11 +3799,13 @@ impl Parser {
             }
         }
 
-        while let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
+        // Blanks are never line breaks, so there are no line starts to
+        // note among them.
+        let rest = &self.source.as_bytes()[self.read_to..];
+        self.read_to += scan::blank_run(rest);
+
+        while let Some(temp_ch) = self.read_char() {
+            self.ch = temp_ch?;
 
             if !is_blank(self.ch) {
                 return Ok(true);
@@ -2396,55 +3814,165 @@ impl Parser {
 
         self.eof = true;
 
-        Ok(true)
+        Ok(false)
     }
 
-    fn expect_newline(&mut self) -> Result<bool, String> {
-        self.consume_blanks()?;
-
-        if !is_newline(self.ch) {
+    /// Skips the block comment starting at `self.ch`, if there is one, and
+    /// any nested in it.
+    fn consume_block_comment(&mut self) -> Result<bool, ParseError> {
+        if !self.expect_whole("{-", |_| false)? {
             return Ok(false);
         }
 
-        while let Some(first_history) = self.charhistory.pop_front() {
-            self.ch = first_history;
+        let mut depth = 1;
 
-            if is_newline(self.ch) {
-                self.currentindent.clear();
-            } else if is_blank(self.ch) {
-                self.currentindent.push(self.ch);
+        while depth > 0 {
+            if self.eof && self.charhistory.is_empty() {
+                return Err(self.missing(
+                    "expected -} to close block comment",
+                    Vec::new()
+                ));
+            }
+
+            if self.expect_whole("{-", |_| false)? {
+                depth += 1;
+            } else if self.expect_whole("-}", |_| false)? {
+                depth -= 1;
             } else {
-                return Ok(true);
+                self.advance()?;
             }
         }
 
-        while let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
+        Ok(true)
+    }
 
-            if is_newline(self.ch) {
-                self.currentindent.clear();
-            } else if is_blank(self.ch) {
-                self.currentindent.push(self.ch);
-            } else {
-                return Ok(true);
+    /// Like `consume_blanks`, but skips line breaks as well, for within
+    /// brackets that may be wrapped over several lines.
+    fn consume_whitespace(&mut self) -> Result<(), ParseError> {
+        while is_blank(self.ch) || is_newline(self.ch) {
+            if self.advance()? {
+                break;
             }
         }
 
-        self.eof = true;
+        Ok(())
+    }
 
-        if is_newline(self.ch) {
-            self.currentindent.clear();
+    /// Consumes a `Newline` layout token, first scanning past the end of
+    /// the current line if no layout tokens are waiting.
+    fn expect_newline(&mut self) -> Result<bool, ParseError> {
+        if self.layout.is_empty() && !self.scan_layout()? {
+            return Ok(false);
         }
 
-        Ok(true)
+        Ok(self.expect_layout(TokenType::Newline))
     }
 
-    fn expect_char(&mut self, c: char) -> Result<bool, String> {
-        if self.ch != c {
-            Ok(false)
+    /// Consumes the next waiting layout token if it is a `type_`.
+    #[inline]
+    fn expect_layout(&mut self, type_: TokenType) -> bool {
+        if self.layout.front() != Some(&type_) {
+            return false;
+        }
+
+        self.layout.pop_front();
+
+        true
+    }
+
+    /// Whether the only waiting layout token is a `Newline`: that is, a
+    /// block has just closed and the next line is back at the indentation
+    /// of the line its header was on, where `else` or `catch` may follow.
+    #[inline]
+    fn continues_header_line(&self) -> bool {
+        self.layout.len() == 1 && self.layout[0] == TokenType::Newline
+    }
+
+    /// Moves from the end of the current line to the first character of
+    /// the next one with any code on it, skipping blank and comment-only
+    /// lines, and queues up layout tokens for the change in indentation.
+    /// Returns `false`, consuming only blanks, if the current line has not
+    /// ended.
+    fn scan_layout(&mut self) -> Result<bool, ParseError> {
+        self.consume_blanks()?;
+
+        if !is_newline(self.ch) {
+            return Ok(false);
+        }
+
+        let mut indent = String::with_capacity(32);
+        let mut in_comment = false;
+        // Blanks after a block comment do not indent the line it is on.
+        let mut after_comment = false;
+
+        while let Some(c) = self.next_char()? {
+            self.ch = c;
+
+            if is_newline(self.ch) {
+                indent.clear();
+                in_comment = false;
+                after_comment = false;
+            } else if in_comment {
+                continue;
+            } else if is_blank(self.ch) {
+                if !after_comment {
+                    indent.push(self.ch);
+                }
+            } else if self.consume_block_comment()? {
+                if self.eof && self.charhistory.is_empty() {
+                    break;
+                }
+
+                // Look at what follows the comment again.
+                after_comment = true;
+                self.charhistory.push_front(self.ch);
+            } else if self.consume_line_comment_op()? {
+                if is_newline(self.ch) {
+                    indent.clear();
+                } else {
+                    in_comment = true;
+                }
+            } else {
+                self.issue_layout(indent)?;
+
+                return Ok(true);
+            }
+        }
+
+        self.eof = true;
+
+        let base = self.indents.base().to_string();
+        self.issue_layout(base)?;
+
+        Ok(true)
+    }
+
+    /// Takes the next character from `self.charhistory`, or failing that
+    /// from the file, without making it `self.ch`.
+    #[inline]
+    fn next_char(&mut self) -> Result<Option<char>, ParseError> {
+        if let Some(first_history) = self.charhistory.pop_front() {
+            return Ok(Some(first_history));
+        }
+
+        match self.read_char() {
+            Some(Ok(c))  => Ok(Some(c)),
+            Some(Err(e)) => Err(e),
+            None         => Ok(None),
+        }
+    }
+
+    /// Queues the layout tokens for a new line indented by `indent`.
+    fn issue_layout(&mut self, indent: String) -> Result<(), ParseError> {
+        match self.indents.issue(indent, &mut self.layout) {
+            Ok(())       => Ok(()),
+            Err(message) => Err(self.bad_indent(message)),
+        }
+    }
+
+    fn expect_char(&mut self, c: char) -> Result<bool, ParseError> {
+        if self.ch != c {
+            Ok(false)
         } else {
             self.advance()?;
 
@@ -2452,7 +3980,7 @@ impl Parser {
         }
     }
 
-    fn expect_char_not_chr_ctrl(&mut self) -> Result<Option<char>, String> {
+    fn expect_char_not_chr_ctrl(&mut self) -> Result<Option<char>, ParseError> {
         if self.ch == '\'' || self.ch == '\\' {
             Ok(None)
         } else {
@@ -2463,8 +3991,19 @@ impl Parser {
         }
     }
 
-    fn expect_char_not_str_ctrl(&mut self) -> Result<Option<char>, String> {
-        if self.ch == '"' || self.ch == '\\' {
+    /// Consumes a character that can be in a string literal as it is,
+    /// which it cannot if it ends the literal, starts an escape, or ends
+    /// the line. `\r` ends the line as `\n` does, for CRLF files.
+    fn expect_char_not_str_ctrl(&mut self) -> Result<Option<char>, ParseError> {
+        if self.eof && self.charhistory.is_empty() {
+            return Ok(None);
+        }
+
+        if self.ch == '"'  ||
+           self.ch == '\\' ||
+           self.ch == '\n' ||
+           self.ch == '\r'
+        {
             Ok(None)
         } else {
             let tmp = self.ch;
@@ -2474,7 +4013,7 @@ impl Parser {
         }
     }
 
-    fn expect_char_esc(&mut self) -> Result<Option<char>, String> {
+    fn expect_char_esc(&mut self) -> Result<Option<char>, ParseError> {
         if self.ch != '\'' &&
            self.ch != '"'  &&
            self.ch != 't'  &&
@@ -2493,7 +4032,7 @@ impl Parser {
         }
     }
 
-    fn expect_char_op(&mut self) -> Result<Option<char>, String> {
+    fn expect_char_op(&mut self) -> Result<Option<char>, ParseError> {
         if self.ch != '?'  &&
            self.ch != '<'  &&
            self.ch != '>'  &&
@@ -2524,338 +4063,482 @@ impl Parser {
         }
     }
 
-    fn expect_keyword(&mut self, kwd: &str) -> Result<bool, String> {
-        let mut kwd_iter = kwd.chars();
+    /// Consumes the keyword `kwd` if it comes next as a whole word.
+    fn expect_keyword(
+        &mut self,
+        kwd: TokenType
+    ) -> Result<bool, ParseError> {
+        if self.keyword_here() != Some(kwd) {
+            return Ok(false);
+        }
 
-        if let Some(first_kwd_ch) = kwd_iter.next() {
-            if self.ch != first_kwd_ch {
-                return Ok(false);
+        while self.ch == '_' || self.ch.is_alphanumeric() {
+            if self.advance()? {
+                break;
             }
-        } else {
-            return Err("empty keyword".to_string());
         }
 
-        let mut historic_stack = Vec::with_capacity(5);
-
-        while let Some(&first_history) = self.charhistory.front() {
-            if let Some(next_ch) = kwd_iter.next() {
-                if first_history != next_ch {
-                    while historic_stack.len() > 1 {
-                        if let Some(historic_pop) = historic_stack.pop() {
-                            self.charhistory.push_front(historic_pop);
-                        }
-                    }
-
-                    if let Some(historic_back) = historic_stack.pop() {
-                        self.ch = historic_back;
-                    }
-
-                    return Ok(false);
-                }
-
-                historic_stack.push(self.ch);
-
-                if let Some(first_history) = self.charhistory.pop_front() {
-                    self.ch = first_history;
-                }
-            } else {
-                let not_keyword = if self.charhistory.is_empty() {
-                    let temp_ch = self.ch;
-
-                    if let Some(temp_ch) = self.charstream.next() {
-                        self.ch = match temp_ch {
-                            Ok(c)  => c,
-                            Err(e) => return Err(e.description().to_string()),
-                        };
-                    } else {
-                        self.eof = true;
-                    }
-
-                    let not_keyword_tmp =
-                        self.ch == '_' || self.ch.is_alphanumeric();
-
-                    self.charhistory.push_back(self.ch);
-                    self.ch = temp_ch;
-
-                    not_keyword_tmp
-                } else {
-                    first_history == '_' || first_history.is_alphanumeric()
-                };
-
-                if not_keyword {
-                    while let Some(historic_back) = historic_stack.pop() {
-                        self.charhistory.push_front(self.ch);
-                        self.ch = historic_back;
-                    }
+        Ok(true)
+    }
 
-                    return Ok(false);
-                }
+    /// The keyword that the word starting at `self.ch` is, if it is one.
+    /// The word is looked up once, however many keywords are tried there.
+    fn keyword_here(&mut self) -> Option<TokenType> {
+        if self.eof && self.charhistory.is_empty() {
+            return None;
+        }
 
-                self.advance()?;
+        let start = self.offset();
 
-                return Ok(true);
+        if let Some((at, ch, ref kwd)) = self.word {
+            if at == start && ch == self.ch {
+                return kwd.clone();
             }
         }
 
-        self.charhistory.push_back(self.ch);
-        let mut history_pushbacks = 1usize;
-
-        while let Some(next_ch) = kwd_iter.next() {
-            if let Some(Ok(temp_ch)) = self.charstream.next() {
-                self.ch = temp_ch;
-
-                if self.ch != next_ch {
-                    while historic_stack.len() > 1 {
-                        if let Some(historic_pop) = historic_stack.pop() {
-                            self.charhistory.push_front(historic_pop);
-                        }
-                    }
-
-                    if let Some(historic_back) = historic_stack.pop() {
-                        self.ch = historic_back;
-                    }
+        let kwd = {
+            let word = self.upcoming_word();
+            let len = scan::ident_run(word.as_bytes());
 
-                    return Ok(false);
-                }
-
-                self.charhistory.push_back(self.ch);
-                history_pushbacks += 1;
+            // A keyword is only ever the whole of an ASCII word.
+            if word[len..].starts_with(char::is_alphanumeric) {
+                None
             } else {
-                self.eof = true;
-
-                break;
+                lexer::keyword(&word[..len])
             }
-        }
+        };
+        self.word = Some((start, self.ch, kwd.clone()));
 
-        if let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
-        } else {
-            self.eof = true;
-        }
+        kwd
+    }
 
-        if self.ch == '_' || self.ch.is_alphanumeric() {
-            while historic_stack.len() > 1 {
-                if let Some(historic_pop) = historic_stack.pop() {
-                    self.charhistory.push_front(historic_pop);
-                }
-            }
+    /// The rest of the source from `self.ch` on, or, if there are
+    /// characters pushed back to read again, as much of it as could be the
+    /// start of a keyword.
+    fn upcoming_word(&self) -> Cow<'_, str> {
+        if self.charhistory.is_empty() {
+            let rest = &self.source[self.offset()..];
 
-            if let Some(historic_back) = historic_stack.pop() {
-                self.ch = historic_back;
+            if rest.starts_with(self.ch) {
+                return Cow::Borrowed(rest);
             }
         }
 
-        for _ in 0..history_pushbacks {
-            self.charhistory.pop_back();
-        }
-
-        Ok(kwd_iter.next().is_none())
+        Cow::Owned(
+            iter::once(self.ch)
+                .chain(self.charhistory.iter().cloned())
+                .chain(self.source[self.read_to..].chars())
+                .take_while(|&c| c == '_' || c.is_alphanumeric())
+                .take(MAX_KEYWORD_LEN + 1)
+                .collect()
+        )
     }
 
-    fn expect_op(&mut self, op: &str) -> Result<bool, String> {
-        let mut op_iter = op.chars();
+    /// The rest of the current line from `self.ch` on.
+    fn upcoming_line(&self) -> Cow<'_, str> {
+        if self.charhistory.is_empty() {
+            let rest = &self.source[self.offset()..];
 
-        if let Some(first_char) = op_iter.next() {
-            if self.ch != first_char {
-                return Ok(false);
+            if rest.starts_with(self.ch) {
+                let len = scan::line_len(rest.as_bytes());
+
+                return Cow::Borrowed(&rest[..len]);
             }
-        } else {
-            return Err("empty operator".to_string());
         }
 
-        let mut historic_stack = Vec::with_capacity(4);
-
-        while let Some(&first_history) = self.charhistory.front() {
-            if let Some(next_char) = op_iter.next() {
-                if first_history != next_char {
-                    while historic_stack.len() > 1 {
-                        if let Some(historic_pop) = historic_stack.pop() {
-                            self.charhistory.push_front(historic_pop);
-                        }
-                    }
-
-                    if let Some(historic_back) = historic_stack.pop() {
-                        self.ch = historic_back;
-                    }
-
-                    return Ok(false);
-                }
-
-                historic_stack.push(self.ch);
-
-                if let Some(first_history) = self.charhistory.pop_front() {
-                    self.ch = first_history;
-                }
-            } else {
-                let not_op = if self.charhistory.is_empty() {
-                    let temp_ch = self.ch;
-
-                    if let Some(temp_ch) = self.charstream.next() {
-                        self.ch = match temp_ch {
-                            Ok(c)  => c,
-                            Err(e) => return Err(e.description().to_string()),
-                        };
-                    } else {
-                        self.eof = true;
-                    }
-
-                    let not_op_tmp = is_op_char(self.ch);
-
-                    self.charhistory.push_back(self.ch);
-                    self.ch = temp_ch;
+        Cow::Owned(
+            iter::once(self.ch)
+                .chain(self.charhistory.iter().cloned())
+                .chain(self.source[self.read_to..].chars())
+                .take_while(|&c| !is_newline(c))
+                .collect()
+        )
+    }
 
-                    not_op_tmp
-                } else {
-                    is_op_char(first_history)
-                };
+    /// Consumes the operator `op` if it comes next and is not the start of
+    /// a longer operator.
+    fn expect_op(&mut self, op: &str) -> Result<bool, ParseError> {
+        self.expect_whole(op, is_op_char)
+    }
 
-                if not_op {
-                    while let Some(historic_back) = historic_stack.pop() {
-                        self.charhistory.push_front(self.ch);
-                        self.ch = historic_back;
-                    }
+    /// Consumes `text` if it comes next and is not followed by a character
+    /// that `continues` it. Otherwise consumes nothing.
+    ///
+    /// The text is matched against what comes next where it is, in
+    /// `self.charhistory` and then the source, so a miss moves nothing,
+    /// however long the run of characters it was found in.
+    fn expect_whole(
+        &mut self,
+        text:      &str,
+        continues: fn(char) -> bool
+    ) -> Result<bool, ParseError> {
+        // Most calls are over by the first character.
+        if !text.starts_with(self.ch) {
+            return Ok(false);
+        }
 
-                    return Ok(false);
-                }
+        let matched = {
+            let mut upcoming = iter::once(self.ch)
+                .chain(self.charhistory.iter().cloned())
+                .chain(self.source[self.read_to..].chars());
 
-                self.advance()?;
+            text.chars().all(|c| upcoming.next() == Some(c)) &&
+                !upcoming.next().is_some_and(continues)
+        };
 
-                return Ok(true);
-            }
+        if !matched {
+            return Ok(false);
         }
 
-        self.charhistory.push_back(self.ch);
-        let mut history_pushbacks = 1usize;
-
-        while let Some(next_ch) = op_iter.next() {
-            if let Some(Ok(temp_ch)) = self.charstream.next() {
-                self.ch = temp_ch;
+        for _ in text.chars() {
+            self.advance()?;
+        }
 
-                if self.ch != next_ch {
-                    while historic_stack.len() > 1 {
-                        if let Some(historic_pop) = historic_stack.pop() {
-                            self.charhistory.push_front(historic_pop);
-                        }
-                    }
+        Ok(true)
+    }
 
-                    if let Some(historic_back) = historic_stack.pop() {
-                        self.ch = historic_back;
-                    }
+    /// Parses the body following a header into `main_ast`: either `sep`
+    /// and a single line on the header's own line, or a block. Either way,
+    /// the layout tokens after the body are queued up, so that a following
+    /// `else` or `catch` can be looked for.
+    fn get_body(
+        &mut self,
+        main_ast: &mut AST,
+        sep:      TokenType
+    ) -> Result<(), ParseError> {
+        self.consume_blanks()?;
 
-                    return Ok(false);
-                }
+        let sep_leaf = match sep {
+            TokenType::Equals    => self.parse_equals()?,
+            TokenType::FatRArrow => self.parse_fat_r_arrow()?,
+            _ => panic!("unhandled inline body separator {:?}", sep),
+        };
 
-                self.charhistory.push_back(self.ch);
-                history_pushbacks += 1;
-            } else {
-                self.eof = true;
+        let sep_leaf = if let Some(s) = sep_leaf {
+            s
+        } else {
+            return self.get_block(main_ast, TokenType::Line);
+        };
 
-                break;
-            }
-        }
+        let line = self.parse_line(false)?;
 
-        if let Some(temp_ch) = self.charstream.next() {
-            self.ch = match temp_ch {
-                Ok(c)  => c,
-                Err(e) => return Err(e.description().to_string()),
-            };
-        } else {
-            self.eof = true;
+        match line {
+            Some(ref l) if !l.children().is_empty() => {},
+            _ => return Err(self.missing(
+                if sep == TokenType::Equals {
+                    "expected expression after ="
+                } else {
+                    "expected expression after =>"
+                },
+                vec![TokenType::Expr]
+            )),
         }
 
-        if is_op_char(self.ch) {
-            while historic_stack.len() > 1 {
-                if let Some(historic_pop) = historic_stack.pop() {
-                    self.charhistory.push_front(historic_pop);
-                }
-            }
+        main_ast.add_child(sep_leaf);
 
-            if let Some(historic_back) = historic_stack.pop() {
-                self.ch = historic_back;
-            }
+        if let Some(l) = line {
+            main_ast.add_child(l);
         }
 
-        for _ in 0..history_pushbacks {
-            self.charhistory.pop_back();
+        if self.layout.is_empty() {
+            self.scan_layout()?;
         }
 
-        Ok(op_iter.next().is_none())
+        Ok(())
     }
 
+    /// Parses the block following a header into `main_ast`. A block is
+    /// `Newline Indent item (Newline item)* Newline Dedent`; the `Newline`
+    /// ending the header's own line is left for the caller.
     fn get_block(
         &mut self,
         main_ast:       &mut AST,
         body_item_type: TokenType
-    ) -> Result<String, String> {
-        let start_indent = self.currentindent.clone();
-
+    ) -> Result<(), ParseError> {
         if !self.expect_newline()? {
-            return Err("expected newline after header".to_string());
+            return Err(self.missing(
+                "expected newline after header",
+                vec![TokenType::Newline]
+            ));
         }
 
-        let block_indent = self.currentindent.clone();
-
-        if start_indent.len() >= block_indent.len() ||
-           !block_indent.starts_with(&start_indent)
-        {
-            return Err("improper indentation after header".to_string());
+        if !self.expect_layout(TokenType::Indent) {
+            return Err(self.bad_indent("improper indentation after header"));
         }
 
-        if let Some(first_item) = match body_item_type {
-            TokenType::Line       => self.parse_line(false)?,
-            TokenType::CaseBranch => self.parse_case_branch()?,
-            _ => return Err("unhandled body item type".to_string()),
-        } {
+        if let Some(first_item) = self.parse_block_item(&body_item_type)? {
             main_ast.add_child(first_item);
         } else {
-            return Err("expected at least one item in block".to_string());
+            return Err(self.missing(
+                "expected at least one item in block",
+                vec![body_item_type.clone()]
+            ));
         };
 
         if !self.expect_newline()? {
-            return Err(
-                "expected newline after first item of block".to_string()
-            );
+            return Err(self.missing(
+                "expected newline after first item of block",
+                vec![TokenType::Newline]
+            ));
         }
 
-        while self.currentindent == block_indent {
-            if let Some(item) = match body_item_type {
-                TokenType::Line       => self.parse_line(false)?,
-                TokenType::CaseBranch => self.parse_case_branch()?,
-                _ => return Err("unhandled body item type".to_string()),
-            } {
+        while !self.expect_layout(TokenType::Dedent) {
+            if let Some(item) = self.parse_block_item(&body_item_type)? {
                 main_ast.add_child(item);
 
                 if !self.expect_newline()? {
-                    return Err(
-                        "expected newline after block item".to_string()
-                    );
+                    return Err(self.missing(
+                        "expected newline after block item",
+                        vec![TokenType::Newline]
+                    ));
                 }
             } else {
-                return Err("expected item in block".to_string());
+                return Err(self.missing(
+                    "expected item in block",
+                    vec![body_item_type.clone()]
+                ));
             }
         }
 
-        Ok(start_indent)
+        Ok(())
+    }
+
+    fn parse_block_item(
+        &mut self,
+        body_item_type: &TokenType
+    ) -> Result<Option<AST>, ParseError> {
+        match *body_item_type {
+            TokenType::Line       => self.parse_line(false),
+            TokenType::CaseBranch => self.parse_case_branch(),
+            _ => panic!("unhandled body item type {:?}", body_item_type),
+        }
+    }
+}
+
+impl<'a> Items<'a> {
+    /// Passes on `e`, from the item starting at byte offset `start`, first
+    /// skipping to where parsing can go on after it, if anywhere.
+    fn fail(
+        &mut self,
+        e:     ParseError,
+        start: usize
+    ) -> Option<Result<AST, ParseError>> {
+        // Brackets left open run on over the lines after them, which may
+        // well be items of their own: go on from the line after the one
+        // the item starts on instead.
+        if self.parser.brackets > 0 {
+            self.parser.brackets = 0;
+            self.parser.layout.clear();
+            self.parser.unread_from(start);
+        }
+
+        let go_on = match e {
+            ParseError::Io(_) => false,
+            _ => self.parser.skip_to_top_level().unwrap_or(false),
+        };
+
+        if !go_on {
+            self.next = ItemKind::Done;
+        }
+
+        Some(Err(e))
+    }
+}
+
+impl<'a> Iterator for Items<'a> {
+    type Item = Result<AST, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let at_eof = self.parser.eof && self.parser.charhistory.is_empty();
+            let start = self.parser.offset();
+
+            let mut item = match self.next {
+                ItemKind::ModDecl => {
+                    self.next = ItemKind::Import;
+
+                    if let Err(e) = self.parser.start() {
+                        return self.fail(e, start);
+                    }
+
+                    match self.parser.parse_mod_decl() {
+                        Ok(Some(mod_decl)) => mod_decl,
+                        // Nothing has been read past the start of the first
+                        // line, so go on from there.
+                        Ok(None) => return Some(Err(self.parser.missing(
+                            "expected module declaration",
+                            vec![TokenType::ModuleKeyword]
+                        ))),
+                        Err(e) => return self.fail(e, start),
+                    }
+                },
+                ItemKind::Done => return None,
+                _ if at_eof    => return None,
+                ItemKind::Import => match self.parser.parse_import() {
+                    Ok(Some(import)) => import,
+                    Ok(None) => {
+                        self.next = ItemKind::Line;
+
+                        continue;
+                    },
+                    Err(e) => return self.fail(e, start),
+                },
+                ItemKind::Line => match self.parser.parse_line(true) {
+                    Ok(Some(line)) => line,
+                    Ok(None)       => return None,
+                    Err(e)         => return self.fail(e, start),
+                },
+            };
+
+            propagate_spans(&mut item);
+
+            return Some(Ok(item));
+        }
+    }
+}
+
+impl AST {
+    /// Where this node's text is in the source file.
+    #[inline(always)]
+    pub fn span(&self) -> Span {
+        self.val().span
+    }
+
+    /// The line this node starts on, or 0 if unknown.
+    #[inline(always)]
+    pub fn line(&self) -> usize {
+        self.val().span.line
+    }
+
+    /// The column this node starts at, or 0 if unknown.
+    #[inline(always)]
+    pub fn col(&self) -> usize {
+        self.val().span.col
     }
 }
 
+/// Creates an interior node of the given type, with room for as many
+/// children as nodes of its type usually have (see `node_capacity`).
 #[inline(always)]
-pub fn new_ast_node(token_type: TokenType, size_hint: usize) -> AST {
-    AST::new(Token::new(token_type, String::new()), size_hint)
+pub fn new_ast_node(token_type: TokenType) -> AST {
+    let capacity = node_capacity(&token_type);
+
+    AST::new(Token::new(token_type, String::new()), capacity)
 }
 
+/// How many children to make room for in a node of type `type_`, when it
+/// is given its first: as many as nodes of its type most often have in the
+/// example programs of `brouwer.md` and a sample of test programs. Imports,
+/// programs, and string literals, which hold a list of any length, get room
+/// for a short one. A node with more children than this grows as a `Vec`
+/// does.
+fn node_capacity(type_: &TokenType) -> usize {
+    match *type_ {
+        TokenType::RealLit     |
+        TokenType::UnaryOp     |
+        TokenType::Return      |
+        TokenType::FnCall      |
+        TokenType::Guard       |
+        TokenType::ModDecl     |
+        TokenType::Propagate   => 2,
+        TokenType::Assign      |
+        TokenType::BinOp       |
+        TokenType::CaseBranch  |
+        TokenType::ChrLit      |
+        TokenType::DictEntry   |
+        TokenType::FixityDecl  |
+        TokenType::Generator   |
+        TokenType::Infixed     |
+        TokenType::MemberIdent |
+        TokenType::Parened     |
+        TokenType::Range       |
+        TokenType::ScopedIdent |
+        TokenType::While       => 3,
+        TokenType::Case        |
+        TokenType::Index       |
+        TokenType::Var         => 4,
+        TokenType::DictLit     |
+        TokenType::FnDecl      |
+        TokenType::For         |
+        TokenType::ListLit     |
+        TokenType::SetLit      |
+        TokenType::Try         |
+        TokenType::TupleLit    => 5,
+        TokenType::DictComp    |
+        TokenType::Lambda      |
+        TokenType::ListComp    |
+        TokenType::SetComp     => 6,
+        TokenType::IfElse      => 7,
+        TokenType::Import      |
+        TokenType::Prog        => 8,
+        TokenType::StrLit      => 16,
+        _                      => 1,
+    }
+}
+
+/// Creates a leaf of the given type holding the lexeme `s`.
 #[inline(always)]
 pub fn new_ast_leaf<S: Into<String>>(token_type: TokenType, s: S) -> AST {
     AST::new(Token::new(token_type, s.into()), 0)
 }
 
+/// Parses `src` as a run of lines lifted out of a block indented by
+/// `base_indent`, such as code pasted into a REPL or embedded in a doc
+/// comment. Every line with code on it must be indented by at least
+/// `base_indent`; lines indented further are nested blocks, as usual. The
+/// lines are returned as the children of a `Prog`.
+///
+/// ```
+/// use brouwer::parser::parse_block_fragment;
+///
+/// let src = "    x = 1\n    while x < 10\n        x = x * 2\n";
+/// let fragment = parse_block_fragment(src, "    ").unwrap();
+///
+/// assert_eq!(fragment.children().len(), 2);
+/// assert!(parse_block_fragment("x = 1\n", "    ").is_err());
+/// ```
+pub fn parse_block_fragment(
+    src:         &str,
+    base_indent: &str
+) -> Result<AST, ParseError> {
+    let mut parser = Parser::from_str(src);
+    parser.indents = IndentStack::with_base(base_indent.to_string());
+
+    parser.parse_fragment()
+}
+
+/// Gives every interior node below `ast` the span covering its children.
+/// Children without a known span (such as empty lines) are skipped.
+pub fn propagate_spans(ast: &mut AST) {
+    if ast.children().is_empty() {
+        return;
+    }
+
+    for child in ast.children_mut() {
+        propagate_spans(child);
+    }
+
+    let span = {
+        let mut known = ast.children()
+                           .iter()
+                           .map(|child| child.span())
+                           .filter(|span| span.is_known());
+
+        match known.next() {
+            Some(first) => first.to(&known.next_back().unwrap_or(first)),
+            None        => return,
+        }
+    };
+
+    ast.val_mut().span = span;
+}
+
+/// Reconstructs source text for `ast` from its leaves, separating tokens
+/// with single spaces (except within string and character literals).
 #[inline(always)]
 pub fn str_repr(ast: &AST) -> String {
-    if !ast.val().lexeme.is_empty() {
-        ast.val().lexeme.clone()
+    if !ast.val().lexeme().is_empty() {
+        ast.val().lexeme().to_string()
     } else {
         let mut ret = String::with_capacity(6 * ast.children().len());
 
@@ -2864,9 +4547,13 @@ pub fn str_repr(ast: &AST) -> String {
 
             let child_type = &child_ast.val().type_;
 
-            if child_type != &TokenType::StrChr      &&
-               child_type != &TokenType::ChrChr      &&
-               child_type != &TokenType::DoubleQuote &&
+            if child_type != &TokenType::StrChr        &&
+               child_type != &TokenType::ChrChr        &&
+               child_type != &TokenType::HexEscape     &&
+               child_type != &TokenType::UnicodeEscape &&
+               child_type != &TokenType::Radix         &&
+               child_type != &TokenType::DoubleQuote   &&
+               child_type != &TokenType::TripleQuote   &&
                child_type != &TokenType::SingleQuote
             {
                 ret.push(' ');
@@ -2877,12 +4564,106 @@ pub fn str_repr(ast: &AST) -> String {
     }
 }
 
+/// Whether the `Subexpr` `subexpr` is an infix operator.
+fn is_operator(subexpr: &AST) -> bool {
+    match subexpr.children().first() {
+        Some(inner) => inner.val().type_ == TokenType::Op ||
+                       inner.val().type_ == TokenType::Infixed,
+        None        => false,
+    }
+}
+
+/// The text of a token that is always spelled the same, and how it is
+/// told apart from the start of a longer token.
+#[inline(always)]
+fn fixed_token(type_: &TokenType) -> (&'static str, Munch) {
+    fixed(type_).unwrap_or_else(||
+        panic!("{:?} is not spelled the same every time", type_)
+    )
+}
+
+/// How a token of type `type_` is written, if it is written the same way
+/// every time.
+pub fn spelling(type_: &TokenType) -> Option<&'static str> {
+    fixed(type_).map(|(text, _)| text)
+}
+
+fn fixed(type_: &TokenType) -> Option<(&'static str, Munch)> {
+    Some(match *type_ {
+        TokenType::ModuleKeyword    => ("module", Munch::Word),
+        TokenType::ExposingKeyword  => ("exposing", Munch::Word),
+        TokenType::HidingKeyword    => ("hiding", Munch::Word),
+        TokenType::ImportKeyword    => ("import", Munch::Word),
+        TokenType::AsKeyword        => ("as", Munch::Word),
+        TokenType::FnKeyword        => ("fn", Munch::Word),
+        TokenType::CaseKeyword      => ("case", Munch::Word),
+        TokenType::IfKeyword        => ("if", Munch::Word),
+        TokenType::ElseKeyword      => ("else", Munch::Word),
+        TokenType::TryKeyword       => ("try", Munch::Word),
+        TokenType::CatchKeyword     => ("catch", Munch::Word),
+        TokenType::WhileKeyword     => ("while", Munch::Word),
+        TokenType::ForKeyword       => ("for", Munch::Word),
+        TokenType::InKeyword        => ("in", Munch::Word),
+        TokenType::VarKeyword       => ("var", Munch::Word),
+        TokenType::ReturnKeyword    => ("return", Munch::Word),
+        TokenType::InfixlKeyword    => ("infixl", Munch::Word),
+        TokenType::InfixrKeyword    => ("infixr", Munch::Word),
+        TokenType::InfixKeyword     => ("infix", Munch::Word),
+        TokenType::TrueKeyword      => ("True", Munch::Word),
+        TokenType::FalseKeyword     => ("False", Munch::Word),
+        TokenType::Underscore       => ("_", Munch::Word),
+        TokenType::Equals           => ("=", Munch::Op),
+        TokenType::Colon            => (":", Munch::Op),
+        TokenType::DoubleColon      => ("::", Munch::Op),
+        TokenType::Dot              => (".", Munch::Op),
+        TokenType::DotDot           => ("..", Munch::Punct),
+        TokenType::DotDotEquals     => ("..=", Munch::Punct),
+        TokenType::LArrow           => ("<-", Munch::Op),
+        TokenType::RArrow           => ("->", Munch::Op),
+        TokenType::FatRArrow        => ("=>", Munch::Op),
+        TokenType::LParen           => ("(", Munch::Char),
+        TokenType::RParen           => (")", Munch::Char),
+        TokenType::LSqBracket       => ("[", Munch::Char),
+        TokenType::RSqBracket       => ("]", Munch::Char),
+        TokenType::LCurlyBracket    => ("{", Munch::Char),
+        TokenType::RCurlyBracket    => ("}", Munch::Char),
+        TokenType::Comma            => (",", Munch::Char),
+        TokenType::Backslash        => ("\\", Munch::Char),
+        TokenType::Bar              => ("|", Munch::Char),
+        TokenType::Backtick         => ("`", Munch::Char),
+        TokenType::At               => ("@", Munch::Op),
+        TokenType::SingleQuote      => ("'", Munch::Char),
+        TokenType::DoubleQuote      => ("\"", Munch::Char),
+        _                           => return None,
+    })
+}
+
+/// The name of an `Op`, or of the function in an `Infixed`.
+fn op_name(op: &AST) -> String {
+    if op.val().type_ == TokenType::Infixed {
+        str_repr(&op.children()[1]).trim().to_string()
+    } else {
+        op.val().lexeme().to_string()
+    }
+}
+
+/// The span of the leftmost leaf below `ast`, for nodes whose own span
+/// has not been worked out yet.
+fn first_leaf_span(ast: &AST) -> Span {
+    match ast.children().first() {
+        Some(child) => first_leaf_span(child),
+        None        => ast.span(),
+    }
+}
+
+/// Prints `ast` to stdout as an indented tree, starting at `cur_depth`
+/// levels of indentation.
 pub fn log_depth_first(ast: &AST, cur_depth: usize) {
     for _ in 0..cur_depth {
         print!("  ");
     }
 
-    let lex = &ast.val().lexeme;
+    let lex = ast.val().lexeme();
 
     if lex.is_empty() {
         println!(" └─ {:?}", ast.val().type_);
@@ -2895,17 +4676,21 @@ pub fn log_depth_first(ast: &AST, cur_depth: usize) {
     }
 }
 
+/// Whether `c` ends a line.
 #[inline(always)]
 pub fn is_newline(c: char) -> bool {
     c == '\n' || c == '\r'
 }
 
+/// Whether `c` is non-newline whitespace.
 #[inline(always)]
 pub fn is_blank(c: char) -> bool {
     c == ' ' || c == '\t'
 }
 
-fn is_op_char(c: char) -> bool {
+/// Whether `c` can be part of an operator.
+#[inline(always)]
+pub fn is_op_char(c: char) -> bool {
     c == '?'  ||
     c == '<'  ||
     c == '>'  ||
@@ -2928,6 +4713,50 @@ fn is_op_char(c: char) -> bool {
     c == ';'
 }
 
+/// Whether the `Subexpr` `subexpr` can be indexed, or have `?` applied to
+/// it: whether it is a name, a literal of a string or a collection, or in
+/// parentheses, rather than a number, an operator, or a construct that
+/// runs to the end of its line.
+fn is_indexable(subexpr: &AST) -> bool {
+    match subexpr.children().first().map(|c| &c.val().type_) {
+        Some(&TokenType::QualIdent) |
+        Some(&TokenType::Parened)   |
+        Some(&TokenType::TupleLit)  |
+        Some(&TokenType::ListLit)   |
+        Some(&TokenType::ListComp)  |
+        Some(&TokenType::DictLit)   |
+        Some(&TokenType::DictComp)  |
+        Some(&TokenType::SetLit)    |
+        Some(&TokenType::SetComp)   |
+        Some(&TokenType::StrLit)    |
+        Some(&TokenType::Index)     |
+        Some(&TokenType::Propagate) => true,
+        _                           => false,
+    }
+}
+
+/// The fixity of an operator with no fixity declaration. Operators not
+/// listed here, and functions used infix, are `infixl 9`.
+pub fn default_fixity(op: &str) -> Fixity {
+    let (prec, assoc) = match op {
+        "||"                        => (2, Assoc::Right),
+        "&&"                        => (3, Assoc::Right),
+        "==" | "!=" | "/=" |
+        "<"  | "<=" | ">"  | ">="   => (4, Assoc::NonAssoc),
+        "+"  | "-"                  => (6, Assoc::Left),
+        "*"  | "/"  | "%"           => (7, Assoc::Left),
+        "^"                         => (8, Assoc::Right),
+        _                           => (9, Assoc::Left),
+    };
+
+    Fixity {
+        prec:  prec,
+        assoc: assoc,
+    }
+}
+
+/// Whether `op_str` is reserved syntax and so cannot be used as an
+/// operator.
 pub fn is_reserved_op(op_str: &str) -> bool {
     op_str == ":"  ||
     op_str == "->" ||
@@ -2935,8 +4764,292 @@ pub fn is_reserved_op(op_str: &str) -> bool {
     op_str == "<-" ||
     op_str == "--" ||
     op_str == "|"  ||
+    op_str == "@"  ||
     op_str == "\\" ||
     op_str == "="  ||
     op_str == "."  ||
     op_str == "::"
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::{self, ExprKind, Stmt};
+    use dump::to_sexpr;
+
+    /// The tree of `src` as an S-expression, all on one line.
+    fn sexpr(src: &str) -> String {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        to_sexpr(&tree).split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// The lines of the program `src`.
+    fn lines(src: &str) -> Vec<Stmt> {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        ast::lower(&tree).unwrap().body
+    }
+
+    /// The first block of the construct on the line `stmt`: the body of a
+    /// `fn`, `for`, or `if`, or of the first branch of a `case`.
+    fn block(stmt: &Stmt) -> &[Stmt] {
+        let kind = match *stmt {
+            Stmt::Fn(ref fn_decl) => return &fn_decl.body,
+            Stmt::Expr(ref expr)  => &expr.kind,
+            _                     => panic!("{:?} has no block", stmt),
+        };
+
+        match *kind {
+            ExprKind::Case(_, ref branches) => &branches[0].body,
+            ExprKind::For(_, _, ref body)   |
+            ExprKind::If(_, ref body, _)    => body,
+            _ => panic!("{:?} has no block", stmt),
+        }
+    }
+
+    #[test]
+    fn question_mark_before_operand_propagates_then_applies() {
+        let tree = sexpr("module Main\n\na = x?y\n");
+
+        assert!(tree.contains(
+            "(FnCall (Subexpr (Propagate (Subexpr (QualIdent (Ident \"x\"))) \
+             (Op \"?\"))) (Subexpr (QualIdent (Ident \"y\"))))"
+        ), "{}", tree);
+    }
+
+    #[test]
+    fn spaced_question_mark_is_an_operator() {
+        let tree = sexpr("module Main\n\na = x ? y\n");
+
+        assert!(!tree.contains("Propagate"), "{}", tree);
+        assert!(tree.contains("(BinOp"), "{}", tree);
+    }
+
+    #[test]
+    fn line_breaks_within_brackets_do_not_end_the_line() {
+        let flat = "module Main\n\n\
+                    xs = [1, 2]\n\
+                    t = (1, 2)\n\
+                    p = (1 + 2)\n\
+                    c = [x | x <- xs, x > 1]\n\
+                    s = {1, 2}\n";
+        let wrapped = "module Main\n\n\
+                       xs = [\n    1,\n    2 -- two\n]\n\
+                       t = (1,\n     2)\n\
+                       p = (1 +\n     2)\n\
+                       c = [x\n    | x <- xs,\n      x > 1\n]\n\
+                       s = {1,\n  2}\n";
+
+        assert_eq!(sexpr(wrapped), sexpr(flat));
+    }
+
+    #[test]
+    fn line_break_after_brackets_ends_the_line() {
+        let tree = sexpr("module Main\n\nxs = [1,\n  2]\nf xs\n");
+
+        assert_eq!(tree.matches("(Line").count(), 2, "{}", tree);
+    }
+
+    #[test]
+    fn tuples_are_not_parened_expressions() {
+        let tree = sexpr("module Main\n\nt = (1, 2)\nu = ()\n");
+
+        assert_eq!(tree.matches("(TupleLit").count(), 2, "{}", tree);
+        assert!(!tree.contains("Parened"), "{}", tree);
+    }
+
+    #[test]
+    fn blocks_nest_to_any_depth() {
+        let lines = lines("module Main\n\n\
+                           case xs\n    \
+                               [x] =>\n        \
+                                   for y in x\n            \
+                                       if y > 0\n                \
+                                           -- a comment\n                \
+                                           fn g z\n                    \
+                                               z + y\n\n                \
+                                           g y\n            \
+                                       after y\n    \
+                               _ => 0\n\
+                           done 1\n");
+
+        assert_eq!(lines.len(), 2);
+
+        let for_body = block(&lines[0]);
+        assert_eq!(for_body.len(), 1);
+
+        let if_body = block(&for_body[0]);
+        assert_eq!(if_body.len(), 2);
+
+        let fn_body = block(&if_body[0]);
+        assert_eq!(fn_body.len(), 2);
+        assert_eq!(block(&fn_body[0]).len(), 1);
+    }
+
+    #[test]
+    fn dedent_by_several_levels_ends_every_block_it_leaves() {
+        let lines = lines("module Main\n\n\
+                           case xs\n    \
+                               [x] =>\n        \
+                                   for y in x\n            \
+                                       if y > 0\n                \
+                                           fn g z\n                    \
+                                               z + y\n    \
+                               _ => 0\n\
+                           done 1\n");
+
+        assert_eq!(lines.len(), 2);
+
+        match lines[0] {
+            Stmt::Expr(ast::Expr {
+                kind: ExprKind::Case(_, ref branches), ..
+            }) => assert_eq!(branches.len(), 2),
+            ref line => panic!("{:?} is not a case", line),
+        }
+
+        let for_body = block(&lines[0]);
+        let if_body = block(&for_body[0]);
+        let fn_body = block(&if_body[0]);
+
+        assert_eq!((for_body.len(), if_body.len(), fn_body.len()), (1, 1, 1));
+        assert_eq!(block(&fn_body[0]).len(), 1);
+    }
+
+    #[test]
+    fn fixity_declarations_set_how_operators_nest() {
+        let tree = sexpr("module Main\n\n\
+                          infixr 5 +++\n\
+                          infixl 7 <>\n\n\
+                          x = a +++ b +++ c\n\
+                          y = a + b <> c\n");
+
+        assert!(tree.contains(
+            "(FixityDecl (InfixrKeyword \"infixr\") (AbsInt \"5\") \
+             (Op \"+++\"))"
+        ), "{}", tree);
+        assert!(tree.contains(
+            "(Expr (BinOp (Expr (Subexpr (QualIdent (Ident \"a\")))) \
+             (Subexpr (Op \"+++\")) (Expr (BinOp"
+        ), "{}", tree);
+        assert!(tree.contains(
+            "(Expr (BinOp (Expr (Subexpr (QualIdent (Ident \"a\")))) \
+             (Subexpr (Op \"+\")) (Expr (BinOp"
+        ), "{}", tree);
+    }
+
+    #[test]
+    fn non_associative_operators_cannot_be_chained() {
+        let src = "module Main\n\ninfix 4 ~~\n\nx = a ~~ b ~~ c\n";
+
+        match Parser::from_str(src).parse() {
+            Err(ParseError::MixedFixity { span, .. }) => {
+                assert_eq!((span.line, span.col), (5, 12));
+            },
+            other => panic!("not a MixedFixity: {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn fixity_precedence_is_one_digit() {
+        let e = Parser::from_str("module Main\n\ninfixl 10 +\n").parse();
+
+        assert_eq!(
+            e.err().map(|e| e.to_string()).unwrap_or_default(),
+            "3:9: precedence must be from 0 to 9"
+        );
+    }
+
+    #[test]
+    fn number_literals_are_read_as_the_lexer_reads_them() {
+        fn leaves(tree: &AST, spans: &mut Vec<(TokenType, Span)>) {
+            match tree.val().type_ {
+                TokenType::Radix  |
+                TokenType::AbsInt |
+                TokenType::AbsReal => {
+                    spans.push((tree.val().type_.clone(), tree.val().span));
+                },
+                _ => for child in tree.children() {
+                    leaves(child, spans);
+                },
+            }
+        }
+
+        let src = "module Main\n\n\
+                   x = [0xff_FF, 1_000.5e-3, 2E+10, 7, 0b1..0o17, 1..n]\n";
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+        let mut parsed = Vec::new();
+        leaves(&tree, &mut parsed);
+
+        let lexed: Vec<_> = lexer::tokenize(src)
+            .unwrap()
+            .into_iter()
+            .filter(|token| match token.type_ {
+                TokenType::Radix  |
+                TokenType::AbsInt |
+                TokenType::AbsReal => true,
+                _                  => false,
+            })
+            .map(|token| (token.type_.clone(), token.span))
+            .collect();
+
+        assert_eq!(parsed.len(), 10);
+        assert_eq!(parsed, lexed);
+    }
+
+    #[test]
+    fn malformed_number_literals_are_errors_to_the_lexer_too() {
+        let literals = [
+            "0xZZ", "1__0", "0x_FF", "1.e3", "0b102", "1_", "1e", "2.5E-"
+        ];
+
+        for literal in &literals {
+            let src = format!("module Main\n\nx = {}\n", literal);
+            let parsed = Parser::from_str(&src).parse().map(|_| ());
+            let lexed = lexer::tokenize(&src).map(|_| ());
+
+            assert!(parsed.is_err(), "{} parsed", literal);
+            assert_eq!(
+                parsed.map_err(|e| e.to_string()),
+                lexed.map_err(|e| e.to_string()),
+                "{}",
+                literal
+            );
+        }
+    }
+
+    #[test]
+    fn line_comments_can_end_any_line() {
+        let plain = "module Main\n\n\
+                     import List as L\n\n\
+                     x = 1\n\
+                     f x\n\
+                     if x\n    y\n\
+                     else\n    z\n";
+        let commented = "module Main -- m\n\n\
+                         import List as L -- i\n\n\
+                         x = 1 -- c\n\
+                         f x--c\n\
+                         if x -- c\n    y -- c\n\
+                         else -- c\n    z --\n";
+
+        assert_eq!(sexpr(commented), sexpr(plain));
+        assert!(lexer::tokenize(commented).is_ok());
+    }
+
+    #[test]
+    fn an_exponent_needs_digits() {
+        for &(src, col) in &[("x = 1e\n", 7), ("x = 1.5E+ 2\n", 10)] {
+            let src = format!("module Main\n\n{}", src);
+
+            match Parser::from_str(&src).parse() {
+                Err(ParseError::MissingToken { message, span, .. }) => {
+                    assert_eq!(message, "expected a digit in exponent");
+                    assert_eq!((span.line, span.col), (3, col));
+                },
+                other => panic!("not a MissingToken: {:?}", other.err()),
+            }
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Hooks for rules and passes of one's own, run alongside the compiler's
+//! without changing it.
+//!
+//! A `CompilerPlugin` is called on each module of a program after it is
+//! parsed, after its names are resolved, and after its types are inferred,
+//! with a read-only view of it and of the rest of the program, and reports
+//! what it finds to a `Sink`. The plugins of a `Plugins` registry are
+//! called in the order they were registered.
+//!
+//! ```
+//! use brouwer::diagnostics::Severity;
+//! use brouwer::loader::Loader;
+//! use brouwer::plugin::{CompilerPlugin, ModuleView, Plugins, Sink};
+//! use brouwer::resolve::{self, Resolution};
+//! use brouwer::vfs::MemoryFs;
+//!
+//! /// Module names must not mention the company's old name.
+//! struct NoOldName;
+//!
+//! impl CompilerPlugin for NoOldName {
+//!     fn name(&self) -> &str {
+//!         "no-old-name"
+//!     }
+//!
+//!     fn after_resolve(
+//!         &self,
+//!         view: &ModuleView,
+//!         _:    &Resolution,
+//!         sink: &mut Sink
+//!     ) {
+//!         let name = &view.loaded.module.name;
+//!
+//!         if name.name.contains("Acme") {
+//!             sink.warning("X0001", "module names Acme", Some(name.span));
+//!         }
+//!     }
+//! }
+//!
+//! let mut files = MemoryFs::new();
+//! files.insert("AcmeMain.bwr", "module AcmeMain\n\nx = 1\n");
+//!
+//! let program = Loader::with_fs(files, vec![".".into()])
+//!     .load("AcmeMain.bwr")
+//!     .unwrap();
+//! let mut plugins = Plugins::new();
+//! plugins.register(NoOldName);
+//!
+//! let view = ModuleView::new(&program, &program.modules[0]);
+//! let resolution = resolve::resolve(&view.loaded.module);
+//! let found = plugins.after_resolve(&view, &resolution);
+//!
+//! assert_eq!(found[0].severity, Severity::Warning);
+//! assert_eq!(found[0].message, "module names Acme");
+//! ```
+
+use diagnostics::{Diagnostic, Severity};
+use loader::{LoadedModule, Program};
+use resolve::Resolution;
+use token::Span;
+use typeck::Typing;
+
+
+/// Rules and passes of one's own. Each hook does nothing unless it is
+/// implemented.
+///
+/// Plugins may be called on several modules at once, from different
+/// threads, so they are `Sync`; anything one keeps between calls needs a
+/// lock.
+pub trait CompilerPlugin: Send + Sync {
+    /// The name of the plugin, such as `"no-old-name"`.
+    fn name(&self) -> &str;
+
+    /// Called once the module has been parsed, and lowered to a typed
+    /// syntax tree.
+    fn after_parse(&self, _view: &ModuleView, _sink: &mut Sink) {}
+
+    /// Called once the names in the module have been resolved.
+    fn after_resolve(
+        &self,
+        _view:       &ModuleView,
+        _resolution: &Resolution,
+        _sink:       &mut Sink
+    ) {}
+
+    /// Called once the types in the module have been inferred.
+    fn after_typeck(
+        &self,
+        _view:   &ModuleView,
+        _typing: &Typing,
+        _sink:   &mut Sink
+    ) {}
+}
+
+/// A module that a plugin is called on, and the program it is part of.
+#[derive(Clone, Copy)]
+pub struct ModuleView<'a> {
+    /// Every module of the program, each after every module it imports.
+    pub program: &'a Program,
+    /// The module.
+    pub loaded:  &'a LoadedModule,
+}
+
+/// Where plugins report what they find.
+#[derive(Clone, Debug, Default)]
+pub struct Sink {
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// The plugins to call, in order.
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Box<dyn CompilerPlugin>>,
+}
+
+
+impl<'a> ModuleView<'a> {
+    /// The view of `loaded`, a module of `program`.
+    pub fn new(program: &'a Program, loaded: &'a LoadedModule) -> Self {
+        ModuleView {
+            program: program,
+            loaded:  loaded,
+        }
+    }
+}
+
+impl Sink {
+    /// Reports `diagnostic`.
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports an error, with the code `code`, which should not be one
+    /// the compiler uses, such as `"E0001"`.
+    pub fn error<S: Into<String>>(
+        &mut self,
+        code:    &'static str,
+        message: S,
+        span:    Option<Span>
+    ) {
+        self.report(Severity::Error, code, message.into(), span);
+    }
+
+    /// Reports a warning, with the code `code`, which should not be one
+    /// the compiler uses.
+    pub fn warning<S: Into<String>>(
+        &mut self,
+        code:    &'static str,
+        message: S,
+        span:    Option<Span>
+    ) {
+        self.report(Severity::Warning, code, message.into(), span);
+    }
+
+    fn report(
+        &mut self,
+        severity: Severity,
+        code:     &'static str,
+        message:  String,
+        span:     Option<Span>
+    ) {
+        self.push(Diagnostic {
+            severity: severity,
+            code:     code,
+            message:  message,
+            span:     span,
+            hint:     None,
+        });
+    }
+}
+
+impl Plugins {
+    /// A registry with no plugins in it.
+    pub fn new() -> Self {
+        Plugins::default()
+    }
+
+    /// Adds `plugin`, to be called after those already registered.
+    pub fn register<P: CompilerPlugin + 'static>(&mut self, plugin: P) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    /// Whether there are no plugins to call.
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// The names of the plugins, in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins.iter().map(|plugin| plugin.name()).collect()
+    }
+
+    /// Calls each plugin's `after_parse` on `view`, and returns what they
+    /// reported.
+    pub fn after_parse(&self, view: &ModuleView) -> Vec<Diagnostic> {
+        self.each(|plugin, sink| plugin.after_parse(view, sink))
+    }
+
+    /// Calls each plugin's `after_resolve` on `view`, and returns what they
+    /// reported.
+    pub fn after_resolve(
+        &self,
+        view:       &ModuleView,
+        resolution: &Resolution
+    ) -> Vec<Diagnostic> {
+        self.each(|plugin, sink| plugin.after_resolve(view, resolution, sink))
+    }
+
+    /// Calls each plugin's `after_typeck` on `view`, and returns what they
+    /// reported.
+    pub fn after_typeck(
+        &self,
+        view:   &ModuleView,
+        typing: &Typing
+    ) -> Vec<Diagnostic> {
+        self.each(|plugin, sink| plugin.after_typeck(view, typing, sink))
+    }
+
+    fn each<F>(&self, mut hook: F) -> Vec<Diagnostic>
+        where F: FnMut(&dyn CompilerPlugin, &mut Sink)
+    {
+        let mut sink = Sink::default();
+
+        for plugin in &self.plugins {
+            hook(&**plugin, &mut sink);
+        }
+
+        sink.diagnostics
+    }
+}
@@ -0,0 +1,213 @@
+//! A Wadler-style pretty-printing document algebra. Documents describe
+//! where line breaks are *allowed*; `render` decides, group by group,
+//! whether a group fits on the rest of the line flat or has to be broken.
+
+/// Line width used when no other width is configured.
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// A document to be laid out by `render`.
+#[derive(Clone, Debug)]
+pub enum Doc {
+    /// The empty document.
+    Nil,
+    /// Literal text, which should not contain newlines.
+    Text(String),
+    /// A space when flat, a newline when broken.
+    Line,
+    /// Nothing when flat, a newline when broken.
+    SoftLine,
+    /// Always a newline; any group containing one is always broken.
+    HardLine,
+    /// Indents any line breaks within the inner document by the given
+    /// number of extra columns.
+    Nest(usize, Box<Doc>),
+    /// Documents laid out one after the other.
+    Concat(Vec<Doc>),
+    /// Lays the inner document out flat if it fits, otherwise broken.
+    Group(Box<Doc>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+
+/// `Doc::Nil`
+pub fn nil() -> Doc {
+    Doc::Nil
+}
+
+/// `Doc::Text`
+pub fn text<S: Into<String>>(s: S) -> Doc {
+    Doc::Text(s.into())
+}
+
+/// `Doc::Line`
+pub fn line() -> Doc {
+    Doc::Line
+}
+
+/// `Doc::SoftLine`
+pub fn softline() -> Doc {
+    Doc::SoftLine
+}
+
+/// `Doc::HardLine`
+pub fn hardline() -> Doc {
+    Doc::HardLine
+}
+
+/// `Doc::Nest`
+pub fn nest(indent: usize, doc: Doc) -> Doc {
+    Doc::Nest(indent, Box::new(doc))
+}
+
+/// `Doc::Group`
+pub fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+/// `Doc::Concat`
+pub fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+/// Concatenates `docs`, putting a copy of `sep` between each pair.
+pub fn join(docs: Vec<Doc>, sep: Doc) -> Doc {
+    let mut joined = Vec::with_capacity(2 * docs.len());
+
+    for (i, doc) in docs.into_iter().enumerate() {
+        if i > 0 {
+            joined.push(sep.clone());
+        }
+
+        joined.push(doc);
+    }
+
+    Doc::Concat(joined)
+}
+
+/// A comma-separated list between `open` and `close`, laid out either as
+/// `[a, b, c]` or, when that does not fit, with one item per line indented
+/// by `indent`:
+///
+/// ```text
+/// [
+///     a,
+///     b,
+///     c
+/// ]
+/// ```
+pub fn bracketed(
+    open:   &str,
+    items:  Vec<Doc>,
+    close:  &str,
+    indent: usize
+) -> Doc {
+    if items.is_empty() {
+        return concat(vec![text(open), text(close)]);
+    }
+
+    group(concat(vec![
+        text(open),
+        nest(indent, concat(vec![
+            softline(),
+            join(items, concat(vec![text(","), line()])),
+        ])),
+        softline(),
+        text(close),
+    ]))
+}
+
+/// Lays `doc` out within `width` columns where possible. Text that is
+/// itself wider than `width` is never split.
+pub fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0usize;
+    let mut stack = vec![(0usize, Mode::Break, doc)];
+
+    while let Some((indent, mode, d)) = stack.pop() {
+        match *d {
+            Doc::Nil => {},
+            Doc::Text(ref s) => {
+                out.push_str(s);
+                column += s.chars().count();
+            },
+            Doc::Line | Doc::SoftLine if mode == Mode::Flat => {
+                if let Doc::Line = *d {
+                    out.push(' ');
+                    column += 1;
+                }
+            },
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                newline(&mut out, indent);
+                column = indent;
+            },
+            Doc::Nest(extra, ref inner) => {
+                stack.push((indent + extra, mode, inner));
+            },
+            Doc::Concat(ref docs) => {
+                for inner in docs.iter().rev() {
+                    stack.push((indent, mode, inner));
+                }
+            },
+            Doc::Group(ref inner) => {
+                let inner_mode = if mode == Mode::Flat ||
+                                    fits(inner, width.saturating_sub(column))
+                {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+
+                stack.push((indent, inner_mode, inner));
+            },
+        }
+    }
+
+    out
+}
+
+/// Whether `doc` laid out flat takes up no more than `remaining` columns.
+fn fits(doc: &Doc, remaining: usize) -> bool {
+    let mut remaining = remaining as isize;
+    let mut stack = vec![doc];
+
+    while let Some(d) = stack.pop() {
+        match *d {
+            Doc::Nil | Doc::SoftLine => {},
+            Doc::Text(ref s) => remaining -= s.chars().count() as isize,
+            Doc::Line => remaining -= 1,
+            Doc::HardLine => return false,
+            Doc::Nest(_, ref inner) | Doc::Group(ref inner) => {
+                stack.push(inner);
+            },
+            Doc::Concat(ref docs) => {
+                for inner in docs.iter().rev() {
+                    stack.push(inner);
+                }
+            },
+        }
+
+        if remaining < 0 {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Starts a new line at `indent`, dropping any trailing blanks left on the
+/// line being ended.
+fn newline(out: &mut String, indent: usize) {
+    let trimmed_len = out.trim_end_matches(' ').len();
+    out.truncate(trimmed_len);
+
+    out.push('\n');
+
+    for _ in 0..indent {
+        out.push(' ');
+    }
+}
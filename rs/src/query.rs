@@ -0,0 +1,316 @@
+//! A small query language over parse trees, after CSS selectors.
+//!
+//! A selector is a chain of node tests. Each test is the name of a node
+//! kind, as `dump` writes it, or `*` for any node, optionally followed by
+//! `[lexeme="text"]` to also require that lexeme. Tests separated by blanks
+//! select descendants, and tests separated by `>` select children, so
+//! `FnDecl > Param Pattern Ident` finds every `Ident` inside a `Pattern`
+//! inside a `Param` of a function declaration. Selectors separated by
+//! commas select everything any of them does. A name that is not a node
+//! kind matches nothing.
+//!
+//! ```
+//! use brouwer::Parser;
+//! use brouwer::query;
+//!
+//! let src = "module Main\n\nfn add x y = x + y\n";
+//! let ast = Parser::from_str(src).parse().unwrap().unwrap();
+//!
+//! let params = query::query(&ast, "FnDecl > Param Pattern Ident").unwrap();
+//! let names: Vec<_> = params.iter().map(|p| p.val().lexeme()).collect();
+//! assert_eq!(names, vec!["x", "y"]);
+//!
+//! let adds = query::query(&ast, "Op[lexeme=\"+\"]").unwrap();
+//! assert_eq!(adds.len(), 1);
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use parser::AST;
+
+
+/// A parsed selector, ready to be matched against any number of trees.
+#[derive(Clone, Debug)]
+pub struct Selector {
+    alternatives: Vec<Vec<Step>>,
+}
+
+/// A selector that does not parse, and where it stops making sense.
+#[derive(Clone, Debug)]
+pub struct QueryError {
+    /// What is wrong.
+    pub message: &'static str,
+    /// The byte offset into the selector of the problem.
+    pub pos:     usize,
+}
+
+/// One test in a chain, and how it relates to the test before it.
+#[derive(Clone, Debug)]
+struct Step {
+    combinator: Combinator,
+    kind:       Option<String>,
+    lexeme:     Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// Reads a selector from its text.
+struct SelectorParser<'a> {
+    src:   &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+
+impl Selector {
+    /// Parses the text of a selector.
+    pub fn parse(src: &str) -> Result<Self, QueryError> {
+        SelectorParser {
+            src:   src,
+            chars: src.char_indices().peekable(),
+        }.parse()
+    }
+
+    /// Every node of `ast` that the selector selects, in preorder.
+    pub fn select<'a>(&self, ast: &'a AST) -> Vec<&'a AST> {
+        let mut selected = Vec::new();
+        let mut path = Vec::with_capacity(32);
+
+        self.walk(ast, &mut path, &mut selected);
+
+        selected
+    }
+
+    fn walk<'a>(
+        &self,
+        ast:      &'a AST,
+        path:     &mut Vec<&'a AST>,
+        selected: &mut Vec<&'a AST>
+    ) {
+        path.push(ast);
+
+        if self.alternatives.iter().any(|steps| matches_at(steps, path)) {
+            selected.push(ast);
+        }
+
+        for child in ast.children() {
+            self.walk(child, path, selected);
+        }
+
+        path.pop();
+    }
+}
+
+impl Step {
+    fn test(&self, ast: &AST) -> bool {
+        let token = ast.val();
+
+        self.kind.as_ref().is_none_or(|kind|
+            *kind == format!("{:?}", token.type_)
+        ) && self.lexeme.as_ref().is_none_or(|lexeme|
+            *lexeme == token.lexeme()
+        )
+    }
+}
+
+impl<'a> SelectorParser<'a> {
+    fn parse(mut self) -> Result<Selector, QueryError> {
+        let mut alternatives = vec![self.parse_chain()?];
+
+        while self.eat(',') {
+            alternatives.push(self.parse_chain()?);
+        }
+
+        if let Some(&(pos, _)) = self.chars.peek() {
+            return Err(QueryError {
+                message: "expected `,`, `>`, or another node test",
+                pos:     pos,
+            });
+        }
+
+        Ok(Selector {
+            alternatives: alternatives,
+        })
+    }
+
+    fn parse_chain(&mut self) -> Result<Vec<Step>, QueryError> {
+        let mut steps = Vec::new();
+        let mut combinator = Combinator::Descendant;
+
+        loop {
+            self.skip_blanks();
+
+            match self.chars.peek().cloned() {
+                Some((pos, '>')) => {
+                    if steps.is_empty() || combinator == Combinator::Child {
+                        return Err(QueryError {
+                            message: "expected a node test before `>`",
+                            pos:     pos,
+                        });
+                    }
+
+                    self.chars.next();
+                    combinator = Combinator::Child;
+                },
+                Some((_, c)) if c == '*' || is_name_char(c) => {
+                    steps.push(self.parse_step(combinator)?);
+                    combinator = Combinator::Descendant;
+                },
+                _ => break,
+            }
+        }
+
+        if steps.is_empty() || combinator == Combinator::Child {
+            return Err(QueryError {
+                message: "expected a node test",
+                pos:     self.pos(),
+            });
+        }
+
+        Ok(steps)
+    }
+
+    fn parse_step(
+        &mut self,
+        combinator: Combinator
+    ) -> Result<Step, QueryError> {
+        let kind = if self.eat('*') {
+            None
+        } else {
+            let start = self.pos();
+
+            while self.chars.peek().is_some_and(|&(_, c)| is_name_char(c)) {
+                self.chars.next();
+            }
+
+            Some(self.src[start..self.pos()].to_string())
+        };
+
+        let lexeme = if self.eat('[') {
+            Some(self.parse_lexeme_test()?)
+        } else {
+            None
+        };
+
+        Ok(Step {
+            combinator: combinator,
+            kind:       kind,
+            lexeme:     lexeme,
+        })
+    }
+
+    /// Parses `lexeme="text"]`, after the `[`. Within the quotes, `\"`
+    /// and `\\` stand for `"` and `\`.
+    fn parse_lexeme_test(&mut self) -> Result<String, QueryError> {
+        for expected in "lexeme=\"".chars() {
+            if !self.eat(expected) {
+                return Err(QueryError {
+                    message: "expected `lexeme=\"` after `[`",
+                    pos:     self.pos(),
+                });
+            }
+        }
+
+        let mut lexeme = String::new();
+
+        loop {
+            match self.chars.next() {
+                Some((_, '"'))  => break,
+                Some((_, '\\')) => if let Some((_, c)) = self.chars.next() {
+                    lexeme.push(c);
+                },
+                Some((_, c))    => lexeme.push(c),
+                None            => {
+                    return Err(QueryError {
+                        message: "expected closing `\"`",
+                        pos:     self.src.len(),
+                    });
+                },
+            }
+        }
+
+        if !self.eat(']') {
+            return Err(QueryError {
+                message: "expected `]`",
+                pos:     self.pos(),
+            });
+        }
+
+        Ok(lexeme)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        match self.chars.peek() {
+            Some(&(_, c)) if c == expected => {
+                self.chars.next();
+
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.chars.peek().is_some_and(|&(_, c)| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn pos(&mut self) -> usize {
+        self.chars.peek().map_or(self.src.len(), |&(pos, _)| pos)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "selector column {}: {}", self.pos + 1, self.message)
+    }
+}
+
+impl Error for QueryError {}
+
+/// Every node of `ast` that `selector` selects, in preorder.
+pub fn query<'a>(
+    ast:      &'a AST,
+    selector: &str
+) -> Result<Vec<&'a AST>, QueryError> {
+    Selector::parse(selector).map(|selector| selector.select(ast))
+}
+
+/// Whether the last node of `path` is selected by `steps`, given that the
+/// rest of `path` are its ancestors, outermost first.
+fn matches_at(steps: &[Step], path: &[&AST]) -> bool {
+    let (step, rest) = match steps.split_last() {
+        Some(split) => split,
+        None        => return true,
+    };
+    let (node, ancestors) = match path.split_last() {
+        Some(split) => split,
+        None        => return false,
+    };
+
+    if !step.test(node) {
+        return false;
+    }
+
+    if rest.is_empty() {
+        return true;
+    }
+
+    match step.combinator {
+        Combinator::Child      => matches_at(rest, ancestors),
+        Combinator::Descendant => (1..ancestors.len() + 1).rev().any(|end|
+            matches_at(rest, &ancestors[..end])
+        ),
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
@@ -0,0 +1,927 @@
+//! Name resolution: working out which definition each name in a module
+//! (see `ast`) refers to.
+//!
+//! Every function, lambda, and block has a scope of its own, nested in the
+//! scope it is written in. A function's parameters are in the same scope
+//! as the lines of its body, as are the names a `case` branch's pattern
+//! binds and those of a `for` loop's, and a `catch`'s name is in the same
+//! scope as its handler.
+//!
+//! Functions can be used anywhere in the block they are declared in, even
+//! before their declaration, and so can anything bound at the top level of
+//! the module. Other bindings can only be used after them. `var x = ...`
+//! always declares `x`, but plain `x = ...` only does if there is no `x`
+//! to assign to already.
+//!
+//! A pattern may bind each name only once, each alternative of an
+//! or-pattern must bind the same names, and a name that the pattern of
+//! a `case` branch, a `for` loop, or a generator binds but that is never
+//! used is warned about, unless it starts with `_`.
+//!
+//! ```
+//! use brouwer::{Parser, ast, resolve};
+//!
+//! let src = "module Main\n\nfn f x\n    x + y\n";
+//! let tree = Parser::from_str(src).parse().unwrap().unwrap();
+//! let resolution = resolve::resolve(&ast::lower(&tree).unwrap());
+//!
+//! assert_eq!(resolution.errors.len(), 1);
+//! assert_eq!(resolution.errors[0].to_string(), "4:9: y is not defined");
+//!
+//! let src = "module Main\n\nfn f pairs\n    for (k, v) in pairs\n        \
+//!            print k\n    case pairs\n        (x, x) => x\n";
+//! let tree = Parser::from_str(src).parse().unwrap().unwrap();
+//! let resolution = resolve::resolve(&ast::lower(&tree).unwrap());
+//!
+//! assert_eq!(resolution.errors[0].to_string(),
+//!            "7:13: x is bound twice in one pattern, first at 7:10");
+//! assert_eq!(resolution.warnings[0].to_string(),
+//!            "4:13: v is bound by the pattern but never used");
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use ast::{Expr, ExprKind, FnDecl, Ident, ImportNames, Module, Name,
+          Operator, Param, Pattern, PatternKind, Qualifier, Stmt};
+use diagnostics::{Diagnostic, Severity};
+use token::Span;
+
+
+/// The names that are in scope in every module without being imported.
+/// `NaN` and `Infinity` are among them because they are only parsed as
+/// literals after a `-`, and as names everywhere else, as are the
+/// constructors of runtime errors (see `runtime`), and `Ok` and `Err`,
+/// which make a `Result` of a success and of a failure.
+pub const PRELUDE: &[&str] = &[
+    "DivideByZero", "Err", "IOError", "IndexError", "Infinity", "KeyError",
+    "NaN", "Ok", "TypeError", "input", "length", "print", "put", "read",
+];
+
+/// An index into `Resolution::scopes`.
+pub type ScopeId = usize;
+
+/// The scopes of a module, with what is defined in each, and what was
+/// wrong with the names in it.
+#[derive(Clone, Debug)]
+pub struct Resolution {
+    /// Every scope, the module's first. Each comes after its parent.
+    pub scopes: Vec<Scope>,
+    /// What was wrong, in the order it is in the module.
+    pub errors:   Vec<ResolveError>,
+    /// What is probably not what was meant, in the order it is in the
+    /// module.
+    pub warnings: Vec<ResolveWarning>,
+}
+
+/// A scope, and the symbols defined in it.
+#[derive(Clone, Debug)]
+pub struct Scope {
+    /// What the scope is of.
+    pub kind:    ScopeKind,
+    /// The scope this one is nested in, unless this is the module's.
+    pub parent:  Option<ScopeId>,
+    /// What is defined in the scope, in the order it is defined.
+    pub symbols: Vec<Symbol>,
+    /// Where in `symbols` each module is.
+    modules:     HashMap<String, usize>,
+    /// Where in `symbols` everything else is.
+    values:      HashMap<String, usize>,
+}
+
+/// What a scope is of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScopeKind {
+    /// The top level of the module.
+    Module,
+    /// A function's parameters and body.
+    Function,
+    /// A lambda's parameters.
+    Lambda,
+    /// The body of an `if`, `while`, `for`, `try`, or `case` branch, or
+    /// a generator in a comprehension and what follows it.
+    Block,
+}
+
+/// A name defined in a scope.
+#[derive(Clone, Debug)]
+pub struct Symbol {
+    /// The name.
+    pub name: String,
+    /// How it is defined.
+    pub kind: SymbolKind,
+    /// Where it is defined.
+    pub span: Span,
+    /// Where it is used, in the order the uses were found.
+    pub uses: Vec<Span>,
+}
+
+/// How a `Symbol` is defined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SymbolKind {
+    /// A module: this one, one that is imported, or an import's alias.
+    /// Modules are named apart from everything else, in `A::b`.
+    Module,
+    /// A name brought in by an import.
+    Import,
+    /// A function declaration.
+    Fn,
+    /// A function or lambda parameter.
+    Param,
+    /// A binding, `x = 1`.
+    Let,
+    /// A mutable binding, `var x = 1`.
+    Var,
+    /// A name bound by the pattern of a `case` branch, a `for` loop, or a
+    /// comprehension's generator.
+    Pattern,
+    /// The name of the error in a `catch`.
+    Catch,
+}
+
+/// Something wrong with the names in a module.
+#[derive(Clone, Debug)]
+pub enum ResolveError {
+    /// A name used, but not defined anywhere it can be seen from.
+    Undefined {
+        /// The name.
+        name: String,
+        /// Where it is used.
+        span: Span,
+    },
+    /// A module used in `A::b` that is not this one or imported.
+    UndefinedModule {
+        /// The module's name.
+        name: String,
+        /// Where it is used.
+        span: Span,
+    },
+    /// A name defined twice in the same scope.
+    Duplicate {
+        /// The name.
+        name:  String,
+        /// Where it is defined again.
+        span:  Span,
+        /// Where it was first defined.
+        first: Span,
+    },
+    /// A name bound twice by the same pattern, as in `(x, x)`.
+    DuplicateBinding {
+        /// The name.
+        name:  String,
+        /// Where it is bound again.
+        span:  Span,
+        /// Where the pattern first binds it.
+        first: Span,
+    },
+    /// A name bound by some alternatives of an or-pattern but not others,
+    /// as in `(x, 0) | (0, y)`.
+    UnevenBinding {
+        /// The name.
+        name: String,
+        /// The alternative that does not bind it, or where another binds
+        /// it when the first does not.
+        span: Span,
+    },
+}
+
+/// Something about the names in a module that is probably a mistake.
+#[derive(Clone, Debug)]
+pub enum ResolveWarning {
+    /// A name bound by the pattern of a `case` branch, a `for` loop, or a
+    /// comprehension's generator, and never used.
+    UnusedBinding {
+        /// The name.
+        name: String,
+        /// Where it is bound.
+        span: Span,
+    },
+}
+
+/// Walks a module, keeping track of the scope it is in.
+struct Resolver {
+    scopes:       Vec<Scope>,
+    current:      ScopeId,
+    errors:       Vec<ResolveError>,
+    /// Whether an `import ... hiding` may have brought names into scope
+    /// that there is no knowing of.
+    open_imports: bool,
+}
+
+
+impl Resolution {
+    /// The symbol `name` refers to in `scope`, if it is defined there or in
+    /// a scope `scope` is nested in. Modules are only found if `module`.
+    pub fn lookup(
+        &self,
+        scope:  ScopeId,
+        name:   &str,
+        module: bool
+    ) -> Option<&Symbol> {
+        let mut scope = Some(scope);
+
+        while let Some(id) = scope {
+            if let Some(ix) = self.scopes[id].get(name, module) {
+                return Some(&self.scopes[id].symbols[ix]);
+            }
+
+            scope = self.scopes[id].parent;
+        }
+
+        None
+    }
+
+    /// Every symbol, in every scope.
+    pub fn symbols(&self) -> Vec<&Symbol> {
+        self.scopes.iter().flat_map(|scope| &scope.symbols).collect()
+    }
+}
+
+impl Scope {
+    fn new(kind: ScopeKind, parent: Option<ScopeId>) -> Self {
+        Scope {
+            kind:    kind,
+            parent:  parent,
+            symbols: Vec::new(),
+            modules: HashMap::new(),
+            values:  HashMap::new(),
+        }
+    }
+
+    /// Where in `symbols` the symbol called `name` is, if it is defined in
+    /// this scope itself. Modules are only found if `module`.
+    pub fn get(&self, name: &str, module: bool) -> Option<usize> {
+        if module {
+            self.modules.get(name).cloned()
+        } else {
+            self.values.get(name).cloned()
+        }
+    }
+
+    fn insert(&mut self, symbol: Symbol) {
+        let ix = self.symbols.len();
+
+        if symbol.kind == SymbolKind::Module {
+            self.modules.insert(symbol.name.clone(), ix);
+        } else {
+            self.values.insert(symbol.name.clone(), ix);
+        }
+
+        self.symbols.push(symbol);
+    }
+}
+
+impl ResolveError {
+    /// Where the error is.
+    pub fn span(&self) -> Span {
+        match *self {
+            ResolveError::Undefined { span, .. }        |
+            ResolveError::UndefinedModule { span, .. }  |
+            ResolveError::Duplicate { span, .. }        |
+            ResolveError::DuplicateBinding { span, .. } |
+            ResolveError::UnevenBinding { span, .. }    => span,
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResolveError::Undefined { ref name, span } => write!(
+                f,
+                "{}:{}: {} is not defined",
+                span.line,
+                span.col,
+                name
+            ),
+            ResolveError::UndefinedModule { ref name, span } => write!(
+                f,
+                "{}:{}: module {} is not imported",
+                span.line,
+                span.col,
+                name
+            ),
+            ResolveError::Duplicate { ref name, span, first } => write!(
+                f,
+                "{}:{}: {} is already defined at {}:{}",
+                span.line,
+                span.col,
+                name,
+                first.line,
+                first.col
+            ),
+            ResolveError::DuplicateBinding { ref name, span, first } => write!(
+                f,
+                "{}:{}: {} is bound twice in one pattern, first at {}:{}",
+                span.line,
+                span.col,
+                name,
+                first.line,
+                first.col
+            ),
+            ResolveError::UnevenBinding { ref name, span } => write!(
+                f,
+                "{}:{}: {} is not bound by every alternative of the pattern",
+                span.line,
+                span.col,
+                name
+            ),
+        }
+    }
+}
+
+impl Error for ResolveError {}
+
+impl ResolveWarning {
+    /// Where the problem is.
+    pub fn span(&self) -> Span {
+        match *self {
+            ResolveWarning::UnusedBinding { span, .. } => span,
+        }
+    }
+
+    /// A stable code for the kind of problem.
+    pub fn code(&self) -> &'static str {
+        match *self {
+            ResolveWarning::UnusedBinding { .. } => "W0201",
+        }
+    }
+
+    /// What is wrong, without the location.
+    pub fn message(&self) -> String {
+        match *self {
+            ResolveWarning::UnusedBinding { ref name, .. } => {
+                format!("{} is bound by the pattern but never used", name)
+            },
+        }
+    }
+
+    /// How the problem might be fixed.
+    pub fn hint(&self) -> Option<String> {
+        match *self {
+            ResolveWarning::UnusedBinding { ref name, .. } => Some(format!(
+                "match it with `_`, or call it `_{}` if it is unused on \
+                 purpose",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ResolveWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span();
+
+        write!(f, "{}:{}: {}", span.line, span.col, self.message())
+    }
+}
+
+impl<'a> From<&'a ResolveWarning> for Diagnostic {
+    fn from(warning: &'a ResolveWarning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code:     warning.code(),
+            message:  warning.message(),
+            span:     Some(warning.span()),
+            hint:     warning.hint(),
+        }
+    }
+}
+
+impl Resolver {
+    fn new() -> Self {
+        Resolver {
+            scopes:       vec![Scope::new(ScopeKind::Module, None)],
+            current:      0,
+            errors:       Vec::new(),
+            open_imports: false,
+        }
+    }
+
+    fn module(&mut self, module: &Module) {
+        self.define(&module.name, SymbolKind::Module);
+
+        for import in &module.imports {
+            match import.names {
+                ImportNames::Alias(ref alias) => {
+                    self.define(alias, SymbolKind::Module);
+                },
+                ImportNames::Exposing(ref names) => {
+                    self.define(&import.module, SymbolKind::Module);
+
+                    for name in names {
+                        self.define(name, SymbolKind::Import);
+                    }
+                },
+                ImportNames::Hiding(_) => {
+                    self.define(&import.module, SymbolKind::Module);
+                    self.open_imports = true;
+                },
+            }
+        }
+
+        // Everything bound at the top level can be used anywhere in the
+        // module, as it is all bound before a function can be called.
+        self.hoist(&module.body, true);
+
+        if let Some(ref exports) = module.exports {
+            for name in &exports.names {
+                self.use_name(name);
+            }
+        }
+
+        self.stmts(&module.body, true);
+    }
+
+    /// Defines what can be used in a block before it is declared: its
+    /// functions, and if `bindings`, everything else it binds too.
+    fn hoist(&mut self, stmts: &[Stmt], bindings: bool) {
+        for stmt in stmts {
+            match *stmt {
+                Stmt::Fn(ref fn_decl) => {
+                    self.define(&fn_decl.name, SymbolKind::Fn);
+                },
+                Stmt::Let(ref let_) if bindings => {
+                    self.bind(&let_.pattern, let_.mutable);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Resolves the lines of a block in the current scope, given whether
+    /// what they bind has been hoisted already.
+    fn stmts(&mut self, stmts: &[Stmt], hoisted: bool) {
+        for stmt in stmts {
+            match *stmt {
+                Stmt::Let(ref let_) => {
+                    self.expr(&let_.value);
+
+                    if !hoisted {
+                        self.bind(&let_.pattern, let_.mutable);
+                    }
+                },
+                Stmt::Fn(ref fn_decl) => self.fn_decl(fn_decl),
+                Stmt::Fixity(ref fixity) => self.operator(&fixity.op),
+                Stmt::Expr(ref expr) => self.expr(expr),
+            }
+        }
+    }
+
+    /// Resolves the lines of a block in a scope of its own.
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.enter(ScopeKind::Block);
+        self.hoist(stmts, false);
+        self.stmts(stmts, false);
+        self.exit();
+    }
+
+    fn fn_decl(&mut self, fn_decl: &FnDecl) {
+        self.enter(ScopeKind::Function);
+        self.params(&fn_decl.params);
+        self.hoist(&fn_decl.body, false);
+        self.stmts(&fn_decl.body, false);
+        self.exit();
+    }
+
+    fn params(&mut self, params: &[Param]) {
+        for param in params {
+            self.pattern(&param.pattern, SymbolKind::Param);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::Int(_)  |
+            ExprKind::Real(_) |
+            ExprKind::Bool(_) |
+            ExprKind::Char(_) |
+            ExprKind::Str(_)  => {},
+            ExprKind::Name(ref name) => self.name(name),
+            ExprKind::Op(ref op) => self.operator(op),
+            ExprKind::Apply(ref f, ref args) => {
+                self.expr(f);
+
+                for arg in args {
+                    self.expr(arg);
+                }
+            },
+            ExprKind::BinOp(ref op, ref lhs, ref rhs) => {
+                self.expr(lhs);
+                self.operator(op);
+                self.expr(rhs);
+            },
+            ExprKind::Range(ref lo, ref hi, _) => {
+                self.expr(lo);
+                self.expr(hi);
+            },
+            ExprKind::Index(ref indexed, ref index) => {
+                self.expr(indexed);
+                self.expr(index);
+            },
+            ExprKind::Slice(ref sliced, ref low, ref high) => {
+                self.expr(sliced);
+
+                for end in low.iter().chain(high) {
+                    self.expr(end);
+                }
+            },
+            ExprKind::Neg(ref operand)       |
+            ExprKind::Return(ref operand)    |
+            ExprKind::Propagate(ref operand) => self.expr(operand),
+            ExprKind::Tuple(ref elems) |
+            ExprKind::List(ref elems)  |
+            ExprKind::Set(ref elems)   => {
+                for elem in elems {
+                    self.expr(elem);
+                }
+            },
+            ExprKind::Dict(ref entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            },
+            ExprKind::ListComp(ref elem, ref qualifiers) |
+            ExprKind::SetComp(ref elem, ref qualifiers)  => {
+                let depth = self.qualifiers(qualifiers);
+                self.expr(elem);
+                self.exit_n(depth);
+            },
+            ExprKind::DictComp(ref entry, ref qualifiers) => {
+                let depth = self.qualifiers(qualifiers);
+                self.expr(&entry.0);
+                self.expr(&entry.1);
+                self.exit_n(depth);
+            },
+            ExprKind::Lambda(ref params, ref body) => {
+                self.enter(ScopeKind::Lambda);
+                self.params(params);
+                self.expr(body);
+                self.exit();
+            },
+            ExprKind::If(ref cond, ref body, ref else_body) => {
+                self.expr(cond);
+                self.block(body);
+
+                if let Some(ref else_body) = *else_body {
+                    self.block(else_body);
+                }
+            },
+            ExprKind::Case(ref subject, ref branches) => {
+                self.expr(subject);
+
+                for branch in branches {
+                    self.enter(ScopeKind::Block);
+                    self.pattern(&branch.pattern, SymbolKind::Pattern);
+                    if let Some(ref guard) = branch.guard {
+                        self.expr(guard);
+                    }
+                    self.hoist(&branch.body, false);
+                    self.stmts(&branch.body, false);
+                    self.exit();
+                }
+            },
+            ExprKind::Try(ref body, ref err, ref handler) => {
+                self.block(body);
+
+                self.enter(ScopeKind::Block);
+                self.define(err, SymbolKind::Catch);
+                self.hoist(handler, false);
+                self.stmts(handler, false);
+                self.exit();
+            },
+            ExprKind::While(ref cond, ref body) => {
+                self.expr(cond);
+                self.block(body);
+            },
+            ExprKind::WhileLet(ref pattern, ref iterated, ref body) |
+            ExprKind::For(ref pattern, ref iterated, ref body) => {
+                self.expr(iterated);
+
+                self.enter(ScopeKind::Block);
+                self.pattern(pattern, SymbolKind::Pattern);
+                self.hoist(body, false);
+                self.stmts(body, false);
+                self.exit();
+            },
+        }
+    }
+
+    /// Resolves the qualifiers of a comprehension, each generator in a
+    /// scope of its own inside the last, and returns how many scopes were
+    /// entered.
+    fn qualifiers(&mut self, qualifiers: &[Qualifier]) -> usize {
+        let mut depth = 0;
+
+        for qualifier in qualifiers {
+            match *qualifier {
+                Qualifier::Generator(ref pattern, ref iterated) => {
+                    self.expr(iterated);
+                    self.enter(ScopeKind::Block);
+                    self.pattern(pattern, SymbolKind::Pattern);
+
+                    depth += 1;
+                },
+                Qualifier::Guard(ref cond) => self.expr(cond),
+            }
+        }
+
+        depth
+    }
+
+    fn name(&mut self, name: &Name) {
+        match *name {
+            Name::Plain(ref ident) | Name::Member(ref ident, _) => {
+                self.use_name(ident);
+            },
+            Name::Scoped(ref modules, _) => self.use_module(&modules[0]),
+        }
+    }
+
+    fn operator(&mut self, op: &Operator) {
+        if let Operator::Infixed(ref name) = *op {
+            self.name(name);
+        }
+    }
+
+    /// Binds the names in the pattern of `x = ...` or `var x = ...`.
+    fn bind(&mut self, pattern: &Pattern, mutable: bool) {
+        if mutable {
+            return self.pattern(pattern, SymbolKind::Var);
+        }
+
+        for ident in self.bound_by(pattern) {
+            // Otherwise this is an assignment to what is there already.
+            if self.find(self.current, &ident.name, false).is_none() {
+                self.define(ident, SymbolKind::Let);
+            }
+        }
+    }
+
+    /// Defines every name that `pattern` binds as a `kind`.
+    fn pattern(&mut self, pattern: &Pattern, kind: SymbolKind) {
+        for ident in self.bound_by(pattern) {
+            self.define(ident, kind);
+        }
+    }
+
+    /// The names that `pattern` binds, each the first time it binds it.
+    /// Any it binds again are reported.
+    fn bound_by<'p>(&mut self, pattern: &'p Pattern) -> Vec<&'p Ident> {
+        let mut bound: Vec<&Ident> = Vec::new();
+
+        for ident in pattern.idents() {
+            let first = bound.iter()
+                             .find(|first| first.name == ident.name)
+                             .map(|first| first.span);
+
+            match first {
+                Some(first) => self.errors.push(
+                    ResolveError::DuplicateBinding {
+                        name:  ident.name.clone(),
+                        span:  ident.span,
+                        first: first,
+                    }
+                ),
+                None => bound.push(ident),
+            }
+        }
+
+        self.alternatives(pattern);
+
+        bound
+    }
+
+    /// Reports each name that an alternative of an or-pattern in `pattern`
+    /// binds but the first does not, or the other way around.
+    fn alternatives(&mut self, pattern: &Pattern) {
+        match pattern.kind {
+            PatternKind::Or(ref alternatives) => {
+                let first = alternatives[0].idents();
+
+                for alternative in &alternatives[1..] {
+                    let idents = alternative.idents();
+
+                    for ident in &first {
+                        if !idents.iter().any(|i| i.name == ident.name) {
+                            self.errors.push(ResolveError::UnevenBinding {
+                                name: ident.name.clone(),
+                                span: alternative.span,
+                            });
+                        }
+                    }
+
+                    for ident in &idents {
+                        if !first.iter().any(|i| i.name == ident.name) {
+                            self.errors.push(ResolveError::UnevenBinding {
+                                name: ident.name.clone(),
+                                span: ident.span,
+                            });
+                        }
+                    }
+                }
+
+                for alternative in alternatives {
+                    self.alternatives(alternative);
+                }
+            },
+            PatternKind::Tuple(ref elems) |
+            PatternKind::List(ref elems)  |
+            PatternKind::Set(ref elems)   => {
+                for elem in elems {
+                    self.alternatives(elem);
+                }
+            },
+            PatternKind::Dict(ref entries) => {
+                for (key, value) in entries {
+                    self.alternatives(key);
+                    self.alternatives(value);
+                }
+            },
+            PatternKind::Prefix(_, ref inner) |
+            PatternKind::Suffix(ref inner, _) |
+            PatternKind::As(_, ref inner)     => self.alternatives(inner),
+            _ => {},
+        }
+    }
+
+    fn define(&mut self, ident: &Ident, kind: SymbolKind) {
+        let scope = &mut self.scopes[self.current];
+
+        if let Some(ix) = scope.get(&ident.name, kind == SymbolKind::Module) {
+            self.errors.push(ResolveError::Duplicate {
+                name:  ident.name.clone(),
+                span:  ident.span,
+                first: scope.symbols[ix].span,
+            });
+
+            return;
+        }
+
+        scope.insert(Symbol {
+            name: ident.name.clone(),
+            kind: kind,
+            span: ident.span,
+            uses: Vec::new(),
+        });
+    }
+
+    fn use_name(&mut self, ident: &Ident) {
+        let found = self.find(self.current, &ident.name, false);
+
+        if let Some((scope, ix)) = found {
+            self.scopes[scope].symbols[ix].uses.push(ident.span);
+        } else if !self.open_imports &&
+                  !PRELUDE.contains(&ident.name.as_str())
+        {
+            self.errors.push(ResolveError::Undefined {
+                name: ident.name.clone(),
+                span: ident.span,
+            });
+        }
+    }
+
+    fn use_module(&mut self, ident: &Ident) {
+        let found = self.find(self.current, &ident.name, true);
+
+        if let Some((scope, ix)) = found {
+            self.scopes[scope].symbols[ix].uses.push(ident.span);
+        } else {
+            self.errors.push(ResolveError::UndefinedModule {
+                name: ident.name.clone(),
+                span: ident.span,
+            });
+        }
+    }
+
+    /// Where the symbol `name` refers to from `scope` is: its scope, and
+    /// its index in that scope.
+    fn find(
+        &self,
+        scope:  ScopeId,
+        name:   &str,
+        module: bool
+    ) -> Option<(ScopeId, usize)> {
+        let mut scope = Some(scope);
+
+        while let Some(id) = scope {
+            if let Some(ix) = self.scopes[id].get(name, module) {
+                return Some((id, ix));
+            }
+
+            scope = self.scopes[id].parent;
+        }
+
+        None
+    }
+
+    fn enter(&mut self, kind: ScopeKind) {
+        let scope = Scope::new(kind, Some(self.current));
+        self.scopes.push(scope);
+        self.current = self.scopes.len() - 1;
+    }
+
+    fn exit(&mut self) {
+        self.current = self.scopes[self.current].parent.unwrap_or(0);
+    }
+
+    fn exit_n(&mut self, n: usize) {
+        for _ in 0..n {
+            self.exit();
+        }
+    }
+}
+
+/// Resolves every name in `module`.
+pub fn resolve(module: &Module) -> Resolution {
+    let mut resolver = Resolver::new();
+    resolver.module(module);
+
+    // What is hoisted is defined before anything else is looked at.
+    resolver.errors.sort_by_key(|e| (e.span().line, e.span().col));
+
+    let mut warnings: Vec<_> = resolver.scopes
+        .iter()
+        .flat_map(|scope| &scope.symbols)
+        .filter(|symbol| {
+            symbol.kind == SymbolKind::Pattern &&
+                symbol.uses.is_empty() &&
+                !symbol.name.starts_with('_')
+        })
+        .map(|symbol| ResolveWarning::UnusedBinding {
+            name: symbol.name.clone(),
+            span: symbol.span,
+        })
+        .collect();
+
+    warnings.sort_by_key(|w| (w.span().line, w.span().col));
+
+    Resolution {
+        scopes:   resolver.scopes,
+        errors:   resolver.errors,
+        warnings: warnings,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast;
+    use parser::Parser;
+
+    fn errors(src: &str) -> Vec<String> {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        resolve(&ast::lower(&tree).unwrap()).errors
+                                             .iter()
+                                             .map(|e| e.to_string())
+                                             .collect()
+    }
+
+    #[test]
+    fn bindings_other_than_functions_cannot_be_used_before_them() {
+        let src = "module Main\n\n\
+                   fn f\n    g 1\n    y = x\n    x = 2\n    y\n\n\
+                   fn g x = x\n";
+
+        assert_eq!(errors(src), vec!["5:9: x is not defined"]);
+    }
+
+    #[test]
+    fn a_name_defined_twice_in_one_scope_is_a_duplicate() {
+        let src = "module Main\n\nfn f x = x\n\nfn f y = y\n";
+
+        assert_eq!(errors(src), vec!["5:4: f is already defined at 3:4"]);
+    }
+
+    #[test]
+    fn qualified_names_need_their_module_imported() {
+        let src = "module Main\n\nx = Other::y\n";
+
+        assert_eq!(errors(src), vec!["3:5: module Other is not imported"]);
+    }
+
+    #[test]
+    fn every_alternative_of_an_or_pattern_binds_the_same_names() {
+        let src = "module Main\n\n\
+                   fn f p\n    case p\n        (x, 0) | (0, y) => 1\n";
+
+        let errors = errors(src);
+
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors.iter().all(|e| {
+            e.ends_with("is not bound by every alternative of the pattern")
+        }));
+    }
+
+    #[test]
+    fn names_are_not_visible_outside_their_block() {
+        let src = "module Main\n\n\
+                   fn f c\n    if c\n        inner = 1\n    inner\n";
+
+        assert_eq!(errors(src), vec!["6:5: inner is not defined"]);
+    }
+}
@@ -0,0 +1,160 @@
+//! What running a program can fail with.
+//!
+//! A runtime error is a value like any other, of the type `Error`, made by
+//! one of the constructors in `CONSTRUCTORS`: `DivideByZero`, or `KeyError`
+//! applied to the key that was missing, and so on. They are in scope in
+//! every module (see `resolve::PRELUDE`), so that a program can make them
+//! itself, and so that a `catch` can tell them apart by their constructor,
+//! once the name it binds is typed as an `Error`.
+//!
+//! On the Rust side, a `RuntimeError` is one of those values, with the
+//! constructor as its variant, for whatever runs a program to report or
+//! map to errors of its own.
+//!
+//! ```
+//! use brouwer::compiler::Constant;
+//! use brouwer::runtime::RuntimeError;
+//!
+//! let error = RuntimeError::IndexError { index: 3, len: 2 };
+//! let (constructor, fields) = error.to_value();
+//!
+//! assert_eq!(constructor, "IndexError");
+//! assert_eq!(fields, vec![Constant::Int(3), Constant::Int(2)]);
+//! assert_eq!(RuntimeError::from_value(constructor, &fields), Some(error));
+//! assert_eq!(
+//!     RuntimeError::KeyError("colour".to_string()).to_string(),
+//!     "no key colour"
+//! );
+//! ```
+
+use std::error::Error;
+use std::fmt;
+
+use compiler::Constant;
+
+
+/// The name of the type of runtime errors.
+pub const ERROR_TYPE: &str = "Error";
+
+/// Each constructor of runtime errors, with the types of its fields, in
+/// order.
+pub const CONSTRUCTORS: &[(&str, &[&str])] = &[
+    ("DivideByZero", &[]),
+    ("KeyError",     &["Str"]),
+    ("TypeError",    &["Str", "Str"]),
+    ("IndexError",   &["Int", "Int"]),
+    ("IOError",      &["Str"]),
+];
+
+/// A runtime error, by the constructor it was made with.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RuntimeError {
+    /// An integer divided by, or taken modulo, zero.
+    DivideByZero,
+    /// A key not in the dictionary it was looked up in, as it would be
+    /// printed.
+    KeyError(String),
+    /// A value of one type where another was needed, which only a program
+    /// that was not typechecked can run into.
+    TypeError {
+        /// The type needed.
+        expected: String,
+        /// The type of the value.
+        found:    String,
+    },
+    /// An index past either end of what was indexed.
+    IndexError {
+        /// The index.
+        index: i64,
+        /// How long what was indexed is.
+        len:   i64,
+    },
+    /// Reading or writing failed, with the message the system gave.
+    IOError(String),
+}
+
+
+impl RuntimeError {
+    /// The name of the constructor the error is made with.
+    pub fn constructor(&self) -> &'static str {
+        match *self {
+            RuntimeError::DivideByZero      => "DivideByZero",
+            RuntimeError::KeyError(_)       => "KeyError",
+            RuntimeError::TypeError { .. }  => "TypeError",
+            RuntimeError::IndexError { .. } => "IndexError",
+            RuntimeError::IOError(_)        => "IOError",
+        }
+    }
+
+    /// The error as a value: its constructor, and its fields, in the order
+    /// given in `CONSTRUCTORS`.
+    pub fn to_value(&self) -> (&'static str, Vec<Constant>) {
+        let fields = match *self {
+            RuntimeError::DivideByZero => Vec::new(),
+            RuntimeError::KeyError(ref key) => vec![Constant::Str(key.clone())],
+            RuntimeError::TypeError { ref expected, ref found } => vec![
+                Constant::Str(expected.clone()),
+                Constant::Str(found.clone()),
+            ],
+            RuntimeError::IndexError { index, len } => vec![
+                Constant::Int(index),
+                Constant::Int(len),
+            ],
+            RuntimeError::IOError(ref message) => {
+                vec![Constant::Str(message.clone())]
+            },
+        };
+
+        (self.constructor(), fields)
+    }
+
+    /// The error made by applying `constructor` to `fields`, or `None` if
+    /// that is not a constructor of runtime errors, or those are not the
+    /// fields it takes.
+    pub fn from_value(constructor: &str, fields: &[Constant]) -> Option<Self> {
+        match (constructor, fields) {
+            ("DivideByZero", &[]) => Some(RuntimeError::DivideByZero),
+            ("KeyError", &[Constant::Str(ref key)]) => {
+                Some(RuntimeError::KeyError(key.clone()))
+            },
+            (
+                "TypeError",
+                &[Constant::Str(ref expected), Constant::Str(ref found)]
+            ) => Some(RuntimeError::TypeError {
+                expected: expected.clone(),
+                found:    found.clone(),
+            }),
+            ("IndexError", &[Constant::Int(index), Constant::Int(len)]) => {
+                Some(RuntimeError::IndexError {
+                    index: index,
+                    len:   len,
+                })
+            },
+            ("IOError", &[Constant::Str(ref message)]) => {
+                Some(RuntimeError::IOError(message.clone()))
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuntimeError::DivideByZero => write!(f, "division by zero"),
+            RuntimeError::KeyError(ref key) => write!(f, "no key {}", key),
+            RuntimeError::TypeError { ref expected, ref found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            },
+            RuntimeError::IndexError { index, len } => write!(
+                f,
+                "index {} is out of range for length {}",
+                index,
+                len
+            ),
+            RuntimeError::IOError(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for RuntimeError {}
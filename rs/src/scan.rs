@@ -0,0 +1,135 @@
+//! Byte-level scanning for the runs of characters the lexer and parser
+//! skip over most: blanks, identifiers, and the rest of a line.
+//!
+//! Each scan looks at eight bytes at a time, packed into a `u64`, testing
+//! every byte in the word at once with a few arithmetic and bitwise
+//! operations (SIMD within a register). Whatever is left over at the end,
+//! less than a word, is scanned a byte at a time by the scalar version of
+//! the same test.
+//!
+//! The scans work on bytes, not characters, so they only ever match ASCII.
+//! A run of identifier characters stops at the first non-ASCII byte, for
+//! the caller to go on from a character at a time.
+//!
+//! ```
+//! use brouwer::scan;
+//!
+//! assert_eq!(scan::blank_run(b" \t  x = 1"), 4);
+//! assert_eq!(scan::ident_run(b"foo_bar2 = 1"), 8);
+//! assert_eq!(scan::line_len(b"-- a comment\nx = 1"), 12);
+//! assert_eq!(scan::char_count("λx → x".as_bytes()), 6);
+//! ```
+
+/// The number of bytes scanned at once.
+const WORD: usize = 8;
+
+/// `0x01` in every byte.
+const ONES: u64 = 0x0101_0101_0101_0101;
+/// `0x7f` in every byte.
+const LOWS: u64 = 0x7f7f_7f7f_7f7f_7f7f;
+/// `0x80` in every byte: the bit each test sets in a byte that passes it.
+const HIGHS: u64 = 0x8080_8080_8080_8080;
+
+
+/// The number of blanks (spaces and tabs) that `bytes` starts with.
+pub fn blank_run(bytes: &[u8]) -> usize {
+    run(bytes, |w| eq(w, b' ') | eq(w, b'\t'), |b| b == b' ' || b == b'\t')
+}
+
+/// The number of ASCII identifier characters (letters, digits, and `_`)
+/// that `bytes` starts with.
+pub fn ident_run(bytes: &[u8]) -> usize {
+    run(bytes, |w| {
+        in_range(w, b'0', b'9') |
+        in_range(w, b'A', b'Z') |
+        in_range(w, b'a', b'z') |
+        eq(w, b'_')
+    }, |b| b == b'_' || (b as char).is_ascii_alphanumeric())
+}
+
+/// The number of bytes before the first line break (`\n` or `\r`) in
+/// `bytes`, or the length of `bytes` if there is none.
+pub fn line_len(bytes: &[u8]) -> usize {
+    run(bytes, |w| !(eq(w, b'\n') | eq(w, b'\r')) & HIGHS, |b|
+        b != b'\n' && b != b'\r'
+    )
+}
+
+/// The number of characters in `bytes`, which must be valid UTF-8: every
+/// byte but the continuation bytes of multi-byte characters.
+pub fn char_count(bytes: &[u8]) -> usize {
+    let mut continuations = 0;
+
+    for chunk in bytes.chunks(WORD) {
+        if chunk.len() < WORD {
+            continuations += chunk.iter()
+                                  .filter(|&&b| b & 0xc0 == 0x80)
+                                  .count();
+
+            break;
+        }
+
+        let w = load(chunk);
+        // A continuation byte is `0b10xxxxxx`: the top bit set, and the
+        // bit below it, shifted up into the top bit's place, clear.
+        continuations += (w & !(w << 1) & HIGHS).count_ones() as usize;
+    }
+
+    bytes.len() - continuations
+}
+
+/// The length of the run of bytes at the start of `bytes` that pass a
+/// test, given both as `matches`, which sets the top bit of every byte of
+/// a word that passes and clears every other bit, and as `scalar`.
+#[inline(always)]
+fn run<M, S>(bytes: &[u8], matches: M, scalar: S) -> usize
+    where M: Fn(u64) -> u64,
+          S: Fn(u8) -> bool
+{
+    let mut len = 0;
+
+    while bytes.len() - len >= WORD {
+        let misses = !matches(load(&bytes[len..len + WORD])) & HIGHS;
+
+        if misses != 0 {
+            // The first byte is the lowest in the word.
+            return len + misses.trailing_zeros() as usize / 8;
+        }
+
+        len += WORD;
+    }
+
+    len + bytes[len..].iter().take_while(|&&b| scalar(b)).count()
+}
+
+/// The first eight bytes of `bytes` as a word, the first byte lowest.
+#[inline(always)]
+fn load(bytes: &[u8]) -> u64 {
+    bytes[..WORD]
+        .iter()
+        .rev()
+        .fold(0, |w, &b| w << 8 | u64::from(b))
+}
+
+/// Sets the top bit of every byte of `w` that is `b`.
+#[inline(always)]
+fn eq(w: u64, b: u8) -> u64 {
+    let diff = w ^ (ONES * u64::from(b));
+
+    // The low seven bits of a byte of `diff` plus `0x7f` carry into its
+    // top bit, and never out of the byte, unless they are all clear.
+    !(((diff & LOWS) + LOWS) | diff) & HIGHS
+}
+
+/// Sets the top bit of every byte of `w` that is ASCII and from `lo` to
+/// `hi`, which must be ASCII, and `lo` not 0.
+#[inline(always)]
+fn in_range(w: u64, lo: u8, hi: u8) -> u64 {
+    let low = w & LOWS;
+    // Adding `0x80 - lo` to a byte below `0x80` carries into its top bit
+    // if it is at least `lo`; adding `0x7f - hi` does if it is over `hi`.
+    let at_least_lo = low + ONES * u64::from(0x80 - lo);
+    let over_hi = low + ONES * u64::from(0x7f - hi);
+
+    at_least_lo & !over_hi & !w & HIGHS
+}
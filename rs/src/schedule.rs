@@ -0,0 +1,368 @@
+//! Running a pass over every module of a program on several threads.
+//!
+//! A module is only handed to the pass once the pass is done with every
+//! module it imports, along with what the pass returned for each of them,
+//! so that a pass can build on what it worked out about the imports;
+//! modules that do not depend on each other are worked on at the same
+//! time. Whichever thread finishes first, the results
+//! come back in the order of `Program::modules`, so that what is reported
+//! from them comes out in the same order every time.
+//!
+//! ```
+//! use brouwer::{schedule, typeck};
+//! use brouwer::loader::Loader;
+//! use brouwer::vfs::MemoryFs;
+//!
+//! let mut files = MemoryFs::new();
+//! files.insert("Main.bwr", "module Main\nimport A (a)\nimport B (b)\n\na\n");
+//! files.insert("A.bwr", "module A exposing a\n\na = 1\n");
+//! files.insert("B.bwr", "module B exposing b\n\nb = 'b'\n");
+//!
+//! let program = Loader::with_fs(files, vec![".".into()])
+//!     .load("Main.bwr")
+//!     .unwrap();
+//! let errors = schedule::run(&program, 4, |loaded, _|
+//!     typeck::infer(&loaded.module).errors.len()
+//! );
+//!
+//! assert_eq!(errors, vec![0, 0, 0]);
+//!
+//! // How many modules deep each module's imports go.
+//! let depths = schedule::run(&program, 4, |_, imports: &[&usize]|
+//!     imports.iter().map(|&&depth| depth + 1).max().unwrap_or(0)
+//! );
+//!
+//! assert_eq!(depths, vec![0, 0, 1]);
+//! ```
+
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+
+use loader::{LoadedModule, Program};
+
+
+/// How far the pass has got through a program, shared by the threads.
+struct State {
+    /// The modules that can be started, in the order they became ready.
+    ready:    Vec<usize>,
+    /// How many of the modules each module imports are not done yet.
+    waiting:  Vec<usize>,
+    done:     usize,
+    /// Whether the pass panicked on some thread, so that the others should
+    /// stop rather than wait for a module that will never be done.
+    panicked: bool,
+}
+
+/// Marks the pass as having panicked if it is dropped while unwinding.
+struct PanicGuard<'a> {
+    state:   &'a Mutex<State>,
+    changed: &'a Condvar,
+}
+
+
+/// How many threads to use by default: one for each core, as far as the
+/// standard library can tell.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Runs `pass` over each module of `program` on up to `jobs` threads,
+/// starting each module once every module it imports is done, and returns
+/// what it returned for each, in the order of `program.modules`. `pass` is
+/// given the module, and what it returned for each module of the program
+/// that the module imports, in the order of the imports.
+///
+/// If `pass` panics, so does this, once the other threads have finished
+/// what they were doing. Modules that import each other in a cycle, which
+/// a `Loader` never loads, have no order to be run in, so they are all run
+/// on this thread in the order of `program.modules`, each given what the
+/// pass returned for those of its imports that were run before it.
+pub fn run<T, F>(program: &Program, jobs: usize, pass: F) -> Vec<T>
+    where T: Send + Sync,
+          F: Fn(&LoadedModule, &[&T]) -> T + Sync
+{
+    let modules = &program.modules;
+    let results: Vec<OnceLock<T>> = modules.iter()
+                                           .map(|_| OnceLock::new())
+                                           .collect();
+
+    let mut imports = vec![Vec::new(); modules.len()];
+    let mut dependents = vec![Vec::new(); modules.len()];
+    let mut waiting = vec![0; modules.len()];
+
+    for (ix, loaded) in modules.iter().enumerate() {
+        for import in &loaded.module.imports {
+            let name = &import.module.name;
+            let dep = modules.iter()
+                             .position(|m| m.module.name.name == *name);
+
+            if let Some(dep) = dep {
+                if !imports[ix].contains(&dep) {
+                    imports[ix].push(dep);
+                    dependents[dep].push(ix);
+                    waiting[ix] += 1;
+                }
+            }
+        }
+    }
+
+    let run_one = |ix: usize| {
+        let done: Vec<&T> = imports[ix].iter()
+                                       .filter_map(|&dep| results[dep].get())
+                                       .collect();
+        let result = pass(&modules[ix], &done);
+
+        if results[ix].set(result).is_err() {
+            unreachable!("module {} was run twice", ix);
+        }
+    };
+
+    if jobs <= 1 || modules.len() <= 1 || !acyclic(&dependents, &waiting) {
+        (0..modules.len()).for_each(run_one);
+
+        return finish(results);
+    }
+
+    let state = Mutex::new(State {
+        ready:    (0..modules.len()).filter(|&ix| waiting[ix] == 0)
+                                    .rev()
+                                    .collect(),
+        waiting:  waiting,
+        done:     0,
+        panicked: false,
+    });
+    let changed = Condvar::new();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(modules.len()) {
+            scope.spawn(|| {
+                let _guard = PanicGuard {
+                    state:   &state,
+                    changed: &changed,
+                };
+
+                while let Some(ix) = next(&state, &changed, modules.len()) {
+                    run_one(ix);
+
+                    let mut state = state.lock().unwrap();
+                    state.done += 1;
+
+                    for &dependent in &dependents[ix] {
+                        state.waiting[dependent] -= 1;
+
+                        if state.waiting[dependent] == 0 {
+                            state.ready.insert(0, dependent);
+                        }
+                    }
+
+                    changed.notify_all();
+                }
+            });
+        }
+    });
+
+    finish(results)
+}
+
+fn finish<T>(results: Vec<OnceLock<T>>) -> Vec<T> {
+    results.into_iter()
+           .map(|result| result.into_inner().expect("every module was run"))
+           .collect()
+}
+
+/// Whether every module can be run once those it imports are, given what
+/// depends on each and how many modules each imports.
+fn acyclic(dependents: &[Vec<usize>], waiting: &[usize]) -> bool {
+    let len = waiting.len();
+    let mut waiting = waiting.to_vec();
+    let mut ready: Vec<_> = (0..len).filter(|&ix| waiting[ix] == 0).collect();
+    let mut runnable = 0;
+
+    while let Some(ix) = ready.pop() {
+        runnable += 1;
+
+        for &dependent in &dependents[ix] {
+            waiting[dependent] -= 1;
+
+            if waiting[dependent] == 0 {
+                ready.push(dependent);
+            }
+        }
+    }
+
+    runnable == len
+}
+
+/// Waits for a module to be ready and takes it, or returns `None` once
+/// there are no more to take.
+fn next(
+    state:   &Mutex<State>,
+    changed: &Condvar,
+    len:     usize
+) -> Option<usize> {
+    let mut state = state.lock().unwrap();
+
+    loop {
+        if state.panicked {
+            return None;
+        }
+
+        if let Some(ix) = state.ready.pop() {
+            return Some(ix);
+        }
+
+        if state.done == len {
+            return None;
+        }
+
+        // Everything not done yet is being worked on, or waits for
+        // something that is.
+        state = changed.wait(state).unwrap();
+    }
+}
+
+impl<'a> Drop for PanicGuard<'a> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            if let Ok(mut state) = self.state.lock() {
+                state.panicked = true;
+            }
+
+            self.changed.notify_all();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use ast;
+    use parser::Parser;
+
+    fn loaded(src: &str) -> LoadedModule {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        LoadedModule {
+            path:   PathBuf::new(),
+            source: src.to_string(),
+            module: ast::lower(&tree).unwrap(),
+            tree:   tree,
+        }
+    }
+
+    fn name(loaded: &LoadedModule) -> String {
+        loaded.module.name.name.clone()
+    }
+
+    #[test]
+    fn modules_start_once_their_imports_are_done() {
+        // Listed out of order, so that the threads cannot just follow it.
+        let program = Program {
+            modules: vec![
+                loaded("module Main\nimport A (a)\nimport B (b)\n\na\n"),
+                loaded("module B exposing b\nimport C (c)\n\nb = c\n"),
+                loaded("module A exposing a\n\na = 1\n"),
+                loaded("module C exposing c\n\nc = 2\n"),
+            ],
+        };
+        let finished = Mutex::new(Vec::new());
+
+        let names = run(&program, 4, |loaded, _| {
+            {
+                let finished = finished.lock().unwrap();
+                for import in &loaded.module.imports {
+                    assert!(finished.contains(&import.module.name));
+                }
+            }
+
+            thread::sleep(Duration::from_millis(10));
+            finished.lock().unwrap().push(name(loaded));
+
+            name(loaded)
+        });
+
+        assert_eq!(names, vec!["Main", "B", "A", "C"]);
+    }
+
+    #[test]
+    fn the_pass_is_given_what_it_returned_for_each_import() {
+        let program = Program {
+            modules: vec![
+                loaded("module C exposing c
+
+c = 2
+"),
+                loaded("module B exposing b
+import C (c)
+
+b = c
+"),
+                loaded("module A exposing a
+
+a = 1
+"),
+                loaded("module Main
+import A (a)
+import B (b)
+
+a
+"),
+            ],
+        };
+
+        for &jobs in &[1, 4] {
+            // Each module's name, then those of everything it imports.
+            let names = run(&program, jobs, |loaded, imports: &[&String]| {
+                let mut names = name(loaded);
+                for import in imports {
+                    names.push_str(&format!(" ({})", import));
+                }
+
+                names
+            });
+
+            assert_eq!(names, vec!["C", "B (C)", "A", "Main (A) (B (C))"]);
+        }
+    }
+
+    #[test]
+    fn modules_that_import_each_other_are_run_in_order() {
+        let program = Program {
+            modules: vec![
+                loaded("module A exposing a\nimport B (b)\n\na = b\n"),
+                loaded("module B exposing b\nimport A (a)\n\nb = a\n"),
+                loaded("module C exposing c\n\nc = 1\n"),
+            ],
+        };
+        let started = Mutex::new(Vec::new());
+
+        let names = run(&program, 4, |loaded, _| {
+            started.lock().unwrap().push(name(loaded));
+            name(loaded)
+        });
+
+        assert_eq!(names, vec!["A", "B", "C"]);
+        assert_eq!(*started.lock().unwrap(), vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn a_panic_in_the_pass_is_not_waited_out() {
+        let program = Program {
+            modules: vec![
+                loaded("module A exposing a\n\na = 1\n"),
+                loaded("module Main\nimport A (a)\n\na\n"),
+            ],
+        };
+
+        run(&program, 2, |loaded, _| {
+            if name(loaded) == "A" {
+                panic!("the pass failed");
+            }
+        });
+    }
+}
@@ -0,0 +1,850 @@
+//! A compile server, which keeps what it has worked out about each file in
+//! memory and answers requests about files over a local socket, so that
+//! editors and build scripts need neither start brouwer nor parse a file
+//! that has not changed since the last request.
+//!
+//! Each request is a line holding a command and, for most, a file:
+//!
+//! - `check <file>`: load the program the file is the main module of, and
+//!   check each of its modules as `brouwer check` does (see `check`).
+//! - `build <file>`: parse the file, lower it, and compile it to bytecode.
+//! - `format <file>`: format the file, without writing it.
+//! - `forget <file>`: drop what is known about the file.
+//! - `shutdown`: stop the server.
+//!
+//! Each response is a line holding a JSON object: `"ok"`, whether the
+//! request succeeded; `"errors"`, a list of what went wrong, each error
+//! starting with the file's name and where in the file it is; from
+//! `check`, `"warnings"`, a list of what is probably wrong, in the same
+//! form; and, from `format`, `"output"`, the formatted source.
+//!
+//! Each connection is served on a thread of its own, so a client that is
+//! slow to send its requests holds up no one else. A request that panics
+//! is answered with the panic, and the server carries on.
+//!
+//! The server listens on a Unix socket that only the user who started it
+//! can connect to (see `bind`), or on a TCP port of `127.0.0.1` where
+//! there are no Unix sockets, which every user of the machine can connect
+//! to.
+//!
+//! A file is worked out again only if its size or modification time has
+//! changed since the last request about it, or, for `check`, that of any
+//! file of its program has. An editor that embeds the server can open a
+//! buffer over a file (see `Server::open_buffer`), which is then read in
+//! place of the file until it is closed.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use ast::{self, Module};
+use check;
+use compiler;
+use diagnostics::{Diagnostic, Severity};
+use dump::json_str;
+use formatter::{self, FormatConfig};
+use ice;
+use loader::Loader;
+use parser::Parser;
+use plugin::Plugins;
+use schedule;
+use vfs::{FileSystem, OverlayFs};
+
+
+/// The port the server listens on, on `127.0.0.1`, when told to listen on
+/// TCP but not on which port.
+pub const DEFAULT_PORT: u16 = 7797;
+
+/// A compile server, with what it knows about each file.
+pub struct Server {
+    parser:  Parser,
+    config:  FormatConfig,
+    plugins: Plugins,
+    files:   HashMap<PathBuf, Entry>,
+    fs:      OverlayFs,
+}
+
+/// Where a `Server` takes connections from.
+pub trait Listener: Send + Sync + 'static {
+    /// Waits for the next connection, and returns a reader and a writer
+    /// over it.
+    fn accept(&self) -> io::Result<Connection>;
+
+    /// Connects to the listener and hangs up, so that an `accept` waiting
+    /// for a connection returns.
+    fn wake(&self) -> io::Result<()>;
+}
+
+/// A connection to a `Listener`: what the client sends, and what is sent
+/// back to it.
+pub type Connection = (Box<dyn Read + Send>, Box<dyn Write + Send>);
+
+/// The size and modification time of a file, or the length of a buffer.
+type Stamp = (u64, Option<SystemTime>);
+
+/// Files, each with its stamp, or `None` if it was not there.
+type Stamps = Vec<(PathBuf, Option<Stamp>)>;
+
+/// What is known about a file, as of when it had `stamp`.
+struct Entry {
+    stamp:     Stamp,
+    src:       Result<String, String>,
+    /// The file lowered, or what stopped it from being.
+    lowered:   Option<Result<Module, Vec<String>>>,
+    /// The answer to `check`, with the stamp of each file the loader read
+    /// or looked for in working it out, if the file was there.
+    checked:   Option<(Stamps, String)>,
+    built:     Option<String>,
+    formatted: Option<String>,
+}
+
+
+impl Server {
+    /// A server that knows nothing yet, which formats with `config`.
+    pub fn new(config: FormatConfig) -> Self {
+        Server::with_plugins(config, Plugins::new())
+    }
+
+    /// A server that knows nothing yet, which formats with `config` and
+    /// calls `plugins` on each module it checks, as `brouwer check` does.
+    pub fn with_plugins(config: FormatConfig, plugins: Plugins) -> Self {
+        Server {
+            parser:  Parser::from_str(""),
+            config:  config,
+            plugins: plugins,
+            files:   HashMap::new(),
+            fs:      OverlayFs::default(),
+        }
+    }
+
+    /// Reads `contents` in place of the file at `path` until the buffer is
+    /// closed, as for an editor's unsaved changes to it. Opening a buffer
+    /// that is already open replaces what it holds.
+    ///
+    /// ```
+    /// use brouwer::formatter::FormatConfig;
+    /// use brouwer::server::Server;
+    ///
+    /// let mut server = Server::new(FormatConfig::default());
+    /// server.open_buffer("Unsaved.bwr", "module Unsaved\n\nx = 1\n");
+    ///
+    /// assert_eq!(server.handle("check Unsaved.bwr").unwrap(),
+    ///            r#"{"ok":true,"errors":[],"warnings":[]}"#);
+    ///
+    /// server.close_buffer("Unsaved.bwr");
+    ///
+    /// assert_eq!(server.handle("check Unsaved.bwr").unwrap(),
+    ///            r#"{"ok":false,"errors":["Unsaved.bwr: cannot be read"]}"#);
+    /// ```
+    pub fn open_buffer<P: AsRef<Path>, S: Into<String>>(
+        &mut self,
+        path:     P,
+        contents: S
+    ) {
+        self.forget_buffer(path.as_ref());
+        self.fs.open(path, contents);
+    }
+
+    /// Closes the buffer open over the file at `path`, so that the file is
+    /// read from disk again.
+    pub fn close_buffer<P: AsRef<Path>>(&mut self, path: P) {
+        self.forget_buffer(path.as_ref());
+        self.fs.close(path);
+    }
+
+    /// Forgets what is known about the file at `path`, and every answer to
+    /// `check`, any of which may have been worked out from the file.
+    fn forget_buffer(&mut self, path: &Path) {
+        self.files.remove(path);
+
+        for entry in self.files.values_mut() {
+            entry.checked = None;
+        }
+    }
+
+    /// Answers requests from each connection to `listener` on a thread of
+    /// its own, until one of them is `shutdown`. The requests themselves
+    /// are answered one at a time, in the order they arrive. A connection
+    /// that fails is dropped; a request that panics is answered with the
+    /// panic as its error, and everything the server knew is forgotten.
+    ///
+    /// ```
+    /// use std::io::{BufRead, BufReader, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::thread;
+    ///
+    /// use brouwer::formatter::FormatConfig;
+    /// use brouwer::server::Server;
+    ///
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let addr = listener.local_addr().unwrap();
+    /// let server = thread::spawn(move ||
+    ///     Server::new(FormatConfig::default()).serve(listener)
+    /// );
+    ///
+    /// // A client that says nothing does not hold up the others.
+    /// let _idle = TcpStream::connect(addr).unwrap();
+    /// let mut client = TcpStream::connect(addr).unwrap();
+    /// let mut response = String::new();
+    ///
+    /// client.write_all(b"shutdown\n").unwrap();
+    /// BufReader::new(client).read_line(&mut response).unwrap();
+    ///
+    /// assert_eq!(response, "{\"ok\":true,\"errors\":[]}\n");
+    /// assert!(server.join().unwrap().is_ok());
+    /// ```
+    pub fn serve<L: Listener>(self, listener: L) -> io::Result<()> {
+        let server = Arc::new(Mutex::new(self));
+        let listener = Arc::new(listener);
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        loop {
+            let connection = listener.accept();
+
+            if stopped.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+
+            let (input, output) = match connection {
+                Ok(connection) => connection,
+                Err(_)         => continue,
+            };
+
+            let server = server.clone();
+            let listener = listener.clone();
+            let stopped = stopped.clone();
+
+            thread::spawn(move || {
+                if let Ok(true) = serve_connection(&server, input, output) {
+                    stopped.store(true, Ordering::SeqCst);
+                    let _ = listener.wake();
+                }
+            });
+        }
+    }
+
+    /// Answers the request `request`, or returns `None` if it is
+    /// `shutdown`.
+    ///
+    /// ```
+    /// use brouwer::formatter::FormatConfig;
+    /// use brouwer::server::Server;
+    ///
+    /// let mut server = Server::new(FormatConfig::default());
+    ///
+    /// assert_eq!(server.handle("check nowhere.bwr").unwrap(),
+    ///            r#"{"ok":false,"errors":["nowhere.bwr: cannot be read"]}"#);
+    /// assert_eq!(server.handle("shutdown"), None);
+    /// ```
+    pub fn handle(&mut self, request: &str) -> Option<String> {
+        let request = request.trim();
+        let (command, path) = match request.find(' ') {
+            Some(ix) => (&request[..ix], request[ix + 1..].trim()),
+            None     => (request, ""),
+        };
+
+        match (command, path.is_empty()) {
+            ("shutdown", true) => None,
+            ("forget", false)  => {
+                self.files.remove(Path::new(path));
+
+                Some(response(true, &[], None))
+            },
+            ("check", false)  |
+            ("build", false)  |
+            ("format", false) => Some(self.answer(command, Path::new(path))),
+            _ => Some(response(
+                false,
+                &[format!("unknown request: {}", request)],
+                None
+            )),
+        }
+    }
+
+    /// The stamp of the file at `path` as it is now.
+    fn stamp(&self, path: &Path) -> io::Result<Stamp> {
+        // A buffer is forgotten whenever it changes, so its length will do.
+        match self.fs.stat(path) {
+            Ok(stat) if self.fs.is_open(path) => Ok((stat.len, None)),
+            _ => fs::metadata(path).map(|meta|
+                (meta.len(), meta.modified().ok())
+            ),
+        }
+    }
+
+    /// Answers `command` about the file at `path`, working it out only if
+    /// it is not known already.
+    fn answer(&mut self, command: &str, path: &Path) -> String {
+        let stamp = match self.stamp(path) {
+            Ok(stamp) => stamp,
+            Err(_)    => {
+                self.files.remove(path);
+
+                return response(
+                    false,
+                    &[format!("{}: cannot be read", path.display())],
+                    None
+                );
+            },
+        };
+
+        let stale = self.files
+                        .get(path)
+                        .is_none_or(|entry| entry.stamp != stamp);
+
+        if stale {
+            self.files.insert(
+                path.to_path_buf(),
+                Entry::read(&self.fs, path, stamp)
+            );
+        }
+
+        if command == "check" {
+            return self.check(path);
+        }
+
+        let entry = self.files.get_mut(path).expect("entry just inserted");
+        let name = path.display().to_string();
+
+        match command {
+            "build" => {
+                if entry.built.is_none() {
+                    let errors = entry.lower(&mut self.parser, &name)
+                                      .map(|module| build(module, &name))
+                                      .unwrap_or_else(|errors| errors);
+
+                    entry.built = Some(
+                        response(errors.is_empty(), &errors, None)
+                    );
+                }
+
+                entry.built.clone().unwrap_or_default()
+            },
+            _ => {
+                if entry.formatted.is_none() {
+                    ice::enter_pass("fmt", path);
+                    let formatted = match entry.src {
+                        Ok(ref src) => formatter::format(src, &self.config)
+                                                 .map_err(|e|
+                                                     format!("{}:{}", name, e)
+                                                 ),
+                        Err(ref e)  => Err(e.clone()),
+                    };
+
+                    entry.formatted = Some(match formatted {
+                        Ok(src) => response(true, &[], Some(&src)),
+                        Err(e)  => response(false, &[e], None),
+                    });
+                }
+
+                entry.formatted.clone().unwrap_or_default()
+            },
+        }
+    }
+
+    /// Answers `check` about the file at `path`, which is known, by
+    /// loading the program it is the main module of and checking each of
+    /// its modules as `brouwer check` does, unless none of the program's
+    /// files have changed since the last time.
+    fn check(&mut self, path: &Path) -> String {
+        if let Some((ref stamps, ref answer)) = self.files[path].checked {
+            let unchanged = stamps.iter().all(|&(ref file, stamp)|
+                self.stamp(file).ok() == stamp
+            );
+
+            if unchanged {
+                return answer.clone();
+            }
+        }
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut loader = Loader::with_fs(&self.fs, vec![dir.to_path_buf()]);
+        let answer = match loader.load(path) {
+            Ok(program) => {
+                let reports = schedule::run(
+                    &program,
+                    schedule::default_jobs(),
+                    |loaded, _| check::check_module(&self.plugins,
+                                                    &program,
+                                                    loaded)
+                );
+                let mut errors = Vec::new();
+                let mut warnings = Vec::new();
+
+                for (loaded, report) in program.modules.iter().zip(reports) {
+                    let name = loaded.path.display();
+
+                    for e in &report.errors {
+                        errors.push(format!("{}:{}", name, e));
+                    }
+
+                    for diagnostic in report.diagnostics() {
+                        let line = diagnostic_line(&name.to_string(),
+                                                   &diagnostic);
+
+                        match diagnostic.severity {
+                            Severity::Error   => errors.push(line),
+                            Severity::Warning => warnings.push(line),
+                        }
+                    }
+                }
+
+                checked(&errors, &warnings)
+            },
+            Err(e) => checked(&[e.to_string()], &[]),
+        };
+
+        // A failure is as out of date as a success once a file it came
+        // from changes, or one that was missing is made.
+        let stamps = loader.looked_at()
+                           .iter()
+                           .map(|file| (file.clone(), self.stamp(file).ok()))
+                           .collect();
+
+        if let Some(entry) = self.files.get_mut(path) {
+            entry.checked = Some((stamps, answer.clone()));
+        }
+
+        answer
+    }
+}
+
+#[cfg(unix)]
+impl Listener for UnixListener {
+    fn accept(&self) -> io::Result<Connection> {
+        let (stream, _) = UnixListener::accept(self)?;
+
+        Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+    }
+
+    fn wake(&self) -> io::Result<()> {
+        let addr = self.local_addr()?;
+        let path = addr.as_pathname().ok_or_else(|| io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            "the socket has no path"
+        ))?;
+
+        UnixStream::connect(path).map(drop)
+    }
+}
+
+impl Listener for TcpListener {
+    fn accept(&self) -> io::Result<Connection> {
+        let (stream, _) = TcpListener::accept(self)?;
+
+        // Each response is a single small write, which would otherwise be
+        // held back waiting for the client's acknowledgement.
+        let _ = stream.set_nodelay(true);
+
+        Ok((Box::new(stream.try_clone()?), Box::new(stream)))
+    }
+
+    fn wake(&self) -> io::Result<()> {
+        TcpStream::connect(self.local_addr()?).map(drop)
+    }
+}
+
+impl Entry {
+    /// Reads the file at `path` from `fs`, where it has `stamp`, knowing
+    /// nothing else about it yet.
+    fn read<F: FileSystem>(
+        fs:    &F,
+        path:  &Path,
+        stamp: (u64, Option<SystemTime>)
+    ) -> Self {
+        Entry {
+            stamp:     stamp,
+            src:       fs.read(path).map_err(|e|
+                format!("{}: {}", path.display(), e)
+            ),
+            lowered:   None,
+            checked:   None,
+            built:     None,
+            formatted: None,
+        }
+    }
+
+    /// The file lowered (see `ast`), or the errors that stopped it from
+    /// being, with `name` as the file's name in them.
+    fn lower(
+        &mut self,
+        parser: &mut Parser,
+        name:   &str
+    ) -> Result<&Module, Vec<String>> {
+        if self.lowered.is_none() {
+            self.lowered = Some(match self.src {
+                Ok(ref src) => lower(parser, src, name),
+                Err(ref e)  => Err(vec![e.clone()]),
+            });
+        }
+
+        match self.lowered {
+            Some(Ok(ref module))  => Ok(module),
+            Some(Err(ref errors)) => Err(errors.clone()),
+            None                  => unreachable!(),
+        }
+    }
+}
+
+/// Where the server listens unless told otherwise: `serve.sock`, in a
+/// directory of brouwer's own in `$XDG_RUNTIME_DIR` or, failing that, in
+/// one named after the user in the system's temporary directory.
+#[cfg(unix)]
+pub fn default_socket() -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(ref dir) if !dir.is_empty() => Path::new(dir).join("brouwer"),
+        _ => {
+            let user = env::var("USER").unwrap_or_else(|_| "user".into());
+
+            env::temp_dir().join(format!("brouwer-{}", user))
+        },
+    };
+
+    dir.join("serve.sock")
+}
+
+/// Listens on a Unix socket at `path` that only the user can connect to.
+///
+/// The directory the socket is in is made, open to the user alone, if it
+/// does not exist; if it does, it must not be open to anyone else either.
+/// A socket left at `path` by a server that has stopped is replaced, but
+/// not one that a server is still listening on, nor anything else.
+#[cfg(unix)]
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    if !dir.as_os_str().is_empty() {
+        match fs::symlink_metadata(dir) {
+            Ok(meta) => if !meta.is_dir() ||
+                           meta.permissions().mode() & 0o077 != 0
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "{} is not a directory open only to its owner",
+                        dir.display()
+                    )
+                ));
+            },
+            Err(_) => fs::DirBuilder::new().recursive(true)
+                                           .mode(0o700)
+                                           .create(dir)?,
+        }
+    }
+
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "something other than a socket is there"
+            ));
+        }
+
+        if UnixStream::connect(path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "a server is already listening there"
+            ));
+        }
+
+        fs::remove_file(path)?;
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    Ok(listener)
+}
+
+/// Answers each request read from `input` on `output`, one at a time
+/// with `server`, returning whether the last was `shutdown`.
+fn serve_connection<R: Read, W: Write>(
+    server:     &Mutex<Server>,
+    input:      R,
+    mut output: W
+) -> io::Result<bool> {
+    for line in BufReader::new(input).lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = {
+            let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+            let answer = panic::catch_unwind(AssertUnwindSafe(||
+                server.handle(&line)
+            ));
+
+            answer.unwrap_or_else(|payload| {
+                // Whatever the request was working out may be half done.
+                server.files.clear();
+
+                Some(response(
+                    false,
+                    &[format!(
+                        "internal compiler error: {}",
+                        ice::payload_message(&*payload)
+                    )],
+                    None
+                ))
+            })
+        };
+
+        let (mut response, shutdown) = match answer {
+            Some(response) => (response, false),
+            None           => (response(true, &[], None), true),
+        };
+
+        response.push('\n');
+        output.write_all(response.as_bytes())?;
+
+        if shutdown {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Parses and lowers `src`, the source of the file called `name`.
+fn lower(
+    parser: &mut Parser,
+    src:    &str,
+    name:   &str
+) -> Result<Module, Vec<String>> {
+    ice::enter_pass("parse", name);
+    parser.reset(src);
+
+    let tree = match parser.parse() {
+        Ok(Some(tree)) => tree,
+        Ok(None)       => return Err(vec![format!("{}: not a program", name)]),
+        Err(e)         => return Err(vec![format!("{}:{}", name, e)]),
+    };
+
+    ice::enter_pass("lower", name);
+    ast::lower(&tree).map_err(|e| vec![format!("{}:{}", name, e)])
+}
+
+/// What stops `module`, from the file called `name`, from compiling.
+fn build(module: &Module, name: &str) -> Vec<String> {
+    ice::enter_pass("compile", name);
+
+    match compiler::compile(module) {
+        Ok(_)  => Vec::new(),
+        Err(e) => vec![format!("{}:{}", name, e)],
+    }
+}
+
+/// A response, as a line of JSON without the newline.
+fn response(ok: bool, errors: &[String], output: Option<&str>) -> String {
+    let mut json = format!(
+        "{{\"ok\":{},\"errors\":{}",
+        ok,
+        json_list(errors)
+    );
+
+    if let Some(output) = output {
+        json += &format!(",\"output\":{}", json_str(output));
+    }
+
+    json.push('}');
+
+    json
+}
+
+/// The response to `check`, which also lists what is probably wrong.
+fn checked(errors: &[String], warnings: &[String]) -> String {
+    format!(
+        "{{\"ok\":{},\"errors\":{},\"warnings\":{}}}",
+        errors.is_empty(),
+        json_list(errors),
+        json_list(warnings)
+    )
+}
+
+fn json_list(items: &[String]) -> String {
+    let items: Vec<_> = items.iter().map(|item| json_str(item)).collect();
+
+    format!("[{}]", items.join(","))
+}
+
+/// `diagnostic`, about the file called `name`, on a line of its own, in
+/// the form of the errors from the other passes.
+fn diagnostic_line(name: &str, diagnostic: &Diagnostic) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error   => "error",
+        Severity::Warning => "warning",
+    };
+
+    match diagnostic.span {
+        Some(span) if span.is_known() => format!(
+            "{}:{}:{}: {}[{}]: {}",
+            name,
+            span.line,
+            span.col,
+            severity,
+            diagnostic.code,
+            diagnostic.message
+        ),
+        _ => format!(
+            "{}: {}[{}]: {}",
+            name,
+            severity,
+            diagnostic.code,
+            diagnostic.message
+        ),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin::{CompilerPlugin, ModuleView, Sink};
+
+    /// A plugin with a bug in it.
+    struct Panics;
+
+    impl CompilerPlugin for Panics {
+        fn name(&self) -> &str {
+            "panics"
+        }
+
+        fn after_parse(&self, _: &ModuleView, _: &mut Sink) {
+            panic!("plugin bug");
+        }
+    }
+
+    fn answers(server: Server, requests: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        let server = Mutex::new(server);
+
+        serve_connection(&server, requests.as_bytes(), &mut output).unwrap();
+
+        String::from_utf8(output).unwrap()
+                                 .lines()
+                                 .map(|line| line.to_string())
+                                 .collect()
+    }
+
+    #[test]
+    fn check_loads_imports_and_reports_warnings() {
+        let mut server = Server::new(FormatConfig::default());
+        server.open_buffer("Main.bwr", "module Main\nimport Util (f)\n\nf 1\n");
+        server.open_buffer(
+            "Util.bwr",
+            "module Util exposing f\n\nfn f pairs\n    for (k, v) in pairs\n\
+             \x20       print k\n    z\n"
+        );
+
+        assert_eq!(
+            server.handle("check Main.bwr").unwrap(),
+            "{\"ok\":false,\
+              \"errors\":[\"Util.bwr:6:5: z is not defined\"],\
+              \"warnings\":[\"Util.bwr:4:13: warning[W0201]: \
+                            v is bound by the pattern but never used\"]}"
+        );
+
+        server.open_buffer("Util.bwr", "module Util exposing f\n\nf = 1\n");
+
+        assert_eq!(server.handle("check Main.bwr").unwrap(),
+                   "{\"ok\":true,\"errors\":[],\"warnings\":[]}");
+    }
+
+    #[test]
+    fn a_failed_check_is_redone_once_an_import_is_fixed() {
+        let dir = env::temp_dir().join(format!(
+            "brouwer-server-imports-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let main = dir.join("Main.bwr");
+        let util = dir.join("Util.bwr");
+        let other = dir.join("Other.bwr");
+        let check = format!("check {}", main.display());
+        fs::write(&main, "module Main\nimport Util (f)\n\nf 1\n").unwrap();
+        fs::write(&util, "module Util exposing f\n\nf = (\n").unwrap();
+
+        let mut server = Server::new(FormatConfig::default());
+        let answer = server.handle(&check).unwrap();
+        assert!(answer.contains("Util.bwr"), "{}", answer);
+
+        fs::write(&util, "module Util exposing f\n\nfn f x = x\n").unwrap();
+        assert_eq!(server.handle(&check).unwrap(),
+                   "{\"ok\":true,\"errors\":[],\"warnings\":[]}");
+
+        fs::write(&util, "module Util exposing f\nimport Other (g)\n\n\
+                          fn f x = g x\n").unwrap();
+        let answer = server.handle(&check).unwrap();
+        assert!(answer.contains("cannot find module Other"), "{}", answer);
+
+        fs::write(&other, "module Other exposing g\n\nfn g x = x\n")
+            .unwrap();
+        assert_eq!(server.handle(&check).unwrap(),
+                   "{\"ok\":true,\"errors\":[],\"warnings\":[]}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn panic_is_answered_and_the_server_carries_on() {
+        let mut plugins = Plugins::new();
+        plugins.register(Panics);
+
+        let mut server = Server::with_plugins(FormatConfig::default(),
+                                              plugins);
+        server.open_buffer("Main.bwr", "module Main\n\nx  =  1\n");
+
+        let answers = answers(server, "check Main.bwr\nformat Main.bwr\n");
+
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0],
+                   "{\"ok\":false,\
+                     \"errors\":[\"internal compiler error: plugin bug\"]}");
+        assert!(answers[1].starts_with("{\"ok\":true"), "{}", answers[1]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_is_open_only_to_the_user() {
+        let dir = env::temp_dir().join(format!(
+            "brouwer-server-test-{}",
+            std::process::id()
+        ));
+        let path = dir.join("run").join("serve.sock");
+
+        let listener = bind(&path).unwrap();
+        let mode = |path: &Path|
+            fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(bind(&path).unwrap_err().kind(), io::ErrorKind::AddrInUse);
+
+        drop(listener);
+        assert!(bind(&path).is_ok(), "a stale socket is replaced");
+
+        fs::set_permissions(path.parent().unwrap(),
+                            fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(bind(&path).unwrap_err().kind(),
+                   io::ErrorKind::PermissionDenied);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
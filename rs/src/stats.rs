@@ -0,0 +1,329 @@
+//! Per-module source metrics, for `brouwer stats`.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use dump::json_str;
+use loader::EXTENSION;
+use parser::{AST, Parser};
+use token::TokenType;
+use vfs::{FileSystem, RealFs};
+
+
+/// Source metrics for a single module (file).
+#[derive(Clone, Debug)]
+pub struct ModuleStats {
+    /// The file the module was read from.
+    pub path:          PathBuf,
+    /// The name in the module declaration, if any.
+    pub module:        Option<String>,
+    /// Total lines in the file.
+    pub lines:         usize,
+    /// Lines with nothing but whitespace.
+    pub blank_lines:   usize,
+    /// Lines with nothing but a line comment.
+    pub comment_lines: usize,
+    /// Number of function declarations, nested ones included.
+    pub functions:     usize,
+    /// Total lines across all function bodies.
+    pub fn_lines:      usize,
+    /// Cyclomatic complexity of the whole module.
+    pub complexity:    usize,
+}
+
+
+impl ModuleStats {
+    /// Average number of lines in a function body, or 0 if the module has
+    /// no functions.
+    pub fn avg_fn_len(&self) -> f64 {
+        if self.functions == 0 {
+            0.0
+        } else {
+            self.fn_lines as f64 / self.functions as f64
+        }
+    }
+
+    /// Fraction of non-blank lines that are line comments.
+    pub fn comment_ratio(&self) -> f64 {
+        let non_blank = self.lines - self.blank_lines;
+
+        if non_blank == 0 {
+            0.0
+        } else {
+            self.comment_lines as f64 / non_blank as f64
+        }
+    }
+}
+
+/// Collects every `.bwr` file under `dir`, recursively, in sorted order.
+pub fn find_sources<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>, String> {
+    find_sources_in(&RealFs, dir)
+}
+
+/// Collects every `.bwr` file under `dir` in `fs`, recursively, in sorted
+/// order.
+pub fn find_sources_in<F: FileSystem, P: AsRef<Path>>(
+    fs:  &F,
+    dir: P
+) -> Result<Vec<PathBuf>, String> {
+    let mut sources = Vec::new();
+
+    collect_sources(fs, dir.as_ref(), &mut sources)?;
+    sources.sort();
+
+    Ok(sources)
+}
+
+fn collect_sources<F: FileSystem>(
+    fs:      &F,
+    dir:     &Path,
+    sources: &mut Vec<PathBuf>
+) -> Result<(), String> {
+    let entries = fs.read_dir(dir).map_err(|e|
+        format!("{}: {}", dir.display(), e)
+    )?;
+
+    for path in entries {
+        if fs.is_dir(&path) {
+            collect_sources(fs, &path, sources)?;
+        } else if path.extension().map(|ext| ext == EXTENSION)
+                                  .unwrap_or(false)
+        {
+            sources.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the module at `path` and computes its metrics.
+pub fn module_stats<P: AsRef<Path>>(path: P) -> Result<ModuleStats, String> {
+    let path = path.as_ref();
+
+    let mut src = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut src))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    let ast = match Parser::from_str(&src).parse() {
+        Ok(Some(ast)) => ast,
+        Ok(_)         => {
+            return Err(format!("{}: parse failed", path.display()));
+        },
+        Err(e)        => {
+            return Err(format!("{}: {}", path.display(), e));
+        },
+    };
+
+    let mut stats = ModuleStats {
+        path:          path.to_path_buf(),
+        module:        module_name(&ast),
+        lines:         0,
+        blank_lines:   0,
+        comment_lines: 0,
+        functions:     0,
+        fn_lines:      0,
+        complexity:    1,
+    };
+
+    for line in src.lines() {
+        let trimmed = line.trim();
+
+        stats.lines += 1;
+
+        if trimmed.is_empty() {
+            stats.blank_lines += 1;
+        } else if trimmed.starts_with("--") {
+            stats.comment_lines += 1;
+        }
+    }
+
+    tally(&ast, &mut stats);
+
+    Ok(stats)
+}
+
+fn module_name(ast: &AST) -> Option<String> {
+    let mod_decl = match ast.children().first() {
+        Some(prog) => match prog.children().first() {
+            Some(first) if first.val().type_ == TokenType::ModDecl => first,
+            _ => return None,
+        },
+        None => return None,
+    };
+
+    mod_decl.children()
+            .iter()
+            .find(|c| c.val().type_ == TokenType::Ident)
+            .map(|ident| ident.val().lexeme().to_string())
+}
+
+fn tally(ast: &AST, stats: &mut ModuleStats) {
+    match ast.val().type_ {
+        TokenType::FnDecl => {
+            stats.functions += 1;
+            stats.fn_lines += count_lines(ast);
+            stats.complexity += 1;
+        },
+        _ => stats.complexity += decision_points(ast),
+    }
+
+    for child in ast.children() {
+        tally(child, stats);
+    }
+}
+
+/// The number of branches a node adds to the control-flow graph, for
+/// McCabe's cyclomatic complexity.
+pub fn decision_points(ast: &AST) -> usize {
+    match ast.val().type_ {
+        // Counted once, by the generator that is its condition.
+        TokenType::IfElse |
+        TokenType::While if ast.children().get(1).is_some_and(|cond|
+            cond.val().type_ == TokenType::Generator
+        ) => 0,
+        TokenType::IfElse    |
+        TokenType::While     |
+        TokenType::For       |
+        TokenType::Try       |
+        TokenType::Generator => 1,
+        TokenType::Case      => {
+            let branches = ast.children()
+                              .iter()
+                              .filter(|c|
+                                  c.val().type_ == TokenType::CaseBranch
+                              )
+                              .count();
+
+            if branches > 0 { branches - 1 } else { 0 }
+        },
+        _ => 0,
+    }
+}
+
+/// Counts the `Line`s nested anywhere below `ast`.
+pub fn count_lines(ast: &AST) -> usize {
+    ast.children().iter().map(|child|
+        if child.val().type_ == TokenType::Line {
+            1 + count_lines(child)
+        } else {
+            count_lines(child)
+        }
+    ).sum()
+}
+
+/// Sums the metrics of every module into one row.
+pub fn totals(all: &[ModuleStats]) -> ModuleStats {
+    let mut total = ModuleStats {
+        path:          PathBuf::from("total"),
+        module:        None,
+        lines:         0,
+        blank_lines:   0,
+        comment_lines: 0,
+        functions:     0,
+        fn_lines:      0,
+        complexity:    0,
+    };
+
+    for stats in all {
+        total.lines += stats.lines;
+        total.blank_lines += stats.blank_lines;
+        total.comment_lines += stats.comment_lines;
+        total.functions += stats.functions;
+        total.fn_lines += stats.fn_lines;
+        total.complexity += stats.complexity;
+    }
+
+    total
+}
+
+/// Renders the metrics as a plain-text table with a totals row.
+pub fn to_table(all: &[ModuleStats]) -> String {
+    let mut rows = Vec::with_capacity(all.len() + 2);
+    rows.push([
+        "module".to_string(),
+        "lines".to_string(),
+        "fns".to_string(),
+        "avg fn len".to_string(),
+        "comments".to_string(),
+        "complexity".to_string(),
+    ]);
+
+    let total = totals(all);
+
+    for stats in all.iter().chain(Some(&total)) {
+        let name = match stats.module {
+            Some(ref m) => format!("{} ({})", m, stats.path.display()),
+            None        => stats.path.display().to_string(),
+        };
+
+        rows.push([
+            name,
+            stats.lines.to_string(),
+            stats.functions.to_string(),
+            format!("{:.1}", stats.avg_fn_len()),
+            format!("{:.0}%", 100.0 * stats.comment_ratio()),
+            stats.complexity.to_string(),
+        ]);
+    }
+
+    let mut widths = [0usize; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in &rows {
+        let mut line = String::new();
+
+        for (i, cell) in row.iter().enumerate() {
+            if i == 0 {
+                line += &format!("{:<w$}", cell, w = widths[i]);
+            } else {
+                line += &format!("  {:>w$}", cell, w = widths[i]);
+            }
+        }
+
+        table += line.trim_end();
+        table.push('\n');
+    }
+
+    table
+}
+
+/// Renders the metrics as a JSON object with a `modules` array and a
+/// `total` entry.
+pub fn to_json(all: &[ModuleStats]) -> String {
+    let entries: Vec<String> = all.iter().map(stats_json).collect();
+
+    format!(
+        "{{\"modules\":[{}],\"total\":{}}}",
+        entries.join(","),
+        stats_json(&totals(all))
+    )
+}
+
+fn stats_json(stats: &ModuleStats) -> String {
+    let module = match stats.module {
+        Some(ref m) => json_str(m),
+        None        => "null".to_string(),
+    };
+
+    format!(
+        "{{\"path\":{},\"module\":{},\"lines\":{},\"blank_lines\":{},\
+         \"comment_lines\":{},\"functions\":{},\"avg_fn_len\":{},\
+         \"comment_ratio\":{},\"complexity\":{}}}",
+        json_str(&stats.path.display().to_string()),
+        module,
+        stats.lines,
+        stats.blank_lines,
+        stats.comment_lines,
+        stats.functions,
+        stats.avg_fn_len(),
+        stats.comment_ratio(),
+        stats.complexity
+    )
+}
@@ -1,105 +1,905 @@
+//! The nodes of the parse tree.
+
+use std::fmt;
+use std::sync::Arc;
+
+/// The kind of a node in the parse tree: either a nonterminal of the
+/// grammar (`Expr`, `FnDecl`, ...) or a concrete token (`Comma`, ...).
+///
+/// This is the parser's working type, and gains and loses variants as the
+/// parser changes; see `kind` for the stable split into `NodeKind` and
+/// `TokenKind`.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub enum TokenType {
+    /// The root of every parse tree; its only child is the `Prog`.
     Root,
+    /// A whole program: module declaration, imports, then lines.
     Prog,
+    /// `module Name exposing a, b`
     ModDecl,
+    /// `import Name (a, b)` or `import Name as N`
     Import,
+    /// A single line, holding at most one `Expr`.
     Line,
+    /// A single `Subexpr`, `FnCall`, `BinOp`, `UnaryOp`, or `Range`.
     Expr,
+    /// Wraps exactly one expression-level construct.
     Subexpr,
+    /// A function applied to one argument: the function, which is a
+    /// `Subexpr` or (for `f x y`, which is `(f x) y`) another `FnCall`,
+    /// then the argument, a `Subexpr`.
+    FnCall,
+    /// An infix operator applied to two operands: an `Expr`, the `Subexpr`
+    /// holding the `Op` or `Infixed`, and another `Expr`.
+    BinOp,
+    /// A prefix `-` applied to an operand: the `Subexpr` holding the `Op`,
+    /// then an `Expr`.
+    UnaryOp,
+    /// A character literal, `'c'`.
     ChrLit,
+    /// A string literal, `"..."`, or a triple-quoted one, `"""..."""`,
+    /// which may span lines.
     StrLit,
+    /// A function declaration, `fn name params -> Type` plus its block.
     FnDecl,
+    /// A parenthesized expression, `(expr)`.
     Parened,
+    /// `return expr`
     Return,
+    /// `case expr` followed by a block of `CaseBranch`es.
     Case,
+    /// `if cond` with its block and an optional `else` part.
     IfElse,
+    /// `try` with its block, then `catch name` with its block.
     Try,
+    /// `while cond` with its block.
     While,
+    /// `for pattern in expr` with its block.
     For,
+    /// An anonymous function, `\x, y -> expr`.
     Lambda,
+    /// A tuple literal, `(a, b)`.
     TupleLit,
+    /// A list literal, `[a, b]`.
     ListLit,
+    /// A list comprehension, `[expr | x <- xs, cond]`.
     ListComp,
+    /// A dictionary literal, `{k = v}`.
     DictLit,
+    /// A dictionary comprehension, `{k = v | x <- xs}`.
     DictComp,
+    /// A set literal, `{a, b}`.
     SetLit,
+    /// A set comprehension, `{expr | x <- xs}`.
     SetComp,
+    /// A member, scoped, or plain identifier.
     QualIdent,
+    /// A scoped or plain identifier.
     NamespacedIdent,
+    /// A plain identifier.
     Ident,
+    /// `a.b`
     MemberIdent,
+    /// `A::b`
     ScopedIdent,
+    /// A type: a name, or a tuple, list, dict, or set type.
     TypeIdent,
+    /// A numeric literal; wraps an `IntLit` or `RealLit`.
     NumLit,
+    /// A boolean literal; wraps a `TrueKeyword` or `FalseKeyword`.
+    BoolLit,
+    /// A user-level operator such as `+` or `>>=`.
     Op,
+    /// A function used infix, `` `f` ``.
     Infixed,
+    /// `infixl 6 <+>`: the precedence and associativity of an operator, for
+    /// the rest of the file.
+    FixityDecl,
+    /// A mutable binding, `var pattern: Type = expr`.
     Var,
+    /// A binding, `pattern: Type = expr`.
     Assign,
+    /// A pattern to match or destructure against.
     Pattern,
+    /// One (possibly escaped) character of a string literal.
     StrChr,
+    /// A function or lambda parameter, optionally typed.
     Param,
+    /// `pattern <- expr` in a comprehension.
     Generator,
+    /// A real literal, including `NaN` and `Infinity`.
     RealLit,
+    /// An integer literal; wraps an optional `Minus`, an optional `Radix`,
+    /// and an `AbsInt`.
     IntLit,
+    /// The digits of an integer literal, without its sign, radix, or
+    /// underscores.
     AbsInt,
+    /// The radix prefix of an integer literal: `0x`, `0o`, or `0b`.
+    Radix,
+    /// The digits of a real literal, without its sign or underscores, and
+    /// with any exponent written as `e`, an optional `-`, and its digits.
     AbsReal,
+    /// The (possibly escaped) character of a character literal.
     ChrChr,
+    /// A character of a character or string literal written as its code in
+    /// hex, `\x41`, in place of a `ChrChr` or `StrChr`.
+    HexEscape,
+    /// A character of a character or string literal written as its code
+    /// point in hex, `\u{1F600}`, in place of a `ChrChr` or `StrChr`.
+    UnicodeEscape,
+    /// `key = value` in a dictionary literal.
+    DictEntry,
+    /// `pattern => line`, or `pattern if cond => line`, within a `case`.
+    CaseBranch,
+    /// A range of integers, `a..b` or `a..=b`: an `Expr`, the `DotDot` or
+    /// `DotDotEquals`, and another `Expr`.
+    Range,
+    /// `xs[i]`, or a slice, `xs[a:b]`, either end of which may be left
+    /// out: the indexed `Subexpr`, `[`, the index `Expr` or the ends and
+    /// the `Colon` between them, and `]`.
+    Index,
+    /// `if cond` between the pattern of a `CaseBranch` and its `=>`.
+    Guard,
+    /// `x?`: the `Subexpr` whose failure is returned, and the `Op` `?`.
+    Propagate,
+    /// `=`
+    Equals,
+    /// `'`
+    SingleQuote,
+    /// `"`
+    DoubleQuote,
+    /// `"""`
+    TripleQuote,
+    /// `module`
+    ModuleKeyword,
+    /// `exposing`
+    ExposingKeyword,
+    /// `hiding`
+    HidingKeyword,
+    /// `import`
+    ImportKeyword,
+    /// `as`
+    AsKeyword,
+    /// `fn`
+    FnKeyword,
+    /// `case`
+    CaseKeyword,
+    /// `if`
+    IfKeyword,
+    /// `else`
+    ElseKeyword,
+    /// `try`
+    TryKeyword,
+    /// `catch`
+    CatchKeyword,
+    /// `while`
+    WhileKeyword,
+    /// `for`
+    ForKeyword,
+    /// `in`
+    InKeyword,
+    /// `var`
+    VarKeyword,
+    /// `NaN`
+    NanKeyword,
+    /// `Infinity`
+    InfinityKeyword,
+    /// `True`
+    TrueKeyword,
+    /// `False`
+    FalseKeyword,
+    /// `return`
+    ReturnKeyword,
+    /// `infixl`
+    InfixlKeyword,
+    /// `infixr`
+    InfixrKeyword,
+    /// `infix`
+    InfixKeyword,
+    /// `.`
+    Dot,
+    /// `..`
+    DotDot,
+    /// `..=`
+    DotDotEquals,
+    /// `,`
+    Comma,
+    /// `:`
+    Colon,
+    /// `_`
+    Underscore,
+    /// `<-`
+    LArrow,
+    /// `->`
+    RArrow,
+    /// `=>`
+    FatRArrow,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `[`
+    LSqBracket,
+    /// `]`
+    RSqBracket,
+    /// `{`
+    LCurlyBracket,
+    /// `}`
+    RCurlyBracket,
+    /// `\`
+    Backslash,
+    /// `::`
+    DoubleColon,
+    /// `-` as the sign of a numeric literal.
+    Minus,
+    /// `|`
+    Bar,
+    /// `` ` ``
+    Backtick,
+    /// `@`
+    At,
+    /// The end of a logical line. Like `Indent` and `Dedent`, this is
+    /// issued by the parser's layout handling and never appears in a tree.
+    Newline,
+    /// An increase in indentation, opening a block.
+    Indent,
+    /// A decrease in indentation, closing a block.
+    Dedent,
+}
+
+
+/// A nonterminal of the grammar: the kind of an interior node of the
+/// parse tree.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum NodeKind {
+    /// The root of every parse tree; its only child is the `Prog`.
+    Root,
+    /// A whole program: module declaration, imports, then lines.
+    Prog,
+    /// `module Name exposing a, b`
+    ModDecl,
+    /// `import Name (a, b)` or `import Name as N`
+    Import,
+    /// A single line, holding at most one `Expr`.
+    Line,
+    /// A single `Subexpr`, `FnCall`, `BinOp`, `UnaryOp`, or `Range`.
+    Expr,
+    /// Wraps exactly one expression-level construct.
+    Subexpr,
+    /// A function applied to one argument: the function, which is a
+    /// `Subexpr` or (for `f x y`, which is `(f x) y`) another `FnCall`,
+    /// then the argument, a `Subexpr`.
+    FnCall,
+    /// An infix operator applied to two operands: an `Expr`, the `Subexpr`
+    /// holding the `Op` or `Infixed`, and another `Expr`.
+    BinOp,
+    /// A prefix `-` applied to an operand: the `Subexpr` holding the `Op`,
+    /// then an `Expr`.
+    UnaryOp,
+    /// A character literal, `'c'`.
+    ChrLit,
+    /// A string literal, `"..."`, or a triple-quoted one, `"""..."""`,
+    /// which may span lines.
+    StrLit,
+    /// A function declaration, `fn name params -> Type` plus its block.
+    FnDecl,
+    /// A parenthesized expression, `(expr)`.
+    Parened,
+    /// `return expr`
+    Return,
+    /// `case expr` followed by a block of `CaseBranch`es.
+    Case,
+    /// `if cond` with its block and an optional `else` part.
+    IfElse,
+    /// `try` with its block, then `catch name` with its block.
+    Try,
+    /// `while cond` with its block.
+    While,
+    /// `for pattern in expr` with its block.
+    For,
+    /// An anonymous function, `\x, y -> expr`.
+    Lambda,
+    /// A tuple literal, `(a, b)`.
+    TupleLit,
+    /// A list literal, `[a, b]`.
+    ListLit,
+    /// A list comprehension, `[expr | x <- xs, cond]`.
+    ListComp,
+    /// A dictionary literal, `{k = v}`.
+    DictLit,
+    /// A dictionary comprehension, `{k = v | x <- xs}`.
+    DictComp,
+    /// A set literal, `{a, b}`.
+    SetLit,
+    /// A set comprehension, `{expr | x <- xs}`.
+    SetComp,
+    /// A member, scoped, or plain identifier.
+    QualIdent,
+    /// A scoped or plain identifier.
+    NamespacedIdent,
+    /// `a.b`
+    MemberIdent,
+    /// `A::b`
+    ScopedIdent,
+    /// A type: a name, or a tuple, list, dict, or set type.
+    TypeIdent,
+    /// A numeric literal; wraps an `IntLit` or `RealLit`.
+    NumLit,
+    /// A boolean literal; wraps a `TrueKeyword` or `FalseKeyword`.
+    BoolLit,
+    /// A function used infix, `` `f` ``.
+    Infixed,
+    /// `infixl 6 <+>`: the precedence and associativity of an operator, for
+    /// the rest of the file.
+    FixityDecl,
+    /// A mutable binding, `var pattern: Type = expr`.
+    Var,
+    /// A binding, `pattern: Type = expr`.
+    Assign,
+    /// A pattern to match or destructure against.
+    Pattern,
+    /// A function or lambda parameter, optionally typed.
+    Param,
+    /// `pattern <- expr` in a comprehension.
+    Generator,
+    /// A real literal, including `NaN` and `Infinity`.
+    RealLit,
+    /// An integer literal; wraps an optional `Minus`, an optional `Radix`,
+    /// and an `AbsInt`.
+    IntLit,
+    /// `key = value` in a dictionary literal.
     DictEntry,
+    /// `pattern => line`, or `pattern if cond => line`, within a `case`.
     CaseBranch,
+    /// A range of integers, `a..b` or `a..=b`: an `Expr`, the `DotDot` or
+    /// `DotDotEquals`, and another `Expr`.
+    Range,
+    /// `xs[i]`, or a slice, `xs[a:b]`, either end of which may be left
+    /// out: the indexed `Subexpr`, `[`, the index `Expr` or the ends and
+    /// the `Colon` between them, and `]`.
+    Index,
+    /// `if cond` between the pattern of a `CaseBranch` and its `=>`.
+    Guard,
+    /// `x?`: the `Subexpr` whose failure is returned, and the `Op` `?`.
+    Propagate,
+}
+
+/// A concrete token: the kind of a leaf of the parse tree, or of a token
+/// from the lexer.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TokenKind {
+    /// A plain identifier.
+    Ident,
+    /// A user-level operator such as `+` or `>>=`.
+    Op,
+    /// One (possibly escaped) character of a string literal.
+    StrChr,
+    /// The digits of an integer literal, without its sign, radix, or
+    /// underscores.
+    AbsInt,
+    /// The radix prefix of an integer literal: `0x`, `0o`, or `0b`.
+    Radix,
+    /// The digits of a real literal, without its sign or underscores, and
+    /// with any exponent written as `e`, an optional `-`, and its digits.
+    AbsReal,
+    /// The (possibly escaped) character of a character literal.
+    ChrChr,
+    /// A character of a character or string literal written as its code in
+    /// hex, `\x41`, in place of a `ChrChr` or `StrChr`.
+    HexEscape,
+    /// A character of a character or string literal written as its code
+    /// point in hex, `\u{1F600}`, in place of a `ChrChr` or `StrChr`.
+    UnicodeEscape,
+    /// `=`
     Equals,
+    /// `'`
     SingleQuote,
+    /// `"`
     DoubleQuote,
+    /// `"""`
+    TripleQuote,
+    /// `module`
     ModuleKeyword,
+    /// `exposing`
     ExposingKeyword,
+    /// `hiding`
     HidingKeyword,
+    /// `import`
     ImportKeyword,
+    /// `as`
     AsKeyword,
+    /// `fn`
     FnKeyword,
+    /// `case`
     CaseKeyword,
+    /// `if`
     IfKeyword,
+    /// `else`
     ElseKeyword,
+    /// `try`
     TryKeyword,
+    /// `catch`
     CatchKeyword,
+    /// `while`
     WhileKeyword,
+    /// `for`
     ForKeyword,
+    /// `in`
     InKeyword,
+    /// `var`
     VarKeyword,
+    /// `NaN`
     NanKeyword,
+    /// `Infinity`
     InfinityKeyword,
+    /// `True`
+    TrueKeyword,
+    /// `False`
+    FalseKeyword,
+    /// `return`
     ReturnKeyword,
+    /// `infixl`
+    InfixlKeyword,
+    /// `infixr`
+    InfixrKeyword,
+    /// `infix`
+    InfixKeyword,
+    /// `.`
     Dot,
+    /// `..`
+    DotDot,
+    /// `..=`
+    DotDotEquals,
+    /// `,`
     Comma,
+    /// `:`
     Colon,
+    /// `_`
     Underscore,
+    /// `<-`
     LArrow,
+    /// `->`
     RArrow,
+    /// `=>`
     FatRArrow,
+    /// `(`
     LParen,
+    /// `)`
     RParen,
+    /// `[`
     LSqBracket,
+    /// `]`
     RSqBracket,
+    /// `{`
     LCurlyBracket,
+    /// `}`
     RCurlyBracket,
+    /// `\`
     Backslash,
+    /// `::`
     DoubleColon,
+    /// `-` as the sign of a numeric literal.
     Minus,
+    /// `|`
     Bar,
+    /// `` ` ``
     Backtick,
+    /// `@`
+    At,
+    /// The end of a logical line. Like `Indent` and `Dedent`, this is
+    /// issued by the parser's layout handling and never appears in a tree.
+    Newline,
+    /// An increase in indentation, opening a block.
+    Indent,
+    /// A decrease in indentation, closing a block.
+    Dedent,
+}
+
+/// What a `TokenType` stands for, split by whether it is an interior node
+/// or a token. Code outside the parser should match on this rather than
+/// on `TokenType`, whose variants follow the parser's needs.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Kind {
+    /// An interior node.
+    Node(NodeKind),
+    /// A leaf, or a token from the lexer.
+    Token(TokenKind),
+}
+
+/// Where a node's text lies in its source file. Lines and columns count
+/// from 1; a span with `line == 0` is unknown (e.g. an empty interior node).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Span {
+    /// Line of the first character.
+    pub line:        usize,
+    /// Column of the first character, counted in characters.
+    pub col:         usize,
+    /// Offset of the first character from the start of the file, in bytes.
+    pub byte_offset: usize,
+    /// Length of the text, in bytes.
+    pub len:         usize,
 }
 
-#[derive(Clone, Debug)]
+/// A node of the parse tree. Leaves carry the text they were parsed from,
+/// as a range of the source they share with every other leaf parsed from
+/// it (see `lexeme`); interior nodes have an empty lexeme.
+#[derive(Clone)]
 pub struct Token {
-    pub type_:  TokenType,
-    pub lexeme: String,
+    /// What kind of node this is.
+    pub type_: TokenType,
+    /// Where the node's text is. Interior nodes cover all their children.
+    pub span:  Span,
+    /// What the lexeme is a range of: the source, for leaves lexed or
+    /// parsed from one, or the lexeme alone, for those made by `new`.
+    /// `None` if the lexeme is empty.
+    text:      Option<Arc<str>>,
+    /// Where the lexeme starts in `text`, in bytes.
+    start:     usize,
+    /// The length of the lexeme, in bytes.
+    len:       usize,
+}
+
+
+impl Span {
+    /// Whether this span points anywhere.
+    #[inline(always)]
+    pub fn is_known(&self) -> bool {
+        self.line != 0
+    }
+
+    /// The byte offset one past the end of the span.
+    #[inline(always)]
+    pub fn end(&self) -> usize {
+        self.byte_offset + self.len
+    }
+
+    /// The span from the start of `self` to the end of `other`.
+    pub fn to(&self, other: &Span) -> Span {
+        Span {
+            line:        self.line,
+            col:         self.col,
+            byte_offset: self.byte_offset,
+            len:         other.end().saturating_sub(self.byte_offset),
+        }
+    }
 }
 
+impl TokenType {
+    /// Whether this is a nonterminal or a concrete token, and which.
+    pub fn kind(&self) -> Kind {
+        match *self {
+            TokenType::Root            => Kind::Node(NodeKind::Root),
+            TokenType::Prog            => Kind::Node(NodeKind::Prog),
+            TokenType::ModDecl         => Kind::Node(NodeKind::ModDecl),
+            TokenType::Import          => Kind::Node(NodeKind::Import),
+            TokenType::Line            => Kind::Node(NodeKind::Line),
+            TokenType::Expr            => Kind::Node(NodeKind::Expr),
+            TokenType::Subexpr         => Kind::Node(NodeKind::Subexpr),
+            TokenType::FnCall          => Kind::Node(NodeKind::FnCall),
+            TokenType::BinOp           => Kind::Node(NodeKind::BinOp),
+            TokenType::UnaryOp         => Kind::Node(NodeKind::UnaryOp),
+            TokenType::ChrLit          => Kind::Node(NodeKind::ChrLit),
+            TokenType::StrLit          => Kind::Node(NodeKind::StrLit),
+            TokenType::FnDecl          => Kind::Node(NodeKind::FnDecl),
+            TokenType::Parened         => Kind::Node(NodeKind::Parened),
+            TokenType::Return          => Kind::Node(NodeKind::Return),
+            TokenType::Case            => Kind::Node(NodeKind::Case),
+            TokenType::IfElse          => Kind::Node(NodeKind::IfElse),
+            TokenType::Try             => Kind::Node(NodeKind::Try),
+            TokenType::While           => Kind::Node(NodeKind::While),
+            TokenType::For             => Kind::Node(NodeKind::For),
+            TokenType::Lambda          => Kind::Node(NodeKind::Lambda),
+            TokenType::TupleLit        => Kind::Node(NodeKind::TupleLit),
+            TokenType::ListLit         => Kind::Node(NodeKind::ListLit),
+            TokenType::ListComp        => Kind::Node(NodeKind::ListComp),
+            TokenType::DictLit         => Kind::Node(NodeKind::DictLit),
+            TokenType::DictComp        => Kind::Node(NodeKind::DictComp),
+            TokenType::SetLit          => Kind::Node(NodeKind::SetLit),
+            TokenType::SetComp         => Kind::Node(NodeKind::SetComp),
+            TokenType::QualIdent       => Kind::Node(NodeKind::QualIdent),
+            TokenType::NamespacedIdent => Kind::Node(NodeKind::NamespacedIdent),
+            TokenType::MemberIdent     => Kind::Node(NodeKind::MemberIdent),
+            TokenType::ScopedIdent     => Kind::Node(NodeKind::ScopedIdent),
+            TokenType::TypeIdent       => Kind::Node(NodeKind::TypeIdent),
+            TokenType::NumLit          => Kind::Node(NodeKind::NumLit),
+            TokenType::BoolLit         => Kind::Node(NodeKind::BoolLit),
+            TokenType::Infixed         => Kind::Node(NodeKind::Infixed),
+            TokenType::FixityDecl      => Kind::Node(NodeKind::FixityDecl),
+            TokenType::Var             => Kind::Node(NodeKind::Var),
+            TokenType::Assign          => Kind::Node(NodeKind::Assign),
+            TokenType::Pattern         => Kind::Node(NodeKind::Pattern),
+            TokenType::Param           => Kind::Node(NodeKind::Param),
+            TokenType::Generator       => Kind::Node(NodeKind::Generator),
+            TokenType::RealLit         => Kind::Node(NodeKind::RealLit),
+            TokenType::IntLit          => Kind::Node(NodeKind::IntLit),
+            TokenType::DictEntry       => Kind::Node(NodeKind::DictEntry),
+            TokenType::CaseBranch      => Kind::Node(NodeKind::CaseBranch),
+            TokenType::Range           => Kind::Node(NodeKind::Range),
+            TokenType::Index           => Kind::Node(NodeKind::Index),
+            TokenType::Guard           => Kind::Node(NodeKind::Guard),
+            TokenType::Propagate       => Kind::Node(NodeKind::Propagate),
+            TokenType::Ident           => Kind::Token(TokenKind::Ident),
+            TokenType::Op              => Kind::Token(TokenKind::Op),
+            TokenType::StrChr          => Kind::Token(TokenKind::StrChr),
+            TokenType::AbsInt          => Kind::Token(TokenKind::AbsInt),
+            TokenType::Radix           => Kind::Token(TokenKind::Radix),
+            TokenType::AbsReal         => Kind::Token(TokenKind::AbsReal),
+            TokenType::ChrChr          => Kind::Token(TokenKind::ChrChr),
+            TokenType::HexEscape       => Kind::Token(TokenKind::HexEscape),
+            TokenType::UnicodeEscape   => Kind::Token(TokenKind::UnicodeEscape),
+            TokenType::Equals          => Kind::Token(TokenKind::Equals),
+            TokenType::SingleQuote     => Kind::Token(TokenKind::SingleQuote),
+            TokenType::DoubleQuote     => Kind::Token(TokenKind::DoubleQuote),
+            TokenType::TripleQuote     => Kind::Token(TokenKind::TripleQuote),
+            TokenType::ModuleKeyword   => Kind::Token(TokenKind::ModuleKeyword),
+            TokenType::ExposingKeyword =>
+                Kind::Token(TokenKind::ExposingKeyword),
+            TokenType::HidingKeyword   => Kind::Token(TokenKind::HidingKeyword),
+            TokenType::ImportKeyword   => Kind::Token(TokenKind::ImportKeyword),
+            TokenType::AsKeyword       => Kind::Token(TokenKind::AsKeyword),
+            TokenType::FnKeyword       => Kind::Token(TokenKind::FnKeyword),
+            TokenType::CaseKeyword     => Kind::Token(TokenKind::CaseKeyword),
+            TokenType::IfKeyword       => Kind::Token(TokenKind::IfKeyword),
+            TokenType::ElseKeyword     => Kind::Token(TokenKind::ElseKeyword),
+            TokenType::TryKeyword      => Kind::Token(TokenKind::TryKeyword),
+            TokenType::CatchKeyword    => Kind::Token(TokenKind::CatchKeyword),
+            TokenType::WhileKeyword    => Kind::Token(TokenKind::WhileKeyword),
+            TokenType::ForKeyword      => Kind::Token(TokenKind::ForKeyword),
+            TokenType::InKeyword       => Kind::Token(TokenKind::InKeyword),
+            TokenType::VarKeyword      => Kind::Token(TokenKind::VarKeyword),
+            TokenType::NanKeyword      => Kind::Token(TokenKind::NanKeyword),
+            TokenType::InfinityKeyword =>
+                Kind::Token(TokenKind::InfinityKeyword),
+            TokenType::TrueKeyword     => Kind::Token(TokenKind::TrueKeyword),
+            TokenType::FalseKeyword    => Kind::Token(TokenKind::FalseKeyword),
+            TokenType::ReturnKeyword   => Kind::Token(TokenKind::ReturnKeyword),
+            TokenType::InfixlKeyword   => Kind::Token(TokenKind::InfixlKeyword),
+            TokenType::InfixrKeyword   => Kind::Token(TokenKind::InfixrKeyword),
+            TokenType::InfixKeyword    => Kind::Token(TokenKind::InfixKeyword),
+            TokenType::Dot             => Kind::Token(TokenKind::Dot),
+            TokenType::DotDot          => Kind::Token(TokenKind::DotDot),
+            TokenType::DotDotEquals    => Kind::Token(TokenKind::DotDotEquals),
+            TokenType::Comma           => Kind::Token(TokenKind::Comma),
+            TokenType::Colon           => Kind::Token(TokenKind::Colon),
+            TokenType::Underscore      => Kind::Token(TokenKind::Underscore),
+            TokenType::LArrow          => Kind::Token(TokenKind::LArrow),
+            TokenType::RArrow          => Kind::Token(TokenKind::RArrow),
+            TokenType::FatRArrow       => Kind::Token(TokenKind::FatRArrow),
+            TokenType::LParen          => Kind::Token(TokenKind::LParen),
+            TokenType::RParen          => Kind::Token(TokenKind::RParen),
+            TokenType::LSqBracket      => Kind::Token(TokenKind::LSqBracket),
+            TokenType::RSqBracket      => Kind::Token(TokenKind::RSqBracket),
+            TokenType::LCurlyBracket   => Kind::Token(TokenKind::LCurlyBracket),
+            TokenType::RCurlyBracket   => Kind::Token(TokenKind::RCurlyBracket),
+            TokenType::Backslash       => Kind::Token(TokenKind::Backslash),
+            TokenType::DoubleColon     => Kind::Token(TokenKind::DoubleColon),
+            TokenType::Minus           => Kind::Token(TokenKind::Minus),
+            TokenType::Bar             => Kind::Token(TokenKind::Bar),
+            TokenType::Backtick        => Kind::Token(TokenKind::Backtick),
+            TokenType::At              => Kind::Token(TokenKind::At),
+            TokenType::Newline         => Kind::Token(TokenKind::Newline),
+            TokenType::Indent          => Kind::Token(TokenKind::Indent),
+            TokenType::Dedent          => Kind::Token(TokenKind::Dedent),
+        }
+    }
+
+    /// The `NodeKind` of a nonterminal, or `None` for a concrete token.
+    pub fn node_kind(&self) -> Option<NodeKind> {
+        match self.kind() {
+            Kind::Node(node) => Some(node),
+            Kind::Token(_)   => None,
+        }
+    }
+
+    /// The `TokenKind` of a concrete token, or `None` for a nonterminal.
+    pub fn token_kind(&self) -> Option<TokenKind> {
+        match self.kind() {
+            Kind::Node(_)      => None,
+            Kind::Token(token) => Some(token),
+        }
+    }
+}
+
+impl From<NodeKind> for TokenType {
+    fn from(node: NodeKind) -> Self {
+        match node {
+            NodeKind::Root            => TokenType::Root,
+            NodeKind::Prog            => TokenType::Prog,
+            NodeKind::ModDecl         => TokenType::ModDecl,
+            NodeKind::Import          => TokenType::Import,
+            NodeKind::Line            => TokenType::Line,
+            NodeKind::Expr            => TokenType::Expr,
+            NodeKind::Subexpr         => TokenType::Subexpr,
+            NodeKind::FnCall          => TokenType::FnCall,
+            NodeKind::BinOp           => TokenType::BinOp,
+            NodeKind::UnaryOp         => TokenType::UnaryOp,
+            NodeKind::ChrLit          => TokenType::ChrLit,
+            NodeKind::StrLit          => TokenType::StrLit,
+            NodeKind::FnDecl          => TokenType::FnDecl,
+            NodeKind::Parened         => TokenType::Parened,
+            NodeKind::Return          => TokenType::Return,
+            NodeKind::Case            => TokenType::Case,
+            NodeKind::IfElse          => TokenType::IfElse,
+            NodeKind::Try             => TokenType::Try,
+            NodeKind::While           => TokenType::While,
+            NodeKind::For             => TokenType::For,
+            NodeKind::Lambda          => TokenType::Lambda,
+            NodeKind::TupleLit        => TokenType::TupleLit,
+            NodeKind::ListLit         => TokenType::ListLit,
+            NodeKind::ListComp        => TokenType::ListComp,
+            NodeKind::DictLit         => TokenType::DictLit,
+            NodeKind::DictComp        => TokenType::DictComp,
+            NodeKind::SetLit          => TokenType::SetLit,
+            NodeKind::SetComp         => TokenType::SetComp,
+            NodeKind::QualIdent       => TokenType::QualIdent,
+            NodeKind::NamespacedIdent => TokenType::NamespacedIdent,
+            NodeKind::MemberIdent     => TokenType::MemberIdent,
+            NodeKind::ScopedIdent     => TokenType::ScopedIdent,
+            NodeKind::TypeIdent       => TokenType::TypeIdent,
+            NodeKind::NumLit          => TokenType::NumLit,
+            NodeKind::BoolLit         => TokenType::BoolLit,
+            NodeKind::Infixed         => TokenType::Infixed,
+            NodeKind::FixityDecl      => TokenType::FixityDecl,
+            NodeKind::Var             => TokenType::Var,
+            NodeKind::Assign          => TokenType::Assign,
+            NodeKind::Pattern         => TokenType::Pattern,
+            NodeKind::Param           => TokenType::Param,
+            NodeKind::Generator       => TokenType::Generator,
+            NodeKind::RealLit         => TokenType::RealLit,
+            NodeKind::IntLit          => TokenType::IntLit,
+            NodeKind::DictEntry       => TokenType::DictEntry,
+            NodeKind::CaseBranch      => TokenType::CaseBranch,
+            NodeKind::Range           => TokenType::Range,
+            NodeKind::Index           => TokenType::Index,
+            NodeKind::Guard           => TokenType::Guard,
+            NodeKind::Propagate       => TokenType::Propagate,
+        }
+    }
+}
+
+impl From<TokenKind> for TokenType {
+    fn from(token: TokenKind) -> Self {
+        match token {
+            TokenKind::Ident           => TokenType::Ident,
+            TokenKind::Op              => TokenType::Op,
+            TokenKind::StrChr          => TokenType::StrChr,
+            TokenKind::AbsInt          => TokenType::AbsInt,
+            TokenKind::Radix           => TokenType::Radix,
+            TokenKind::AbsReal         => TokenType::AbsReal,
+            TokenKind::ChrChr          => TokenType::ChrChr,
+            TokenKind::HexEscape       => TokenType::HexEscape,
+            TokenKind::UnicodeEscape   => TokenType::UnicodeEscape,
+            TokenKind::Equals          => TokenType::Equals,
+            TokenKind::SingleQuote     => TokenType::SingleQuote,
+            TokenKind::DoubleQuote     => TokenType::DoubleQuote,
+            TokenKind::TripleQuote     => TokenType::TripleQuote,
+            TokenKind::ModuleKeyword   => TokenType::ModuleKeyword,
+            TokenKind::ExposingKeyword => TokenType::ExposingKeyword,
+            TokenKind::HidingKeyword   => TokenType::HidingKeyword,
+            TokenKind::ImportKeyword   => TokenType::ImportKeyword,
+            TokenKind::AsKeyword       => TokenType::AsKeyword,
+            TokenKind::FnKeyword       => TokenType::FnKeyword,
+            TokenKind::CaseKeyword     => TokenType::CaseKeyword,
+            TokenKind::IfKeyword       => TokenType::IfKeyword,
+            TokenKind::ElseKeyword     => TokenType::ElseKeyword,
+            TokenKind::TryKeyword      => TokenType::TryKeyword,
+            TokenKind::CatchKeyword    => TokenType::CatchKeyword,
+            TokenKind::WhileKeyword    => TokenType::WhileKeyword,
+            TokenKind::ForKeyword      => TokenType::ForKeyword,
+            TokenKind::InKeyword       => TokenType::InKeyword,
+            TokenKind::VarKeyword      => TokenType::VarKeyword,
+            TokenKind::NanKeyword      => TokenType::NanKeyword,
+            TokenKind::InfinityKeyword => TokenType::InfinityKeyword,
+            TokenKind::TrueKeyword     => TokenType::TrueKeyword,
+            TokenKind::FalseKeyword    => TokenType::FalseKeyword,
+            TokenKind::ReturnKeyword   => TokenType::ReturnKeyword,
+            TokenKind::InfixlKeyword   => TokenType::InfixlKeyword,
+            TokenKind::InfixrKeyword   => TokenType::InfixrKeyword,
+            TokenKind::InfixKeyword    => TokenType::InfixKeyword,
+            TokenKind::Dot             => TokenType::Dot,
+            TokenKind::DotDot          => TokenType::DotDot,
+            TokenKind::DotDotEquals    => TokenType::DotDotEquals,
+            TokenKind::Comma           => TokenType::Comma,
+            TokenKind::Colon           => TokenType::Colon,
+            TokenKind::Underscore      => TokenType::Underscore,
+            TokenKind::LArrow          => TokenType::LArrow,
+            TokenKind::RArrow          => TokenType::RArrow,
+            TokenKind::FatRArrow       => TokenType::FatRArrow,
+            TokenKind::LParen          => TokenType::LParen,
+            TokenKind::RParen          => TokenType::RParen,
+            TokenKind::LSqBracket      => TokenType::LSqBracket,
+            TokenKind::RSqBracket      => TokenType::RSqBracket,
+            TokenKind::LCurlyBracket   => TokenType::LCurlyBracket,
+            TokenKind::RCurlyBracket   => TokenType::RCurlyBracket,
+            TokenKind::Backslash       => TokenType::Backslash,
+            TokenKind::DoubleColon     => TokenType::DoubleColon,
+            TokenKind::Minus           => TokenType::Minus,
+            TokenKind::Bar             => TokenType::Bar,
+            TokenKind::Backtick        => TokenType::Backtick,
+            TokenKind::At              => TokenType::At,
+            TokenKind::Newline         => TokenType::Newline,
+            TokenKind::Indent          => TokenType::Indent,
+            TokenKind::Dedent          => TokenType::Dedent,
+        }
+    }
+}
+
+impl From<Kind> for TokenType {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::Node(node)   => node.into(),
+            Kind::Token(token) => token.into(),
+        }
+    }
+}
 
 impl Token {
+    /// Creates a token of the given type with the given lexeme and an
+    /// unknown span.
     pub fn new(type_: TokenType, lexeme: String) -> Self {
+        let len = lexeme.len();
+
         Token {
-            type_:  type_,
-            lexeme: lexeme,
+            type_: type_,
+            span:  Span::default(),
+            text:  if len == 0 { None } else { Some(lexeme.into()) },
+            start: 0,
+            len:   len,
         }
     }
+
+    /// Creates a leaf of the given type whose lexeme is the text of `src`
+    /// at `span`, without copying it.
+    pub fn in_source(type_: TokenType, src: &Arc<str>, span: Span) -> Self {
+        debug_assert!(src.get(span.byte_offset..span.end()).is_some());
+
+        Token {
+            type_: type_,
+            span:  span,
+            text:  if span.len == 0 { None } else { Some(src.clone()) },
+            start: span.byte_offset,
+            len:   span.len,
+        }
+    }
+
+    /// The text of a leaf, or `""` for interior nodes.
+    #[inline]
+    pub fn lexeme(&self) -> &str {
+        match self.text {
+            Some(ref text) => &text[self.start..self.start + self.len],
+            None           => "",
+        }
+    }
+
+    /// Moves the token to `span` in `src`, where the same text as its
+    /// lexeme now is, so that it no longer keeps its old source alive.
+    pub fn move_to(&mut self, src: &Arc<str>, span: Span) {
+        debug_assert!(self.len == 0 ||
+                      src.get(span.byte_offset..span.end()) ==
+                          Some(self.lexeme()));
+
+        if self.text.is_some() {
+            self.text = Some(src.clone());
+            self.start = span.byte_offset;
+        }
+
+        self.span = span;
+    }
+
+    /// Whether this is a nonterminal or a concrete token, and which.
+    #[inline(always)]
+    pub fn kind(&self) -> Kind {
+        self.type_.kind()
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Token")
+         .field("type_", &self.type_)
+         .field("lexeme", &self.lexeme())
+         .field("span", &self.span)
+         .finish()
+    }
 }
@@ -1,35 +1,68 @@
+//! A generic rose tree, used to hold the parse tree.
+
+/// A node holding a value of type `T` and any number of ordered children.
+///
+/// A node's children cannot be kept in place in the node, not even a few
+/// of them, as a node would then have to be bigger than itself. So they are
+/// kept in a `Vec`, which is only allocated when the first child is added:
+/// the parser makes many nodes that it then throws away, having found that
+/// what it was trying to parse is not there, and leaves are never given any
+/// children at all.
 pub struct Tree<T> {
-    value:    T,
-    children: Vec<Tree<T>>,
+    value:     T,
+    children:  Vec<Tree<T>>,
+    /// How many children to make room for when the first is added.
+    size_hint: u32,
 }
 
 
 impl<T> Tree<T> {
+    /// Creates a childless node, which will make room for `size_hint`
+    /// children when it is given its first.
     pub fn new(value: T, size_hint: usize) -> Self {
         Tree {
-            value:    value,
-            children: Vec::with_capacity(size_hint),
+            value:     value,
+            children:  Vec::new(),
+            size_hint: size_hint as u32,
         }
     }
 
+    /// The value held at this node.
     pub fn val(&self) -> &T {
         &self.value
     }
 
+    /// The value held at this node, mutably.
+    pub fn val_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Appends `child` as the last child of this node.
     pub fn add_child(&mut self, child: Self) {
-        self.children.place_back() <- child;
+        if self.children.capacity() == 0 {
+            self.children.reserve_exact(self.size_hint.max(1) as usize);
+        }
+
+        self.children.push(child);
     }
 
+    /// The children of this node, in order.
     pub fn children(&self) -> &Vec<Tree<T>> {
         &self.children
     }
+
+    /// The children of this node, in order, mutably.
+    pub fn children_mut(&mut self) -> &mut [Tree<T>] {
+        &mut self.children
+    }
 }
 
 impl<T> Clone for Tree<T> where T: Clone {
     fn clone(&self) -> Self {
         Tree {
-            value:    self.value.clone(),
-            children: self.children.clone(),
+            value:     self.value.clone(),
+            children:  self.children.clone(),
+            size_hint: self.size_hint,
         }
     }
 }
@@ -0,0 +1,1623 @@
+//! Type inference, in the style of Hindley and Milner: works out the type of
+//! everything in a module (see `ast`) from how it is used, checks that
+//! against the annotations, and reports where the two disagree.
+//!
+//! Functions, and lambdas bound to a name inside a block, are generalized:
+//! `fn id x = x` can be used as `Int -> Int` in one place and `Str -> Str`
+//! in another. Every other binding, and every `var`, has the one type.
+//!
+//! Integer literals can be of any numeric type (`Int`, `Nat`, or `Real`),
+//! and are `Int`s if nothing says which, and arithmetic works on any
+//! numeric type, so long as both operands have the same one. Lowercase
+//! names in annotations are type variables, shared by all the annotations
+//! of a function.
+//!
+//! Names that `resolve` cannot find, names from other modules, members
+//! (`a.b`), and operators other than the built-in ones can be of any type.
+//!
+//! ```
+//! use brouwer::{Parser, ast, typeck};
+//!
+//! let src = "module Main\n\nfn double x = x + x\n\ny = double \"two\"\n";
+//! let tree = Parser::from_str(src).parse().unwrap().unwrap();
+//! let typing = typeck::infer(&ast::lower(&tree).unwrap());
+//!
+//! assert_eq!(typing.types[0], ("double".to_string(), "a -> a".to_string()));
+//! assert_eq!(typing.errors[0].to_string(),
+//!            "5:12: expected a number, found Str");
+//! ```
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use ast::{Expr, ExprKind, FnDecl, Ident, ImportNames, Let, Module, Name,
+          Operator, Param, Pattern, PatternKind, Qualifier, Stmt, TypeExpr,
+          TypeKind};
+use runtime;
+use token::Span;
+
+
+/// The built-in types that arithmetic works on.
+const NUMERIC: &[&str] = &["Int", "Nat", "Real"];
+
+/// The types worked out for a module, and what was wrong with them.
+#[derive(Clone, Debug)]
+pub struct Typing {
+    /// Each name bound at the top level of the module, with its type, in
+    /// the order the names are bound.
+    pub types:  Vec<(String, String)>,
+    /// What was wrong, in the order it is in the module.
+    pub errors: Vec<TypeError>,
+}
+
+/// Where the types in a module disagree. The types are written as they
+/// would be in an annotation, with their variables named `a`, `b`, and so
+/// on, in the order they appear.
+#[derive(Clone, Debug)]
+pub enum TypeError {
+    /// Something of one type where another was expected.
+    Mismatch {
+        /// The type expected.
+        expected: String,
+        /// The type found.
+        found:    String,
+        /// Where it was found.
+        span:     Span,
+    },
+    /// Something not numeric where a number was expected.
+    NotNumeric {
+        /// The type found.
+        found: String,
+        /// Where it was found.
+        span:  Span,
+    },
+    /// Something whose type would have to contain itself, such as `x` in
+    /// `[x] == x`.
+    Infinite {
+        /// The type, which is a variable.
+        var:   String,
+        /// What the type would have to be.
+        type_: String,
+        /// Where it was found.
+        span:  Span,
+    },
+}
+
+/// A type, as it is being worked out.
+#[derive(Clone, Debug)]
+enum Type {
+    /// A type not yet known: an index into `Checker::vars`.
+    Var(usize),
+    /// `Unit`, the type of `()`.
+    Unit,
+    /// A type with a name and no parameters, such as `Int`.
+    Con(String),
+    /// `(A, B)`: never empty, as `()` is `Unit`.
+    Tuple(Vec<Type>),
+    /// `[A]`
+    List(Box<Type>),
+    /// `{A}`
+    Set(Box<Type>),
+    /// `{K, V}`
+    Dict(Box<Type>, Box<Type>),
+    /// `Result E A`: a failure holding an `E`, or a success holding an `A`.
+    Result(Box<Type>, Box<Type>),
+    /// A function, from its parameters to its result.
+    Fn(Vec<Type>, Box<Type>),
+}
+
+/// A type that each use of a name can fill the variables of in with types
+/// of its own.
+#[derive(Clone, Debug)]
+struct Scheme {
+    vars:  Vec<usize>,
+    type_: Type,
+}
+
+/// A type variable.
+#[derive(Clone, Debug)]
+struct Var {
+    /// The type it is, once that is known.
+    bound:   Option<Type>,
+    /// How many generalized bindings deep the outermost binding whose type
+    /// it is in is. Only variables deeper than a binding are generalized
+    /// with it.
+    level:   u32,
+    /// Whether it can only be a numeric type.
+    numeric: bool,
+}
+
+/// Why two types could not be made the same.
+enum Failure {
+    Mismatch,
+    /// A type that is not numeric was found where a number was expected.
+    NotNumeric(Type),
+    /// A number was found where a type that is not numeric was expected.
+    Number(Type),
+    Infinite(usize, Type),
+}
+
+/// How a pattern binds the names in it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Binding {
+    /// Each name is new.
+    Declare,
+    /// Each name is new unless there is one to assign to, as in `x = 1`.
+    Assign,
+    /// Each name was declared already, as everything bound at the top
+    /// level of the module is before anything else is checked.
+    Hoisted,
+}
+
+/// Works out the types in a module.
+struct Checker {
+    vars:        Vec<Var>,
+    level:       u32,
+    scopes:      Vec<HashMap<String, Scheme>>,
+    /// The result type of each function or lambda being checked, the
+    /// innermost last.
+    returns:     Vec<Type>,
+    /// What each lowercase name in the current function's annotations
+    /// stands for.
+    type_params: HashMap<String, Type>,
+    errors:      Vec<TypeError>,
+}
+
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TypeError::Mismatch { ref expected, ref found, span } => write!(
+                f,
+                "{}:{}: expected {}, found {}",
+                span.line,
+                span.col,
+                expected,
+                found
+            ),
+            TypeError::NotNumeric { ref found, span } => write!(
+                f,
+                "{}:{}: expected a number, found {}",
+                span.line,
+                span.col,
+                found
+            ),
+            TypeError::Infinite { ref var, ref type_, span } => write!(
+                f,
+                "{}:{}: infinite type: {} would have to be {}",
+                span.line,
+                span.col,
+                var,
+                type_
+            ),
+        }
+    }
+}
+
+impl Error for TypeError {}
+
+impl TypeError {
+    /// Where the error is.
+    pub fn span(&self) -> Span {
+        match *self {
+            TypeError::Mismatch { span, .. }   |
+            TypeError::NotNumeric { span, .. } |
+            TypeError::Infinite { span, .. }   => span,
+        }
+    }
+}
+
+impl Type {
+    fn con(name: &str) -> Self {
+        Type::Con(name.to_string())
+    }
+
+    fn unit() -> Self {
+        Type::Unit
+    }
+
+    /// The type of a tuple of `elems`, which is `Unit` if there are none.
+    fn tuple(elems: Vec<Type>) -> Self {
+        if elems.is_empty() {
+            Type::Unit
+        } else {
+            Type::Tuple(elems)
+        }
+    }
+
+    fn result(failure: Type, success: Type) -> Self {
+        Type::Result(Box::new(failure), Box::new(success))
+    }
+
+    fn func(params: Vec<Type>, result: Type) -> Self {
+        Type::Fn(params, Box::new(result))
+    }
+}
+
+impl Checker {
+    fn new() -> Self {
+        let mut checker = Checker {
+            vars:        Vec::new(),
+            level:       0,
+            scopes:      vec![HashMap::new()],
+            returns:     Vec::new(),
+            type_params: HashMap::new(),
+            errors:      Vec::new(),
+        };
+
+        checker.prelude();
+
+        checker
+    }
+
+    /// Declares the names in `resolve::PRELUDE`.
+    fn prelude(&mut self) {
+        let a = self.fresh();
+        self.define_scheme("print", vec![a], Type::func(
+            vec![Type::Var(a)],
+            Type::unit()
+        ));
+        self.define_scheme("put", vec![], Type::func(
+            vec![Type::con("Str")],
+            Type::unit()
+        ));
+
+        let a = self.fresh();
+        self.define_scheme("read", vec![a], Type::func(
+            vec![Type::con("Str")],
+            Type::Var(a)
+        ));
+        self.define_scheme("input", vec![], Type::con("Str"));
+
+        let a = self.fresh();
+        self.define_scheme("length", vec![a], Type::func(
+            vec![Type::Var(a)],
+            Type::con("Int")
+        ));
+
+        let (e, a) = (self.fresh(), self.fresh());
+        self.define_scheme("Ok", vec![e, a], Type::func(
+            vec![Type::Var(a)],
+            Type::result(Type::Var(e), Type::Var(a))
+        ));
+
+        let (e, a) = (self.fresh(), self.fresh());
+        self.define_scheme("Err", vec![e, a], Type::func(
+            vec![Type::Var(e)],
+            Type::result(Type::Var(e), Type::Var(a))
+        ));
+        self.define_scheme("NaN", vec![], Type::con("Real"));
+        self.define_scheme("Infinity", vec![], Type::con("Real"));
+
+        for &(constructor, fields) in runtime::CONSTRUCTORS {
+            let error = Type::con(runtime::ERROR_TYPE);
+            let type_ = if fields.is_empty() {
+                error
+            } else {
+                Type::func(fields.iter().map(|f| Type::con(f)).collect(), error)
+            };
+
+            self.define_scheme(constructor, vec![], type_);
+        }
+
+        // The prelude is a scope of its own, which the module may shadow.
+        self.scopes.push(HashMap::new());
+    }
+
+    fn module(&mut self, module: &Module) {
+        for import in &module.imports {
+            if let ImportNames::Exposing(ref names) = import.names {
+                for name in names {
+                    self.define_unknown(&name.name);
+                }
+            }
+        }
+
+        // Everything bound at the top level can be used anywhere in the
+        // module, as in `resolve`, so it is all declared up front.
+        for stmt in &module.body {
+            if let Stmt::Let(ref let_) = *stmt {
+                for ident in let_.pattern.idents() {
+                    if !let_.mutable && self.is_defined_here(&ident.name) {
+                        continue;
+                    }
+
+                    let var = self.fresh_type();
+                    self.define(&ident.name, var);
+                }
+            }
+        }
+
+        self.fns(&module.body);
+        self.stmts(&module.body, Binding::Hoisted, false);
+    }
+
+    /// Makes every number whose type is still not known, and that is not
+    /// generalized, an `Int`.
+    fn default_numbers(&mut self) {
+        for var in &mut self.vars {
+            if var.numeric && var.bound.is_none() && var.level == 0 {
+                var.bound = Some(Type::con("Int"));
+            }
+        }
+    }
+
+    /// The type of each name bound at the top level of `module`.
+    fn types(&self, module: &Module) -> Vec<(String, String)> {
+        let mut types: Vec<(String, String)> = Vec::new();
+
+        for stmt in &module.body {
+            let idents = match *stmt {
+                Stmt::Fn(ref fn_decl) => vec![&fn_decl.name],
+                Stmt::Let(ref let_)   => let_.pattern.idents(),
+                _                     => continue,
+            };
+
+            for ident in idents {
+                if types.iter().any(|(name, _)| *name == ident.name) {
+                    continue;
+                }
+
+                if let Some(scheme) = self.lookup(&ident.name) {
+                    let type_ = self.render(&[&scheme.type_]).remove(0);
+                    types.push((ident.name.clone(), type_));
+                }
+            }
+        }
+
+        types
+    }
+
+    /// Checks the functions declared in a block, which can all be used
+    /// anywhere in it, and so are generalized together before anything
+    /// else in the block is checked.
+    fn fns(&mut self, stmts: &[Stmt]) {
+        let fns: Vec<&FnDecl> = stmts.iter().filter_map(|stmt| match *stmt {
+            Stmt::Fn(ref fn_decl) => Some(fn_decl),
+            _                     => None,
+        }).collect();
+
+        if fns.is_empty() {
+            return;
+        }
+
+        self.level += 1;
+
+        let vars: Vec<Type> = fns.iter().map(|fn_decl| {
+            let var = self.fresh_type();
+            self.define(&fn_decl.name.name, var.clone());
+
+            var
+        }).collect();
+
+        for (fn_decl, var) in fns.iter().zip(&vars) {
+            let type_ = self.fn_decl(fn_decl);
+            self.unify(var, &type_, fn_decl.name.span);
+        }
+
+        self.level -= 1;
+
+        for (fn_decl, var) in fns.iter().zip(&vars) {
+            let scheme = self.generalize(var);
+            self.define_scheme(&fn_decl.name.name, scheme.vars, scheme.type_);
+        }
+    }
+
+    fn fn_decl(&mut self, fn_decl: &FnDecl) -> Type {
+        let outer_params = self.type_params.clone();
+        self.scopes.push(HashMap::new());
+
+        let params = self.params(&fn_decl.params);
+        let result = match fn_decl.ret_type {
+            Some(ref ret_type) => self.annotation(ret_type),
+            None               => self.fresh_type(),
+        };
+
+        self.returns.push(result.clone());
+        self.fns(&fn_decl.body);
+        let body = self.stmts(&fn_decl.body, Binding::Assign, true);
+        self.returns.pop();
+
+        let span = fn_decl.body.last().map_or(fn_decl.span, stmt_span);
+        self.unify(&result, &body, span);
+
+        self.scopes.pop();
+        self.type_params = outer_params;
+
+        Type::func(params, result)
+    }
+
+    fn params(&mut self, params: &[Param]) -> Vec<Type> {
+        params.iter().map(|param| {
+            let type_ = self.pattern(&param.pattern, Binding::Declare);
+
+            if let Some(ref annotation) = param.type_ {
+                let annotated = self.annotation(annotation);
+                self.unify(&annotated, &type_, param.pattern.span);
+            }
+
+            type_
+        }).collect()
+    }
+
+    /// Checks the lines of a block in the current scope, returning the
+    /// type of its last line, if `used` and that line is an expression,
+    /// or else `()`.
+    fn stmts(&mut self, stmts: &[Stmt], binding: Binding, used: bool) -> Type {
+        let mut last = Type::unit();
+
+        for (i, stmt) in stmts.iter().enumerate() {
+            let used = used && i == stmts.len() - 1;
+
+            last = match *stmt {
+                Stmt::Let(ref let_) => {
+                    self.let_(let_, binding);
+
+                    Type::unit()
+                },
+                Stmt::Expr(ref expr) => self.expr_used(expr, used),
+                Stmt::Fn(_) | Stmt::Fixity(_) => Type::unit(),
+            };
+        }
+
+        last
+    }
+
+    /// Checks the lines of a block in a scope of its own.
+    fn block(&mut self, stmts: &[Stmt], used: bool) -> Type {
+        self.scopes.push(HashMap::new());
+        self.fns(stmts);
+        let type_ = self.stmts(stmts, Binding::Assign, used);
+        self.scopes.pop();
+
+        type_
+    }
+
+    fn let_(&mut self, let_: &Let, binding: Binding) {
+        let binding = if let_.mutable && binding == Binding::Assign {
+            Binding::Declare
+        } else {
+            binding
+        };
+
+        // A lambda bound to a new name is generalized, like a function.
+        let generalize = !let_.mutable &&
+            binding == Binding::Assign &&
+            is_lambda(&let_.value) &&
+            match let_.pattern.kind {
+                PatternKind::Bind(ref ident) => !self.is_defined(&ident.name),
+                _                            => false,
+            };
+
+        if generalize {
+            self.level += 1;
+        }
+
+        let value = self.expr(&let_.value);
+
+        if let Some(ref annotation) = let_.type_ {
+            let annotated = self.annotation(annotation);
+            self.unify(&annotated, &value, let_.value.span);
+        }
+
+        if generalize {
+            self.level -= 1;
+
+            if let PatternKind::Bind(ref ident) = let_.pattern.kind {
+                let scheme = self.generalize(&value);
+                self.define_scheme(&ident.name, scheme.vars, scheme.type_);
+            }
+
+            return;
+        }
+
+        let pattern = self.pattern(&let_.pattern, binding);
+        self.unify(&pattern, &value, let_.value.span);
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Type {
+        self.expr_used(expr, true)
+    }
+
+    /// The type of `expr`. Unless its value is `used`, the branches of an
+    /// `if`, `case`, or `try` need not have the same type.
+    fn expr_used(&mut self, expr: &Expr, used: bool) -> Type {
+        match expr.kind {
+            ExprKind::Int(_) => {
+                let var = self.fresh();
+                self.vars[var].numeric = true;
+
+                Type::Var(var)
+            },
+            ExprKind::Real(_) => Type::con("Real"),
+            ExprKind::Bool(_) => Type::con("Bool"),
+            ExprKind::Char(_) => Type::con("Char"),
+            ExprKind::Str(_)  => Type::con("Str"),
+            ExprKind::Name(Name::Plain(ref ident)) => self.name(ident),
+            ExprKind::Name(_) => self.fresh_type(),
+            ExprKind::Op(ref op) => self.operator(op),
+            ExprKind::Apply(ref f, ref args) => {
+                let f_type = self.expr(f);
+                let params: Vec<Type> = args.iter()
+                                            .map(|_| self.fresh_type())
+                                            .collect();
+                let result = self.fresh_type();
+
+                self.unify(
+                    &Type::func(params.clone(), result.clone()),
+                    &f_type,
+                    f.span
+                );
+
+                for (param, arg) in params.iter().zip(args) {
+                    let arg_type = self.expr(arg);
+                    self.unify(param, &arg_type, arg.span);
+                }
+
+                result
+            },
+            ExprKind::BinOp(ref op, ref lhs, ref rhs) => {
+                let op_type = self.operator(op);
+                let lhs_type = self.expr(lhs);
+                let rhs_type = self.expr(rhs);
+                let (l, r) = (self.fresh_type(), self.fresh_type());
+                let result = self.fresh_type();
+
+                self.unify(
+                    &Type::func(vec![l.clone(), r.clone()], result.clone()),
+                    &op_type,
+                    op.span()
+                );
+                self.unify(&l, &lhs_type, lhs.span);
+                self.unify(&r, &rhs_type, rhs.span);
+
+                result
+            },
+            ExprKind::Range(ref lo, ref hi, _) => {
+                for end in &[lo, hi] {
+                    let end_type = self.expr(end);
+                    self.unify(&Type::con("Int"), &end_type, end.span);
+                }
+
+                Type::List(Box::new(Type::con("Int")))
+            },
+            ExprKind::Index(ref indexed, ref index) => {
+                let indexed_type = self.expr(indexed);
+                let (key, elem) = self.indexed_by(&indexed_type, indexed.span);
+                let index_type = self.expr(index);
+                self.unify(&key, &index_type, index.span);
+
+                elem
+            },
+            ExprKind::Slice(ref sliced, ref low, ref high) => {
+                let sliced_type = self.expr(sliced);
+
+                match self.shallow(&sliced_type) {
+                    Type::Con(ref name) if name == "Str" => {},
+                    _ => {
+                        let elem = self.fresh_type();
+                        self.unify(
+                            &Type::List(Box::new(elem)),
+                            &sliced_type,
+                            sliced.span
+                        );
+                    },
+                }
+
+                for end in low.iter().chain(high) {
+                    let end_type = self.expr(end);
+                    self.unify(&Type::con("Int"), &end_type, end.span);
+                }
+
+                sliced_type
+            },
+            ExprKind::Neg(ref operand) => {
+                let type_ = self.expr(operand);
+                let number = self.fresh();
+                self.vars[number].numeric = true;
+                self.unify(&Type::Var(number), &type_, operand.span);
+
+                type_
+            },
+            ExprKind::Return(ref value) => {
+                let type_ = self.expr(value);
+
+                if let Some(result) = self.returns.last().cloned() {
+                    self.unify(&result, &type_, value.span);
+                }
+
+                // Nothing ever sees the value of a `return` itself.
+                self.fresh_type()
+            },
+            // `Result e a -> a`, where the enclosing function returns a
+            // `Result` with failures of the same type `e`.
+            ExprKind::Propagate(ref result) => {
+                let type_ = self.expr(result);
+                let (failure, success) = (self.fresh_type(), self.fresh_type());
+                self.unify(
+                    &Type::result(failure.clone(), success.clone()),
+                    &type_,
+                    result.span
+                );
+
+                if let Some(returned) = self.returns.last().cloned() {
+                    let other_success = self.fresh_type();
+                    self.unify(
+                        &returned,
+                        &Type::result(failure, other_success),
+                        expr.span
+                    );
+                }
+
+                success
+            },
+            ExprKind::Tuple(ref elems) => Type::tuple(
+                elems.iter().map(|elem| self.expr(elem)).collect()
+            ),
+            ExprKind::List(ref elems) => {
+                Type::List(Box::new(self.elems(elems)))
+            },
+            ExprKind::Set(ref elems) => Type::Set(Box::new(self.elems(elems))),
+            ExprKind::Dict(ref entries) => {
+                let (key, value) = (self.fresh_type(), self.fresh_type());
+
+                for (k, v) in entries {
+                    let k_type = self.expr(k);
+                    self.unify(&key, &k_type, k.span);
+                    let v_type = self.expr(v);
+                    self.unify(&value, &v_type, v.span);
+                }
+
+                Type::Dict(Box::new(key), Box::new(value))
+            },
+            ExprKind::ListComp(ref elem, ref qualifiers) => {
+                self.scopes.push(HashMap::new());
+                self.qualifiers(qualifiers);
+                let type_ = self.expr(elem);
+                self.scopes.pop();
+
+                Type::List(Box::new(type_))
+            },
+            ExprKind::SetComp(ref elem, ref qualifiers) => {
+                self.scopes.push(HashMap::new());
+                self.qualifiers(qualifiers);
+                let type_ = self.expr(elem);
+                self.scopes.pop();
+
+                Type::Set(Box::new(type_))
+            },
+            ExprKind::DictComp(ref entry, ref qualifiers) => {
+                self.scopes.push(HashMap::new());
+                self.qualifiers(qualifiers);
+                let key = self.expr(&entry.0);
+                let value = self.expr(&entry.1);
+                self.scopes.pop();
+
+                Type::Dict(Box::new(key), Box::new(value))
+            },
+            ExprKind::Lambda(ref params, ref body) => {
+                self.scopes.push(HashMap::new());
+                let params = self.params(params);
+                let result = self.fresh_type();
+
+                self.returns.push(result.clone());
+                let body_type = self.expr(body);
+                self.returns.pop();
+                self.unify(&result, &body_type, body.span);
+
+                self.scopes.pop();
+
+                Type::func(params, result)
+            },
+            ExprKind::If(ref cond, ref body, ref else_body) => {
+                self.condition(cond);
+
+                let then_type = self.block(body, used);
+
+                match *else_body {
+                    Some(ref else_body) => {
+                        let else_type = self.block(else_body, used);
+
+                        if used {
+                            let span = else_body.last()
+                                                .map_or(expr.span, stmt_span);
+                            self.unify(&then_type, &else_type, span);
+                        }
+
+                        then_type
+                    },
+                    None => Type::unit(),
+                }
+            },
+            ExprKind::Case(ref subject, ref branches) => {
+                let subject_type = self.expr(subject);
+                let result = self.fresh_type();
+
+                for branch in branches {
+                    self.scopes.push(HashMap::new());
+
+                    let pattern = self.pattern(
+                        &branch.pattern,
+                        Binding::Declare
+                    );
+                    self.unify(&subject_type, &pattern, branch.pattern.span);
+
+                    if let Some(ref guard) = branch.guard {
+                        self.condition(guard);
+                    }
+
+                    self.fns(&branch.body);
+                    let body = self.stmts(
+                        &branch.body,
+                        Binding::Assign,
+                        used
+                    );
+
+                    if used {
+                        let span = branch.body
+                                         .last()
+                                         .map_or(branch.span, stmt_span);
+                        self.unify(&result, &body, span);
+                    }
+
+                    self.scopes.pop();
+                }
+
+                if used { result } else { Type::unit() }
+            },
+            ExprKind::Try(ref body, ref err, ref handler) => {
+                let body_type = self.block(body, used);
+
+                self.scopes.push(HashMap::new());
+                self.define_unknown(&err.name);
+                self.fns(handler);
+                let handler_type = self.stmts(handler, Binding::Assign, used);
+                self.scopes.pop();
+
+                if used {
+                    let span = handler.last().map_or(err.span, stmt_span);
+                    self.unify(&body_type, &handler_type, span);
+                }
+
+                body_type
+            },
+            ExprKind::While(ref cond, ref body) => {
+                self.condition(cond);
+                self.block(body, false);
+
+                Type::unit()
+            },
+            ExprKind::WhileLet(ref pattern, ref subject, ref body) => {
+                let subject_type = self.expr(subject);
+
+                self.scopes.push(HashMap::new());
+                let pattern_type = self.pattern(pattern, Binding::Declare);
+                self.unify(&subject_type, &pattern_type, pattern.span);
+                self.fns(body);
+                self.stmts(body, Binding::Assign, false);
+                self.scopes.pop();
+
+                Type::unit()
+            },
+            ExprKind::For(ref pattern, ref iterated, ref body) => {
+                let iterated_type = self.expr(iterated);
+                let elem = self.elem_of(&iterated_type, iterated.span);
+
+                self.scopes.push(HashMap::new());
+                let pattern_type = self.pattern(pattern, Binding::Declare);
+                self.unify(&elem, &pattern_type, pattern.span);
+                self.fns(body);
+                self.stmts(body, Binding::Assign, false);
+                self.scopes.pop();
+
+                Type::unit()
+            },
+        }
+    }
+
+    /// The type of the elements of a list or set, all of which must be of
+    /// the same type.
+    fn elems(&mut self, elems: &[Expr]) -> Type {
+        let type_ = self.fresh_type();
+
+        for elem in elems {
+            let elem_type = self.expr(elem);
+            self.unify(&type_, &elem_type, elem.span);
+        }
+
+        type_
+    }
+
+    fn condition(&mut self, cond: &Expr) {
+        let type_ = self.expr(cond);
+        self.unify(&Type::con("Bool"), &type_, cond.span);
+    }
+
+    /// Checks the qualifiers of a comprehension, in the current scope.
+    fn qualifiers(&mut self, qualifiers: &[Qualifier]) {
+        for qualifier in qualifiers {
+            match *qualifier {
+                Qualifier::Generator(ref pattern, ref iterated) => {
+                    let iterated_type = self.expr(iterated);
+                    let elem = self.elem_of(&iterated_type, iterated.span);
+                    let pattern_type = self.pattern(pattern, Binding::Declare);
+                    self.unify(&elem, &pattern_type, pattern.span);
+                },
+                Qualifier::Guard(ref cond) => self.condition(cond),
+            }
+        }
+    }
+
+    /// The type of what iterating over something of type `type_` yields:
+    /// the characters of a string, the elements of a list or set, or the
+    /// keys of a dictionary.
+    fn elem_of(&mut self, type_: &Type, span: Span) -> Type {
+        match self.shallow(type_) {
+            Type::Con(ref name) if name == "Str" => Type::con("Char"),
+            Type::Set(elem)                      => *elem,
+            Type::Dict(key, _)                   => *key,
+            _ => {
+                let elem = self.fresh_type();
+                self.unify(&Type::List(Box::new(elem.clone())), type_, span);
+
+                elem
+            },
+        }
+    }
+
+    /// What indexes something of type `type_`, and what it is indexed to:
+    /// a character of a string or an element of a list, by an `Int`, or a
+    /// value of a dictionary, by its key.
+    fn indexed_by(&mut self, type_: &Type, span: Span) -> (Type, Type) {
+        match self.shallow(type_) {
+            Type::Con(ref name) if name == "Str" => {
+                (Type::con("Int"), Type::con("Char"))
+            },
+            Type::Dict(key, value) => (*key, *value),
+            _ => {
+                let elem = self.fresh_type();
+                self.unify(&Type::List(Box::new(elem.clone())), type_, span);
+
+                (Type::con("Int"), elem)
+            },
+        }
+    }
+
+    fn name(&mut self, ident: &Ident) -> Type {
+        match self.lookup(&ident.name).cloned() {
+            Some(scheme) => self.instantiate(&scheme),
+            // `resolve` reports it.
+            None         => self.fresh_type(),
+        }
+    }
+
+    /// The type of an operator, as a function of its two operands.
+    fn operator(&mut self, op: &Operator) -> Type {
+        let symbol = match *op {
+            Operator::Symbol(ref symbol) => symbol,
+            Operator::Infixed(Name::Plain(ref ident)) => {
+                return self.name(ident);
+            },
+            Operator::Infixed(_) => return self.fresh_type(),
+        };
+
+        let operand = self.fresh();
+
+        match symbol.name.as_str() {
+            "+" | "-" | "*" | "/" | "%" | "^" => {
+                self.vars[operand].numeric = true;
+                let operand = Type::Var(operand);
+
+                Type::func(vec![operand.clone(), operand.clone()], operand)
+            },
+            "==" | "!=" | "/=" | "<" | "<=" | ">" | ">=" => Type::func(
+                vec![Type::Var(operand), Type::Var(operand)],
+                Type::con("Bool")
+            ),
+            "&&" | "||" => Type::func(
+                vec![Type::con("Bool"), Type::con("Bool")],
+                Type::con("Bool")
+            ),
+            _ => Type::func(
+                vec![Type::Var(operand), self.fresh_type()],
+                self.fresh_type()
+            ),
+        }
+    }
+
+    /// The type of `pattern`, binding the names in it as `binding` says.
+    fn pattern(&mut self, pattern: &Pattern, binding: Binding) -> Type {
+        match pattern.kind {
+            PatternKind::Bind(ref ident) => self.bind(ident, binding),
+            PatternKind::Wildcard => self.fresh_type(),
+            PatternKind::Int(_) | PatternKind::Range(..) => {
+                let var = self.fresh();
+                self.vars[var].numeric = true;
+
+                Type::Var(var)
+            },
+            PatternKind::Real(_) => Type::con("Real"),
+            PatternKind::Bool(_) => Type::con("Bool"),
+            PatternKind::Char(_) => Type::con("Char"),
+            PatternKind::Str(_)  => Type::con("Str"),
+            PatternKind::Prefix(_, ref rest) |
+            PatternKind::Suffix(ref rest, _) => {
+                let rest_type = self.pattern(rest, binding);
+                self.unify(&Type::con("Str"), &rest_type, rest.span);
+
+                Type::con("Str")
+            },
+            PatternKind::As(ref ident, ref matched) => {
+                let type_ = self.bind(ident, binding);
+                let matched_type = self.pattern(matched, binding);
+                self.unify(&type_, &matched_type, matched.span);
+
+                type_
+            },
+            PatternKind::Or(ref alternatives) => {
+                let type_ = self.fresh_type();
+
+                // The first alternative binds the names, and the others
+                // match them.
+                for (ix, alternative) in alternatives.iter().enumerate() {
+                    let alternative_type = self.pattern(
+                        alternative,
+                        if ix == 0 { binding } else { Binding::Assign }
+                    );
+                    self.unify(&type_, &alternative_type, alternative.span);
+                }
+
+                type_
+            },
+            PatternKind::Tuple(ref elems) => Type::tuple(
+                elems.iter()
+                     .map(|elem| self.pattern(elem, binding))
+                     .collect()
+            ),
+            PatternKind::List(ref elems) => Type::List(
+                Box::new(self.elem_patterns(elems, binding))
+            ),
+            PatternKind::Set(ref elems) => Type::Set(
+                Box::new(self.elem_patterns(elems, binding))
+            ),
+            PatternKind::Dict(ref entries) => {
+                let (key, value) = (self.fresh_type(), self.fresh_type());
+
+                for (k, v) in entries {
+                    let k_type = self.pattern(k, binding);
+                    self.unify(&key, &k_type, k.span);
+                    let v_type = self.pattern(v, binding);
+                    self.unify(&value, &v_type, v.span);
+                }
+
+                Type::Dict(Box::new(key), Box::new(value))
+            },
+        }
+    }
+
+    fn elem_patterns(&mut self, elems: &[Pattern], binding: Binding) -> Type {
+        let type_ = self.fresh_type();
+
+        for elem in elems {
+            let elem_type = self.pattern(elem, binding);
+            self.unify(&type_, &elem_type, elem.span);
+        }
+
+        type_
+    }
+
+    /// The type of the name `ident` in a pattern, binding it as `binding`
+    /// says.
+    fn bind(&mut self, ident: &Ident, binding: Binding) -> Type {
+        let existing = match binding {
+            Binding::Declare => None,
+            Binding::Assign  => self.lookup(&ident.name).cloned(),
+            Binding::Hoisted => self.scopes
+                                    .last()
+                                    .and_then(|s| s.get(&ident.name))
+                                    .cloned(),
+        };
+
+        match existing {
+            Some(scheme) => self.instantiate(&scheme),
+            None         => {
+                let var = self.fresh_type();
+                self.define(&ident.name, var.clone());
+
+                var
+            },
+        }
+    }
+
+    /// The type an annotation stands for.
+    fn annotation(&mut self, annotation: &TypeExpr) -> Type {
+        match annotation.kind {
+            TypeKind::Name(Name::Plain(ref ident)) => {
+                let lowercase = ident.name
+                                     .chars()
+                                     .next()
+                                     .is_some_and(char::is_lowercase);
+
+                if ident.name == "Unit" {
+                    return Type::Unit;
+                } else if !lowercase {
+                    return Type::Con(ident.name.clone());
+                }
+
+                if let Some(param) = self.type_params.get(&ident.name) {
+                    return param.clone();
+                }
+
+                let param = self.fresh_type();
+                self.type_params.insert(ident.name.clone(), param.clone());
+
+                param
+            },
+            TypeKind::Name(ref name) => Type::Con(name.to_string()),
+            TypeKind::Tuple(ref elems) => Type::tuple(
+                elems.iter().map(|elem| self.annotation(elem)).collect()
+            ),
+            TypeKind::List(ref elem) => {
+                Type::List(Box::new(self.annotation(elem)))
+            },
+            TypeKind::Set(ref elem) => {
+                Type::Set(Box::new(self.annotation(elem)))
+            },
+            TypeKind::Dict(ref key, ref value) => Type::Dict(
+                Box::new(self.annotation(key)),
+                Box::new(self.annotation(value))
+            ),
+        }
+    }
+
+    /// Makes `found` the same type as `expected`, reporting at `span` if it
+    /// cannot be.
+    fn unify(&mut self, expected: &Type, found: &Type, span: Span) {
+        let error = match self.unify_types(expected, found) {
+            Ok(())                       => return,
+            Err(Failure::Mismatch)       => {
+                let mut types = self.render(&[expected, found]);
+                let found = types.pop().unwrap_or_default();
+
+                TypeError::Mismatch {
+                    expected: types.pop().unwrap_or_default(),
+                    found:    found,
+                    span:     span,
+                }
+            },
+            Err(Failure::NotNumeric(t))  => TypeError::NotNumeric {
+                found: self.render(&[&t]).remove(0),
+                span:  span,
+            },
+            Err(Failure::Number(t))      => TypeError::Mismatch {
+                expected: self.render(&[&t]).remove(0),
+                found:    "a number".to_string(),
+                span:     span,
+            },
+            Err(Failure::Infinite(v, t)) => {
+                let mut types = self.render(&[&Type::Var(v), &t]);
+                let type_ = types.pop().unwrap_or_default();
+
+                TypeError::Infinite {
+                    var:   types.pop().unwrap_or_default(),
+                    type_: type_,
+                    span:  span,
+                }
+            },
+        };
+
+        self.errors.push(error);
+    }
+
+    fn unify_types(&mut self, a: &Type, b: &Type) -> Result<(), Failure> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(v), Type::Var(w)) if v == w => Ok(()),
+            (Type::Var(v), t) => self.bind_var(v, t),
+            (t, Type::Var(v)) => match self.bind_var(v, t) {
+                Err(Failure::NotNumeric(t)) => Err(Failure::Number(t)),
+                result                      => result,
+            },
+            (Type::Unit, Type::Unit) => Ok(()),
+            (Type::Con(ref x), Type::Con(ref y)) if x == y => Ok(()),
+            (Type::Tuple(ref xs), Type::Tuple(ref ys)) |
+            (Type::Fn(ref xs, _), Type::Fn(ref ys, _))
+                if xs.len() != ys.len() => Err(Failure::Mismatch),
+            (Type::Tuple(xs), Type::Tuple(ys)) => {
+                for (x, y) in xs.iter().zip(&ys) {
+                    self.unify_types(x, y)?;
+                }
+
+                Ok(())
+            },
+            (Type::List(x), Type::List(y)) |
+            (Type::Set(x), Type::Set(y))   => self.unify_types(&x, &y),
+            (Type::Dict(k1, v1), Type::Dict(k2, v2))     |
+            (Type::Result(k1, v1), Type::Result(k2, v2)) => {
+                self.unify_types(&k1, &k2)?;
+                self.unify_types(&v1, &v2)
+            },
+            (Type::Fn(xs, x), Type::Fn(ys, y)) => {
+                for (x, y) in xs.iter().zip(&ys) {
+                    self.unify_types(x, y)?;
+                }
+
+                self.unify_types(&x, &y)
+            },
+            _ => Err(Failure::Mismatch),
+        }
+    }
+
+    /// Makes the unbound variable `var` stand for `type_`.
+    fn bind_var(&mut self, var: usize, type_: Type) -> Result<(), Failure> {
+        if self.vars[var].numeric {
+            match type_ {
+                Type::Var(other) => self.vars[other].numeric = true,
+                Type::Con(ref name) if NUMERIC.contains(&name.as_str()) => {},
+                _ => return Err(Failure::NotNumeric(type_)),
+            }
+        }
+
+        let level = self.vars[var].level;
+
+        if self.occurs(var, level, &type_) {
+            return Err(Failure::Infinite(var, type_));
+        }
+
+        self.vars[var].bound = Some(type_);
+
+        Ok(())
+    }
+
+    /// Whether `var` occurs in `type_`, lowering the level of every other
+    /// variable in it to at most `level` along the way, as it can be
+    /// generalized no sooner than `var` can now.
+    fn occurs(&mut self, var: usize, level: u32, type_: &Type) -> bool {
+        match self.shallow(type_) {
+            Type::Var(v) => {
+                if v == var {
+                    return true;
+                }
+
+                if self.vars[v].level > level {
+                    self.vars[v].level = level;
+                }
+
+                false
+            },
+            Type::Unit | Type::Con(_) => false,
+            Type::Tuple(elems) => {
+                elems.iter().any(|elem| self.occurs(var, level, elem))
+            },
+            Type::List(elem) | Type::Set(elem) => {
+                self.occurs(var, level, &elem)
+            },
+            Type::Dict(key, value) | Type::Result(key, value) => {
+                self.occurs(var, level, &key) ||
+                    self.occurs(var, level, &value)
+            },
+            Type::Fn(params, result) => {
+                params.iter().any(|param| self.occurs(var, level, param)) ||
+                    self.occurs(var, level, &result)
+            },
+        }
+    }
+
+    /// `type_`, with the variable it is replaced by what it is bound to,
+    /// if it is a bound variable.
+    fn shallow(&self, type_: &Type) -> Type {
+        let mut type_ = type_;
+
+        while let Type::Var(v) = *type_ {
+            match self.vars[v].bound {
+                Some(ref bound) => type_ = bound,
+                None            => break,
+            }
+        }
+
+        type_.clone()
+    }
+
+    /// `type_`, with every bound variable in it replaced by what it is
+    /// bound to.
+    fn resolve(&self, type_: &Type) -> Type {
+        match self.shallow(type_) {
+            Type::Tuple(elems) => Type::Tuple(
+                elems.iter().map(|elem| self.resolve(elem)).collect()
+            ),
+            Type::List(elem) => Type::List(Box::new(self.resolve(&elem))),
+            Type::Set(elem)  => Type::Set(Box::new(self.resolve(&elem))),
+            Type::Dict(key, value) => Type::Dict(
+                Box::new(self.resolve(&key)),
+                Box::new(self.resolve(&value))
+            ),
+            Type::Result(failure, success) => Type::result(
+                self.resolve(&failure),
+                self.resolve(&success)
+            ),
+            Type::Fn(params, result) => Type::Fn(
+                params.iter().map(|param| self.resolve(param)).collect(),
+                Box::new(self.resolve(&result))
+            ),
+            type_ => type_,
+        }
+    }
+
+    /// A scheme for `type_` in which its variables deeper than the current
+    /// level can be filled in anew at each use.
+    fn generalize(&self, type_: &Type) -> Scheme {
+        let type_ = self.resolve(type_);
+        let mut vars = Vec::new();
+        free_vars(&type_, &mut vars);
+        vars.retain(|&v| self.vars[v].level > self.level);
+
+        Scheme {
+            vars:  vars,
+            type_: type_,
+        }
+    }
+
+    /// `scheme`'s type with fresh variables in place of its own.
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        if scheme.vars.is_empty() {
+            return scheme.type_.clone();
+        }
+
+        let fresh: HashMap<usize, Type> = scheme.vars.iter().map(|&v| {
+            let var = self.fresh();
+            self.vars[var].numeric = self.vars[v].numeric;
+
+            (v, Type::Var(var))
+        }).collect();
+
+        substitute(&scheme.type_, &fresh)
+    }
+
+    /// Writes out `types` as annotations, naming their unbound variables
+    /// `a`, `b`, and so on, in the order they appear in all of them.
+    fn render(&self, types: &[&Type]) -> Vec<String> {
+        let mut names = HashMap::new();
+
+        types.iter()
+             .map(|type_| render(&self.resolve(type_), &mut names))
+             .collect()
+    }
+
+    fn fresh(&mut self) -> usize {
+        self.vars.push(Var {
+            bound:   None,
+            level:   self.level,
+            numeric: false,
+        });
+
+        self.vars.len() - 1
+    }
+
+    fn fresh_type(&mut self) -> Type {
+        Type::Var(self.fresh())
+    }
+
+    fn define(&mut self, name: &str, type_: Type) {
+        self.define_scheme(name, Vec::new(), type_);
+    }
+
+    /// Declares `name` as being of any type at all, wherever it is used.
+    fn define_unknown(&mut self, name: &str) {
+        let var = self.fresh();
+        self.define_scheme(name, vec![var], Type::Var(var));
+    }
+
+    fn define_scheme(&mut self, name: &str, vars: Vec<usize>, type_: Type) {
+        let scope = self.scopes.len() - 1;
+        self.scopes[scope].insert(name.to_string(), Scheme {
+            vars:  vars,
+            type_: type_,
+        });
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Scheme> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(scheme) = scope.get(name) {
+                return Some(scheme);
+            }
+        }
+
+        None
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        self.lookup(name).is_some()
+    }
+
+    fn is_defined_here(&self, name: &str) -> bool {
+        self.scopes.last().is_some_and(|scope| scope.contains_key(name))
+    }
+}
+
+/// Works out the types in `module`.
+pub fn infer(module: &Module) -> Typing {
+    let mut checker = Checker::new();
+    checker.module(module);
+    checker.default_numbers();
+
+    let mut errors = checker.errors.clone();
+    errors.sort_by_key(|e| (e.span().line, e.span().col));
+
+    Typing {
+        types:  checker.types(module),
+        errors: errors,
+    }
+}
+
+/// Whether the annotation `annotation` stands for `Unit`, the type of
+/// `()`, which it can be written as too.
+pub fn is_unit(annotation: &TypeExpr) -> bool {
+    match Checker::new().annotation(annotation) {
+        Type::Unit => true,
+        _          => false,
+    }
+}
+
+fn is_lambda(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Lambda(..) => true,
+        _                    => false,
+    }
+}
+
+fn stmt_span(stmt: &Stmt) -> Span {
+    match *stmt {
+        Stmt::Let(ref let_)      => let_.span,
+        Stmt::Fn(ref fn_decl)    => fn_decl.span,
+        Stmt::Fixity(ref fixity) => fixity.span,
+        Stmt::Expr(ref expr)     => expr.span,
+    }
+}
+
+/// Adds the variables in `type_` that are not in `vars` already to it.
+fn free_vars(type_: &Type, vars: &mut Vec<usize>) {
+    match *type_ {
+        Type::Var(v) => {
+            if !vars.contains(&v) {
+                vars.push(v);
+            }
+        },
+        Type::Unit | Type::Con(_) => {},
+        Type::Tuple(ref elems) => {
+            for elem in elems {
+                free_vars(elem, vars);
+            }
+        },
+        Type::List(ref elem) | Type::Set(ref elem) => free_vars(elem, vars),
+        Type::Dict(ref key, ref value) | Type::Result(ref key, ref value) => {
+            free_vars(key, vars);
+            free_vars(value, vars);
+        },
+        Type::Fn(ref params, ref result) => {
+            for param in params {
+                free_vars(param, vars);
+            }
+
+            free_vars(result, vars);
+        },
+    }
+}
+
+/// `type_`, with the variables in `vars` replaced by what they map to.
+fn substitute(type_: &Type, vars: &HashMap<usize, Type>) -> Type {
+    match *type_ {
+        Type::Var(v) => vars.get(&v).cloned().unwrap_or(Type::Var(v)),
+        Type::Unit | Type::Con(_) => type_.clone(),
+        Type::Tuple(ref elems) => Type::Tuple(
+            elems.iter().map(|elem| substitute(elem, vars)).collect()
+        ),
+        Type::List(ref elem) => Type::List(Box::new(substitute(elem, vars))),
+        Type::Set(ref elem)  => Type::Set(Box::new(substitute(elem, vars))),
+        Type::Dict(ref key, ref value) => Type::Dict(
+            Box::new(substitute(key, vars)),
+            Box::new(substitute(value, vars))
+        ),
+        Type::Result(ref failure, ref success) => Type::result(
+            substitute(failure, vars),
+            substitute(success, vars)
+        ),
+        Type::Fn(ref params, ref result) => Type::Fn(
+            params.iter().map(|param| substitute(param, vars)).collect(),
+            Box::new(substitute(result, vars))
+        ),
+    }
+}
+
+/// Writes out `type_`, naming each of its variables from `names`, or if it
+/// has no name yet, the next letter not taken.
+fn render(type_: &Type, names: &mut HashMap<usize, String>) -> String {
+    match *type_ {
+        Type::Var(v) => {
+            let next = names.len();
+
+            names.entry(v).or_insert_with(|| var_name(next)).clone()
+        },
+        Type::Unit => "Unit".to_string(),
+        Type::Con(ref name) => name.clone(),
+        Type::Tuple(ref elems) => format!(
+            "({})",
+            elems.iter()
+                 .map(|elem| render(elem, names))
+                 .collect::<Vec<_>>()
+                 .join(", ")
+        ),
+        Type::List(ref elem) => format!("[{}]", render(elem, names)),
+        Type::Set(ref elem)  => format!("{{{}}}", render(elem, names)),
+        Type::Dict(ref key, ref value) => format!(
+            "{{{}, {}}}",
+            render(key, names),
+            render(value, names)
+        ),
+        Type::Result(ref failure, ref success) => {
+            let mut arg = |type_: &Type| match *type_ {
+                Type::Fn(..) | Type::Result(..) => {
+                    format!("({})", render(type_, names))
+                },
+                _ => render(type_, names),
+            };
+            let failure = arg(failure);
+
+            format!("Result {} {}", failure, arg(success))
+        },
+        Type::Fn(ref params, ref result) => {
+            // Written as the parameters of a lambda are: `a, b -> c`.
+            let params: Vec<_> = params.iter().map(|param| match *param {
+                Type::Fn(..) => format!("({})", render(param, names)),
+                _            => render(param, names),
+            }).collect();
+
+            if params.is_empty() {
+                format!("() -> {}", render(result, names))
+            } else {
+                format!("{} -> {}", params.join(", "), render(result, names))
+            }
+        },
+    }
+}
+
+/// The name of the `n`th type variable: `a` to `z`, then `a1` to `z1`, and
+/// so on.
+fn var_name(n: usize) -> String {
+    let letter = (b'a' + (n % 26) as u8) as char;
+
+    match n / 26 {
+        0     => letter.to_string(),
+        round => format!("{}{}", letter, round),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast;
+    use parser::Parser;
+
+    fn check(src: &str) -> Typing {
+        let tree = Parser::from_str(src).parse().unwrap().unwrap();
+
+        infer(&ast::lower(&tree).unwrap())
+    }
+
+    fn type_of(typing: &Typing, name: &str) -> String {
+        typing.types.iter()
+                    .find(|&(n, _)| n == name)
+                    .map(|(_, type_)| type_.clone())
+                    .unwrap()
+    }
+
+    #[test]
+    fn propagate_unwraps_a_result_and_returns_its_failure() {
+        let typing = check(
+            "module Main\n\n\
+             fn half n\n    if n % 2 == 0\n        Ok (n / 2)\n    \
+             else\n        Err \"odd\"\n\n\
+             fn quarter n\n    h = (half n)?\n    half h\n"
+        );
+
+        assert!(typing.errors.is_empty(), "{:?}", typing.errors);
+        assert_eq!(type_of(&typing, "quarter"), "a -> Result Str a");
+    }
+
+    #[test]
+    fn propagate_needs_a_function_returning_a_result() {
+        let typing = check(
+            "module Main\n\n\
+             fn half n = Ok (n / 2)\n\n\
+             fn bad n\n    h = (half n)?\n    h + 1\n"
+        );
+
+        assert_eq!(typing.errors.len(), 1);
+        assert!(
+            typing.errors[0].to_string().starts_with("7:5: expected Result")
+        );
+    }
+
+    #[test]
+    fn propagate_does_not_use_names_in_the_module() {
+        let typing = check(
+            "module Main\n\n\
+             fn unwrap x = 0\n\n\
+             fn failed x = True\n\n\
+             fn f r\n    x = r?\n    Ok (length x)\n"
+        );
+
+        assert!(typing.errors.is_empty(), "{:?}", typing.errors);
+        assert_eq!(type_of(&typing, "f"), "Result a b -> Result a Int");
+    }
+
+    #[test]
+    fn unit_annotations_are_the_type_of_unit_values() {
+        let typing = check(
+            "module Main\n\n\
+             fn say x -> Unit\n    print x\n\n\
+             fn nothing -> Unit\n    ()\n\n\
+             fn three -> Unit\n    3\n"
+        );
+
+        assert_eq!(type_of(&typing, "say"), "a -> Unit");
+        assert_eq!(type_of(&typing, "nothing"), "() -> Unit");
+        assert_eq!(typing.errors.len(), 1, "{:?}", typing.errors);
+        assert_eq!(typing.errors[0].to_string(),
+                   "10:5: expected Unit, found a number");
+    }
+
+    #[test]
+    fn annotations_that_disagree_with_their_values_are_mismatches() {
+        let typing = check(
+            "module Main\n\n\
+             x: Str = 3\n\n\
+             fn f (n: Int) -> Str = n\n"
+        );
+
+        let errors: Vec<_> =
+            typing.errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec!["3:10: expected Str, found a number",
+                                "5:24: expected Str, found Int"]);
+    }
+
+    #[test]
+    fn arithmetic_on_something_not_numeric_is_an_error() {
+        let typing = check("module Main\n\nx = True * 2\n");
+
+        assert_eq!(typing.errors.len(), 1);
+        match typing.errors[0] {
+            TypeError::NotNumeric { ref found, span } => {
+                assert_eq!(found, "Bool");
+                assert_eq!((span.line, span.col), (3, 5));
+            },
+            ref e => panic!("expected NotNumeric, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn a_function_applied_to_itself_has_an_infinite_type() {
+        let typing = check("module Main\n\nfn f x = x x\n");
+
+        assert_eq!(typing.errors.len(), 1);
+        match typing.errors[0] {
+            TypeError::Infinite { ref var, .. } => assert_eq!(var, "a"),
+            ref e => panic!("expected Infinite, got {:?}", e),
+        }
+    }
+
+    #[test]
+    fn an_error_does_not_stop_the_rest_of_the_module_being_checked() {
+        let typing = check(
+            "module Main\n\n\
+             x = 1 + \"one\"\n\n\
+             y = \"two\"\n\n\
+             z = y + 2\n"
+        );
+
+        assert_eq!(typing.errors.len(), 2, "{:?}", typing.errors);
+        assert_eq!(typing.errors[0].span().line, 3);
+        assert_eq!(typing.errors[1].span().line, 7);
+        assert_eq!(type_of(&typing, "y"), "Str");
+    }
+}
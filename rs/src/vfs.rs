@@ -0,0 +1,351 @@
+//! Where source files are read from: the disk, or anything else that can
+//! stand in for it.
+//!
+//! The loader (see `loader`) and `stats::find_sources_in` only touch files
+//! through a `FileSystem`, so that tests, editors with unsaved buffers
+//! (see `OverlayFs`), and programs with sources bundled into them can hand
+//! them files that are not on disk.
+//!
+//! ```
+//! use brouwer::loader::Loader;
+//! use brouwer::vfs::MemoryFs;
+//!
+//! let mut files = MemoryFs::new();
+//! files.insert("src/Main.bwr", "module Main\nimport Util (f)\n\nf 1\n");
+//! files.insert("src/Util.bwr", "module Util exposing f\n\nfn f x = x\n");
+//!
+//! let mut loader = Loader::with_fs(files, vec!["src".into()]);
+//! let program = loader.load("src/Main.bwr").unwrap();
+//!
+//! assert_eq!(program.modules[0].module.name.name, "Util");
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use loader::normalize;
+
+
+/// A place that files can be read from.
+pub trait FileSystem {
+    /// The contents of the file at `path`, which must be UTF-8.
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// What is at `path`.
+    fn stat(&self, path: &Path) -> io::Result<Stat>;
+
+    /// The files and directories in the directory at `path`, in no
+    /// particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Whether there is a file at `path`.
+    fn is_file(&self, path: &Path) -> bool {
+        self.stat(path).map(|stat| stat.kind == FileKind::File)
+                       .unwrap_or(false)
+    }
+
+    /// Whether there is a directory at `path`.
+    fn is_dir(&self, path: &Path) -> bool {
+        self.stat(path).map(|stat| stat.kind == FileKind::Dir)
+                       .unwrap_or(false)
+    }
+}
+
+/// What `FileSystem::stat` finds at a path.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Stat {
+    /// Whether it is a file or a directory.
+    pub kind: FileKind,
+    /// How many bytes long it is, if it is a file.
+    pub len:  u64,
+}
+
+/// What kind of thing a `Stat` is of.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileKind {
+    /// A file.
+    File,
+    /// A directory.
+    Dir,
+}
+
+/// The disk, through `std::fs`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RealFs;
+
+/// Files held in memory, each under a path. The directories are those
+/// that the paths of the files are in.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryFs {
+    files: BTreeMap<PathBuf, String>,
+}
+
+/// Buffers that an editor has open, over another filesystem (by default
+/// the disk). A path with a buffer open reads as the buffer, saved or not;
+/// any other reads as it does in the filesystem underneath.
+///
+/// ```
+/// use brouwer::loader::Loader;
+/// use brouwer::vfs::{FileSystem, MemoryFs, OverlayFs};
+///
+/// let mut disk = MemoryFs::new();
+/// disk.insert("Main.bwr", "module Main\nimport Util (f)\n\nf 1\n");
+/// disk.insert("Util.bwr", "module Util exposing f\n\nfn f x = x\n");
+///
+/// let mut files = OverlayFs::new(disk);
+/// files.open("Util.bwr", "module Util exposing f, g\n\nfn f x = x\n");
+/// files.open("New.bwr", "module New\n");
+///
+/// assert!(files.is_file("New.bwr".as_ref()));
+///
+/// let mut loader = Loader::with_fs(files, vec![".".into()]);
+/// let program = loader.load("Main.bwr").unwrap();
+///
+/// assert!(program.get("Util").unwrap().source.contains("f, g"));
+///
+/// loader.fs_mut().close("Util.bwr");
+/// let program = loader.load("Main.bwr").unwrap();
+///
+/// assert!(!program.get("Util").unwrap().source.contains("f, g"));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OverlayFs<F = RealFs> {
+    base:    F,
+    buffers: MemoryFs,
+}
+
+
+/// A filesystem borrowed, as for a `Loader` that should not own it.
+impl<F: FileSystem + ?Sized> FileSystem for &F {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        (**self).read(path)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        (**self).stat(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        (**self).read_dir(path)
+    }
+}
+
+impl FileSystem for RealFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        let mut contents = String::new();
+        File::open(path)?.read_to_string(&mut contents)?;
+
+        Ok(contents)
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        let metadata = fs::metadata(path)?;
+
+        let kind = if metadata.is_dir() {
+            FileKind::Dir
+        } else {
+            FileKind::File
+        };
+
+        Ok(Stat {
+            kind: kind,
+            len:  metadata.len(),
+        })
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut entries = Vec::new();
+
+        for entry in fs::read_dir(path)? {
+            entries.push(entry?.path());
+        }
+
+        Ok(entries)
+    }
+}
+
+impl MemoryFs {
+    /// A filesystem with no files in it.
+    pub fn new() -> Self {
+        MemoryFs::default()
+    }
+
+    /// Puts `contents` in the file at `path`, replacing what was there.
+    pub fn insert<P: AsRef<Path>, S: Into<String>>(
+        &mut self,
+        path:     P,
+        contents: S
+    ) {
+        self.files.insert(normalize(path.as_ref()), contents.into());
+    }
+
+    /// Removes the file at `path`, returning what was in it.
+    pub fn remove<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        self.files.remove(&normalize(path.as_ref()))
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        let path = normalize(path);
+
+        if let Some(contents) = self.files.get(&path) {
+            return Ok(Stat {
+                kind: FileKind::File,
+                len:  contents.len() as u64,
+            });
+        }
+
+        if self.files.keys().any(|file| within(file, &path).is_some()) {
+            Ok(Stat {
+                kind: FileKind::Dir,
+                len:  0,
+            })
+        } else {
+            Err(not_found(&path))
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let dir = normalize(path);
+        let mut entries: Vec<PathBuf> = Vec::new();
+
+        for file in self.files.keys() {
+            let rest = match within(file, &dir) {
+                Some(rest) => rest,
+                None       => continue,
+            };
+
+            if let Some(first) = rest.components().next() {
+                let entry = normalize(&dir.join(first.as_os_str()));
+
+                if !entries.contains(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            return Err(match self.stat(&dir) {
+                Ok(_)  => io::Error::other(
+                    "not a directory"
+                ),
+                Err(e) => e,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+impl<F: FileSystem> OverlayFs<F> {
+    /// `base`, with no buffers open over it.
+    pub fn new(base: F) -> Self {
+        OverlayFs {
+            base:    base,
+            buffers: MemoryFs::new(),
+        }
+    }
+
+    /// The filesystem underneath the buffers.
+    pub fn base(&self) -> &F {
+        &self.base
+    }
+
+    /// The filesystem underneath the buffers, mutably.
+    pub fn base_mut(&mut self) -> &mut F {
+        &mut self.base
+    }
+
+    /// Opens a buffer holding `contents` at `path`, or replaces what the
+    /// buffer already open there holds.
+    pub fn open<P: AsRef<Path>, S: Into<String>>(
+        &mut self,
+        path:     P,
+        contents: S
+    ) {
+        self.buffers.insert(path, contents);
+    }
+
+    /// Closes the buffer at `path`, returning what was in it, so that the
+    /// path reads as it does underneath again.
+    pub fn close<P: AsRef<Path>>(&mut self, path: P) -> Option<String> {
+        self.buffers.remove(path)
+    }
+
+    /// Whether there is a buffer open at `path`.
+    pub fn is_open<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.buffers.is_file(path.as_ref())
+    }
+}
+
+impl<F: FileSystem> FileSystem for OverlayFs<F> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        if self.is_open(path) {
+            self.buffers.read(path)
+        } else {
+            self.base.read(path)
+        }
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<Stat> {
+        if self.is_open(path) {
+            return self.buffers.stat(path);
+        }
+
+        // A buffer may be open in a directory that has not been made yet.
+        self.base
+            .stat(path)
+            .or_else(|e| self.buffers.stat(path).map_err(|_| e))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        let opened = self.buffers.read_dir(path);
+        let mut entries = match self.base.read_dir(path) {
+            Ok(entries) => entries,
+            Err(e)      => return opened.map_err(|_| e),
+        };
+
+        for entry in opened.unwrap_or_default() {
+            if !entries.iter().any(|e| normalize(e) == entry) {
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Where `file` is relative to the directory `dir`, if it is in it. Both
+/// are normalized.
+fn within<'a>(file: &'a Path, dir: &Path) -> Option<&'a Path> {
+    let rest = if dir == Path::new(".") {
+        file
+    } else {
+        file.strip_prefix(dir).ok()?
+    };
+
+    if rest.as_os_str().is_empty() || rest.has_root() ||
+       rest.starts_with("..")
+    {
+        None
+    } else {
+        Some(rest)
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display())
+    )
+}